@@ -0,0 +1,54 @@
+//! Captures the git commit and the build time for `GET /version`, which reads
+//! them with `env!`.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|text| text.trim().to_string())
+}
+
+fn main() {
+    // Source archives have no repository; their commit is reported as unknown
+    let git_commit = match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(hash) if !hash.is_empty() => {
+            let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+                .is_some_and(|changes| !changes.is_empty());
+            if dirty {
+                format!("{}-dirty", hash)
+            } else {
+                hash
+            }
+        }
+        _ => "unknown".to_string(),
+    };
+
+    // SOURCE_DATE_EPOCH pins the time for reproducible builds
+    let build_timestamp = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=MIRSEODB_GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=MIRSEODB_BUILD_TIMESTAMP={}", build_timestamp);
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // A commit or checkout changes these
+    for path in [".git/HEAD", ".git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -10,6 +10,9 @@ pub struct AuthConfig {
     pub perms: HashMap<String, PermissionGroup>,
     pub setup_completed: bool,
     pub admin_email: Option<String>,
+    /// Per-user GRANT/REVOKE entries, checked before the user's role.
+    #[serde(default)]
+    pub grants: HashMap<String, UserGrants>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +21,48 @@ pub struct PermissionGroup {
     pub deny: Vec<String>,
 }
 
+/// A privilege (`SELECT`, ..., or `ALL`) on one table, or on every table when
+/// `table` is `None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrantEntry {
+    pub privilege: String,
+    pub table: Option<String>,
+}
+
+/// Table-level entries decide before global ones, and within a scope a
+/// revocation beats a grant. Anything left undecided falls back to the role.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserGrants {
+    pub granted: Vec<GrantEntry>,
+    pub revoked: Vec<GrantEntry>,
+}
+
+impl UserGrants {
+    /// Table-level entries decide before global ones; at the same level a
+    /// revocation wins. `None` when no entry covers `operation` on `table`.
+    fn decide(&self, operation: &str, table: Option<&str>) -> Option<bool> {
+        let applies = |entry: &GrantEntry, table_scoped: bool| {
+            let scope_matches = match (&entry.table, table) {
+                (Some(granted), Some(table)) => table_scoped && granted.eq_ignore_ascii_case(table),
+                (None, _) => !table_scoped,
+                (Some(_), None) => false,
+            };
+            scope_matches && privilege_covers(&entry.privilege, operation)
+        };
+
+        for table_scoped in [true, false] {
+            if self.revoked.iter().any(|entry| applies(entry, table_scoped)) {
+                return Some(false);
+            }
+            if self.granted.iter().any(|entry| applies(entry, table_scoped)) {
+                return Some(true);
+            }
+        }
+
+        None
+    }
+}
+
 impl Default for AuthConfig {
     fn default() -> Self {
         let emails = HashMap::new(); // 초기에는 빈 상태로 시작
@@ -49,6 +94,7 @@ impl Default for AuthConfig {
             perms,
             setup_completed: false,
             admin_email: None,
+            grants: HashMap::new(),
         }
     }
 }
@@ -133,51 +179,111 @@ impl AuthConfig {
         self.perm_manager.contains(&email.to_string())
     }
 
-    pub fn check_sql_permission(&self, email: &str, sql_statement: &str) -> bool {
-        let role = self.get_user_role(email).unwrap_or("default");
-
-        if let Some(perms) = self.perms.get(role) {
-            let sql_upper = sql_statement.trim().to_uppercase();
-            let operation = extract_sql_operation(&sql_upper);
-
-            // 먼저 deny 리스트 확인
-            for deny_pattern in &perms.deny {
-                if matches_sql_pattern(&sql_upper, deny_pattern) {
-                    return false;
-                }
+    /// Records a GRANT, replacing any revocation of the same privilege and scope.
+    pub fn grant(
+        &mut self,
+        email: &str,
+        privileges: &[String],
+        table: Option<&str>,
+    ) -> Result<(), String> {
+        let grants = self.grants.entry(email.to_string()).or_default();
+        for privilege in privileges {
+            let entry = GrantEntry {
+                privilege: privilege.clone(),
+                table: table.map(|table| table.to_string()),
+            };
+            grants.revoked.retain(|revoked| *revoked != entry);
+            if !grants.granted.contains(&entry) {
+                grants.granted.push(entry);
             }
+        }
+        self.save()
+    }
 
-            // allow 리스트 확인
-            for allow_pattern in &perms.allow {
-                if matches_sql_pattern(&sql_upper, allow_pattern) {
-                    return true;
-                }
+    /// Records a REVOKE. It also blocks privileges the user's role would allow.
+    pub fn revoke(
+        &mut self,
+        email: &str,
+        privileges: &[String],
+        table: Option<&str>,
+    ) -> Result<(), String> {
+        let grants = self.grants.entry(email.to_string()).or_default();
+        for privilege in privileges {
+            let entry = GrantEntry {
+                privilege: privilege.clone(),
+                table: table.map(|table| table.to_string()),
+            };
+            grants.granted.retain(|granted| *granted != entry);
+            if !grants.revoked.contains(&entry) {
+                grants.revoked.push(entry);
             }
+        }
+        self.save()
+    }
+
+    /// The GRANT/REVOKE decision for `sql_upper`, or `None` when no entry applies.
+    /// Grants allow the statement only when they cover every table it touches:
+    /// the operation on its target table, SELECT on each table it reads through
+    /// a join, subquery or UNION branch. A revocation of any of them denies it.
+    fn grant_decision(&self, email: &str, sql_upper: &str) -> Option<bool> {
+        let grants = self.grants.get(email)?;
+        let table = extract_sql_table(sql_upper);
+
+        let mut decisions = vec![grants.decide(extract_sql_operation(sql_upper), table.as_deref())];
+        decisions.extend(
+            extract_read_tables(sql_upper)
+                .iter()
+                .filter(|read| table.as_deref() != Some(read.as_str()))
+                .map(|read| grants.decide("SELECT", Some(read))),
+        );
 
-            // 기본적으로 거부
-            false
+        if decisions.contains(&Some(false)) {
+            Some(false)
+        } else if decisions.iter().all(|decision| *decision == Some(true)) {
+            Some(true)
         } else {
-            // 알 수 없는 역할은 기본 권한으로 처리
-            if let Some(default_perms) = self.perms.get("default") {
-                let sql_upper = sql_statement.trim().to_uppercase();
-
-                for deny_pattern in &default_perms.deny {
-                    if matches_sql_pattern(&sql_upper, deny_pattern) {
-                        return false;
-                    }
-                }
+            None
+        }
+    }
 
-                for allow_pattern in &default_perms.allow {
-                    if matches_sql_pattern(&sql_upper, allow_pattern) {
-                        return true;
-                    }
-                }
-            }
-            false
+    pub fn check_sql_permission(&self, email: &str, sql_statement: &str) -> bool {
+        let sql_upper = sql_statement.trim().to_uppercase();
+        // Transaction control and USE touch no data; the statements after them are
+        // checked on their own. Grants name tables only, so they hold in every database
+        if is_transaction_control(&sql_upper) || sql_upper.starts_with("USE ") {
+            return true;
+        }
+
+        if let Some(allowed) = self.grant_decision(email, &sql_upper) {
+            return allowed;
+        }
+
+        let role = self.get_user_role(email).unwrap_or("default");
+        // 알 수 없는 역할은 기본 권한으로 처리
+        let Some(perms) = self.perms.get(role).or_else(|| self.perms.get("default")) else {
+            return false;
+        };
+
+        // 먼저 deny 리스트 확인
+        if perms.deny.iter().any(|deny_pattern| matches_sql_pattern(&sql_upper, deny_pattern)) {
+            return false;
         }
+
+        // allow 리스트 확인; 기본적으로 거부
+        perms.allow.iter().any(|allow_pattern| matches_sql_pattern(&sql_upper, allow_pattern))
     }
 }
 
+fn is_transaction_control(sql: &str) -> bool {
+    let words: Vec<&str> = sql.trim_end_matches(';').split_whitespace().collect();
+    matches!(
+        words.as_slice(),
+        ["BEGIN" | "COMMIT" | "ROLLBACK"]
+            | ["BEGIN" | "COMMIT" | "ROLLBACK", "TRANSACTION" | "WORK"]
+            | ["START", "TRANSACTION"]
+    )
+}
+
 fn extract_sql_operation(sql: &str) -> &str {
     let words: Vec<&str> = sql.split_whitespace().collect();
     if words.is_empty() {
@@ -229,6 +335,74 @@ fn extract_sql_operation(sql: &str) -> &str {
     }
 }
 
+/// Table a statement targets, for table-level grants.
+fn extract_sql_table(sql: &str) -> Option<String> {
+    let words: Vec<&str> = sql.split_whitespace().collect();
+    let after = |keyword: &str| {
+        words
+            .iter()
+            .position(|word| *word == keyword)
+            .and_then(|pos| words.get(pos + 1))
+    };
+
+    let table = match *words.first()? {
+        "SELECT" | "DELETE" => after("FROM"),
+        "INSERT" => after("INTO"),
+        "UPDATE" => words.get(1),
+        "CREATE" | "DROP" | "ALTER" if words.get(1) == Some(&"TABLE") => words.get(2),
+        _ => None,
+    }?;
+
+    clean_table_word(table)
+}
+
+/// Every table named after a FROM or JOIN, including those in subqueries and
+/// UNION branches and every table of a `FROM a, b` list.
+fn extract_read_tables(sql: &str) -> Vec<String> {
+    let sql = sql.replace(',', " , ");
+    let words: Vec<&str> = sql.split_whitespace().collect();
+    let mut tables = Vec::new();
+
+    for (pos, word) in words.iter().enumerate() {
+        if *word != "FROM" && *word != "JOIN" {
+            continue;
+        }
+        let mut next = pos + 1;
+        while let Some(table) = words.get(next) {
+            tables.extend(clean_table_word(table));
+            // `FROM a AS x, b y`: skip the alias up to the comma, if any
+            let Some(comma) = words[next + 1..]
+                .iter()
+                .take(3)
+                .position(|word| *word == ",")
+            else {
+                break;
+            };
+            next += comma + 2;
+        }
+    }
+    tables
+}
+
+fn clean_table_word(word: &str) -> Option<String> {
+    let table = word
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .trim_matches(|ch| matches!(ch, '[' | ']' | '`' | '"' | '\'' | ';' | ',' | ')'));
+    (!table.is_empty()).then(|| table.to_string())
+}
+
+/// `ALL` covers every operation; `DROP` covers `DROP TABLE`, and so on.
+fn privilege_covers(privilege: &str, operation: &str) -> bool {
+    privilege == "ALL"
+        || operation == privilege
+        || operation
+            .strip_prefix(privilege)
+            .map(|rest| rest.starts_with(' '))
+            .unwrap_or(false)
+}
+
 fn matches_sql_pattern(sql: &str, pattern: &str) -> bool {
     if pattern == "*" {
         return true;
@@ -301,4 +475,59 @@ mod tests {
         assert!(!config.check_sql_permission("admin@example.com", "DROP TABLE users"));
         assert!(!config.check_sql_permission("admin@example.com", "DROP DATABASE test"));
     }
+
+    #[test]
+    fn test_table_grants_override_global_revocations() {
+        let mut config = AuthConfig::default();
+        config.emails.insert("user@example.com".to_string(), "user".to_string());
+        let grants = config.grants.entry("user@example.com".to_string()).or_default();
+        grants.revoked.push(GrantEntry {
+            privilege: "ALL".to_string(),
+            table: None,
+        });
+        grants.granted.push(GrantEntry {
+            privilege: "DELETE".to_string(),
+            table: Some("ORDERS".to_string()),
+        });
+
+        // The table-level grant beats both the global revocation and the role's deny list
+        let allowed = |sql| config.check_sql_permission("user@example.com", sql);
+        assert!(allowed("DELETE FROM orders WHERE id = 1"));
+        assert!(!allowed("DELETE FROM users WHERE id = 1"));
+        assert!(!allowed("SELECT * FROM orders"));
+        // Reading another table through a subquery needs SELECT on it as well
+        assert!(!allowed("DELETE FROM orders WHERE id IN (SELECT id FROM users)"));
+    }
+
+    #[test]
+    fn test_table_grant_covers_only_its_own_table() {
+        let mut config = AuthConfig::default();
+        config.emails.insert("viewer@example.com".to_string(), "viewer".to_string());
+        config.perms.insert(
+            "viewer".to_string(),
+            PermissionGroup {
+                allow: vec![],
+                deny: vec![],
+            },
+        );
+        config.grants.entry("viewer@example.com".to_string()).or_default().granted.push(
+            GrantEntry {
+                privilege: "SELECT".to_string(),
+                table: Some("ORDERS".to_string()),
+            },
+        );
+        let allowed = |sql| config.check_sql_permission("viewer@example.com", sql);
+
+        assert!(allowed("SELECT * FROM orders"));
+        assert!(allowed("SELECT * FROM orders o WHERE o.id IN (SELECT id FROM orders)"));
+        // Another table anywhere in the statement falls back to the role, which denies
+        for sql in [
+            "SELECT * FROM orders UNION SELECT * FROM secrets",
+            "SELECT * FROM orders WHERE id IN (SELECT order_id FROM secrets)",
+            "SELECT * FROM orders JOIN secrets ON orders.id = secrets.id",
+            "SELECT * FROM orders o, secrets s",
+        ] {
+            assert!(!allowed(sql), "{}", sql);
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use super::core_types::{SqlValue, DatabaseError};
+use super::indexing::RebuildProgress;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
@@ -117,7 +118,11 @@ impl ColumnBloomFilter {
         }
     }
 
-    pub fn build_from_table(&mut self, table_data: &[(std::collections::HashMap<String, SqlValue>, usize)]) {
+    pub fn build_from_table(
+        &mut self,
+        table_data: &[(std::collections::HashMap<String, SqlValue>, usize)],
+        progress: &mut RebuildProgress,
+    ) {
         self.clear();
         self.row_count = table_data.len();
 
@@ -131,6 +136,7 @@ impl ColumnBloomFilter {
                 });
                 filter.insert(value);
             }
+            progress.advance(1);
         }
     }
 
@@ -278,28 +284,12 @@ impl ChunkedTableScanner {
         let avg_row_size = if chunk.is_empty() {
             1024
         } else {
-            chunk.iter().map(|row| self.estimate_row_size(row)).sum::<usize>() / chunk.len()
+            chunk.iter().map(|row| row.estimated_size()).sum::<usize>() / chunk.len()
         };
 
         chunk.len() * avg_row_size
     }
 
-    fn estimate_row_size(&self, row: &super::core_types::Row) -> usize {
-        let mut size = std::mem::size_of::<super::core_types::Row>();
-
-        for (key, value) in &row.columns {
-            size += key.len() + match value {
-                SqlValue::Integer(_) => 8,
-                SqlValue::Float(_) => 8,
-                SqlValue::Text(s) => s.len(),
-                SqlValue::Boolean(_) => 1,
-                SqlValue::Null => 0,
-            };
-        }
-
-        size
-    }
-
     pub fn adaptive_chunk_size(&self, total_rows: usize, available_memory_mb: usize) -> usize {
         let estimated_row_size = 1024;
         let max_chunk_size = (available_memory_mb * 1024 * 1024) / estimated_row_size;
@@ -0,0 +1,182 @@
+use super::auth::AuthConfig;
+use super::configuration::ConfigManager;
+use super::core_types::DatabaseError;
+use super::engine::{Database, DatabaseRegistry};
+use super::routing::RouteConfig;
+use super::server::{start_health_server, HttpServerHandle};
+use super::smart_parser::AnySQL;
+use std::env;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+
+pub const DEFAULT_DATABASE_NAME: &str = "mirseodb";
+pub const DEFAULT_HTTP_PORT: u16 = 3306;
+pub const DEFAULT_CORS_ORIGIN: &str = "*";
+pub const DEFAULT_BIND_ADDR: &str = "127.0.0.1";
+
+/// Settings for starting a database and its HTTP API without the web console.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub database_name: String,
+    /// First port to try; the server moves up to the next free port if it is taken.
+    pub port: u16,
+    /// IP address the HTTP listener binds; `0.0.0.0` accepts other hosts too.
+    /// Checked when the server starts.
+    pub bind_addr: String,
+    pub api_token: Option<String>,
+    /// Value of `Access-Control-Allow-Origin` on API responses.
+    pub cors_origin: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            database_name: DEFAULT_DATABASE_NAME.to_string(),
+            port: DEFAULT_HTTP_PORT,
+            bind_addr: DEFAULT_BIND_ADDR.to_string(),
+            api_token: None,
+            cors_origin: DEFAULT_CORS_ORIGIN.to_string(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Defaults plus `MIRSEODB_BIND_ADDR`, `MIRSEODB_API_TOKEN` and
+    /// `MIRSEODB_CORS_ORIGIN`, matching the standalone binary.
+    pub fn from_env() -> Self {
+        Self {
+            bind_addr: env::var("MIRSEODB_BIND_ADDR")
+                .unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string()),
+            api_token: env::var("MIRSEODB_API_TOKEN").ok(),
+            cors_origin: env::var("MIRSEODB_CORS_ORIGIN")
+                .unwrap_or_else(|_| DEFAULT_CORS_ORIGIN.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+pub struct ServerHandle {
+    databases: Arc<DatabaseRegistry>,
+    database_name: String,
+    http: HttpServerHandle,
+}
+
+impl ServerHandle {
+    pub fn port(&self) -> u16 {
+        self.http.port()
+    }
+
+    /// The default database, which queries naming no other one run against.
+    pub fn database(&self) -> Arc<RwLock<Database>> {
+        self.databases.default_database()
+    }
+
+    pub fn databases(&self) -> Arc<DatabaseRegistry> {
+        Arc::clone(&self.databases)
+    }
+
+    pub fn database_name(&self) -> &str {
+        &self.database_name
+    }
+
+    /// Stops the HTTP listener and writes out any deferred ASYNC table changes
+    /// of every open database.
+    pub fn shutdown(self) -> Result<(), DatabaseError> {
+        self.http.shutdown();
+        self.databases.flush_pending()?;
+
+        println!("[MirseoDB] Server for database '{}' stopped", self.database_name);
+        Ok(())
+    }
+}
+
+/// Loads (or creates) the database and starts the HTTP API. Does not spawn the
+/// console or block; the caller owns the returned handle.
+pub fn run_server(config: ServerConfig) -> Result<ServerHandle, DatabaseError> {
+    let bind_addr: IpAddr = config.bind_addr.trim().parse().map_err(|_| {
+        DatabaseError::NetworkError(format!(
+            "Invalid bind address '{}' (MIRSEODB_BIND_ADDR): expected an IP address such as \
+             127.0.0.1 or 0.0.0.0",
+            config.bind_addr
+        ))
+    })?;
+    let databases = Arc::new(DatabaseRegistry::new(initialize_database(&config.database_name)?));
+    println!("[MirseoDB] Database initialized successfully");
+
+    let security_config = ConfigManager::load();
+    let parser = Arc::new(AnySQL::new().with_max_nesting_depth(security_config.max_query_depth));
+    println!(
+        "[MirseoDB] AnySQL HYPERTHINKING engine initialized - All SQL dialects supported automatically!"
+    );
+
+    let route_config = match RouteConfig::load() {
+        Ok(config) => {
+            println!("[MirseoDB] Route configuration loaded successfully");
+            Arc::new(config)
+        }
+        Err(e) => {
+            eprintln!("[MirseoDB] Route configuration failed to load: {}", e);
+            println!("[MirseoDB] Continuing without route forwarding...");
+            Arc::new(RouteConfig {
+                routes: std::collections::HashMap::new(),
+            })
+        }
+    };
+
+    if security_config.sql_injection_protect {
+        println!("[MirseoDB] SQL injection protection enabled (SQL_INJECTON_PROTECT=1)");
+    } else {
+        println!("[MirseoDB] SQL injection protection disabled (SQL_INJECTON_PROTECT=0)");
+    }
+
+    if config.api_token.is_some() {
+        println!("[MirseoDB] API authentication enabled via MIRSEODB_API_TOKEN");
+    } else {
+        println!("[MirseoDB] API authentication disabled (set MIRSEODB_API_TOKEN to enable)");
+    }
+
+    let http = start_health_server(
+        bind_addr,
+        config.port,
+        Arc::clone(&databases),
+        parser,
+        route_config,
+        config.api_token,
+        config.cors_origin,
+    )
+    .map_err(|e| DatabaseError::NetworkError(format!("HTTP endpoint failed to start: {}", e)))?;
+
+    println!(
+        "[MirseoDB] HTTP endpoint ready: http://{}/health (and /query)",
+        http.local_addr()
+    );
+
+    Ok(ServerHandle {
+        databases,
+        database_name: config.database_name,
+        http,
+    })
+}
+
+pub fn initialize_database(db_name: &str) -> Result<Arc<RwLock<Database>>, DatabaseError> {
+    ConfigManager::ensure_exists()?;
+    AuthConfig::ensure_exists().map_err(DatabaseError::IoError)?;
+
+    println!("[MirseoDB] Loading database '{}'...", db_name);
+
+    let database = match Database::load(db_name.to_string()) {
+        Ok(db) => {
+            println!(
+                "[MirseoDB] Existing database '{}' loaded successfully",
+                db_name
+            );
+            db
+        }
+        Err(_) => {
+            println!("[MirseoDB] Creating new database '{}'", db_name);
+            Database::new(db_name.to_string())
+        }
+    };
+
+    Ok(Arc::new(RwLock::new(database)))
+}
@@ -0,0 +1,216 @@
+//! Small dependency-free LZ77 codec for `.mdb` files.
+//!
+//! Compressed layout: the original length (u32 LE), then groups of one flag byte
+//! followed by up to eight items. A clear flag bit is a literal byte; a set bit is
+//! a back-reference of u16 LE distance and one byte holding `length - MIN_MATCH`.
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = MIN_MATCH + u8::MAX as usize;
+const WINDOW_SIZE: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 32; // candidates checked per position
+
+/// Codec applied to the table section of a database file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    None,
+    Lz,
+}
+
+impl Codec {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" | "0" => Some(Self::None),
+            "lz" | "1" => Some(Self::Lz),
+            _ => None,
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Lz => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Lz),
+            _ => None,
+        }
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert_position(input: &[u8], head: &mut [usize], prev: &mut [usize], position: usize) {
+    if position + MIN_MATCH <= input.len() {
+        let slot = hash(&input[position..]);
+        prev[position] = head[slot];
+        head[slot] = position;
+    }
+}
+
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 8);
+    output.extend_from_slice(&(input.len() as u32).to_le_bytes());
+
+    // head: most recent position per hash; prev: previous position with the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; input.len()];
+
+    let mut flag_pos = output.len();
+    let mut flag_bit = 8;
+    let mut position = 0;
+
+    while position < input.len() {
+        if flag_bit == 8 {
+            flag_pos = output.len();
+            output.push(0);
+            flag_bit = 0;
+        }
+
+        let mut best_len = 0;
+        let mut best_distance = 0;
+
+        if position + MIN_MATCH <= input.len() {
+            let max_len = MAX_MATCH.min(input.len() - position);
+            let mut candidate = head[hash(&input[position..])];
+            let mut checked = 0;
+
+            while candidate != usize::MAX
+                && position - candidate <= WINDOW_SIZE
+                && checked < MAX_CHAIN
+            {
+                let len = input[candidate..]
+                    .iter()
+                    .zip(&input[position..position + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_distance = position - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                checked += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            output[flag_pos] |= 1 << flag_bit;
+            output.extend_from_slice(&(best_distance as u16).to_le_bytes());
+            output.push((best_len - MIN_MATCH) as u8);
+            for offset in 0..best_len {
+                insert_position(input, &mut head, &mut prev, position + offset);
+            }
+            position += best_len;
+        } else {
+            output.push(input[position]);
+            insert_position(input, &mut head, &mut prev, position);
+            position += 1;
+        }
+        flag_bit += 1;
+    }
+
+    output
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 4 {
+        return Err("Compressed data is missing its length header".to_string());
+    }
+
+    let expected_len = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    // The header is untrusted: no input byte decodes to more than MAX_MATCH bytes
+    if expected_len > (data.len() - 4).saturating_mul(MAX_MATCH) {
+        return Err(format!(
+            "Compressed data claims {} bytes, more than its {} bytes can hold",
+            expected_len,
+            data.len()
+        ));
+    }
+    // Let the buffer grow instead of reserving a highly compressible length at once
+    let mut output = Vec::with_capacity(expected_len.min(data.len().saturating_mul(4)));
+    let mut cursor = 4;
+
+    while output.len() < expected_len {
+        let flags = *data
+            .get(cursor)
+            .ok_or_else(|| "Compressed data ends early".to_string())?;
+        cursor += 1;
+
+        for bit in 0..8 {
+            if output.len() >= expected_len {
+                break;
+            }
+
+            if flags & (1 << bit) == 0 {
+                let byte = *data
+                    .get(cursor)
+                    .ok_or_else(|| "Compressed data ends early".to_string())?;
+                output.push(byte);
+                cursor += 1;
+            } else {
+                let reference = data
+                    .get(cursor..cursor + 3)
+                    .ok_or_else(|| "Compressed data ends early".to_string())?;
+                let distance = u16::from_le_bytes([reference[0], reference[1]]) as usize;
+                let len = reference[2] as usize + MIN_MATCH;
+                cursor += 3;
+
+                if distance == 0 || distance > output.len() {
+                    return Err("Compressed data has an invalid back-reference".to_string());
+                }
+
+                // Byte by byte: a match may overlap the bytes it produces
+                let start = output.len() - distance;
+                for offset in 0..len {
+                    output.push(output[start + offset]);
+                }
+            }
+        }
+    }
+
+    if output.len() != expected_len {
+        return Err("Compressed data length mismatch".to_string());
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_edge_cases() {
+        let long_run = vec![b'a'; 1000];
+        let mixed: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for input in [&b""[..], b"abc", b"abcabcabcabcabcabc", &long_run, &mixed] {
+            let compressed = compress(input);
+            assert_eq!(decompress(&compressed).unwrap(), input);
+        }
+
+        assert!(compress(&long_run).len() < 50);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_input() {
+        let compressed = compress(b"hello hello hello hello");
+        assert!(decompress(&compressed[..compressed.len() - 2]).is_err());
+        // A back-reference before the start of the output
+        assert!(decompress(&[8, 0, 0, 0, 0b1, 9, 0, 4]).is_err());
+        // A length header far beyond what the input could decode to
+        let error = decompress(&[0xFF, 0xFF, 0xFF, 0xFF, 0, b'a']).unwrap_err();
+        assert!(error.contains("claims 4294967295 bytes"), "{}", error);
+    }
+}
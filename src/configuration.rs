@@ -2,21 +2,94 @@ use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
-use super::core_types::DatabaseError;
+use super::compression::Codec;
+use super::core_types::{DatabaseError, UngroupedColumns};
+use super::indexing::IndexCheck;
+use super::routing::check_auth_webhook_url;
+use super::security::ControlCharPolicy;
 
 const CONFIG_DIR: &str = ".mirseoDB";
 const CONFIG_FILE: &str = "config.cfg";
 pub const SQL_INJECTION_KEY: &str = "SQL_INJECTON_PROTECT";
+pub const FSYNC_ON_SAVE_KEY: &str = "FSYNC_ON_SAVE";
+pub const MAX_QUERY_DEPTH_KEY: &str = "MAX_QUERY_DEPTH";
+pub const DEFAULT_MAX_QUERY_DEPTH: usize = 64;
+pub const REJECT_NUL_BYTES_KEY: &str = "REJECT_NUL_BYTES";
+pub const TEXT_CONTROL_CHARS_KEY: &str = "TEXT_CONTROL_CHARS";
+pub const MAX_TEXT_LENGTH_KEY: &str = "MAX_TEXT_LENGTH";
+pub const AUTH_WEBHOOK_URL_KEY: &str = "AUTH_WEBHOOK_URL";
+pub const COMPRESSION_KEY: &str = "COMPRESSION";
+pub const REPAIR_ON_LOAD_KEY: &str = "REPAIR_ON_LOAD";
+pub const CHECKPOINT_EVERY_WRITES_KEY: &str = "CHECKPOINT_EVERY_WRITES";
+pub const DEFAULT_CHECKPOINT_EVERY_WRITES: usize = 1000;
+pub const CHECKPOINT_INTERVAL_SECS_KEY: &str = "CHECKPOINT_INTERVAL_SECS";
+pub const DEFAULT_CHECKPOINT_INTERVAL_SECS: u64 = 60;
+pub const UNGROUPED_COLUMNS_KEY: &str = "UNGROUPED_COLUMNS";
+pub const EXPORT_RETENTION_SECS_KEY: &str = "EXPORT_RETENTION_SECS";
+pub const DEFAULT_EXPORT_RETENTION_SECS: u64 = 24 * 60 * 60;
+pub const MAX_QUERY_MEMORY_MB_KEY: &str = "MAX_QUERY_MEMORY_MB";
+pub const DEFAULT_MAX_QUERY_MEMORY_MB: usize = 512;
+pub const GZIP_MIN_BYTES_KEY: &str = "GZIP_MIN_BYTES";
+pub const DEFAULT_GZIP_MIN_BYTES: usize = 1024;
+pub const INDEX_CHECK_ON_LOAD_KEY: &str = "INDEX_CHECK_ON_LOAD";
 
 #[derive(Clone, Debug)]
 pub struct ConfigOptions {
     pub sql_injection_protect: bool,
+    /// fsync the database file after every save. Disabling trades crash safety for write latency.
+    pub fsync_on_save: bool,
+    /// Deepest parenthesis/condition nesting a query may use before it is rejected.
+    pub max_query_depth: usize,
+    /// Reject text values containing NUL bytes on INSERT/UPDATE.
+    pub reject_nul_bytes: bool,
+    /// allow, reject or strip other control characters in text values.
+    pub text_control_chars: ControlCharPolicy,
+    /// Longest text value accepted, in characters; 0 in the file means unlimited.
+    pub max_text_length: Option<usize>,
+    /// External endpoint that replaces built-in token checks when set.
+    pub auth_webhook_url: Option<String>,
+    /// Codec for database files: none (default) or lz.
+    pub compression: Codec,
+    /// Load the readable tables of a truncated or corrupt file instead of failing.
+    pub repair_on_load: bool,
+    /// Deferred ASYNC writes that trigger a checkpoint; 0 in the file means only
+    /// the timed flush writes them.
+    pub checkpoint_every_writes: Option<usize>,
+    /// Seconds between the heartbeat flushes of ASYNC changes.
+    pub checkpoint_interval_secs: u64,
+    /// strict (default) or relaxed handling of selected columns missing from GROUP BY.
+    pub ungrouped_columns: UngroupedColumns,
+    /// Seconds a file written by /export/file is kept; 0 in the file keeps them all.
+    pub export_retention_secs: Option<u64>,
+    /// Memory budget of one query's result rows, sorts and groups, in megabytes.
+    pub max_query_memory_mb: usize,
+    /// Smallest response body gzip-compressed for clients that accept it; 0 in the
+    /// file disables compression.
+    pub gzip_min_bytes: Option<usize>,
+    /// off (default), check or repair: compare every index with the rows after a
+    /// database is loaded. Reads each table once, so large databases open slower.
+    pub index_check_on_load: IndexCheck,
 }
 
 impl Default for ConfigOptions {
     fn default() -> Self {
         Self {
             sql_injection_protect: true,
+            fsync_on_save: true,
+            max_query_depth: DEFAULT_MAX_QUERY_DEPTH,
+            reject_nul_bytes: true,
+            text_control_chars: ControlCharPolicy::Allow,
+            max_text_length: None,
+            auth_webhook_url: None,
+            compression: Codec::None,
+            repair_on_load: false,
+            checkpoint_every_writes: Some(DEFAULT_CHECKPOINT_EVERY_WRITES),
+            checkpoint_interval_secs: DEFAULT_CHECKPOINT_INTERVAL_SECS,
+            ungrouped_columns: UngroupedColumns::Strict,
+            export_retention_secs: Some(DEFAULT_EXPORT_RETENTION_SECS),
+            max_query_memory_mb: DEFAULT_MAX_QUERY_MEMORY_MB,
+            gzip_min_bytes: Some(DEFAULT_GZIP_MIN_BYTES),
+            index_check_on_load: IndexCheck::Off,
         }
     }
 }
@@ -46,7 +119,16 @@ impl ConfigManager {
                 DatabaseError::IoError(format!("Failed to create config file: {}", e))
             })?;
 
-            let default_content = format!("{}=1\n", SQL_INJECTION_KEY);
+            let default_content = format!(
+                "{}=1\n{}=1\n{}={}\n{}=1\n{}=allow\n{}=0\n",
+                SQL_INJECTION_KEY,
+                FSYNC_ON_SAVE_KEY,
+                MAX_QUERY_DEPTH_KEY,
+                DEFAULT_MAX_QUERY_DEPTH,
+                REJECT_NUL_BYTES_KEY,
+                TEXT_CONTROL_CHARS_KEY,
+                MAX_TEXT_LENGTH_KEY
+            );
             file.write_all(default_content.as_bytes()).map_err(|e| {
                 DatabaseError::IoError(format!("Failed to write default config: {}", e))
             })?;
@@ -66,20 +148,83 @@ impl ConfigManager {
             return ConfigOptions::default();
         }
 
-        let enabled = contents
-            .lines()
-            .filter_map(|line| parse_key_value(line))
-            .find_map(|(key, value)| {
-                if key.eq_ignore_ascii_case(SQL_INJECTION_KEY) {
-                    Some(parse_bool_flag(&value))
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(true);
+        let value_of = |name: &str| {
+            contents
+                .lines()
+                .filter_map(parse_key_value)
+                .find_map(|(key, value)| {
+                    if key.eq_ignore_ascii_case(name) {
+                        Some(value)
+                    } else {
+                        None
+                    }
+                })
+        };
+        let flag = |name: &str| value_of(name).map(|value| parse_bool_flag(&value)).unwrap_or(true);
 
         ConfigOptions {
-            sql_injection_protect: enabled,
+            sql_injection_protect: flag(SQL_INJECTION_KEY),
+            fsync_on_save: flag(FSYNC_ON_SAVE_KEY),
+            max_query_depth: value_of(MAX_QUERY_DEPTH_KEY)
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|depth| *depth > 0)
+                .unwrap_or(DEFAULT_MAX_QUERY_DEPTH),
+            reject_nul_bytes: flag(REJECT_NUL_BYTES_KEY),
+            text_control_chars: value_of(TEXT_CONTROL_CHARS_KEY)
+                .and_then(|value| ControlCharPolicy::parse(&value))
+                .unwrap_or_default(),
+            max_text_length: value_of(MAX_TEXT_LENGTH_KEY)
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|length| *length > 0),
+            // A rejected URL is kept, so the webhook check fails closed
+            auth_webhook_url: value_of(AUTH_WEBHOOK_URL_KEY)
+                .filter(|url| !url.is_empty())
+                .inspect(|url| {
+                    if let Err(e) = check_auth_webhook_url(url) {
+                        eprintln!("[MirseoDB] {}; authenticated requests will be refused", e);
+                    }
+                }),
+            compression: value_of(COMPRESSION_KEY)
+                .and_then(|value| Codec::parse(&value))
+                .unwrap_or_default(),
+            repair_on_load: value_of(REPAIR_ON_LOAD_KEY)
+                .map(|value| parse_bool_flag(&value))
+                .unwrap_or(false),
+            checkpoint_every_writes: match value_of(CHECKPOINT_EVERY_WRITES_KEY)
+                .and_then(|value| value.parse::<usize>().ok())
+            {
+                Some(0) => None,
+                Some(writes) => Some(writes),
+                None => Some(DEFAULT_CHECKPOINT_EVERY_WRITES),
+            },
+            checkpoint_interval_secs: value_of(CHECKPOINT_INTERVAL_SECS_KEY)
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL_SECS),
+            ungrouped_columns: value_of(UNGROUPED_COLUMNS_KEY)
+                .and_then(|value| UngroupedColumns::parse(&value))
+                .unwrap_or_default(),
+            export_retention_secs: match value_of(EXPORT_RETENTION_SECS_KEY)
+                .and_then(|value| value.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(secs) => Some(secs),
+                None => Some(DEFAULT_EXPORT_RETENTION_SECS),
+            },
+            max_query_memory_mb: value_of(MAX_QUERY_MEMORY_MB_KEY)
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|mb| *mb > 0)
+                .unwrap_or(DEFAULT_MAX_QUERY_MEMORY_MB),
+            gzip_min_bytes: match value_of(GZIP_MIN_BYTES_KEY)
+                .and_then(|value| value.parse::<usize>().ok())
+            {
+                Some(0) => None,
+                Some(bytes) => Some(bytes),
+                None => Some(DEFAULT_GZIP_MIN_BYTES),
+            },
+            index_check_on_load: value_of(INDEX_CHECK_ON_LOAD_KEY)
+                .and_then(|value| IndexCheck::parse(&value))
+                .unwrap_or_default(),
         }
     }
 }
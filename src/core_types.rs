@@ -1,10 +1,12 @@
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum SqlValue {
     Integer(i64),
     Float(f64),
-    Text(String),
+    Text(Arc<str>),
     Boolean(bool),
     Null,
 }
@@ -21,6 +23,39 @@ pub struct Table {
     pub rows: Vec<Row>,
     pub index_manager: super::indexing::IndexManager,
     pub next_row_id: usize,
+    pub next_auto_increment: i64, // the value INSERT gives the AUTO_INCREMENT column next
+    pub durability: Durability,
+    pub dictionaries: super::dictionary::TableDictionaries,
+    pub comment: Option<String>,
+}
+
+/// When a table's writes reach disk. `Sync` tables are written and fsynced on
+/// every change; `Async` tables are written by the periodic heartbeat flush.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    #[default]
+    Sync,
+    Async,
+}
+
+/// What a grouped SELECT does with a plain column that is neither grouped nor
+/// aggregated. `Strict` rejects the query as standard SQL does; `Relaxed` takes
+/// the value from the first row of each group, like MySQL without ONLY_FULL_GROUP_BY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UngroupedColumns {
+    #[default]
+    Strict,
+    Relaxed,
+}
+
+impl UngroupedColumns {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "strict" => Some(Self::Strict),
+            "relaxed" => Some(Self::Relaxed),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +64,9 @@ pub struct ColumnDefinition {
     pub data_type: DataType,
     pub nullable: bool,
     pub primary_key: bool,
+    pub comment: Option<String>,
+    pub default: Option<SqlValue>, // filled in when INSERT leaves the column out
+    pub auto_increment: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +77,60 @@ pub enum DataType {
     Boolean,
 }
 
+impl DataType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataType::Integer => "INTEGER",
+            DataType::Float => "FLOAT",
+            DataType::Text => "TEXT",
+            DataType::Boolean => "BOOLEAN",
+        }
+    }
+}
+
+impl SqlValue {
+    /// Type of a concrete value; `None` for NULL, which carries no type.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            SqlValue::Integer(_) => Some(DataType::Integer),
+            SqlValue::Float(_) => Some(DataType::Float),
+            SqlValue::Text(_) => Some(DataType::Text),
+            SqlValue::Boolean(_) => Some(DataType::Boolean),
+            SqlValue::Null => None,
+        }
+    }
+
+    /// Approximate bytes the value holds, for query memory accounting.
+    pub fn estimated_size(&self) -> usize {
+        match self {
+            SqlValue::Integer(_) | SqlValue::Float(_) => 8,
+            SqlValue::Text(s) => s.len(),
+            SqlValue::Boolean(_) => 1,
+            SqlValue::Null => 0,
+        }
+    }
+}
+
+impl Row {
+    pub fn estimated_size(&self) -> usize {
+        let values: usize = self
+            .columns
+            .iter()
+            .map(|(name, value)| name.len() + value.estimated_size())
+            .sum();
+        std::mem::size_of::<Row>() + values
+    }
+}
+
+/// What an INSERT does when its row hits an existing primary key or unique value.
+#[derive(Debug, Clone)]
+pub enum OnConflict {
+    DoNothing,
+    /// Assignments for the existing row, with `VALUES(col)` / `EXCLUDED.col`
+    /// already replaced by the inserted value.
+    Update(Vec<(String, SqlValue)>),
+}
+
 #[derive(Debug, Clone)]
 pub enum SqlStatement {
     CreateDatabase {
@@ -47,17 +139,38 @@ pub enum SqlStatement {
     CreateTable {
         table_name: String,
         columns: Vec<ColumnDefinition>,
+        durability: Durability,
+        comment: Option<String>,
+        /// `IF NOT EXISTS`: an existing table is left as it is instead of being an error.
+        if_not_exists: bool,
     },
     Insert {
         table_name: String,
         columns: Vec<String>,
         values: Vec<SqlValue>,
+        on_conflict: Option<OnConflict>,
+        /// `ON CONFLICT (cols)`: the primary key or unique index the upsert
+        /// applies to; empty for any of them.
+        conflict_target: Vec<String>,
     },
     Select {
         table_name: String,
         columns: Vec<String>,
-        where_clause: Option<WhereClause>,
+        /// `(SELECT ...) [AS name]` items of the select list, each run once and
+        /// added to every result row under `name`.
+        scalar_subqueries: Vec<(String, SqlStatement)>,
+        /// `column [AS] alias` items as (column, alias); the result row holds the
+        /// value under the alias instead of the column name.
+        column_aliases: Vec<(String, String)>,
+        /// `SELECT DISTINCT`: repeated result rows are dropped before paging.
+        distinct: bool,
+        /// Aggregate items of the select list; with `group_by` empty they fold the
+        /// whole result into one row.
+        aggregates: Vec<Aggregate>,
+        group_by: Vec<String>,
+        where_clause: Option<WhereExpr>,
         optimization_hint: Option<QueryOptimizationHint>,
+        order_by: Option<Vec<OrderBy>>,
         limit: Option<usize>,
         offset: Option<usize>,
     },
@@ -70,48 +183,158 @@ pub enum SqlStatement {
         limit: Option<usize>,
         offset: Option<usize>,
     },
+    /// `CREATE [UNIQUE] INDEX`; one column makes a plain index, several a
+    /// composite one.
     CreateCompositeIndex {
         index_name: String,
         table_name: String,
         column_names: Vec<String>,
         is_unique: bool,
+        /// `COLLATE NOCASE`: text keys are case-folded, so a unique index treats
+        /// `'A'` and `'a'` as the same value. Single-column indexes only.
+        case_insensitive: bool,
     },
+    /// `table_name` is `None` when the statement does not name the table.
     DropIndex {
         index_name: String,
+        table_name: Option<String>,
     },
     Update {
         table_name: String,
         set_clauses: Vec<(String, SqlValue)>,
-        where_clause: Option<WhereClause>,
+        where_clause: Option<WhereExpr>,
     },
     Delete {
         table_name: String,
-        where_clause: Option<WhereClause>,
+        where_clause: Option<WhereExpr>,
     },
     DropTable {
         table_name: String,
+        /// `IF EXISTS`: a missing table is not an error.
+        if_exists: bool,
     },
     DropDatabase {
         database_name: String,
     },
+    /// Empties every table of the open database but keeps the schema. The name
+    /// must be the open database's, as confirmation.
+    ResetDatabase {
+        database_name: String,
+    },
+    /// Makes another database the default of the connection's later queries.
+    Use {
+        database_name: String,
+    },
     AlterTable {
         table_name: String,
         action: AlterAction,
     },
+    /// Recomputes column statistics for one table, or every table when `None`.
+    Analyze {
+        table_name: Option<String>,
+    },
+    /// `table_name` is `None` for global grants (`ON *`).
+    Grant {
+        privileges: Vec<String>,
+        table_name: Option<String>,
+        grantee: String,
+    },
+    Revoke {
+        privileges: Vec<String>,
+        table_name: Option<String>,
+        grantee: String,
+    },
+    /// `left UNION [ALL] right`. Longer chains nest on the left, and result
+    /// columns are named after the leftmost SELECT.
+    Union {
+        left: Box<SqlStatement>,
+        right: Box<SqlStatement>,
+        all: bool,
+    },
+    /// Transaction control: changes after BEGIN reach the disk on COMMIT, or are
+    /// discarded by ROLLBACK.
+    Begin,
+    Commit,
+    Rollback,
+    /// Writes and fsyncs the database file now
+    Checkpoint,
+    /// `SHOW TABLES`: one row per table name.
+    ShowTables,
+    /// `DESCRIBE <table>` or `SHOW COLUMNS FROM <table>`: one row per column.
+    Describe {
+        table_name: String,
+    },
+    /// `COMMENT ON TABLE | COLUMN`; `column_name` is `None` for the table itself
+    /// and `comment` is `None` for `IS NULL`, which removes the comment.
+    Comment {
+        table_name: String,
+        column_name: Option<String>,
+        comment: Option<String>,
+    },
+}
+
+/// An aggregate in a select list, e.g. `COUNT(*)` or `SUM(price) AS total`.
+/// `column` is `None` only for `COUNT(*)`; `name` is the result column.
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    pub column: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "COUNT" => Some(AggregateFunction::Count),
+            "SUM" => Some(AggregateFunction::Sum),
+            "AVG" => Some(AggregateFunction::Avg),
+            "MIN" => Some(AggregateFunction::Min),
+            "MAX" => Some(AggregateFunction::Max),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Avg => "AVG",
+            AggregateFunction::Min => "MIN",
+            AggregateFunction::Max => "MAX",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OrderBy {
     pub column: String,
     pub direction: SortDirection,
+    pub nulls: NullsOrder,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SortDirection {
     Asc,
     Desc,
 }
 
+/// Placement of NULLs for a sort key, independent of the sort direction.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum NullsOrder {
+    First,
+    #[default]
+    Last,
+}
+
 #[derive(Debug, Clone)]
 pub enum AlterAction {
     AddColumn { column: ColumnDefinition },
@@ -125,7 +348,9 @@ impl SqlStatement {
         match self {
             SqlStatement::DropTable { .. } => true,
             SqlStatement::DropDatabase { .. } => true,
+            SqlStatement::ResetDatabase { .. } => true,
             SqlStatement::AlterTable { .. } => true,
+            SqlStatement::Grant { .. } | SqlStatement::Revoke { .. } => true,
             SqlStatement::Delete {
                 where_clause: None, ..
             } => true, // WHERE 절이 없는 DELETE는 위험
@@ -136,6 +361,19 @@ impl SqlStatement {
         }
     }
 
+    /// SELECT, UNION and the schema listings only read tables, so they can
+    /// share the database lock.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            SqlStatement::Select { .. }
+                | SqlStatement::ComplexSelect { .. }
+                | SqlStatement::Union { .. }
+                | SqlStatement::ShowTables
+                | SqlStatement::Describe { .. }
+        )
+    }
+
     pub fn get_operation_name(&self) -> &'static str {
         match self {
             SqlStatement::CreateDatabase { .. } => "CREATE DATABASE",
@@ -143,13 +381,26 @@ impl SqlStatement {
             SqlStatement::Insert { .. } => "INSERT",
             SqlStatement::Select { .. } => "SELECT",
             SqlStatement::ComplexSelect { .. } => "COMPLEX SELECT",
-            SqlStatement::CreateCompositeIndex { .. } => "CREATE COMPOSITE INDEX",
+            SqlStatement::CreateCompositeIndex { .. } => "CREATE INDEX",
             SqlStatement::DropIndex { .. } => "DROP INDEX",
             SqlStatement::Update { .. } => "UPDATE",
             SqlStatement::Delete { .. } => "DELETE",
             SqlStatement::DropTable { .. } => "DROP TABLE",
             SqlStatement::DropDatabase { .. } => "DROP DATABASE",
+            SqlStatement::ResetDatabase { .. } => "RESET DATABASE",
+            SqlStatement::Use { .. } => "USE",
             SqlStatement::AlterTable { .. } => "ALTER TABLE",
+            SqlStatement::Analyze { .. } => "ANALYZE",
+            SqlStatement::Grant { .. } => "GRANT",
+            SqlStatement::Revoke { .. } => "REVOKE",
+            SqlStatement::Union { .. } => "UNION",
+            SqlStatement::Begin => "BEGIN",
+            SqlStatement::Commit => "COMMIT",
+            SqlStatement::Rollback => "ROLLBACK",
+            SqlStatement::Checkpoint => "CHECKPOINT",
+            SqlStatement::ShowTables => "SHOW TABLES",
+            SqlStatement::Describe { .. } => "DESCRIBE",
+            SqlStatement::Comment { .. } => "COMMENT",
         }
     }
 }
@@ -161,6 +412,68 @@ pub struct WhereClause {
     pub value: SqlValue,
 }
 
+/// A WHERE condition tree. The parser gives AND precedence over OR and keeps
+/// parenthesized groups as nested nodes.
+#[derive(Debug, Clone)]
+pub enum WhereExpr {
+    Comparison(WhereClause),
+    In {
+        column: String,
+        source: InSource,
+        negated: bool,
+    },
+    /// `column <op> (SELECT ...)`. The engine runs the subquery once and turns
+    /// the node into a `Comparison` against its single value.
+    ScalarComparison {
+        column: String,
+        operator: ComparisonOperator,
+        subquery: Box<SqlStatement>,
+    },
+    /// `column <op> NOW()` or `CURRENT_TIMESTAMP`. Like a scalar subquery, the
+    /// engine resolves it into a `Comparison` once per statement, against the
+    /// current UTC time as `'YYYY-MM-DD HH:MM:SS'` text.
+    CurrentTimeComparison {
+        column: String,
+        operator: ComparisonOperator,
+    },
+    /// `column [NOT] BETWEEN low AND high`, inclusive at both ends.
+    Between {
+        column: String,
+        low: SqlValue,
+        high: SqlValue,
+        negated: bool,
+    },
+    /// A bare BOOLEAN column used as the condition: `active` or `NOT active`.
+    BooleanColumn { column: String, negated: bool },
+    And(Vec<WhereExpr>),
+    Or(Vec<WhereExpr>),
+}
+
+/// The operand of `column IN (...)`.
+#[derive(Debug, Clone)]
+pub enum InSource {
+    Values(Vec<SqlValue>),
+    /// A non-correlated SELECT of one column. The engine runs it once before
+    /// scanning and replaces it with `Values`.
+    Subquery(Box<SqlStatement>),
+}
+
+impl WhereExpr {
+    /// The single comparison, for paths that only optimize simple predicates.
+    pub fn as_comparison(&self) -> Option<&WhereClause> {
+        match self {
+            WhereExpr::Comparison(clause) => Some(clause),
+            _ => None,
+        }
+    }
+}
+
+impl From<WhereClause> for WhereExpr {
+    fn from(clause: WhereClause) -> Self {
+        WhereExpr::Comparison(clause)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ComplexWhereClause {
     pub conditions: Vec<WhereCondition>,
@@ -218,6 +531,37 @@ pub struct TableScanOptions {
     pub collect_statistics: bool,
 }
 
+/// Bytes held by the result rows and intermediate structures (sort buffers,
+/// group maps) of one query. Charging past the limit fails the query with
+/// `QueryTooComplex`.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: usize,
+    used: Cell<usize>, // charged from `&self` scans
+}
+
+impl MemoryBudget {
+    pub fn new(limit_mb: usize) -> Self {
+        Self {
+            limit: limit_mb.saturating_mul(1024 * 1024),
+            used: Cell::new(0),
+        }
+    }
+
+    pub fn charge(&self, bytes: usize) -> Result<(), DatabaseError> {
+        let used = self.used.get().saturating_add(bytes);
+        self.used.set(used);
+        if used > self.limit {
+            return Err(DatabaseError::QueryTooComplex);
+        }
+        Ok(())
+    }
+
+    pub fn charge_row(&self, row: &Row) -> Result<(), DatabaseError> {
+        self.charge(row.estimated_size())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ComparisonOperator {
     Equal,
@@ -226,6 +570,9 @@ pub enum ComparisonOperator {
     LessThan,
     GreaterThanOrEqual,
     LessThanOrEqual,
+    /// `%` matches any run of characters and `_` exactly one; `\` escapes either.
+    Like,
+    NotLike,
 }
 
 #[derive(Debug)]
@@ -237,6 +584,7 @@ pub enum DatabaseError {
     UniqueConstraintViolation(String),
     PrimaryKeyViolation(String),
     IndexAlreadyExists(String),
+    TableAlreadyExists(String),
     InvalidDataType(String),
     PermissionDenied(String),
     IndexNotFound(String),
@@ -248,6 +596,8 @@ pub enum DatabaseError {
     SqlInjectionDetected,
     QueryTooComplex,
     InvalidIndexHint(String),
+    DatabaseNotFound(String),
+    DatabaseAlreadyExists(String),
 }
 
 impl std::fmt::Display for DatabaseError {
@@ -264,6 +614,7 @@ impl std::fmt::Display for DatabaseError {
                 write!(f, "Primary key violation: {}", msg)
             }
             DatabaseError::IndexAlreadyExists(name) => write!(f, "Index '{}' already exists", name),
+            DatabaseError::TableAlreadyExists(name) => write!(f, "Table '{}' already exists", name),
             DatabaseError::InvalidDataType(msg) => write!(f, "Invalid data type: {}", msg),
             DatabaseError::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
             DatabaseError::IndexNotFound(name) => write!(f, "Index '{}' not found", name),
@@ -277,6 +628,12 @@ impl std::fmt::Display for DatabaseError {
             DatabaseError::SqlInjectionDetected => write!(f, "SQL injection attempt detected"),
             DatabaseError::QueryTooComplex => write!(f, "Query too complex"),
             DatabaseError::InvalidIndexHint(msg) => write!(f, "Invalid index hint: {}", msg),
+            DatabaseError::DatabaseNotFound(name) => {
+                write!(f, "Database '{}' does not exist", name)
+            }
+            DatabaseError::DatabaseAlreadyExists(name) => {
+                write!(f, "Database '{}' already exists", name)
+            }
         }
     }
 }
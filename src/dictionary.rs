@@ -0,0 +1,124 @@
+//! Dictionary encoding for low-cardinality text columns. While a column has at
+//! most `MAX_DICTIONARY_SIZE` distinct values, rows holding the same text share
+//! one `Arc<str>` instead of each owning a copy. Queries see ordinary
+//! `SqlValue::Text` values either way. On disk values are written out in full
+//! and re-interned when the table is loaded.
+
+use super::core_types::{Row, SqlValue};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Distinct values a column may reach before it stops being dictionary-encoded.
+pub const MAX_DICTIONARY_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Default)]
+struct ColumnDictionary {
+    values: HashSet<Arc<str>>,
+    /// Set once the column exceeds `MAX_DICTIONARY_SIZE`; the dictionary is
+    /// dropped and the column is left alone from then on.
+    abandoned: bool,
+}
+
+impl ColumnDictionary {
+    fn intern(&mut self, value: &mut SqlValue) {
+        let SqlValue::Text(text) = value else {
+            return;
+        };
+        if self.abandoned {
+            return;
+        }
+
+        if let Some(shared) = self.values.get(&**text) {
+            *text = Arc::clone(shared);
+        } else if self.values.len() < MAX_DICTIONARY_SIZE {
+            self.values.insert(Arc::clone(text));
+        } else {
+            self.values = HashSet::new();
+            self.abandoned = true;
+        }
+    }
+}
+
+/// Per-column dictionaries for one table, created lazily as text is written.
+#[derive(Debug, Clone, Default)]
+pub struct TableDictionaries {
+    columns: HashMap<String, ColumnDictionary>,
+}
+
+impl TableDictionaries {
+    /// Dictionaries for existing rows, interning their text values in place.
+    pub fn build(rows: &mut [Row]) -> Self {
+        let mut dictionaries = Self::default();
+        for row in rows {
+            dictionaries.intern_row(&mut row.columns);
+        }
+        dictionaries
+    }
+
+    pub fn intern(&mut self, column: &str, value: &mut SqlValue) {
+        if !matches!(value, SqlValue::Text(_)) {
+            return;
+        }
+        if let Some(dictionary) = self.columns.get_mut(column) {
+            dictionary.intern(value);
+        } else {
+            self.columns
+                .entry(column.to_string())
+                .or_default()
+                .intern(value);
+        }
+    }
+
+    pub fn intern_row(&mut self, columns: &mut HashMap<String, SqlValue>) {
+        for (column, value) in columns.iter_mut() {
+            self.intern(column, value);
+        }
+    }
+
+    /// Distinct values in `column`'s dictionary, or `None` when the column is not
+    /// dictionary-encoded.
+    pub fn dictionary_size(&self, column: &str) -> Option<usize> {
+        self.columns
+            .get(column)
+            .filter(|dictionary| !dictionary.abandoned)
+            .map(|dictionary| dictionary.values.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_row(status: &str, note: String) -> Row {
+        Row {
+            columns: [
+                ("status".to_string(), SqlValue::Text(status.into())),
+                ("note".to_string(), SqlValue::Text(note.into())),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn text_of<'a>(row: &'a Row, column: &str) -> &'a Arc<str> {
+        match row.columns.get(column) {
+            Some(SqlValue::Text(text)) => text,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_low_cardinality_columns_share_strings() {
+        let statuses = ["open", "closed", "pending"];
+        let mut rows: Vec<Row> = (0..MAX_DICTIONARY_SIZE + 10)
+            .map(|i| text_row(statuses[i % statuses.len()], format!("note {}", i)))
+            .collect();
+        let dictionaries = TableDictionaries::build(&mut rows);
+
+        assert!(Arc::ptr_eq(text_of(&rows[0], "status"), text_of(&rows[3], "status")));
+        assert_eq!(&**text_of(&rows[4], "status"), "closed");
+        // `note` outgrew the dictionary, so only `status` is still encoded
+        assert_eq!(dictionaries.dictionary_size("status"), Some(3));
+        assert_eq!(dictionaries.dictionary_size("note"), None);
+    }
+}
@@ -1,14 +1,23 @@
+use super::auth::AuthConfig;
 use super::bloom_filter::{ColumnBloomFilter, ChunkedTableScanner, ScanStatistics};
 use super::configuration::ConfigManager;
 use super::core_types::{
-    ColumnDefinition, ComparisonOperator, DatabaseError, Row, SqlStatement, SqlValue, Table,
-    WhereClause, TableScanOptions,
+    Aggregate, AggregateFunction, ColumnDefinition, ComparisonOperator, DataType, DatabaseError,
+    Durability, InSource, MemoryBudget, NullsOrder, OnConflict, OrderBy, Row, SortDirection,
+    SqlStatement,
+    SqlValue, Table, UngroupedColumns, WhereClause, WhereExpr, TableScanOptions,
 };
-use super::indexing::{IndexKey, IndexManager};
+use super::dictionary::TableDictionaries;
+use super::indexing::{IndexCheck, IndexKey, IndexManager, RebuildProgress, RowChange};
 use super::persistence::StorageEngine;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Instant;
+use super::security::{normalize_identifier, normalize_table_name, ValueValidator};
+use super::smart_parser::{split_statements, AnySQL, DetectedDialect};
+use super::statistics::{compare_same_type, TableStatistics};
+use super::wal::{self, LoggedStatement, WriteAheadLog};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 pub struct Database {
     pub name: String,
@@ -19,13 +28,39 @@ pub struct Database {
     bloom_filters: HashMap<String, ColumnBloomFilter>,
     table_scan_options: TableScanOptions,
     scan_statistics: HashMap<String, ScanStatistics>,
+    pending_flush: bool, // ASYNC table changes not yet written to disk
+    parser: Option<AnySQL>, // created on first `query` call
+    value_validator: ValueValidator,
+    column_statistics: HashMap<String, TableStatistics>, // computed on demand, dropped on writes
+    transaction: Option<Transaction>,
+    wal: WriteAheadLog,
+    replaying: bool, // applying write-ahead log records on load; saves are held back
+    last_checkpoint: SystemTime, // last CHECKPOINT, or when the database was opened
+    checkpoint_every_writes: Option<usize>, // deferred writes that trigger a checkpoint
+    checkpoint_interval: Duration,
+    deferred_writes: usize, // ASYNC changes since the file was last written
+    ungrouped_columns: UngroupedColumns,
+    memory_limit_mb: Option<usize>, // per-request override of `max_memory_mb`
+}
+
+/// An open BEGIN. Writes inside it are not persisted until COMMIT; ROLLBACK puts
+/// `snapshot` back in place of the tables.
+struct Transaction {
+    snapshot: HashMap<String, Table>,
+    /// Client session that opened it, for servers sharing one `Database`
+    session: Option<String>,
+    last_used: Instant,
+    dirty: bool, // a save was skipped, so COMMIT must write the file
+    log: Vec<LoggedStatement>, // write-ahead log records appended at COMMIT
 }
 
 impl Database {
     pub fn new(name: String) -> Self {
+        let config = ConfigManager::load();
         Self {
             name: name.clone(),
             tables: HashMap::new(),
+            wal: WriteAheadLog::new(&name, config.fsync_on_save),
             storage: StorageEngine::new(name),
             column_cache: HashMap::new(),
             query_cache: HashMap::new(),
@@ -34,14 +69,35 @@ impl Database {
             table_scan_options: TableScanOptions {
                 use_bloom_filter: true,
                 chunk_size: 1000,
-                max_memory_mb: 256,
+                max_memory_mb: config.max_query_memory_mb,
                 enable_early_termination: true,
                 collect_statistics: true,
             },
             scan_statistics: HashMap::new(),
+            pending_flush: false,
+            parser: None,
+            value_validator: ValueValidator::from_config(&config),
+            column_statistics: HashMap::new(),
+            transaction: None,
+            replaying: false,
+            last_checkpoint: SystemTime::now(),
+            checkpoint_every_writes: config.checkpoint_every_writes,
+            checkpoint_interval: Duration::from_secs(config.checkpoint_interval_secs),
+            deferred_writes: 0,
+            ungrouped_columns: config.ungrouped_columns,
+            memory_limit_mb: None,
         }
     }
 
+    /// A database whose statements are not written to a write-ahead log, for
+    /// tests that never reload it.
+    #[cfg(test)]
+    pub(crate) fn in_memory(name: &str) -> Self {
+        let mut db = Self::new(name.to_string());
+        db.wal = WriteAheadLog::detached();
+        db
+    }
+
     pub fn create_database(name: String) -> Result<Self, DatabaseError> {
         use std::fs;
         use std::path::Path;
@@ -57,10 +113,19 @@ impl Database {
         // Create the database file
         let db_file_path = mirseo_db_dir.join(format!("{}.mdb", name));
 
-        // Create empty database file
-        fs::File::create(&db_file_path).map_err(|e| {
-            DatabaseError::IoError(format!("Failed to create database file: {}", e))
-        })?;
+        // Create empty database file; an existing one is never truncated
+        fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&db_file_path)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    DatabaseError::DatabaseAlreadyExists(name.clone())
+                }
+                _ => DatabaseError::IoError(format!("Failed to create database file: {}", e)),
+            })?;
+        // A log left by an earlier database of the same name must not be replayed
+        wal::remove_log(&wal::log_path(&name))?;
 
         // Create route.cfg file in .mirseoDB directory
         let route_cfg_path = mirseo_db_dir.join("route.cfg");
@@ -88,9 +153,14 @@ impl Database {
 
     pub fn load(name: String) -> Result<Self, DatabaseError> {
         let storage = StorageEngine::new(name.clone());
-        let tables = storage.load_tables()?;
+        let (tables, file_lsn) = storage.load_tables()?;
+        let config = ConfigManager::load();
+        let mut wal = WriteAheadLog::new(&name, config.fsync_on_save);
+        wal.advance_to(file_lsn);
+        let records = wal.read_records()?;
 
         let mut db = Self {
+            wal,
             name,
             tables,
             storage,
@@ -100,22 +170,239 @@ impl Database {
             table_scan_options: crate::core_types::TableScanOptions {
                 use_bloom_filter: true,
                 chunk_size: 10000,
-                max_memory_mb: 512,
+                max_memory_mb: config.max_query_memory_mb,
                 enable_early_termination: true,
                 collect_statistics: true,
             },
             scan_statistics: HashMap::new(),
+            pending_flush: false,
+            parser: None,
+            value_validator: ValueValidator::from_config(&config),
+            column_statistics: HashMap::new(),
+            transaction: None,
+            replaying: false,
+            last_checkpoint: SystemTime::now(),
+            checkpoint_every_writes: config.checkpoint_every_writes,
+            checkpoint_interval: Duration::from_secs(config.checkpoint_interval_secs),
+            deferred_writes: 0,
+            ungrouped_columns: config.ungrouped_columns,
+            memory_limit_mb: None,
         };
 
+        db.replay(records, file_lsn)?;
+        if config.index_check_on_load != IndexCheck::Off {
+            db.check_indexes(config.index_check_on_load == IndexCheck::Repair)?;
+        }
         db.rebuild_column_cache();
         db.rebuild_bloom_filters();
 
         Ok(db)
     }
 
+    /// Compares every index with its table's rows and logs each mismatch. With
+    /// `repair` the indexes of the affected tables are rebuilt from the rows.
+    /// Returns the number of mismatches found.
+    fn check_indexes(&mut self, repair: bool) -> Result<usize, DatabaseError> {
+        const LOGGED_PER_TABLE: usize = 10;
+
+        let mut table_names: Vec<String> = self.tables.keys().cloned().collect();
+        table_names.sort();
+
+        let mut total = 0;
+        for table_name in table_names {
+            let Some(table) = self.tables.get_mut(&table_name) else {
+                continue;
+            };
+            let mismatches = table.index_manager.find_mismatches(&table.rows);
+            if mismatches.is_empty() {
+                continue;
+            }
+
+            for mismatch in mismatches.iter().take(LOGGED_PER_TABLE) {
+                eprintln!("[MirseoDB] Table '{}': {}", table_name, mismatch);
+            }
+            if mismatches.len() > LOGGED_PER_TABLE {
+                eprintln!(
+                    "[MirseoDB] Table '{}': {} more index mismatches not shown",
+                    table_name,
+                    mismatches.len() - LOGGED_PER_TABLE
+                );
+            }
+            if repair {
+                reindex_table(table)?;
+                println!("[MirseoDB] Rebuilt the indexes of table '{}'", table_name);
+            }
+            total += mismatches.len();
+        }
+
+        println!(
+            "[MirseoDB] Index check of '{}': {} mismatches{}",
+            self.name,
+            total,
+            if repair && total > 0 { ", repaired" } else { "" }
+        );
+        Ok(total)
+    }
+
+    /// Re-applies the logged statements the database file does not include yet,
+    /// then saves once so the log can be dropped.
+    fn replay(&mut self, records: Vec<wal::WalRecord>, file_lsn: u64) -> Result<(), DatabaseError> {
+        let records: Vec<_> = records.into_iter().filter(|record| record.lsn > file_lsn).collect();
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let parser = AnySQL::new();
+        self.replaying = true;
+        for record in &records {
+            let logged = &record.statement;
+            let result = parser
+                .parse_with_params(&logged.sql, logged.dialect.clone(), &logged.params)
+                .and_then(|(statement, _)| self.execute(statement));
+            // The statement failed the same way when it was first run
+            if let Err(err) = result {
+                eprintln!(
+                    "[MirseoDB] Write-ahead log record {} not replayed: {}",
+                    record.lsn, err
+                );
+            }
+        }
+        self.replaying = false;
+
+        println!(
+            "[MirseoDB] Replayed {} write-ahead log records into '{}'",
+            records.len(),
+            self.name
+        );
+        self.save_all()
+    }
+
+    /// Parses `sql` with AnySQL and executes it directly, for in-process use.
+    /// There is no HTTP, JSON, auth or 2FA layer on this path.
+    pub fn query(&mut self, sql: &str) -> Result<Vec<Row>, DatabaseError> {
+        self.query_with_params(sql, &[])
+    }
+
+    /// Like `query`, with the `?` placeholders in `sql` bound to `params` in order.
+    pub fn query_with_params(
+        &mut self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> Result<Vec<Row>, DatabaseError> {
+        let (statement, _) = self
+            .parser
+            .get_or_insert_with(AnySQL::new)
+            .parse_with_params(sql, None, params)?;
+        self.execute_logged(statement, sql, params, None)
+    }
+
+    /// Executes `statement`, first recording `sql` and its `params` in the
+    /// write-ahead log if the statement changes tables. Inside a transaction the
+    /// record is held until COMMIT, so a rolled back statement is never replayed.
+    /// `dialect` is the one the client chose, `None` when it was detected.
+    pub fn execute_logged(
+        &mut self,
+        statement: SqlStatement,
+        sql: &str,
+        params: &[SqlValue],
+        dialect: Option<&DetectedDialect>,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        if !is_logged(&statement) {
+            return self.execute(statement);
+        }
+        let mut statement = statement;
+        let mut sql = sql.trim().to_string();
+        // A replay must compare with the time the statement first ran at
+        if let SqlStatement::Update {
+            where_clause: Some(where_clause),
+            ..
+        }
+        | SqlStatement::Delete {
+            where_clause: Some(where_clause),
+            ..
+        } = &mut statement
+        {
+            let now = current_timestamp();
+            if resolve_current_time(where_clause, &now) {
+                sql = with_timestamp_literals(&sql, &now);
+            }
+        }
+
+        let record = LoggedStatement {
+            dialect: dialect.cloned(),
+            sql,
+            params: params.to_vec(),
+        };
+        if self.transaction.is_none() {
+            self.wal.append(&[record])?;
+            return self.execute(statement);
+        }
+
+        let rows = self.execute(statement)?;
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.log.push(record);
+        }
+        Ok(rows)
+    }
+
+    /// Lowers the memory budget of the statements executed next to `limit_mb`
+    /// megabytes; it never exceeds `MAX_QUERY_MEMORY_MB`. `None` restores that.
+    pub fn set_memory_limit_mb(&mut self, limit_mb: Option<usize>) {
+        self.memory_limit_mb = limit_mb;
+    }
+
+    /// Runs `statement` under a fresh memory budget, which the UNION branches
+    /// and subqueries it runs share.
     pub fn execute(&mut self, statement: SqlStatement) -> Result<Vec<Row>, DatabaseError> {
+        let budget = self.memory_budget(self.memory_limit_mb);
+        self.execute_statement(statement, &budget)
+    }
+
+    /// Runs a SELECT or UNION without `&mut self`, so readers holding a shared
+    /// lock can run side by side. `memory_limit_mb` lowers the budget like
+    /// `set_memory_limit_mb` does for `execute`; other statements are rejected.
+    pub fn execute_read(
+        &self,
+        statement: SqlStatement,
+        memory_limit_mb: Option<usize>,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        self.read_statement(statement, &self.memory_budget(memory_limit_mb))
+    }
+
+    fn memory_budget(&self, limit_mb: Option<usize>) -> MemoryBudget {
+        let max_mb = self.table_scan_options.max_memory_mb;
+        MemoryBudget::new(limit_mb.map_or(max_mb, |mb| mb.min(max_mb)))
+    }
+
+    fn execute_statement(
+        &mut self,
+        statement: SqlStatement,
+        budget: &MemoryBudget,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.last_used = Instant::now();
+            if matches!(
+                statement,
+                SqlStatement::CreateDatabase { .. }
+                    | SqlStatement::DropDatabase { .. }
+                    | SqlStatement::Grant { .. }
+                    | SqlStatement::Revoke { .. }
+            ) {
+                return Err(DatabaseError::InvalidSqlSyntax(format!(
+                    "{} cannot be rolled back, so it is not allowed inside a transaction",
+                    statement.get_operation_name()
+                )));
+            }
+        }
+
         match statement {
             SqlStatement::CreateDatabase { database_name } => {
+                // Recreating a database would truncate its file
+                if database_name.eq_ignore_ascii_case(&self.name)
+                    || StorageEngine::stored_database_name(&database_name).is_some()
+                {
+                    return Err(DatabaseError::DatabaseAlreadyExists(database_name));
+                }
                 // Create the database file in .mirseoDB directory
                 Self::create_database(database_name)?;
                 Ok(vec![])
@@ -123,31 +410,74 @@ impl Database {
             SqlStatement::CreateTable {
                 table_name,
                 columns,
+                durability,
+                comment,
+                if_not_exists,
             } => {
-                self.create_table_with_indexes(table_name, columns)?;
+                // Checked under the same lock as the insert, so concurrent
+                // creates of one table cannot both succeed
+                if self.tables.contains_key(&table_name) {
+                    if if_not_exists {
+                        return Ok(vec![]);
+                    }
+                    return Err(DatabaseError::TableAlreadyExists(table_name));
+                }
+                self.create_table_with_indexes(table_name, columns, durability, comment)?;
                 Ok(vec![])
             }
             SqlStatement::Insert {
                 table_name,
                 columns,
-                values,
+                mut values,
+                on_conflict,
+                conflict_target,
             } => {
-                self.insert_row_with_indexes(table_name, columns, values)?;
-                Ok(vec![])
+                for (column, value) in columns.iter().zip(values.iter_mut()) {
+                    self.value_validator.check(column, value)?;
+                }
+                if let Some(on_conflict) = on_conflict {
+                    if let Some(row_index) =
+                        self.conflicting_row(&table_name, &conflict_target, &columns, &values)?
+                    {
+                        if let OnConflict::Update(mut set_clauses) = on_conflict {
+                            for (column, value) in set_clauses.iter_mut() {
+                                self.value_validator.check(column, value)?;
+                            }
+                            self.update_rows_with_indexes(
+                                &table_name,
+                                vec![row_index],
+                                set_clauses,
+                            )?;
+                        }
+                        return Ok(vec![]);
+                    }
+                }
+                let generated = self.insert_row_with_indexes(table_name, columns, values)?;
+                // The generated id is the result, like RETURNING would give it
+                Ok(generated
+                    .map(|(column, id)| Row {
+                        columns: HashMap::from([(column, SqlValue::Integer(id))]),
+                    })
+                    .into_iter()
+                    .collect())
             }
-            SqlStatement::Select {
-                table_name,
-                columns,
-                where_clause,
-                optimization_hint,
-                limit,
-                offset,
-            } => self.select_with_advanced_scan(&table_name, &columns, where_clause.as_ref(), limit, offset),
+            statement @ (SqlStatement::Select { .. }
+            | SqlStatement::ComplexSelect { .. }
+            | SqlStatement::Union { .. }
+            | SqlStatement::ShowTables
+            | SqlStatement::Describe { .. }) => self.read_statement(statement, budget),
             SqlStatement::Update {
                 table_name,
-                set_clauses,
-                where_clause,
+                mut set_clauses,
+                mut where_clause,
             } => {
+                if let Some(where_clause) = where_clause.as_mut() {
+                    self.resolve_subqueries(where_clause, budget)?;
+                }
+                for (column, value) in set_clauses.iter_mut() {
+                    self.value_validator.check(column, value)?;
+                }
+
                 let indices_to_update: Vec<usize> = if let Some(ref where_clause) = where_clause {
                     let table = self
                         .tables
@@ -173,25 +503,16 @@ impl Database {
                     (0..self.tables.get(&table_name).unwrap().rows.len()).collect()
                 };
 
-                let table = self
-                    .tables
-                    .get_mut(&table_name)
-                    .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
-
-                for index in indices_to_update {
-                    let row = &mut table.rows[index];
-                    for (column_name, new_value) in &set_clauses {
-                        row.columns.insert(column_name.clone(), new_value.clone());
-                    }
-                }
-
-                self.storage.save_tables(&self.tables)?;
+                self.update_rows_with_indexes(&table_name, indices_to_update, set_clauses)?;
                 Ok(vec![])
             }
             SqlStatement::Delete {
                 table_name,
-                where_clause,
+                mut where_clause,
             } => {
+                if let Some(where_clause) = where_clause.as_mut() {
+                    self.resolve_subqueries(where_clause, budget)?;
+                }
                 let indices_to_delete: Vec<usize> = if let Some(ref where_clause) = where_clause {
                     let table = self
                         .tables
@@ -229,21 +550,67 @@ impl Database {
                         table.rows.remove(index);
                     }
                 }
+                reindex_table(table)?;
 
-                self.storage.save_tables(&self.tables)?;
+                self.persist_changes(&table_name)?;
                 Ok(vec![])
             }
-            SqlStatement::DropTable { table_name } => {
-                self.tables.remove(&table_name);
-                self.storage.save_tables(&self.tables)?;
+            SqlStatement::DropTable {
+                table_name,
+                if_exists,
+            } => {
+                if self.tables.remove(&table_name).is_none() {
+                    if if_exists {
+                        return Ok(vec![]);
+                    }
+                    return Err(DatabaseError::TableNotFound(table_name));
+                }
+                // A table created later under the same name must not see these
+                self.column_cache.remove(&table_name);
+                self.bloom_filters.remove(&table_name);
+                self.column_statistics.remove(&table_name);
+                self.query_cache.clear();
+                self.save_all()?;
                 Ok(vec![])
             }
             SqlStatement::DropDatabase { database_name } => {
-                // Drop database is a dangerous operation - clear all tables
+                if !database_name.eq_ignore_ascii_case(&self.name) {
+                    // Another database only loses its file; the open one is untouched
+                    if StorageEngine::remove_database_file(&database_name)? {
+                        return Ok(vec![]);
+                    }
+                    return Err(DatabaseError::DatabaseNotFound(database_name));
+                }
+
                 self.tables.clear();
-                self.storage.save_tables(&self.tables)?;
+                self.column_cache.clear();
+                self.query_cache.clear();
+                self.bloom_filters.clear();
+                self.column_statistics.clear();
+                self.pending_flush = false;
+                StorageEngine::remove_database_file(&self.name)?;
                 Ok(vec![])
             }
+            SqlStatement::ResetDatabase { database_name } => {
+                if !database_name.eq_ignore_ascii_case(&self.name) {
+                    return Err(DatabaseError::InvalidSqlSyntax(format!(
+                        "RESET DATABASE must name the open database '{}', not '{}'",
+                        self.name, database_name
+                    )));
+                }
+                self.reset_tables()
+            }
+            // Switching databases is up to the holder of several; on its own a
+            // database can only be used by its own name
+            SqlStatement::Use { database_name } => {
+                if database_name.eq_ignore_ascii_case(&self.name) {
+                    return Ok(vec![]);
+                }
+                Err(DatabaseError::InvalidSqlSyntax(format!(
+                    "USE {} needs a server holding several databases; this is '{}'",
+                    database_name, self.name
+                )))
+            }
             SqlStatement::AlterTable { table_name, action } => {
                 use super::core_types::AlterAction;
 
@@ -269,7 +636,7 @@ impl Database {
                         let default_value = match column.data_type {
                             super::core_types::DataType::Integer => SqlValue::Integer(0),
                             super::core_types::DataType::Float => SqlValue::Float(0.0),
-                            super::core_types::DataType::Text => SqlValue::Text("".to_string()),
+                            super::core_types::DataType::Text => SqlValue::Text("".into()),
                             super::core_types::DataType::Boolean => SqlValue::Boolean(false),
                         };
 
@@ -312,37 +679,482 @@ impl Database {
                         self.column_cache.insert(table_name.clone(), Arc::new(column_names));
                     }
                 }
+                // Columns were added, dropped or retyped; start the dictionaries over
+                table.dictionaries = TableDictionaries::build(&mut table.rows);
 
-                self.storage.save_tables(&self.tables)?;
+                self.persist_changes(&table_name)?;
                 Ok(vec![])
             }
-
-            SqlStatement::ComplexSelect {
-                table_name: _,
-                columns: _,
-                complex_where: _,
-                optimization_hint: _,
-                order_by: _,
-                limit: _,
-                offset: _,
+            SqlStatement::CreateCompositeIndex {
+                index_name,
+                table_name,
+                column_names,
+                is_unique,
+                case_insensitive,
             } => {
+                self.create_index(
+                    index_name,
+                    &table_name,
+                    column_names,
+                    is_unique,
+                    case_insensitive,
+                )?;
                 Ok(vec![])
             }
-            SqlStatement::CreateCompositeIndex {
-                index_name: _,
-                table_name: _,
-                column_names: _,
-                is_unique: _,
+            SqlStatement::DropIndex {
+                index_name,
+                table_name,
+            } => {
+                self.drop_index(&index_name, table_name.as_deref())?;
+                Ok(vec![])
+            }
+            SqlStatement::Grant {
+                privileges,
+                table_name,
+                grantee,
+            } => {
+                let mut auth_config = AuthConfig::load().map_err(DatabaseError::IoError)?;
+                auth_config
+                    .grant(&grantee, &privileges, table_name.as_deref())
+                    .map_err(DatabaseError::IoError)?;
+                Ok(vec![])
+            }
+            SqlStatement::Revoke {
+                privileges,
+                table_name,
+                grantee,
+            } => {
+                let mut auth_config = AuthConfig::load().map_err(DatabaseError::IoError)?;
+                auth_config
+                    .revoke(&grantee, &privileges, table_name.as_deref())
+                    .map_err(DatabaseError::IoError)?;
+                Ok(vec![])
+            }
+            SqlStatement::Begin => {
+                self.begin_transaction(None)?;
+                Ok(vec![])
+            }
+            SqlStatement::Commit => {
+                self.commit_transaction()?;
+                Ok(vec![])
+            }
+            SqlStatement::Rollback => {
+                self.rollback_transaction()?;
+                Ok(vec![])
+            }
+            SqlStatement::Comment {
+                table_name,
+                column_name,
+                comment,
             } => {
+                let table = self
+                    .tables
+                    .get_mut(&table_name)
+                    .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
+                match column_name {
+                    Some(column_name) => {
+                        table
+                            .columns
+                            .iter_mut()
+                            .find(|column| column.name == column_name)
+                            .ok_or(DatabaseError::ColumnNotFound(column_name))?
+                            .comment = comment;
+                    }
+                    None => table.comment = comment,
+                }
+                self.persist_changes(&table_name)?;
                 Ok(vec![])
             }
-            SqlStatement::DropIndex { index_name: _ } => {
+            SqlStatement::Checkpoint => {
+                let checkpoint = self.checkpoint()?;
+                let mut columns = HashMap::new();
+                columns.insert("lsn".to_string(), SqlValue::Integer(checkpoint.lsn as i64));
+                columns.insert(
+                    "bytes_written".to_string(),
+                    SqlValue::Integer(checkpoint.bytes_written as i64),
+                );
+                Ok(vec![Row { columns }])
+            }
+            SqlStatement::Analyze { table_name } => {
+                let table_names: Vec<String> = match table_name {
+                    Some(table_name) => vec![table_name],
+                    None => self.tables.keys().cloned().collect(),
+                };
+
+                for table_name in table_names {
+                    let table = self
+                        .tables
+                        .get(&table_name)
+                        .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
+                    let statistics = TableStatistics::compute(table);
+                    self.column_statistics.insert(table_name, statistics);
+                }
                 Ok(vec![])
             }
         }
     }
 
+    /// The SELECT side of `execute_statement`, which needs only `&self`.
+    fn read_statement(
+        &self,
+        statement: SqlStatement,
+        budget: &MemoryBudget,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        let distinct_columns = match &statement {
+            SqlStatement::Select { distinct: true, .. } => Some(self.output_columns(&statement)?),
+            _ => None,
+        };
+        match statement {
+            SqlStatement::Select {
+                table_name,
+                columns,
+                scalar_subqueries,
+                column_aliases,
+                distinct: _,
+                aggregates,
+                group_by,
+                mut where_clause,
+                optimization_hint,
+                order_by,
+                limit,
+                offset,
+            } => {
+                // DISTINCT pages the deduplicated rows, so the scan must not page first
+                let (scan_limit, scan_offset) = match distinct_columns {
+                    Some(_) => (None, None),
+                    None => (limit, offset),
+                };
+                if let Some(where_clause) = where_clause.as_mut() {
+                    self.resolve_subqueries(where_clause, budget)?;
+                }
+                let mut scalar_values = Vec::with_capacity(scalar_subqueries.len());
+                for (name, subquery) in scalar_subqueries {
+                    let value =
+                        self.scalar_subquery_value(subquery, budget)?.unwrap_or(SqlValue::Null);
+                    scalar_values.push((name, value));
+                }
+
+                let columns = self.resolve_projection(&table_name, &columns)?;
+                let mut rows = if !aggregates.is_empty() || !group_by.is_empty() {
+                    self.select_grouped(
+                        &table_name,
+                        &columns,
+                        &aggregates,
+                        &group_by,
+                        where_clause.as_ref(),
+                        order_by.as_deref().unwrap_or_default(),
+                        budget,
+                    )
+                    .map(|rows| {
+                        rows.into_iter()
+                            .skip(scan_offset.unwrap_or(0))
+                            .take(scan_limit.unwrap_or(usize::MAX))
+                            .collect()
+                    })
+                } else {
+                    match order_by {
+                        Some(order_by) if !order_by.is_empty() => self.select_ordered(
+                            &table_name,
+                            &columns,
+                            where_clause.as_ref(),
+                            &order_by,
+                            scan_limit,
+                            scan_offset,
+                            budget,
+                        ),
+                        _ => self.select_with_advanced_scan(
+                            &table_name,
+                            &columns,
+                            where_clause.as_ref(),
+                            scan_limit,
+                            scan_offset,
+                            budget,
+                        ),
+                    }
+                }?;
+
+                let aliases = self.resolve_aliases(&table_name, &column_aliases)?;
+                apply_aliases(&mut rows, &columns, &aliases);
+
+                if let Some(distinct_columns) = distinct_columns {
+                    let names: Vec<String> =
+                        distinct_columns.into_iter().map(|(name, _)| name).collect();
+                    rows = distinct_rows(rows, &names)
+                        .into_iter()
+                        .skip(offset.unwrap_or(0))
+                        .take(limit.unwrap_or(usize::MAX))
+                        .collect();
+                }
+
+                for row in &mut rows {
+                    for (name, value) in &scalar_values {
+                        row.columns.insert(name.clone(), value.clone());
+                    }
+                }
+                Ok(rows)
+            }
+            SqlStatement::Union { left, right, all } => {
+                self.select_union(*left, *right, all, budget)
+            }
+            SqlStatement::ComplexSelect { .. } => Ok(vec![]),
+            SqlStatement::ShowTables => {
+                let mut table_names: Vec<&String> = self.tables.keys().collect();
+                table_names.sort();
+                Ok(table_names
+                    .into_iter()
+                    .map(|table_name| {
+                        let mut columns = HashMap::new();
+                        columns.insert(
+                            "table_name".to_string(),
+                            SqlValue::Text(table_name.as_str().into()),
+                        );
+                        Row { columns }
+                    })
+                    .collect())
+            }
+            SqlStatement::Describe { table_name } => {
+                let table = self
+                    .tables
+                    .get(&table_name)
+                    .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
+                Ok(table.columns.iter().map(describe_column).collect())
+            }
+            other => Err(DatabaseError::InvalidSqlSyntax(format!(
+                "{} changes the database and cannot run as a read",
+                other.get_operation_name()
+            ))),
+        }
+    }
+
+    /// Removes every row of every table, with the indexes, dictionaries and row
+    /// ids built from them, and saves once. Returns one row with the number of
+    /// `tables_cleared` and `rows_cleared`.
+    fn reset_tables(&mut self) -> Result<Vec<Row>, DatabaseError> {
+        let mut rows_cleared = 0;
+        for table in self.tables.values_mut() {
+            rows_cleared += table.rows.len();
+            table.rows = Vec::new();
+            table.dictionaries = TableDictionaries::default();
+            table.next_auto_increment = 1;
+            reindex_table(table)?;
+        }
+        self.query_cache.clear();
+        self.column_statistics.clear();
+        self.rebuild_bloom_filters();
+        self.save_all()?;
+
+        println!(
+            "[MirseoDB] RESET DATABASE cleared {} rows from {} tables of '{}'",
+            rows_cleared,
+            self.tables.len(),
+            self.name
+        );
+        let mut columns = HashMap::new();
+        columns.insert("tables_cleared".to_string(), SqlValue::Integer(self.tables.len() as i64));
+        columns.insert("rows_cleared".to_string(), SqlValue::Integer(rows_cleared as i64));
+        Ok(vec![Row { columns }])
+    }
+
+    /// Column statistics for `table_name`, computed on first use and cached until
+    /// the table changes or ANALYZE refreshes them.
+    pub fn column_statistics(&mut self, table_name: &str) -> Result<&TableStatistics, DatabaseError> {
+        if !self.column_statistics.contains_key(table_name) {
+            let table = self
+                .tables
+                .get(table_name)
+                .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+            self.column_statistics
+                .insert(table_name.to_string(), TableStatistics::compute(table));
+        }
+
+        Ok(&self.column_statistics[table_name])
+    }
+
+    /// Writes the database file after a change to `table_name`, unless the table
+    /// is ASYNC, in which case the write is deferred to `flush_pending`.
+    fn persist_changes(&mut self, table_name: &str) -> Result<(), DatabaseError> {
+        self.column_statistics.remove(table_name);
+
+        let deferred = self
+            .tables
+            .get(table_name)
+            .map(|table| table.durability == Durability::Async)
+            .unwrap_or(false);
+
+        if deferred {
+            self.pending_flush = true;
+            self.deferred_writes += 1;
+            // A burst of writes is checkpointed without waiting for the heartbeat
+            let threshold_reached = self
+                .checkpoint_every_writes
+                .is_some_and(|every| self.deferred_writes >= every);
+            if threshold_reached && self.transaction.is_none() && !self.replaying {
+                println!(
+                    "[MirseoDB] Auto-checkpoint after {} deferred writes",
+                    self.deferred_writes
+                );
+                self.checkpoint()?;
+            }
+            return Ok(());
+        }
+
+        self.save_all()
+    }
+
+    fn save_all(&mut self) -> Result<(), DatabaseError> {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.dirty = true;
+            return Ok(());
+        }
+        if self.replaying {
+            self.pending_flush = true;
+            return Ok(());
+        }
+
+        self.storage.save_tables(&self.tables, self.wal.last_lsn())?;
+        // The file now holds every logged change
+        self.wal.truncate()?;
+        self.pending_flush = false;
+        self.deferred_writes = 0;
+        Ok(())
+    }
+
+    /// Writes every table, including unflushed ASYNC changes, and fsyncs the file
+    /// even when `FSYNC_ON_SAVE=0`, so the data is on disk before e.g. a backup.
+    pub fn checkpoint(&mut self) -> Result<Checkpoint, DatabaseError> {
+        // Writing now would put uncommitted changes on disk
+        if self.transaction.is_some() {
+            return Err(DatabaseError::InvalidSqlSyntax(
+                "CHECKPOINT cannot run while a transaction is open".to_string(),
+            ));
+        }
+
+        let lsn = self.wal.last_lsn();
+        let bytes_written = self.storage.checkpoint(&self.tables, lsn)?;
+        self.wal.truncate()?;
+        self.pending_flush = false;
+        self.deferred_writes = 0;
+        self.last_checkpoint = SystemTime::now();
+        Ok(Checkpoint { lsn, bytes_written })
+    }
+
+    /// How often the server's heartbeat should call `flush_pending`
+    /// (`CHECKPOINT_INTERVAL_SECS`).
+    pub fn checkpoint_interval(&self) -> Duration {
+        self.checkpoint_interval
+    }
+
+    /// LSN of the newest write-ahead log record and the time of the last checkpoint.
+    pub fn durability_status(&self) -> (u64, SystemTime) {
+        (self.wal.last_lsn(), self.last_checkpoint)
+    }
+
+    /// Writes out deferred ASYNC table changes. Returns whether anything was flushed.
+    pub fn flush_pending(&mut self) -> Result<bool, DatabaseError> {
+        // Uncommitted changes must not reach the disk
+        if !self.pending_flush || self.transaction.is_some() {
+            return Ok(false);
+        }
+
+        self.save_all()?;
+        Ok(true)
+    }
+
+    /// Opens a transaction owned by `session`. Only one can be open at a time.
+    pub fn begin_transaction(&mut self, session: Option<String>) -> Result<(), DatabaseError> {
+        if self.transaction.is_some() {
+            return Err(DatabaseError::InvalidSqlSyntax(
+                "A transaction is already in progress".to_string(),
+            ));
+        }
+
+        self.transaction = Some(Transaction {
+            snapshot: self.tables.clone(),
+            session,
+            last_used: Instant::now(),
+            dirty: false,
+            log: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Ends the open transaction, writing its changes to disk in one save.
+    pub fn commit_transaction(&mut self) -> Result<(), DatabaseError> {
+        let transaction = self.transaction.take().ok_or_else(no_transaction)?;
+        if !transaction.log.is_empty() {
+            self.wal.append(&transaction.log)?;
+        }
+        if transaction.dirty {
+            self.save_all()?;
+        }
+        Ok(())
+    }
+
+    /// Ends the open transaction, restoring the tables as they were at BEGIN.
+    pub fn rollback_transaction(&mut self) -> Result<(), DatabaseError> {
+        let transaction = self.transaction.take().ok_or_else(no_transaction)?;
+        self.tables = transaction.snapshot;
+        self.column_statistics.clear();
+        self.rebuild_column_cache();
+        self.rebuild_bloom_filters();
+        Ok(())
+    }
+
+    /// The session owning the open transaction, if any, and how long it has been
+    /// since it last ran a statement.
+    pub fn open_transaction(&self) -> Option<(Option<&str>, Duration)> {
+        self.transaction.as_ref().map(|transaction| {
+            (
+                transaction.session.as_deref(),
+                transaction.last_used.elapsed(),
+            )
+        })
+    }
+
     fn evaluate_where_clause(
+        &self,
+        row: &Row,
+        where_clause: &WhereExpr,
+    ) -> Result<bool, DatabaseError> {
+        match where_clause {
+            WhereExpr::Comparison(comparison) => self.evaluate_comparison(row, comparison),
+            WhereExpr::In {
+                column,
+                source,
+                negated,
+            } => self.evaluate_in(row, column, source, *negated),
+            WhereExpr::Between {
+                column,
+                low,
+                high,
+                negated,
+            } => self.evaluate_between(row, column, low, high, *negated),
+            WhereExpr::BooleanColumn { column, negated } => {
+                evaluate_boolean_column(row, column, *negated)
+            }
+            WhereExpr::ScalarComparison { .. } | WhereExpr::CurrentTimeComparison { .. } => {
+                Err(unresolved_subquery())
+            }
+            WhereExpr::And(terms) => {
+                for term in terms {
+                    if !self.evaluate_where_clause(row, term)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            WhereExpr::Or(terms) => {
+                for term in terms {
+                    if self.evaluate_where_clause(row, term)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn evaluate_comparison(
         &self,
         row: &Row,
         where_clause: &WhereClause,
@@ -373,15 +1185,369 @@ impl Database {
                 let cmp = self.compare_values(row_value, &where_clause.value);
                 cmp == std::cmp::Ordering::Less || cmp == std::cmp::Ordering::Equal
             }
+            ComparisonOperator::Like | ComparisonOperator::NotLike => {
+                let matched = match (row_value, &where_clause.value) {
+                    (SqlValue::Text(text), SqlValue::Text(pattern)) => like_matches(text, pattern),
+                    // NULL and non-text values match neither LIKE nor NOT LIKE
+                    _ => return Ok(false),
+                };
+                matched == matches!(where_clause.operator, ComparisonOperator::Like)
+            }
         })
     }
 
-    fn compare_values(&self, a: &SqlValue, b: &SqlValue) -> std::cmp::Ordering {
-        match (a, b) {
+    /// IN matches only values of the row's own type. A NULL row value matches
+    /// neither IN nor NOT IN.
+    fn evaluate_in(
+        &self,
+        row: &Row,
+        column: &str,
+        source: &InSource,
+        negated: bool,
+    ) -> Result<bool, DatabaseError> {
+        let row_value = row
+            .columns
+            .get(column)
+            .ok_or_else(|| DatabaseError::ColumnNotFound(column.to_string()))?;
+        if matches!(row_value, SqlValue::Null) {
+            return Ok(false);
+        }
+
+        match source {
+            InSource::Values(values) => {
+                let found = values.iter().any(|value| {
+                    compare_same_type(row_value, value) == Some(std::cmp::Ordering::Equal)
+                });
+                Ok(found != negated)
+            }
+            InSource::Subquery(_) => Err(unresolved_subquery()),
+        }
+    }
+
+    /// Like IN, bounds of another type than the row value (or a NULL row value)
+    /// match neither BETWEEN nor NOT BETWEEN. `low > high` matches nothing.
+    fn evaluate_between(
+        &self,
+        row: &Row,
+        column: &str,
+        low: &SqlValue,
+        high: &SqlValue,
+        negated: bool,
+    ) -> Result<bool, DatabaseError> {
+        let row_value = row
+            .columns
+            .get(column)
+            .ok_or_else(|| DatabaseError::ColumnNotFound(column.to_string()))?;
+
+        let (Some(against_low), Some(against_high)) = (
+            compare_same_type(row_value, low),
+            compare_same_type(row_value, high),
+        ) else {
+            return Ok(false);
+        };
+        let in_range =
+            against_low != std::cmp::Ordering::Less && against_high != std::cmp::Ordering::Greater;
+        Ok(in_range != negated)
+    }
+
+    /// Runs every subquery in `where_clause` once and replaces it with the values
+    /// it returned. Subqueries cannot reference the outer row, so one run serves
+    /// the whole scan. NOW() is read here too, so every row sees the same time.
+    fn resolve_subqueries(
+        &self,
+        where_clause: &mut WhereExpr,
+        budget: &MemoryBudget,
+    ) -> Result<(), DatabaseError> {
+        match where_clause {
+            WhereExpr::Comparison(_)
+            | WhereExpr::Between { .. }
+            | WhereExpr::BooleanColumn { .. } => {}
+            WhereExpr::And(terms) | WhereExpr::Or(terms) => {
+                for term in terms {
+                    self.resolve_subqueries(term, budget)?;
+                }
+            }
+            WhereExpr::In { source, .. } => {
+                if let InSource::Subquery(subquery) = source {
+                    let values = self.subquery_values(subquery.as_ref().clone(), budget)?;
+                    *source = InSource::Values(values);
+                }
+            }
+            WhereExpr::ScalarComparison {
+                column,
+                operator,
+                subquery,
+            } => {
+                let value = self.scalar_subquery_value(subquery.as_ref().clone(), budget)?;
+                let resolved = match value {
+                    Some(value) => WhereExpr::Comparison(WhereClause {
+                        column: column.clone(),
+                        operator: operator.clone(),
+                        value,
+                    }),
+                    // No row means a NULL operand, which no comparison matches
+                    None => WhereExpr::Or(Vec::new()),
+                };
+                *where_clause = resolved;
+            }
+            WhereExpr::CurrentTimeComparison { column, operator } => {
+                *where_clause = WhereExpr::Comparison(WhereClause {
+                    column: column.clone(),
+                    operator: operator.clone(),
+                    value: current_timestamp(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Concatenates both branches under the left branch's column names. Without
+    /// ALL, repeated rows are dropped and the first occurrence kept.
+    fn select_union(
+        &self,
+        left: SqlStatement,
+        right: SqlStatement,
+        all: bool,
+        budget: &MemoryBudget,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        let left_columns = self.output_columns(&left)?;
+        let right_columns = self.output_columns(&right)?;
+        if left_columns.len() != right_columns.len() {
+            return Err(DatabaseError::InvalidSqlSyntax(format!(
+                "UNION branches select {} and {} columns",
+                left_columns.len(),
+                right_columns.len()
+            )));
+        }
+        for ((left_name, left_type), (right_name, right_type)) in
+            left_columns.iter().zip(&right_columns)
+        {
+            if let (Some(left_type), Some(right_type)) = (left_type, right_type) {
+                if !union_compatible(left_type, right_type) {
+                    return Err(DatabaseError::InvalidDataType(format!(
+                        "UNION column '{}' is {} but '{}' is {}",
+                        left_name,
+                        left_type.as_str(),
+                        right_name,
+                        right_type.as_str()
+                    )));
+                }
+            }
+        }
+
+        let mut rows = self.read_statement(left, budget)?;
+        for mut row in self.read_statement(right, budget)? {
+            let columns = right_columns
+                .iter()
+                .zip(&left_columns)
+                .filter_map(|((right_name, _), (left_name, _))| {
+                    row.columns.remove(right_name).map(|value| (left_name.clone(), value))
+                })
+                .collect();
+            rows.push(Row { columns });
+        }
+
+        if all {
+            return Ok(rows);
+        }
+        let names: Vec<String> = left_columns.into_iter().map(|(name, _)| name).collect();
+        Ok(distinct_rows(rows, &names))
+    }
+
+    /// Rows `statement` would return without its LIMIT/OFFSET, reported as
+    /// `total_count` by `?with_count=1`. A plain SELECT counts WHERE matches
+    /// without building result rows and uses an index when one covers the WHERE;
+    /// otherwise every row is checked. Grouped SELECTs and UNIONs run in full.
+    pub fn count_matches(&self, statement: &SqlStatement) -> Result<usize, DatabaseError> {
+        let budget = self.memory_budget(None);
+        match statement {
+            SqlStatement::Select {
+                table_name,
+                where_clause,
+                aggregates,
+                group_by,
+                distinct: false,
+                ..
+            } if aggregates.is_empty() && group_by.is_empty() => {
+                let mut where_clause = where_clause.clone();
+                if let Some(where_clause) = where_clause.as_mut() {
+                    self.resolve_subqueries(where_clause, &budget)?;
+                }
+                self.count_where(table_name, where_clause.as_ref())
+            }
+            SqlStatement::Select { .. } => {
+                let mut unpaged = statement.clone();
+                if let SqlStatement::Select { limit, offset, .. } = &mut unpaged {
+                    *limit = None;
+                    *offset = None;
+                }
+                Ok(self.read_statement(unpaged, &budget)?.len())
+            }
+            SqlStatement::Union { .. } => {
+                Ok(self.read_statement(statement.clone(), &budget)?.len())
+            }
+            other => Err(DatabaseError::InvalidSqlSyntax(format!(
+                "A match count is only available for SELECT, not {}",
+                other.get_operation_name()
+            ))),
+        }
+    }
+
+    fn count_where(
+        &self,
+        table_name: &str,
+        where_clause: Option<&WhereExpr>,
+    ) -> Result<usize, DatabaseError> {
+        let table = self.tables.get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        let Some(where_clause) = where_clause else {
+            return Ok(table.rows.len());
+        };
+        if self.where_outside_bounds(table_name, Some(where_clause)) {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+        let mut check = |row: &Row| -> Result<(), DatabaseError> {
+            if self.evaluate_where_clause_optimized(row, where_clause)? {
+                count += 1;
+            }
+            Ok(())
+        };
+        match index_candidates(table, Some(where_clause)) {
+            Some(row_ids) => {
+                for row in row_ids.iter().filter_map(|&row_id| table.rows.get(row_id)) {
+                    check(row)?;
+                }
+            }
+            None => {
+                for row in &table.rows {
+                    check(row)?;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Result column names of a SELECT (or UNION) in select-list order, with the
+    /// declared type where the column comes from the table definition.
+    fn output_columns(
+        &self,
+        statement: &SqlStatement,
+    ) -> Result<Vec<(String, Option<DataType>)>, DatabaseError> {
+        let (table_name, columns, column_aliases, aggregates, scalar_subqueries) = match statement {
+            SqlStatement::Select {
+                table_name,
+                columns,
+                column_aliases,
+                aggregates,
+                scalar_subqueries,
+                ..
+            } => (table_name, columns, column_aliases, aggregates, scalar_subqueries),
+            SqlStatement::Union { left, .. } => return self.output_columns(left),
+            other => {
+                return Err(DatabaseError::InvalidSqlSyntax(format!(
+                    "UNION branches must be SELECTs, not {}",
+                    other.get_operation_name()
+                )));
+            }
+        };
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
+
+        let mut names = self.resolve_projection(table_name, columns)?;
+        if names.len() == 1 && names[0] == "*" && aggregates.is_empty() {
+            names = table.columns.iter().map(|column| column.name.clone()).collect();
+        }
+
+        let declared_type = |name: &str| {
+            table
+                .columns
+                .iter()
+                .find(|column| column.name == name)
+                .map(|column| column.data_type.clone())
+        };
+        let mut aliases = self.resolve_aliases(table_name, column_aliases)?;
+        let mut output: Vec<(String, Option<DataType>)> = names
+            .into_iter()
+            .map(|name| {
+                let data_type = declared_type(&name);
+                match aliases.iter().position(|(column, _)| *column == name) {
+                    Some(position) => (aliases.remove(position).1, data_type),
+                    None => (name, data_type),
+                }
+            })
+            .collect();
+        output.extend(aggregates.iter().map(|aggregate| {
+            let data_type = match aggregate.function {
+                AggregateFunction::Count => Some(DataType::Integer),
+                AggregateFunction::Avg => Some(DataType::Float),
+                _ => aggregate.column.as_deref().and_then(declared_type),
+            };
+            (aggregate.name.clone(), data_type)
+        }));
+        output.extend(scalar_subqueries.iter().map(|(name, _)| (name.clone(), None)));
+        Ok(output)
+    }
+
+    /// The single value of a one-column subquery, or `None` when it returns no row.
+    fn scalar_subquery_value(
+        &self,
+        subquery: SqlStatement,
+        budget: &MemoryBudget,
+    ) -> Result<Option<SqlValue>, DatabaseError> {
+        let mut values = self.subquery_values(subquery, budget)?;
+        if values.len() > 1 {
+            return Err(DatabaseError::InvalidSqlSyntax(format!(
+                "Scalar subquery returned {} rows; expected at most one",
+                values.len()
+            )));
+        }
+        Ok(values.pop())
+    }
+
+    fn subquery_values(
+        &self,
+        subquery: SqlStatement,
+        budget: &MemoryBudget,
+    ) -> Result<Vec<SqlValue>, DatabaseError> {
+        if !matches!(subquery, SqlStatement::Select { .. }) {
+            return Err(DatabaseError::InvalidSqlSyntax(
+                "Subquery must be a SELECT".to_string(),
+            ));
+        }
+
+        let projection = self.output_columns(&subquery)?;
+        let [(column, _)] = projection.as_slice() else {
+            return Err(DatabaseError::InvalidSqlSyntax(format!(
+                "Subquery must select exactly one column, not {}",
+                projection.len()
+            )));
+        };
+        let column = column.clone();
+
+        Ok(self
+            .read_statement(subquery, budget)?
+            .into_iter()
+            .map(|mut row| row.columns.remove(&column).unwrap_or(SqlValue::Null))
+            .collect())
+    }
+
+    fn compare_values(&self, a: &SqlValue, b: &SqlValue) -> std::cmp::Ordering {
+        match (a, b) {
             (SqlValue::Integer(a), SqlValue::Integer(b)) => a.cmp(b),
             (SqlValue::Float(a), SqlValue::Float(b)) => {
                 a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
             }
+            // INTEGER columns against FLOAT operands such as an AVG
+            (SqlValue::Integer(a), SqlValue::Float(b)) => {
+                (*a as f64).partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+            }
+            (SqlValue::Float(a), SqlValue::Integer(b)) => {
+                a.partial_cmp(&(*b as f64)).unwrap_or(std::cmp::Ordering::Equal)
+            }
             (SqlValue::Text(a), SqlValue::Text(b)) => a.cmp(b),
             (SqlValue::Boolean(a), SqlValue::Boolean(b)) => a.cmp(b),
             (SqlValue::Null, SqlValue::Null) => std::cmp::Ordering::Equal,
@@ -392,10 +1558,45 @@ impl Database {
     fn create_table_with_indexes(
         &mut self,
         table_name: String,
-        columns: Vec<ColumnDefinition>,
+        mut columns: Vec<ColumnDefinition>,
+        durability: Durability,
+        comment: Option<String>,
     ) -> Result<(), DatabaseError> {
         let mut index_manager = IndexManager::new();
 
+        for (position, column) in columns.iter().enumerate() {
+            if columns[..position].iter().any(|c| c.name == column.name) {
+                return Err(DatabaseError::ParseError(format!(
+                    "Duplicate column name '{}'",
+                    column.name
+                )));
+            }
+        }
+
+        for column in columns.iter_mut().filter(|c| c.primary_key) {
+            column.nullable = false;
+        }
+        for column in columns.iter_mut() {
+            if let Some(default) = column.default.take() {
+                column.default = Some(coerce_to_column(column, default)?);
+            }
+        }
+        let mut auto_increment = columns.iter().filter(|c| c.auto_increment);
+        if let Some(column) = auto_increment.next() {
+            if !matches!(column.data_type, DataType::Integer) {
+                return Err(DatabaseError::InvalidDataType(format!(
+                    "AUTO_INCREMENT column '{}' must be INTEGER",
+                    column.name
+                )));
+            }
+            if let Some(second) = auto_increment.next() {
+                return Err(DatabaseError::ParseError(format!(
+                    "Table '{}' can have one AUTO_INCREMENT column, not also '{}'",
+                    table_name, second.name
+                )));
+            }
+        }
+
         for column in &columns {
             if column.primary_key {
                 let index_name = format!("pk_{}", column.name);
@@ -420,10 +1621,14 @@ impl Database {
             rows: Vec::new(),
             index_manager,
             next_row_id: 0,
+            next_auto_increment: 1,
+            durability,
+            dictionaries: TableDictionaries::default(),
+            comment,
         };
 
         self.tables.insert(table_name.clone(), table);
-        self.storage.save_tables(&self.tables)?;
+        self.persist_changes(&table_name)?;
 
         // 🚀 OPTIMIZATION: Update column cache when creating table
         let column_names: Vec<String> = self.tables[&table_name].columns.iter().map(|c| c.name.clone()).collect();
@@ -436,29 +1641,252 @@ impl Database {
         Ok(())
     }
 
+    /// Creates an index over the existing rows: a plain index for one column,
+    /// case-folded with `case_insensitive`, and a composite index for several. When the rows violate `is_unique` the index
+    /// is removed again and the violation returned.
+    fn create_index(
+        &mut self,
+        index_name: String,
+        table_name: &str,
+        column_names: Vec<String>,
+        is_unique: bool,
+        case_insensitive: bool,
+    ) -> Result<(), DatabaseError> {
+        // Plain and composite indexes of every table share one namespace
+        if !self.tables_with_index(&index_name).is_empty() {
+            return Err(DatabaseError::IndexAlreadyExists(index_name));
+        }
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+
+        for column_name in &column_names {
+            if !table.columns.iter().any(|column| &column.name == column_name) {
+                return Err(DatabaseError::ColumnNotFound(column_name.clone()));
+            }
+        }
+
+        match column_names.as_slice() {
+            [column_name] if case_insensitive => table.index_manager.create_case_insensitive_index(
+                index_name.clone(),
+                column_name.clone(),
+                is_unique,
+                false,
+            )?,
+            [column_name] => table.index_manager.create_index(
+                index_name.clone(),
+                column_name.clone(),
+                is_unique,
+                false,
+            )?,
+            _ => table.index_manager.create_composite_index(
+                index_name.clone(),
+                column_names,
+                is_unique,
+            )?,
+        }
+
+        if let Err(err) = reindex_table(table) {
+            table.index_manager.drop_index(&index_name)?;
+            reindex_table(table)?;
+            return Err(err);
+        }
+
+        println!(
+            "[MirseoDB] Created index '{}' on table '{}'",
+            index_name, table_name
+        );
+        self.persist_changes(table_name)
+    }
+
+    /// The tables holding an index called `index_name`, sorted by name. Only the
+    /// automatic `pk_<column>` indexes can appear in more than one.
+    fn tables_with_index(&self, index_name: &str) -> Vec<String> {
+        let mut table_names: Vec<String> = self
+            .tables
+            .values()
+            .filter(|table| {
+                table.index_manager.get_index(index_name).is_some()
+                    || table.index_manager.get_composite_index(index_name).is_some()
+            })
+            .map(|table| table.name.clone())
+            .collect();
+        table_names.sort();
+        table_names
+    }
+
+    /// Drops `index_name` from `table_name`, or from the one table that has it
+    /// when the statement names no table. Primary key indexes cannot be dropped.
+    fn drop_index(
+        &mut self,
+        index_name: &str,
+        table_name: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let table_name = match table_name {
+            Some(table_name) => table_name.to_string(),
+            None => match self.tables_with_index(index_name).as_slice() {
+                [] => return Err(DatabaseError::IndexNotFound(index_name.to_string())),
+                [table_name] => table_name.clone(),
+                table_names => {
+                    return Err(DatabaseError::InvalidSqlSyntax(format!(
+                        "Index '{}' exists on tables {}; use DROP INDEX {} ON <table>",
+                        index_name,
+                        table_names.join(", "),
+                        index_name
+                    )))
+                }
+            },
+        };
+        let table = self
+            .tables
+            .get_mut(&table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
+
+        if table
+            .index_manager
+            .get_index(index_name)
+            .is_some_and(|index| index.is_primary)
+        {
+            return Err(DatabaseError::InvalidSqlSyntax(format!(
+                "Index '{}' enforces the primary key and cannot be dropped",
+                index_name
+            )));
+        }
+        table.index_manager.drop_index(index_name)?;
+
+        println!(
+            "[MirseoDB] Dropped index '{}' from table '{}'",
+            index_name, table_name
+        );
+        self.persist_changes(&table_name)
+    }
+
+    /// Applies `set_clauses` to the rows at `row_indices`, keeping the indexes in
+    /// step. Used by UPDATE and by an upsert that hit an existing row.
+    fn update_rows_with_indexes(
+        &mut self,
+        table_name: &str,
+        row_indices: Vec<usize>,
+        mut set_clauses: Vec<(String, SqlValue)>,
+    ) -> Result<(), DatabaseError> {
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+
+        for (column_name, new_value) in set_clauses.iter_mut() {
+            let Some(column) = table.columns.iter().find(|c| c.name == *column_name) else {
+                continue;
+            };
+            if column.primary_key && matches!(new_value, SqlValue::Null) {
+                return Err(DatabaseError::PrimaryKeyViolation(format!(
+                    "Primary key column '{}' cannot be NULL",
+                    column_name
+                )));
+            }
+            *new_value = coerce_to_column(column, new_value.clone())?;
+        }
+
+        let updated: Vec<(usize, HashMap<String, SqlValue>)> = row_indices
+            .into_iter()
+            .map(|index| {
+                let mut columns = table.rows[index].columns.clone();
+                for (column_name, new_value) in &set_clauses {
+                    let mut new_value = new_value.clone();
+                    table.dictionaries.intern(column_name, &mut new_value);
+                    columns.insert(column_name.clone(), new_value);
+                }
+                (index, columns)
+            })
+            .collect();
+
+        // Row ids in the indexes are row positions (see `reindex_table`)
+        let changes: Vec<RowChange> = updated
+            .iter()
+            .map(|(index, columns)| (*index, &table.rows[*index].columns, columns))
+            .collect();
+        table.index_manager.update_rows(&changes)?;
+
+        for (index, columns) in updated {
+            table.rows[index].columns = columns;
+        }
+
+        self.persist_changes(table_name)?;
+        Ok(())
+    }
+
+    /// The row an INSERT of `values` would collide with on a primary key or
+    /// unique index, or only on the one over the `target` columns when given.
+    fn conflicting_row(
+        &self,
+        table_name: &str,
+        target: &[String],
+        columns: &[String],
+        values: &[SqlValue],
+    ) -> Result<Option<usize>, DatabaseError> {
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        if !target.is_empty() && !table.index_manager.has_unique_key(target) {
+            return Err(DatabaseError::ParseError(format!(
+                "ON CONFLICT ({}) does not match a primary key or unique index of '{}'",
+                target.join(", "),
+                table_name
+            )));
+        }
+        let row_columns = inserted_row(table, columns, values)?;
+        Ok(table.index_manager.find_conflict(&row_columns, target))
+    }
+
+    /// Returns the AUTO_INCREMENT column and the value generated for it, if the
+    /// INSERT left the column out or set it to NULL.
     fn insert_row_with_indexes(
         &mut self,
         table_name: String,
         columns: Vec<String>,
         values: Vec<SqlValue>,
-    ) -> Result<(), DatabaseError> {
+    ) -> Result<Option<(String, i64)>, DatabaseError> {
         let table = self
             .tables
             .get_mut(&table_name)
             .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
 
-        let mut row_columns = HashMap::new();
+        let mut row_columns = inserted_row(table, &columns, &values)?;
 
-        for table_column in &table.columns {
-            if let Some(pos) = columns.iter().position(|c| c == &table_column.name) {
-                if let Some(value) = values.get(pos) {
-                    row_columns.insert(table_column.name.clone(), value.clone());
+        // Explicit values move the counter past them, so generated ids never collide
+        let mut generated = None;
+        let mut next_auto_increment = table.next_auto_increment;
+        if let Some(column) = table.columns.iter().find(|c| c.auto_increment) {
+            match row_columns.get(&column.name) {
+                None | Some(SqlValue::Null) => {
+                    let id = next_auto_increment;
+                    next_auto_increment = id.checked_add(1).ok_or_else(|| {
+                        DatabaseError::InvalidDataType(format!(
+                            "AUTO_INCREMENT column '{}' has no values left",
+                            column.name
+                        ))
+                    })?;
+                    row_columns.insert(column.name.clone(), SqlValue::Integer(id));
+                    generated = Some((column.name.clone(), id));
                 }
-            } else if !table_column.nullable && !table_column.primary_key {
-                return Err(DatabaseError::ColumnNotFound(format!(
-                    "Non-nullable column '{}' requires a value",
-                    table_column.name
-                )));
+                Some(SqlValue::Integer(id)) => {
+                    next_auto_increment = next_auto_increment.max(id.saturating_add(1));
+                }
+                Some(_) => {}
+            }
+        }
+
+        for table_column in table.columns.iter().filter(|c| c.primary_key) {
+            match row_columns.get(&table_column.name) {
+                None | Some(SqlValue::Null) => {
+                    return Err(DatabaseError::PrimaryKeyViolation(format!(
+                        "Primary key column '{}' cannot be NULL",
+                        table_column.name
+                    )));
+                }
+                Some(_) => {}
             }
         }
 
@@ -474,31 +1902,32 @@ impl Database {
         }
 
         let row_id = table.next_row_id;
-        table.next_row_id += 1;
-
         table
             .index_manager
             .insert_into_indexes(&row_columns, row_id)?;
+        table.next_row_id += 1;
 
+        table.dictionaries.intern_row(&mut row_columns);
         let row = Row {
             columns: row_columns,
         };
         table.rows.push(row);
+        table.next_auto_increment = next_auto_increment;
 
-        self.storage.save_tables(&self.tables)?;
+        self.persist_changes(&table_name)?;
 
         println!(
             "[MirseoDB] Inserted row with ID {} into table '{}'",
             row_id, table_name
         );
-        Ok(())
+        Ok(generated)
     }
 
     fn select_with_indexes(
         &self,
         table_name: String,
         columns: Vec<String>,
-        where_clause: Option<WhereClause>,
+        where_clause: Option<WhereExpr>,
     ) -> Result<Vec<Row>, DatabaseError> {
         let table = self
             .tables
@@ -507,7 +1936,7 @@ impl Database {
 
         let mut candidate_row_ids: Option<Vec<usize>> = None;
 
-        if let Some(ref where_clause) = where_clause {
+        if let Some(where_clause) = where_clause.as_ref().and_then(WhereExpr::as_comparison) {
             if let Some(index) = table
                 .index_manager
                 .find_best_index_for_query(&where_clause.column)
@@ -543,6 +1972,10 @@ impl Database {
                         }
                         result
                     }
+                    // Every row is a candidate; the WHERE check below does the matching
+                    ComparisonOperator::Like | ComparisonOperator::NotLike => {
+                        (0..table.rows.len()).collect()
+                    }
                 });
             }
         }
@@ -595,6 +2028,114 @@ impl Database {
         Ok(result_rows)
     }
 
+    /// Output column names and type names for a SELECT result. Types come from the
+    /// table definition and are inferred from the first non-NULL value otherwise.
+    pub fn result_schema(
+        &self,
+        table_name: &str,
+        columns: &[String],
+        rows: &[Row],
+    ) -> Vec<(String, &'static str)> {
+        let table = self.tables.get(table_name);
+        let columns = self
+            .resolve_projection(table_name, columns)
+            .unwrap_or_else(|_| columns.to_vec());
+
+        let names: Vec<String> = if columns.len() == 1 && columns[0] == "*" {
+            match table {
+                Some(table) if !table.columns.is_empty() => {
+                    table.columns.iter().map(|c| c.name.clone()).collect()
+                }
+                _ => {
+                    let mut names: Vec<String> = rows
+                        .iter()
+                        .flat_map(|row| row.columns.keys().cloned())
+                        .collect();
+                    names.sort();
+                    names.dedup();
+                    names
+                }
+            }
+        } else {
+            columns
+        };
+
+        names
+            .into_iter()
+            .map(|name| {
+                let declared = table
+                    .and_then(|table| table.columns.iter().find(|c| c.name == name))
+                    .map(|c| c.data_type.clone());
+                let data_type = declared.or_else(|| {
+                    rows.iter()
+                        .filter_map(|row| row.columns.get(&name))
+                        .find_map(SqlValue::data_type)
+                });
+                let type_name = data_type.map(|t| t.as_str()).unwrap_or("UNKNOWN");
+                (name, type_name)
+            })
+            .collect()
+    }
+
+    /// Resolves table-qualified projections (`users.name`, `users.*`) against the
+    /// source table. Qualifiers naming the source are stripped; any other qualifier
+    /// refers to a table that is not part of the query and is rejected.
+    fn resolve_projection(
+        &self,
+        table_name: &str,
+        columns: &[String],
+    ) -> Result<Vec<String>, DatabaseError> {
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+
+        let mut resolved = Vec::with_capacity(columns.len());
+
+        for column in columns {
+            let Some((qualifier, column_name)) = column.rsplit_once('.') else {
+                resolved.push(column.clone());
+                continue;
+            };
+
+            if normalize_table_name(qualifier) != table.name {
+                return Err(DatabaseError::ColumnNotFound(format!(
+                    "{} (table '{}' is not part of this query)",
+                    column, qualifier
+                )));
+            }
+
+            let column_name = normalize_identifier(column_name);
+            if column_name == "*" {
+                if columns.len() == 1 || table.columns.is_empty() {
+                    resolved.push("*".to_string());
+                } else {
+                    resolved.extend(table.columns.iter().map(|c| c.name.clone()));
+                }
+            } else {
+                resolved.push(column_name);
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// `column_aliases` with each column resolved like the select list is.
+    fn resolve_aliases(
+        &self,
+        table_name: &str,
+        column_aliases: &[(String, String)],
+    ) -> Result<Vec<(String, String)>, DatabaseError> {
+        column_aliases
+            .iter()
+            .map(|(column, alias)| {
+                let resolved = self.resolve_projection(table_name, std::slice::from_ref(column))?;
+                let column = resolved.into_iter().next().unwrap_or_else(|| column.clone());
+                Ok((column, alias.clone()))
+            })
+            .collect()
+    }
+
     fn project_columns(&self, row: &Row, columns: &[String]) -> Row {
         self.project_columns_optimized(row, columns)
     }
@@ -622,11 +2163,54 @@ impl Database {
     fn evaluate_where_clause_optimized(
         &self,
         row: &Row,
-        where_clause: &WhereClause,
+        where_clause: &WhereExpr,
     ) -> Result<bool, DatabaseError> {
-        // 🚀 OPTIMIZATION: Fast path for common column access
-        let row_value = match row.columns.get(&where_clause.column) {
-            Some(value) => value,
+        match where_clause {
+            WhereExpr::Comparison(comparison) => self.evaluate_comparison_optimized(row, comparison),
+            WhereExpr::In {
+                column,
+                source,
+                negated,
+            } => self.evaluate_in(row, column, source, *negated),
+            WhereExpr::Between {
+                column,
+                low,
+                high,
+                negated,
+            } => self.evaluate_between(row, column, low, high, *negated),
+            WhereExpr::BooleanColumn { column, negated } => {
+                evaluate_boolean_column(row, column, *negated)
+            }
+            WhereExpr::ScalarComparison { .. } | WhereExpr::CurrentTimeComparison { .. } => {
+                Err(unresolved_subquery())
+            }
+            WhereExpr::And(terms) => {
+                for term in terms {
+                    if !self.evaluate_where_clause_optimized(row, term)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            WhereExpr::Or(terms) => {
+                for term in terms {
+                    if self.evaluate_where_clause_optimized(row, term)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn evaluate_comparison_optimized(
+        &self,
+        row: &Row,
+        where_clause: &WhereClause,
+    ) -> Result<bool, DatabaseError> {
+        // 🚀 OPTIMIZATION: Fast path for common column access
+        let row_value = match row.columns.get(&where_clause.column) {
+            Some(value) => value,
             None => return Err(DatabaseError::ColumnNotFound(where_clause.column.clone())),
         };
 
@@ -652,6 +2236,14 @@ impl Database {
                 let cmp = self.compare_values_fast(row_value, &where_clause.value);
                 cmp == std::cmp::Ordering::Less || cmp == std::cmp::Ordering::Equal
             }
+            ComparisonOperator::Like | ComparisonOperator::NotLike => {
+                let matched = match (row_value, &where_clause.value) {
+                    (SqlValue::Text(text), SqlValue::Text(pattern)) => like_matches(text, pattern),
+                    // NULL and non-text values match neither LIKE nor NOT LIKE
+                    _ => return Ok(false),
+                };
+                matched == matches!(where_clause.operator, ComparisonOperator::Like)
+            }
         })
     }
 
@@ -662,6 +2254,13 @@ impl Database {
             (SqlValue::Float(a), SqlValue::Float(b)) => {
                 a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
             }
+            // INTEGER columns against FLOAT operands such as an AVG
+            (SqlValue::Integer(a), SqlValue::Float(b)) => {
+                (*a as f64).partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+            }
+            (SqlValue::Float(a), SqlValue::Integer(b)) => {
+                a.partial_cmp(&(*b as f64)).unwrap_or(std::cmp::Ordering::Equal)
+            }
             (SqlValue::Text(a), SqlValue::Text(b)) => a.cmp(b),
             (SqlValue::Boolean(a), SqlValue::Boolean(b)) => a.cmp(b),
             (SqlValue::Null, SqlValue::Null) => std::cmp::Ordering::Equal,
@@ -689,6 +2288,175 @@ impl Database {
         (self.column_cache.len(), self.query_cache.len())
     }
 
+    /// `CREATE TABLE` statements recreating `table_name`, or every table in name
+    /// order when `None`, with identifiers and types written for `dialect`.
+    pub fn export_ddl(
+        &self,
+        table_name: Option<&str>,
+        dialect: &DetectedDialect,
+    ) -> Result<String, DatabaseError> {
+        let mut tables: Vec<&Table> = match table_name {
+            Some(table_name) => vec![self
+                .tables
+                .get(table_name)
+                .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?],
+            None => self.tables.values().collect(),
+        };
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // MySQL writes comments inline; the other dialects need separate statements
+        let inline_comments = *dialect == DetectedDialect::MySQL;
+        let mut ddl = String::new();
+        for table in tables {
+            let mut comments = String::new();
+            let columns: Vec<String> = table
+                .columns
+                .iter()
+                .map(|column| {
+                    let mut definition = format!(
+                        "    {} {}",
+                        dialect.quote_identifier(&column.name),
+                        dialect.column_type(&column.data_type)
+                    );
+                    if column.primary_key {
+                        definition.push_str(" PRIMARY KEY");
+                    } else if !column.nullable {
+                        definition.push_str(" NOT NULL");
+                    }
+                    if column.auto_increment {
+                        definition.push_str(dialect.auto_increment());
+                    }
+                    if let Some(default) = &column.default {
+                        definition.push_str(" DEFAULT ");
+                        definition.push_str(&dialect.literal(default));
+                    }
+                    match &column.comment {
+                        Some(comment) if inline_comments => {
+                            definition.push_str(" COMMENT ");
+                            definition.push_str(&dialect.quote_string(comment));
+                        }
+                        Some(comment) => comments.push_str(&comment_ddl(
+                            dialect,
+                            &table.name,
+                            Some(&column.name),
+                            comment,
+                        )),
+                        None => {}
+                    }
+                    definition
+                })
+                .collect();
+
+            let mut options = String::new();
+            match &table.comment {
+                Some(comment) if inline_comments => {
+                    options = format!(" COMMENT={}", dialect.quote_string(comment));
+                }
+                Some(comment) => {
+                    let statement = comment_ddl(dialect, &table.name, None, comment);
+                    comments.insert_str(0, &statement);
+                }
+                None => {}
+            }
+            ddl.push_str(&format!(
+                "CREATE TABLE {} (\n{}\n){};\n{}",
+                dialect.quote_identifier(&table.name),
+                columns.join(",\n"),
+                options,
+                comments
+            ));
+        }
+        Ok(ddl)
+    }
+
+    /// Statements that turn the current schema into the one `target_ddl` creates,
+    /// in a safe order: CREATE TABLE for new tables, ADD, MODIFY and DROP COLUMN
+    /// per table, then DROP TABLE. Nullability, indexes and comments are not
+    /// compared.
+    pub fn schema_diff(&self, target_ddl: &str) -> Result<Vec<String>, DatabaseError> {
+        let parser = AnySQL::new();
+        let mut target: BTreeMap<String, (&str, Vec<ColumnDefinition>)> = BTreeMap::new();
+        for sql in split_statements(target_ddl) {
+            match parser.parse(sql)? {
+                SqlStatement::CreateTable {
+                    table_name,
+                    columns,
+                    ..
+                } => {
+                    if target.insert(table_name.clone(), (sql, columns)).is_some() {
+                        return Err(DatabaseError::InvalidSqlSyntax(format!(
+                            "Target DDL creates table '{}' more than once",
+                            table_name
+                        )));
+                    }
+                }
+                // Comments are part of /export/ddl output but not compared
+                SqlStatement::Comment { .. } => {}
+                other => {
+                    return Err(DatabaseError::InvalidSqlSyntax(format!(
+                        "Target DDL may only contain CREATE TABLE and COMMENT statements, found {}",
+                        other.get_operation_name()
+                    )));
+                }
+            }
+        }
+
+        // Names are quoted so keywords and mixed case survive a round trip
+        let quote = |name: &str| DetectedDialect::Standard.quote_identifier(name);
+        let mut statements = Vec::new();
+        let mut alters = Vec::new();
+        for (table_name, (create_sql, columns)) in &target {
+            let Some(table) = self.tables.get(table_name) else {
+                statements.push(format!("{};", create_sql));
+                continue;
+            };
+
+            for column in columns {
+                let current = table.columns.iter().find(|current| current.name == column.name);
+                let action = match current {
+                    None => "ADD",
+                    Some(current) if current.data_type.as_str() != column.data_type.as_str() => {
+                        "MODIFY"
+                    }
+                    Some(_) => continue,
+                };
+                alters.push(format!(
+                    "ALTER TABLE {} {} COLUMN {} {};",
+                    quote(table_name),
+                    action,
+                    quote(&column.name),
+                    column.data_type.as_str()
+                ));
+            }
+            for current in &table.columns {
+                if !columns.iter().any(|column| column.name == current.name) {
+                    alters.push(format!(
+                        "ALTER TABLE {} DROP COLUMN {};",
+                        quote(table_name),
+                        quote(&current.name)
+                    ));
+                }
+            }
+        }
+        statements.append(&mut alters);
+
+        let mut dropped: Vec<&String> =
+            self.tables.keys().filter(|name| !target.contains_key(*name)).collect();
+        dropped.sort();
+        statements.extend(dropped.into_iter().map(|name| format!("DROP TABLE {};", quote(name))));
+
+        Ok(statements)
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            tables: self.tables.len(),
+            column_cache_entries: self.column_cache.len(),
+            query_cache_entries: self.query_cache.len(),
+            bloom_filters: self.bloom_filters.len(),
+        }
+    }
+
     fn rebuild_bloom_filters(&mut self) {
         self.bloom_filters.clear();
 
@@ -700,24 +2468,76 @@ impl Database {
                 .map(|(idx, row)| (row.columns.clone(), idx))
                 .collect();
 
-            bloom_filter.build_from_table(&table_data);
+            let mut progress = RebuildProgress::new(
+                format!("the bloom filter of table '{}'", table_name),
+                table_data.len(),
+            );
+            bloom_filter.build_from_table(&table_data, &mut progress);
+            progress.finish();
             self.bloom_filters.insert(table_name.clone(), bloom_filter);
         }
     }
 
     fn select_with_advanced_scan(
-        &mut self,
+        &self,
         table_name: &str,
         columns: &[String],
-        where_clause: Option<&WhereClause>,
+        where_clause: Option<&WhereExpr>,
         limit: Option<usize>,
         offset: Option<usize>,
+        budget: &MemoryBudget,
     ) -> Result<Vec<Row>, DatabaseError> {
         let table = self.tables.get(table_name)
             .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
 
+        if page_is_empty(table.rows.len(), limit, offset) {
+            return Ok(Vec::new());
+        }
+
+        if self.where_outside_bounds(table_name, where_clause) {
+            println!(
+                "[MirseoDB] Skip scan for table '{}': WHERE is outside the column bounds",
+                table_name
+            );
+            return Ok(Vec::new());
+        }
+
+        if let Some((row_ids, access)) = index_lookup(table, where_clause) {
+            if let IndexAccess::Composite { index, .. } = &access {
+                println!(
+                    "[MirseoDB] Using composite index '{}' for query optimization",
+                    index
+                );
+            }
+            println!(
+                "[MirseoDB] Index lookup on table '{}': {} candidate rows",
+                table_name,
+                row_ids.len()
+            );
+            let mut results = Vec::new();
+            let mut to_skip = offset.unwrap_or(0);
+            for row in row_ids.iter().filter_map(|&row_id| table.rows.get(row_id)) {
+                if limit.is_some_and(|limit| results.len() >= limit) {
+                    break;
+                }
+                if let Some(where_clause) = where_clause {
+                    if !self.evaluate_where_clause_optimized(row, where_clause)? {
+                        continue;
+                    }
+                }
+                if to_skip > 0 {
+                    to_skip -= 1;
+                    continue;
+                }
+                let row = self.project_columns_optimized(row, columns);
+                budget.charge_row(&row)?;
+                results.push(row);
+            }
+            return Ok(results);
+        }
+
         if !self.table_scan_options.use_bloom_filter {
-            return self.select_basic(table_name, columns, where_clause, limit, offset);
+            return self.select_basic(table_name, columns, where_clause, limit, offset, budget);
         }
 
         let bloom_filter = self.bloom_filters.get(table_name);
@@ -743,18 +2563,21 @@ impl Database {
                     return Ok(None);
                 }
 
-                Ok(Some(self.project_columns_optimized(row, columns)))
+                let row = self.project_columns_optimized(row, columns);
+                budget.charge_row(&row)?;
+                Ok(Some(row))
             };
 
+            // The bloom filter can only rule out single comparisons
             results = scanner.scan_with_bloom_filter(
                 &table.rows,
                 bloom_filter,
-                where_clause,
+                where_clause.and_then(WhereExpr::as_comparison),
                 limit,
                 processor,
             )?;
         } else {
-            results = self.select_basic(table_name, columns, where_clause, limit, offset)?;
+            results = self.select_basic(table_name, columns, where_clause, limit, offset, budget)?;
         }
 
         if self.table_scan_options.collect_statistics {
@@ -765,17 +2588,219 @@ impl Database {
         Ok(results)
     }
 
+    /// ORDER BY needs every matching row before LIMIT/OFFSET apply, and the sort
+    /// keys may not be projected, so rows are sorted whole and projected last.
+    #[allow(clippy::too_many_arguments)]
+    fn select_ordered(
+        &self,
+        table_name: &str,
+        columns: &[String],
+        where_clause: Option<&WhereExpr>,
+        order_by: &[OrderBy],
+        limit: Option<usize>,
+        offset: Option<usize>,
+        budget: &MemoryBudget,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        let table = self.tables.get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        if let Some(key) = order_by
+            .iter()
+            .find(|key| !table.columns.iter().any(|column| column.name == key.column))
+        {
+            return Err(DatabaseError::ColumnNotFound(key.column.clone()));
+        }
+        if page_is_empty(table.rows.len(), limit, offset) {
+            return Ok(Vec::new());
+        }
+
+        let all_columns = vec!["*".to_string()];
+        let mut rows = self.select_with_advanced_scan(
+            table_name,
+            &all_columns,
+            where_clause,
+            None,
+            None,
+            budget,
+        )?;
+
+        // The merge sort buffers up to half the rows
+        budget.charge(rows.len() / 2 * std::mem::size_of::<Row>())?;
+        rows.sort_by(|a, b| self.compare_rows_for_order(a, b, order_by));
+
+        Ok(rows
+            .iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|row| self.project_columns_optimized(row, columns))
+            .collect())
+    }
+
+    /// Buckets the rows matching `where_clause` by their `group_by` values and
+    /// folds each bucket into one row holding the selected group columns and the
+    /// aggregate results. Without GROUP BY the whole result is a single group, even
+    /// when no row matches. Other selected columns are an error unless
+    /// `UNGROUPED_COLUMNS=relaxed`, which takes them from each group's first row.
+    /// Groups come out ordered by their key unless `order_by` names result columns
+    /// to sort on.
+    #[allow(clippy::too_many_arguments)]
+    fn select_grouped(
+        &self,
+        table_name: &str,
+        columns: &[String],
+        aggregates: &[Aggregate],
+        group_by: &[String],
+        where_clause: Option<&WhereExpr>,
+        order_by: &[OrderBy],
+        budget: &MemoryBudget,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        let table = self.tables.get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        let referenced = group_by
+            .iter()
+            .chain(columns)
+            .chain(aggregates.iter().filter_map(|aggregate| aggregate.column.as_ref()));
+        for column in referenced {
+            if !table.columns.iter().any(|definition| &definition.name == column) {
+                return Err(DatabaseError::ColumnNotFound(column.clone()));
+            }
+        }
+        let ungrouped = columns.iter().find(|column| !group_by.contains(column));
+        if let (Some(column), UngroupedColumns::Strict) = (ungrouped, self.ungrouped_columns) {
+            return Err(DatabaseError::InvalidSqlSyntax(format!(
+                "Column '{}' must appear in GROUP BY or be used in an aggregate function \
+                 (set UNGROUPED_COLUMNS=relaxed in .mirseoDB/config.cfg to return the \
+                 value from the first row of each group, as MySQL does)",
+                column
+            )));
+        }
+        let output_names: Vec<&String> = columns
+            .iter()
+            .chain(aggregates.iter().map(|aggregate| &aggregate.name))
+            .collect();
+        if let Some(key) = order_by.iter().find(|key| !output_names.contains(&&key.column)) {
+            return Err(DatabaseError::ColumnNotFound(format!(
+                "{} (ORDER BY of a grouped query must name a result column)",
+                key.column
+            )));
+        }
+
+        let all_columns = vec!["*".to_string()];
+        let rows = self.select_with_advanced_scan(
+            table_name,
+            &all_columns,
+            where_clause,
+            None,
+            None,
+            budget,
+        )?;
+
+        let new_group = |row: Option<&Row>| {
+            let values: HashMap<String, SqlValue> = columns
+                .iter()
+                .map(|column| {
+                    let value = row.and_then(|row| row.columns.get(column));
+                    (column.clone(), value.cloned().unwrap_or(SqlValue::Null))
+                })
+                .collect();
+            let accumulators: Vec<Accumulator> =
+                aggregates.iter().map(|aggregate| Accumulator::new(aggregate.function)).collect();
+            (values, accumulators)
+        };
+
+        let mut groups = BTreeMap::new();
+        for row in &rows {
+            let key: Vec<IndexKey> = group_by
+                .iter()
+                .map(|column| row.columns.get(column).map(IndexKey::from).unwrap_or(IndexKey::Null))
+                .collect();
+            let (_, accumulators) = match groups.entry(key) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let group = new_group(Some(row));
+                    let key_values = group_by.iter().filter_map(|column| row.columns.get(column));
+                    let values = key_values.chain(group.0.values());
+                    let size = values.map(SqlValue::estimated_size).sum::<usize>()
+                        + group.1.len() * std::mem::size_of::<Accumulator>();
+                    budget.charge(size)?;
+                    entry.insert(group)
+                }
+            };
+            for (accumulator, aggregate) in accumulators.iter_mut().zip(aggregates) {
+                accumulator.add(aggregate, row)?;
+            }
+        }
+        if group_by.is_empty() && groups.is_empty() {
+            groups.insert(Vec::new(), new_group(None));
+        }
+
+        let mut grouped: Vec<Row> = groups
+            .into_values()
+            .map(|(mut values, accumulators)| {
+                for (aggregate, accumulator) in aggregates.iter().zip(accumulators) {
+                    values.insert(aggregate.name.clone(), accumulator.finish());
+                }
+                Row { columns: values }
+            })
+            .collect();
+        if !order_by.is_empty() {
+            grouped.sort_by(|a, b| self.compare_rows_for_order(a, b, order_by));
+        }
+        Ok(grouped)
+    }
+
+    fn compare_rows_for_order(&self, a: &Row, b: &Row, order_by: &[OrderBy]) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        for key in order_by {
+            let left = a.columns.get(&key.column).unwrap_or(&SqlValue::Null);
+            let right = b.columns.get(&key.column).unwrap_or(&SqlValue::Null);
+
+            // NULL placement follows the NULLS clause, not the sort direction
+            let ordering = match (left, right) {
+                (SqlValue::Null, SqlValue::Null) => Ordering::Equal,
+                (SqlValue::Null, _) => match key.nulls {
+                    NullsOrder::First => Ordering::Less,
+                    NullsOrder::Last => Ordering::Greater,
+                },
+                (_, SqlValue::Null) => match key.nulls {
+                    NullsOrder::First => Ordering::Greater,
+                    NullsOrder::Last => Ordering::Less,
+                },
+                _ => {
+                    let ordering = self.compare_values(left, right);
+                    match key.direction {
+                        SortDirection::Asc => ordering,
+                        SortDirection::Desc => ordering.reverse(),
+                    }
+                }
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    }
+
     fn select_basic(
         &self,
         table_name: &str,
         columns: &[String],
-        where_clause: Option<&WhereClause>,
+        where_clause: Option<&WhereExpr>,
         limit: Option<usize>,
         offset: Option<usize>,
+        budget: &MemoryBudget,
     ) -> Result<Vec<Row>, DatabaseError> {
         let table = self.tables.get(table_name)
             .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
 
+        if page_is_empty(table.rows.len(), limit, offset)
+            || self.where_outside_bounds(table_name, where_clause)
+        {
+            return Ok(Vec::new());
+        }
+
         let mut results = Vec::new();
         let skip_count = offset.unwrap_or(0);
         let mut current_skip = 0;
@@ -797,19 +2822,2786 @@ impl Database {
                 break;
             }
 
-            results.push(self.project_columns_optimized(row, columns));
+            let row = self.project_columns_optimized(row, columns);
+            budget.charge_row(&row)?;
+            results.push(row);
         }
 
         Ok(results)
     }
 
+    /// Whether cached column bounds prove `where_clause` matches nothing. Only
+    /// statistics already cached are consulted; a SELECT never computes them.
+    fn where_outside_bounds(&self, table_name: &str, where_clause: Option<&WhereExpr>) -> bool {
+        let (Some(where_clause), Some(statistics)) =
+            (where_clause, self.column_statistics.get(table_name))
+        else {
+            return false;
+        };
+
+        fn rules_out(statistics: &TableStatistics, expr: &WhereExpr) -> bool {
+            match expr {
+                WhereExpr::Comparison(comparison) => statistics
+                    .column(&comparison.column)
+                    .map(|column| column.rules_out(comparison))
+                    .unwrap_or(false),
+                WhereExpr::In { .. }
+                | WhereExpr::ScalarComparison { .. }
+                | WhereExpr::CurrentTimeComparison { .. }
+                | WhereExpr::BooleanColumn { .. } => false,
+                // Outside the bounds at either end rules out the whole range
+                WhereExpr::Between {
+                    column,
+                    low,
+                    high,
+                    negated: false,
+                } => {
+                    let bound = |operator, value: &SqlValue| WhereClause {
+                        column: column.clone(),
+                        operator,
+                        value: value.clone(),
+                    };
+                    statistics
+                        .column(column)
+                        .map(|stats| {
+                            stats.rules_out(&bound(ComparisonOperator::GreaterThanOrEqual, low))
+                                || stats.rules_out(&bound(ComparisonOperator::LessThanOrEqual, high))
+                        })
+                        .unwrap_or(false)
+                }
+                WhereExpr::Between { negated: true, .. } => false,
+                WhereExpr::And(terms) => terms.iter().any(|term| rules_out(statistics, term)),
+                WhereExpr::Or(terms) => terms.iter().all(|term| rules_out(statistics, term)),
+            }
+        }
+
+        rules_out(statistics, where_clause)
+    }
+
+    /// Describes how a SELECT would be executed without running it.
+    pub fn explain(&self, statement: &SqlStatement) -> Result<QueryPlan, DatabaseError> {
+        let (table_name, where_clause) = match statement {
+            SqlStatement::Select {
+                table_name,
+                where_clause,
+                ..
+            } => (table_name, where_clause.as_ref()),
+            other => {
+                return Err(DatabaseError::ParseError(format!(
+                    "EXPLAIN supports SELECT only, not {}",
+                    other.get_operation_name()
+                )));
+            }
+        };
+
+        if !self.tables.contains_key(table_name) {
+            return Err(DatabaseError::TableNotFound(table_name.clone()));
+        }
+
+        let plan = if self.where_outside_bounds(table_name, where_clause) {
+            QueryPlan {
+                table_name: table_name.clone(),
+                strategy: "skip_scan",
+                detail: Some(
+                    "WHERE is outside the cached min/max bounds; returns no rows without scanning"
+                        .to_string(),
+                ),
+            }
+        } else if let Some((row_ids, access)) =
+            index_lookup(&self.tables[table_name], where_clause)
+        {
+            let detail = match access {
+                IndexAccess::Composite { index, columns } => format!(
+                    "composite index '{}' on ({}) narrows the scan to {} candidate rows",
+                    index,
+                    columns.join(", "),
+                    row_ids.len()
+                ),
+                IndexAccess::Conjunct(WhereExpr::Comparison(WhereClause {
+                    column,
+                    operator: ComparisonOperator::Like,
+                    value: SqlValue::Text(pattern),
+                })) => format!(
+                    "LIKE '{}' is read as a prefix range of the index on {}, which narrows the \
+                     scan to {} candidate rows",
+                    pattern,
+                    column,
+                    row_ids.len()
+                ),
+                _ => format!("an index narrows the scan to {} candidate rows", row_ids.len()),
+            };
+            QueryPlan {
+                table_name: table_name.clone(),
+                strategy: "index_lookup",
+                detail: Some(detail),
+            }
+        } else {
+            QueryPlan {
+                table_name: table_name.clone(),
+                strategy: "full_scan",
+                detail: None,
+            }
+        };
+
+        Ok(plan)
+    }
+
     fn index_key_to_sql_value(&self, key: &IndexKey) -> Result<SqlValue, DatabaseError> {
         match key {
             IndexKey::Integer(i) => Ok(SqlValue::Integer(*i)),
             IndexKey::Float(f) => Ok(SqlValue::Float(f.value())),
-            IndexKey::Text(s) => Ok(SqlValue::Text(s.clone())),
+            IndexKey::Text(s) => Ok(SqlValue::Text(s.as_str().into())),
             IndexKey::Boolean(b) => Ok(SqlValue::Boolean(*b)),
             IndexKey::Null => Ok(SqlValue::Null),
         }
     }
 }
+
+/// The databases a server holds, by name. Names match without regard to case,
+/// since the parser uppercases unquoted ones; a database that is not open yet
+/// is loaded from its file the first time it is asked for.
+pub struct DatabaseRegistry {
+    default_name: String,
+    databases: Mutex<HashMap<String, Arc<RwLock<Database>>>>,
+}
+
+impl DatabaseRegistry {
+    pub fn new(default: Arc<RwLock<Database>>) -> Self {
+        let default_name = default.read().unwrap_or_else(PoisonError::into_inner).name.clone();
+        let mut databases = HashMap::new();
+        databases.insert(default_name.to_ascii_uppercase(), default);
+        Self {
+            default_name,
+            databases: Mutex::new(databases),
+        }
+    }
+
+    /// The database of queries that name none.
+    pub fn default_name(&self) -> &str {
+        &self.default_name
+    }
+
+    pub fn default_database(&self) -> Arc<RwLock<Database>> {
+        self.get(&self.default_name)
+            .expect("the default database is always registered")
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<RwLock<Database>>, DatabaseError> {
+        let mut databases = self.lock();
+        let key = name.to_ascii_uppercase();
+        if let Some(database) = databases.get(&key) {
+            return Ok(Arc::clone(database));
+        }
+
+        let stored_name = StorageEngine::stored_database_name(name)
+            .ok_or_else(|| DatabaseError::DatabaseNotFound(name.to_string()))?;
+        let database = Arc::new(RwLock::new(Database::load(stored_name)?));
+        databases.insert(key, Arc::clone(&database));
+        Ok(database)
+    }
+
+    /// Whether `name` is registered, which a database is from its first use on.
+    pub fn is_open(&self, name: &str) -> bool {
+        self.lock().contains_key(&name.to_ascii_uppercase())
+    }
+
+    /// Adds a database that was opened elsewhere, replacing one of the same name.
+    pub fn register(&self, database: Database) -> Arc<RwLock<Database>> {
+        let key = database.name.to_ascii_uppercase();
+        let database = Arc::new(RwLock::new(database));
+        self.lock().insert(key, Arc::clone(&database));
+        database
+    }
+
+    /// Brings the entry of `name` in line with its file after CREATE or DROP
+    /// DATABASE: a created database is reloaded, a dropped one forgotten. The
+    /// default database stays registered, as DROP empties it in place.
+    pub fn refresh(&self, name: &str) -> Result<(), DatabaseError> {
+        if name.eq_ignore_ascii_case(&self.default_name) {
+            return Ok(());
+        }
+        self.lock().remove(&name.to_ascii_uppercase());
+        if let Some(stored_name) = StorageEngine::stored_database_name(name) {
+            self.register(Database::load(stored_name)?);
+        }
+        Ok(())
+    }
+
+    /// Writes out the deferred ASYNC changes of every open database. All of them
+    /// are flushed even if one fails; the first error is returned.
+    pub fn flush_pending(&self) -> Result<bool, DatabaseError> {
+        let databases: Vec<_> = self.lock().values().cloned().collect();
+        let mut flushed = false;
+        let mut first_error = None;
+        for database in databases {
+            let result = match database.write() {
+                Ok(mut db) => db.flush_pending(),
+                Err(_) => Err(DatabaseError::IoError("Database lock poisoned".to_string())),
+            };
+            match result {
+                Ok(wrote) => flushed |= wrote,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(flushed), Err)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<RwLock<Database>>>> {
+        self.databases.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// SQL LIKE matching: `%` is any run of characters, `_` is one character and a
+/// backslash makes the next character literal. Case-sensitive.
+fn like_matches(text: &str, pattern: &str) -> bool {
+    enum Token {
+        Literal(char),
+        AnyOne,
+        AnyRun,
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        tokens.push(match ch {
+            '\\' => Token::Literal(chars.next().unwrap_or('\\')),
+            '%' => Token::AnyRun,
+            '_' => Token::AnyOne,
+            other => Token::Literal(other),
+        });
+    }
+
+    let text: Vec<char> = text.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // Last `%` seen and the text position it currently absorbs up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::AnyOne) => {
+                t += 1;
+                p += 1;
+            }
+            Some(Token::Literal(ch)) if *ch == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            Some(Token::AnyRun) => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            _ => match backtrack {
+                Some((run_p, run_t)) => {
+                    backtrack = Some((run_p, run_t + 1));
+                    p = run_p + 1;
+                    t = run_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[p..].iter().all(|token| matches!(token, Token::AnyRun))
+}
+
+/// Execution strategy reported by `Database::explain`.
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    pub table_name: String,
+    pub strategy: &'static str,
+    pub detail: Option<String>,
+}
+
+/// In-memory table and cache sizes reported by `/metrics`.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub tables: usize,
+    pub column_cache_entries: usize,
+    pub query_cache_entries: usize,
+    pub bloom_filters: usize,
+}
+
+/// A statement attaching `comment` to a table, or to one of its columns, for
+/// dialects without inline comments.
+fn comment_ddl(
+    dialect: &DetectedDialect,
+    table_name: &str,
+    column_name: Option<&str>,
+    comment: &str,
+) -> String {
+    if *dialect == DetectedDialect::MsSQL {
+        let column = column_name
+            .map(|column| format!(", 'COLUMN', {}", dialect.quote_string(column)))
+            .unwrap_or_default();
+        return format!(
+            "EXEC sp_addextendedproperty 'MS_Description', {}, 'SCHEMA', 'dbo', 'TABLE', {}{};\n",
+            dialect.quote_string(comment),
+            dialect.quote_string(table_name),
+            column
+        );
+    }
+
+    let target = match column_name {
+        Some(column) => format!(
+            "COLUMN {}.{}",
+            dialect.quote_identifier(table_name),
+            dialect.quote_identifier(column)
+        ),
+        None => format!("TABLE {}", dialect.quote_identifier(table_name)),
+    };
+    format!("COMMENT ON {} IS {};\n", target, dialect.quote_string(comment))
+}
+
+/// Outcome of `Database::checkpoint`.
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint {
+    /// Database file writes since the database was opened, this one included
+    pub lsn: u64,
+    pub bytes_written: usize,
+}
+
+/// Numeric columns mix freely; any other type only unions with itself.
+fn union_compatible(left: &DataType, right: &DataType) -> bool {
+    matches!(
+        (left, right),
+        (DataType::Integer | DataType::Float, DataType::Integer | DataType::Float)
+            | (DataType::Text, DataType::Text)
+            | (DataType::Boolean, DataType::Boolean)
+    )
+}
+
+/// Running state of one aggregate over a group. NULLs are skipped by every
+/// function except `COUNT(*)`.
+enum Accumulator {
+    Count(i64),
+    Sum(Option<SqlValue>),
+    Avg { total: f64, count: usize },
+    Min(Option<SqlValue>),
+    Max(Option<SqlValue>),
+}
+
+impl Accumulator {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Avg => Accumulator::Avg { total: 0.0, count: 0 },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+        }
+    }
+
+    fn add(&mut self, aggregate: &Aggregate, row: &Row) -> Result<(), DatabaseError> {
+        let Some(column) = &aggregate.column else {
+            if let Accumulator::Count(count) = self {
+                *count += 1;
+            }
+            return Ok(());
+        };
+        let value = match row.columns.get(column) {
+            None | Some(SqlValue::Null) => return Ok(()),
+            Some(value) => value,
+        };
+        let not_numeric = || {
+            DatabaseError::InvalidDataType(format!(
+                "{} needs numeric values, but '{}' holds {:?}",
+                aggregate.function.as_str(),
+                column,
+                value
+            ))
+        };
+
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => {
+                *sum = Some(match (sum.take(), value) {
+                    (None, SqlValue::Integer(_) | SqlValue::Float(_)) => value.clone(),
+                    (Some(SqlValue::Integer(a)), SqlValue::Integer(b)) => {
+                        SqlValue::Integer(a.checked_add(*b).ok_or_else(|| {
+                            DatabaseError::InvalidDataType(format!(
+                                "SUM({}) overflows a 64-bit integer",
+                                column
+                            ))
+                        })?)
+                    }
+                    (Some(SqlValue::Integer(a)), SqlValue::Float(b)) => SqlValue::Float(a as f64 + b),
+                    (Some(SqlValue::Float(a)), SqlValue::Integer(b)) => SqlValue::Float(a + *b as f64),
+                    (Some(SqlValue::Float(a)), SqlValue::Float(b)) => SqlValue::Float(a + b),
+                    _ => return Err(not_numeric()),
+                });
+            }
+            Accumulator::Avg { total, count } => {
+                *total += match value {
+                    SqlValue::Integer(i) => *i as f64,
+                    SqlValue::Float(f) => *f,
+                    _ => return Err(not_numeric()),
+                };
+                *count += 1;
+            }
+            Accumulator::Min(extreme) | Accumulator::Max(extreme) => {
+                let wanted = if aggregate.function == AggregateFunction::Min {
+                    std::cmp::Ordering::Less
+                } else {
+                    std::cmp::Ordering::Greater
+                };
+                let replace = match extreme.as_ref() {
+                    None => true,
+                    Some(current) => {
+                        compare_same_type(value, current).ok_or_else(|| {
+                            DatabaseError::InvalidDataType(format!(
+                                "{}({}) cannot compare {:?} with {:?}",
+                                aggregate.function.as_str(),
+                                column,
+                                value,
+                                current
+                            ))
+                        })? == wanted
+                    }
+                };
+                if replace {
+                    *extreme = Some(value.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> SqlValue {
+        match self {
+            Accumulator::Count(count) => SqlValue::Integer(count),
+            Accumulator::Avg { count: 0, .. } => SqlValue::Null,
+            Accumulator::Avg { total, count } => SqlValue::Float(total / count as f64),
+            Accumulator::Sum(value) | Accumulator::Min(value) | Accumulator::Max(value) => {
+                value.unwrap_or(SqlValue::Null)
+            }
+        }
+    }
+}
+
+/// Moves the value of each aliased column to its alias. A column that is also
+/// selected without an alias, or through `*`, keeps its own name as well.
+fn apply_aliases(rows: &mut [Row], columns: &[String], aliases: &[(String, String)]) {
+    if aliases.is_empty() {
+        return;
+    }
+    let kept = |column: &String| {
+        let selected = columns.iter().filter(|c| *c == column || *c == "*").count();
+        selected > aliases.iter().filter(|(c, _)| c == column).count()
+    };
+
+    for row in rows {
+        let values: Vec<Option<SqlValue>> =
+            aliases.iter().map(|(column, _)| row.columns.get(column).cloned()).collect();
+        for (column, _) in aliases {
+            if !kept(column) {
+                row.columns.remove(column);
+            }
+        }
+        for ((_, alias), value) in aliases.iter().zip(values) {
+            if let Some(value) = value {
+                row.columns.insert(alias.clone(), value);
+            }
+        }
+    }
+}
+
+/// Drops rows whose values in `columns` repeat an earlier row's, keeping order.
+/// Values compare exactly, so `1` and `1.0` stay distinct.
+fn distinct_rows(rows: Vec<Row>, columns: &[String]) -> Vec<Row> {
+    let mut seen = BTreeSet::new();
+    rows.into_iter()
+        .filter(|row| {
+            let key: Vec<IndexKey> = columns
+                .iter()
+                .map(|column| row.columns.get(column).map(IndexKey::from).unwrap_or(IndexKey::Null))
+                .collect();
+            seen.insert(key)
+        })
+        .collect()
+}
+
+/// Truth of a bare BOOLEAN column condition. NULL satisfies neither `column` nor
+/// `NOT column`; any other type is an error rather than being coerced.
+fn evaluate_boolean_column(row: &Row, column: &str, negated: bool) -> Result<bool, DatabaseError> {
+    match row.columns.get(column) {
+        Some(SqlValue::Boolean(value)) => Ok(*value != negated),
+        Some(SqlValue::Null) => Ok(false),
+        Some(other) => Err(DatabaseError::InvalidDataType(format!(
+            "Column '{}' holds {:?}, not a BOOLEAN; only BOOLEAN columns can be used as a \
+             condition without a comparison",
+            column, other
+        ))),
+        None => Err(DatabaseError::ColumnNotFound(column.to_string())),
+    }
+}
+
+/// The columns an INSERT gives a new row of `table`: the listed values coerced
+/// to their column types, and defaults for the columns left out.
+fn inserted_row(
+    table: &Table,
+    columns: &[String],
+    values: &[SqlValue],
+) -> Result<HashMap<String, SqlValue>, DatabaseError> {
+    let mut row_columns = HashMap::new();
+
+    for table_column in &table.columns {
+        if let Some(pos) = columns.iter().position(|c| c == &table_column.name) {
+            if let Some(value) = values.get(pos) {
+                let value = coerce_to_column(table_column, value.clone())?;
+                row_columns.insert(table_column.name.clone(), value);
+            }
+        } else if let Some(default) = &table_column.default {
+            row_columns.insert(table_column.name.clone(), default.clone());
+        } else if !table_column.nullable && !table_column.primary_key {
+            return Err(DatabaseError::ColumnNotFound(format!(
+                "Non-nullable column '{}' requires a value",
+                table_column.name
+            )));
+        }
+    }
+
+    Ok(row_columns)
+}
+
+/// `value` as the declared type of `column`: integers widen to FLOAT, whole
+/// floats and integer text become INTEGER, numeric text becomes FLOAT and 0 or 1
+/// a BOOLEAN. Other values of the wrong type, and NULL in a NOT NULL column, are
+/// rejected. NULL primary keys are left to the primary key check.
+fn coerce_to_column(column: &ColumnDefinition, value: SqlValue) -> Result<SqlValue, DatabaseError> {
+    let coerced = match (&column.data_type, &value) {
+        (_, SqlValue::Null) if column.nullable || column.primary_key => return Ok(value),
+        (_, SqlValue::Null) => {
+            return Err(DatabaseError::InvalidDataType(format!(
+                "Column '{}' is NOT NULL and cannot be set to NULL",
+                column.name
+            )));
+        }
+        (DataType::Integer, SqlValue::Integer(_))
+        | (DataType::Float, SqlValue::Float(_))
+        | (DataType::Text, SqlValue::Text(_))
+        | (DataType::Boolean, SqlValue::Boolean(_)) => return Ok(value),
+        (DataType::Integer, SqlValue::Float(float))
+            if float.fract() == 0.0 && float.abs() < i64::MAX as f64 =>
+        {
+            Some(SqlValue::Integer(*float as i64))
+        }
+        (DataType::Integer, SqlValue::Text(text)) => {
+            text.trim().parse::<i64>().ok().map(SqlValue::Integer)
+        }
+        (DataType::Float, SqlValue::Integer(integer)) => Some(SqlValue::Float(*integer as f64)),
+        (DataType::Float, SqlValue::Text(text)) => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|float| float.is_finite())
+            .map(SqlValue::Float),
+        (DataType::Boolean, SqlValue::Integer(integer @ (0 | 1))) => {
+            Some(SqlValue::Boolean(*integer == 1))
+        }
+        _ => None,
+    };
+    coerced.ok_or_else(|| {
+        DatabaseError::InvalidDataType(format!(
+            "Column '{}' is {} and cannot hold {:?}",
+            column.name,
+            column.data_type.as_str(),
+            value
+        ))
+    })
+}
+
+/// The `DESCRIBE` row of one column.
+fn describe_column(column: &ColumnDefinition) -> Row {
+    let mut columns = HashMap::new();
+    columns.insert("column_name".to_string(), SqlValue::Text(column.name.as_str().into()));
+    columns.insert("data_type".to_string(), SqlValue::Text(column.data_type.as_str().into()));
+    columns.insert("nullable".to_string(), SqlValue::Boolean(column.nullable));
+    columns.insert("primary_key".to_string(), SqlValue::Boolean(column.primary_key));
+    columns.insert("default".to_string(), column.default.clone().unwrap_or(SqlValue::Null));
+    columns.insert("auto_increment".to_string(), SqlValue::Boolean(column.auto_increment));
+    Row { columns }
+}
+
+/// Statements that change tables and so go through the write-ahead log.
+fn is_logged(statement: &SqlStatement) -> bool {
+    matches!(
+        statement,
+        SqlStatement::CreateTable { .. }
+            | SqlStatement::Insert { .. }
+            | SqlStatement::Update { .. }
+            | SqlStatement::Delete { .. }
+            | SqlStatement::DropTable { .. }
+            | SqlStatement::ResetDatabase { .. }
+            | SqlStatement::AlterTable { .. }
+            | SqlStatement::CreateCompositeIndex { .. }
+            | SqlStatement::DropIndex { .. }
+            | SqlStatement::Comment { .. }
+    )
+}
+
+fn no_transaction() -> DatabaseError {
+    DatabaseError::InvalidSqlSyntax("No transaction in progress".to_string())
+}
+
+/// The current UTC time in the text form NOW() compares with.
+fn current_timestamp() -> SqlValue {
+    let now = time::OffsetDateTime::now_utc();
+    SqlValue::Text(
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        )
+        .into(),
+    )
+}
+
+/// Replaces the NOW() comparisons in `expr` with comparisons against `now`.
+/// Returns whether there were any.
+fn resolve_current_time(expr: &mut WhereExpr, now: &SqlValue) -> bool {
+    match expr {
+        WhereExpr::CurrentTimeComparison { column, operator } => {
+            *expr = WhereExpr::Comparison(WhereClause {
+                column: column.clone(),
+                operator: operator.clone(),
+                value: now.clone(),
+            });
+            true
+        }
+        WhereExpr::And(terms) | WhereExpr::Or(terms) => terms
+            .iter_mut()
+            .fold(false, |found, term| resolve_current_time(term, now) | found),
+        _ => false,
+    }
+}
+
+/// `sql` with each `NOW()` or `CURRENT_TIMESTAMP` after its WHERE replaced by
+/// the literal `TIMESTAMP '<now>'`, skipping quoted text.
+fn with_timestamp_literals(sql: &str, now: &SqlValue) -> String {
+    let SqlValue::Text(now) = now else {
+        return sql.to_string();
+    };
+    let literal = format!("TIMESTAMP '{}'", now);
+    let upper = sql.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    let is_word = |at: usize| bytes.get(at).is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_');
+    let word_at = |at: usize, word: &str| {
+        bytes[at..].starts_with(word.as_bytes())
+            && (at == 0 || !is_word(at - 1))
+            && !is_word(at + word.len())
+    };
+    // Index just past `()` (with optional spaces) at `at`, if it is there
+    let empty_parentheses = |at: usize| {
+        let rest = upper[at..].trim_start();
+        let rest = rest.strip_prefix('(')?.trim_start().strip_prefix(')')?;
+        Some(upper.len() - rest.len())
+    };
+
+    let mut result = String::with_capacity(sql.len());
+    let mut in_where = false;
+    let mut quote = None;
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        match quote {
+            Some(open) if byte == open => quote = None,
+            Some(_) => {}
+            None if matches!(byte, b'\'' | b'"' | b'`') => quote = Some(byte),
+            None if word_at(i, "WHERE") => in_where = true,
+            None if in_where && (word_at(i, "NOW") || word_at(i, "CURRENT_TIMESTAMP")) => {
+                let word_end = i + if word_at(i, "NOW") { 3 } else { 17 };
+                let end = match (empty_parentheses(word_end), word_at(i, "NOW")) {
+                    (Some(end), _) => end,
+                    (None, false) => word_end,
+                    (None, true) => {
+                        i += 1;
+                        continue;
+                    }
+                };
+                result.push_str(&sql[copied..i]);
+                result.push_str(&literal);
+                copied = end;
+                i = end;
+                continue;
+            }
+            None => {}
+        }
+        i += 1;
+    }
+    result.push_str(&sql[copied..]);
+    result
+}
+
+fn unresolved_subquery() -> DatabaseError {
+    DatabaseError::InvalidSqlSyntax("Subquery was not resolved before evaluation".to_string())
+}
+
+/// Row positions, in table order, that can satisfy the WHERE according to an
+/// index. A lone `column = value`, `column IN (values)`, `column BETWEEN low AND
+/// high` or `column LIKE 'prefix%'` is looked up directly; equalities on the
+/// leading columns of a composite index are looked up together. For an AND the
+/// most selective lookup (fewest candidates) is used and the other conjuncts
+/// become a residual filter. `None` when no usable index exists; the caller still
+/// checks every candidate against the whole WHERE.
+fn index_candidates(table: &Table, where_clause: Option<&WhereExpr>) -> Option<Vec<usize>> {
+    index_lookup(table, where_clause).map(|(row_ids, _)| row_ids)
+}
+
+/// Which index `index_lookup` chose and for which part of the WHERE.
+enum IndexAccess<'a> {
+    /// A single-column index, for one term of the WHERE.
+    Conjunct(&'a WhereExpr),
+    /// A composite index, for its leading `columns`.
+    Composite { index: String, columns: Vec<String> },
+}
+
+/// `index_candidates` along with how the index was used.
+fn index_lookup<'a>(
+    table: &Table,
+    where_clause: Option<&'a WhereExpr>,
+) -> Option<(Vec<usize>, IndexAccess<'a>)> {
+    let terms = match where_clause? {
+        WhereExpr::And(terms) => terms.as_slice(),
+        expr => std::slice::from_ref(expr),
+    };
+    // The composite lookup comes first so it wins a tie
+    let (mut row_ids, access) = composite_candidates(table, terms)
+        .into_iter()
+        .chain(terms.iter().filter_map(|term| {
+            conjunct_candidates(table, term).map(|row_ids| (row_ids, IndexAccess::Conjunct(term)))
+        }))
+        .min_by_key(|(row_ids, _)| row_ids.len())?;
+
+    row_ids.sort_unstable();
+    row_ids.dedup();
+    Some((row_ids, access))
+}
+
+/// Column and value of `column = value` when an exact index lookup finds the
+/// same rows as the scan. The scan counts NULLs and mismatched types as equal,
+/// which a lookup cannot reproduce; only a NOT NULL column compared with a value
+/// of its declared type is safe.
+fn exact_equality<'a>(table: &Table, expr: &'a WhereExpr) -> Option<(&'a str, &'a SqlValue)> {
+    let WhereExpr::Comparison(WhereClause {
+        column,
+        operator: ComparisonOperator::Equal,
+        value,
+    }) = expr
+    else {
+        return None;
+    };
+    let definition = table.columns.iter().find(|definition| &definition.name == column)?;
+    let value_type = value.data_type()?;
+    if (definition.nullable && !definition.primary_key)
+        || value_type.as_str() != definition.data_type.as_str()
+    {
+        return None;
+    }
+    Some((column, value))
+}
+
+/// Looks up the equalities among `terms` in the composite index they best cover.
+/// Only the leading index columns that all have an equality are used: the whole
+/// key is found exactly and a shorter prefix by `find_prefix`. A BETWEEN on the
+/// last index column after such a prefix becomes a key range.
+fn composite_candidates<'a>(
+    table: &Table,
+    terms: &[WhereExpr],
+) -> Option<(Vec<usize>, IndexAccess<'a>)> {
+    let equalities: Vec<(&str, &SqlValue)> =
+        terms.iter().filter_map(|term| exact_equality(table, term)).collect();
+    let columns: Vec<String> = equalities.iter().map(|(column, _)| column.to_string()).collect();
+    let index = table.index_manager.find_best_composite_index(&columns)?;
+    // Rows leaving a nullable column out are not in the index at all
+    let indexed = index.column_names.iter().all(|name| {
+        table
+            .columns
+            .iter()
+            .any(|column| &column.name == name && (!column.nullable || column.primary_key))
+    });
+    if !indexed {
+        return None;
+    }
+
+    let mut prefix: Vec<&SqlValue> = index
+        .column_names
+        .iter()
+        .map_while(|name| equalities.iter().find(|(column, _)| column == name).map(|(_, v)| *v))
+        .collect();
+    let mut used = index.column_names[..prefix.len()].to_vec();
+    if prefix.is_empty() {
+        return None;
+    }
+
+    let row_ids = if prefix.len() == index.column_names.len() {
+        index.find_exact(&prefix)
+    } else {
+        let next = &index.column_names[prefix.len()];
+        let range = terms.iter().find_map(|term| match term {
+            WhereExpr::Between {
+                column,
+                low,
+                high,
+                negated: false,
+            } if column == next && prefix.len() + 1 == index.column_names.len() => {
+                Some((low, high))
+            }
+            _ => None,
+        });
+        match range {
+            Some((low, high)) => {
+                used.push(next.clone());
+                match compare_same_type(low, high)? {
+                    std::cmp::Ordering::Greater => Vec::new(),
+                    _ => {
+                        let mut end = prefix.clone();
+                        prefix.push(low);
+                        end.push(high);
+                        index.find_range_composite(Some(&prefix), Some(&end))
+                    }
+                }
+            }
+            None => index.find_prefix(&prefix),
+        }
+    };
+
+    Some((
+        row_ids,
+        IndexAccess::Composite {
+            index: index.name.clone(),
+            columns: used,
+        },
+    ))
+}
+
+fn conjunct_candidates(table: &Table, expr: &WhereExpr) -> Option<Vec<usize>> {
+    if let Some((column, value)) = exact_equality(table, expr) {
+        let index = table.index_manager.find_best_index_for_query(column)?;
+        return Some(index.find_exact(value));
+    }
+    match expr {
+        WhereExpr::In {
+            column,
+            source: InSource::Values(values),
+            negated: false,
+        } => {
+            let index = table.index_manager.find_best_index_for_query(column)?;
+            Some(values.iter().flat_map(|value| index.find_exact(value)).collect())
+        }
+        WhereExpr::Between {
+            column,
+            low,
+            high,
+            negated: false,
+        } => {
+            let index = table.index_manager.find_best_index_for_query(column)?;
+            // Folded text keys do not order like the case-sensitive comparison
+            if index.case_insensitive {
+                return None;
+            }
+            match compare_same_type(low, high)? {
+                std::cmp::Ordering::Greater => Some(Vec::new()),
+                _ => Some(index.find_range(Some(low), Some(high))),
+            }
+        }
+        WhereExpr::Comparison(WhereClause {
+            column,
+            operator: ComparisonOperator::Like,
+            value: SqlValue::Text(pattern),
+        }) => {
+            // Every text starting with the prefix sorts between the prefix and its
+            // successor; the LIKE itself is then checked on each candidate
+            let prefix = like_prefix(pattern)?;
+            let index = table.index_manager.find_best_index_for_query(column)?;
+            if index.case_insensitive {
+                return None;
+            }
+            let low = SqlValue::Text(prefix.as_str().into());
+            let high = prefix_successor(&prefix).map(|high| SqlValue::Text(high.into()));
+            Some(index.find_range(Some(&low), high.as_ref()))
+        }
+        _ => None,
+    }
+}
+
+/// The literal text of a `prefix%` LIKE pattern: characters (backslash escapes
+/// included) followed by a single trailing `%` and no other wildcard.
+fn like_prefix(pattern: &str) -> Option<String> {
+    let mut prefix = String::new();
+    let mut chars = pattern.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => prefix.push(chars.next().unwrap_or('\\')),
+            '%' if chars.as_str().is_empty() && !prefix.is_empty() => return Some(prefix),
+            '%' | '_' => return None,
+            other => prefix.push(other),
+        }
+    }
+    None
+}
+
+/// The prefix with its last character incremented ('Jo' -> 'Jp'), which sorts
+/// after every string starting with `prefix`. `None` when no such string exists.
+fn prefix_successor(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+
+    while let Some(last) = chars.pop() {
+        // Skips the surrogate gap, which has no chars
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// True when LIMIT/OFFSET alone guarantee an empty result: the table has no more
+/// rows than the offset skips, or the limit is zero.
+fn page_is_empty(row_count: usize, limit: Option<usize>, offset: Option<usize>) -> bool {
+    limit == Some(0) || offset.is_some_and(|offset| offset > 0 && offset >= row_count)
+}
+
+/// Rebuilds every index of `table` so that row ids equal row positions again,
+/// which removing rows from the middle of `rows` would otherwise break.
+fn reindex_table(table: &mut Table) -> Result<(), DatabaseError> {
+    let snapshot: Vec<(HashMap<String, SqlValue>, usize)> = table
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(row_id, row)| (row.columns.clone(), row_id))
+        .collect();
+
+    table.index_manager.rebuild_all_indexes(&table.name, &snapshot)?;
+    table.next_row_id = table.rows.len();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ASYNC tables defer persistence and in-memory databases keep no write-ahead
+    // log, so these tests never write a file
+    #[test]
+    fn test_query_parses_and_executes() {
+        let mut db = Database::in_memory("query_api_test");
+
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (1, 'alice')").unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (2, 'bob')").unwrap();
+
+        let rows = db.query("SELECT name FROM users WHERE id = 2").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(matches!(
+            rows[0].columns.get("name"),
+            Some(SqlValue::Text(name)) if &**name == "bob"
+        ));
+
+        assert!(matches!(
+            db.query("SELEC name FROM users"),
+            Err(DatabaseError::ParseError(_))
+        ));
+    }
+
+    fn ordered_ids(db: &mut Database, sql: &str) -> Vec<i64> {
+        db.query(sql)
+            .unwrap()
+            .iter()
+            .map(|row| match row.columns.get("id") {
+                Some(SqlValue::Integer(id)) => *id,
+                other => panic!("unexpected id {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_order_by_nulls_placement() {
+        let mut db = Database::in_memory("order_by_test");
+        db.query("CREATE TABLE scores (id INTEGER PRIMARY KEY, score INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO scores (id, score) VALUES (1, 20)").unwrap();
+        db.query("INSERT INTO scores (id, score) VALUES (2, NULL)").unwrap();
+        db.query("INSERT INTO scores (id, score) VALUES (3, 10)").unwrap();
+        db.query("INSERT INTO scores (id) VALUES (4)").unwrap();
+
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM scores ORDER BY score, id"),
+            vec![3, 1, 2, 4]
+        );
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM scores ORDER BY score DESC, id"),
+            vec![1, 3, 2, 4]
+        );
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM scores ORDER BY score NULLS FIRST, id DESC"),
+            vec![4, 2, 3, 1]
+        );
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM scores ORDER BY score DESC NULLS LAST, id"),
+            vec![1, 3, 2, 4]
+        );
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM scores ORDER BY score DESC NULLS FIRST, id LIMIT 3"),
+            vec![2, 4, 1]
+        );
+    }
+
+    #[test]
+    fn test_order_by_columns_in_opposite_directions() {
+        let mut db = Database::in_memory("order_by_multi_test");
+        db.query(
+            "CREATE TABLE staff (id INTEGER PRIMARY KEY, dept TEXT, salary INTEGER) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+        let staff = [(1, "ops", 50), (2, "dev", 70), (3, "ops", 90), (4, "dev", 40), (5, "dev", 70)];
+        for (id, dept, salary) in staff {
+            db.query(&format!(
+                "INSERT INTO staff (id, dept, salary) VALUES ({}, '{}', {})",
+                id, dept, salary
+            ))
+            .unwrap();
+        }
+
+        let query = "SELECT id FROM staff WHERE salary > 45 ORDER BY dept ASC, salary DESC, id";
+        assert_eq!(ordered_ids(&mut db, query), vec![2, 5, 3, 1]);
+        // Paging applies after the sort, so consecutive pages line up
+        assert_eq!(ordered_ids(&mut db, &format!("{} LIMIT 2", query)), vec![2, 5]);
+        assert_eq!(ordered_ids(&mut db, &format!("{} LIMIT 2 OFFSET 2", query)), vec![3, 1]);
+        assert!(matches!(
+            db.query("SELECT id FROM staff ORDER BY missing"),
+            Err(DatabaseError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_enforces_unique_index() {
+        let mut db = Database::in_memory("update_unique_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (1, 'alice')").unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (2, 'bob')").unwrap();
+
+        assert!(matches!(
+            db.query("UPDATE users SET id = 1 WHERE name = 'bob'"),
+            Err(DatabaseError::UniqueConstraintViolation(_))
+        ));
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM users ORDER BY id"), vec![1, 2]);
+
+        // Updating a row to its own current value is not a conflict
+        db.query("UPDATE users SET id = 2 WHERE name = 'bob'").unwrap();
+        db.query("UPDATE users SET id = 3 WHERE name = 'bob'").unwrap();
+        assert!(db.query("INSERT INTO users (id, name) VALUES (2, 'carol')").is_ok());
+        assert!(db.query("INSERT INTO users (id, name) VALUES (3, 'dave')").is_err());
+    }
+
+    #[test]
+    fn test_update_conflict_rolls_back_all_rows() {
+        let mut db = Database::in_memory("update_rollback_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (1, 'a')").unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (2, 'b')").unwrap();
+        db.query("INSERT INTO users (id, name) VALUES (3, 'c')").unwrap();
+
+        // The first row can take id 9, the second one conflicts with it
+        assert!(db.query("UPDATE users SET id = 9").is_err());
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM users ORDER BY id"),
+            vec![1, 2, 3]
+        );
+        assert!(db.query("INSERT INTO users (id, name) VALUES (9, 'd')").is_ok());
+        assert!(db.query("INSERT INTO users (id, name) VALUES (1, 'e')").is_err());
+
+        db.query("DELETE FROM users WHERE id = 2").unwrap();
+        assert!(db.query("INSERT INTO users (id, name) VALUES (2, 'f')").is_ok());
+    }
+
+    #[test]
+    fn test_primary_key_rejects_null() {
+        let mut db = Database::in_memory("primary_key_null_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+
+        for sql in [
+            "INSERT INTO users (id, name) VALUES (NULL, 'a')",
+            "INSERT INTO users (name) VALUES ('b')",
+        ] {
+            assert!(
+                matches!(db.query(sql), Err(DatabaseError::PrimaryKeyViolation(_))),
+                "{}",
+                sql
+            );
+        }
+        db.query("INSERT INTO users (id, name) VALUES (1, 'c')").unwrap();
+        assert!(matches!(
+            db.query("UPDATE users SET id = NULL WHERE name = 'c'"),
+            Err(DatabaseError::PrimaryKeyViolation(_))
+        ));
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM users ORDER BY id"), vec![1]);
+    }
+
+    #[test]
+    fn test_offset_beyond_table_is_empty() {
+        let mut db = Database::in_memory("offset_bounds_test");
+        db.query("CREATE TABLE t (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+        for id in 1..=3 {
+            db.query(&format!("INSERT INTO t (id) VALUES ({})", id)).unwrap();
+        }
+
+        assert!(db.query("SELECT id FROM t OFFSET 100000000").unwrap().is_empty());
+        assert!(db.query("SELECT id FROM t ORDER BY id OFFSET 3").unwrap().is_empty());
+        assert!(db.query("SELECT id FROM t LIMIT 0").unwrap().is_empty());
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM t ORDER BY id LIMIT 18446744073709551615 OFFSET 2"),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn test_drop_database_only_drops_the_named_database() {
+        let mut db = Database::in_memory("drop_database_test");
+        db.query("CREATE TABLE t (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+        db.query("INSERT INTO t (id) VALUES (1)").unwrap();
+
+        assert!(matches!(
+            db.query("DROP DATABASE no_such_database"),
+            Err(DatabaseError::DatabaseNotFound(_))
+        ));
+        assert!(matches!(
+            db.query("CREATE DATABASE drop_database_test"),
+            Err(DatabaseError::DatabaseAlreadyExists(_))
+        ));
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM t"), vec![1]);
+
+        db.query("DROP DATABASE drop_database_test").unwrap();
+        assert!(db.tables.is_empty());
+    }
+
+    #[test]
+    fn test_column_statistics_refresh_after_writes() {
+        let mut db = Database::in_memory("column_statistics_test");
+        db.query("CREATE TABLE t (id INTEGER PRIMARY KEY, age INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO t (id, age) VALUES (1, 30)").unwrap();
+
+        let max_age = |db: &mut Database| {
+            let statistics = db.column_statistics("T").unwrap();
+            statistics.column("age").unwrap().max.clone()
+        };
+        assert!(matches!(max_age(&mut db), Some(SqlValue::Integer(30))));
+
+        db.query("INSERT INTO t (id, age) VALUES (2, 45)").unwrap();
+        assert!(matches!(max_age(&mut db), Some(SqlValue::Integer(45))));
+
+        db.query("ANALYZE TABLE t").unwrap();
+        assert_eq!(db.column_statistics("T").unwrap().row_count, 2);
+        assert!(db.query("ANALYZE missing").is_err());
+    }
+
+    #[test]
+    fn test_out_of_range_where_skips_scan() {
+        let mut db = Database::in_memory("skip_scan_test");
+        db.query("CREATE TABLE t (id INTEGER PRIMARY KEY, age INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        for id in 1..=3 {
+            db.query(&format!("INSERT INTO t (id, age) VALUES ({}, {})", id, id * 30))
+                .unwrap();
+        }
+
+        let select = "SELECT id FROM t WHERE age > 200";
+        let statement = AnySQL::new().parse(select).unwrap();
+        assert_eq!(db.explain(&statement).unwrap().strategy, "full_scan");
+
+        db.query("ANALYZE t").unwrap();
+        assert_eq!(db.explain(&statement).unwrap().strategy, "skip_scan");
+        assert!(db.query(select).unwrap().is_empty());
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM t WHERE age >= 90"), vec![3]);
+    }
+
+    #[test]
+    fn test_compound_where_precedence_and_grouping() {
+        let mut db = Database::in_memory("compound_where_test");
+        db.query(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, age INTEGER, country TEXT) DURABILITY ASYNC",
+        )
+        .unwrap();
+        for (id, age, country) in [(1, 15, "KR"), (2, 20, "KR"), (3, 30, "US"), (4, 40, "JP")] {
+            db.query(&format!(
+                "INSERT INTO users (id, age, country) VALUES ({}, {}, '{}')",
+                id, age, country
+            ))
+            .unwrap();
+        }
+
+        let ids = |db: &mut Database, condition: &str| {
+            ordered_ids(db, &format!("SELECT id FROM users WHERE {} ORDER BY id", condition))
+        };
+
+        assert_eq!(ids(&mut db, "age > 18 AND country = 'KR'"), vec![2]);
+        // AND binds tighter: country = 'US' OR (country = 'KR' AND age > 18)
+        assert_eq!(ids(&mut db, "country = 'US' OR country = 'KR' AND age > 18"), vec![2, 3]);
+        assert_eq!(ids(&mut db, "(country = 'US' OR country = 'KR') AND age > 18"), vec![2, 3]);
+        assert_eq!(
+            ids(&mut db, "(country = 'US' OR country = 'KR') AND (age<18 OR age>=30)"),
+            vec![1, 3]
+        );
+
+        db.query("DELETE FROM users WHERE country = 'JP' OR age < 18").unwrap();
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM users ORDER BY id"), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_where_literals_keep_spacing_and_keywords() {
+        let mut db = Database::in_memory("where_literal_test");
+        db.query("CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT) DURABILITY ASYNC")
+            .unwrap();
+        for (id, body) in [(1, "a  b"), (2, "a b"), (3, "x LIMIT 1"), (4, "p OR q")] {
+            db.query(&format!("INSERT INTO notes (id, body) VALUES ({}, '{}')", id, body))
+                .unwrap();
+        }
+
+        let ids = |db: &mut Database, condition: &str| {
+            ordered_ids(db, &format!("SELECT id FROM notes WHERE {} ORDER BY id", condition))
+        };
+        assert_eq!(ids(&mut db, "body = 'a  b'"), vec![1]);
+        assert_eq!(ids(&mut db, "body = 'x LIMIT 1'"), vec![3]);
+        assert_eq!(ids(&mut db, "body = 'p OR q' OR body = 'a b'"), vec![2, 4]);
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM notes WHERE body = 'x LIMIT 1' LIMIT 5"),
+            vec![3]
+        );
+
+        db.query("UPDATE notes SET body = 'c  d' WHERE body = 'a  b'").unwrap();
+        assert_eq!(ids(&mut db, "body = 'c  d'"), vec![1]);
+        db.query("DELETE FROM notes WHERE body = 'x LIMIT 1'").unwrap();
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM notes ORDER BY id"), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_like_patterns() {
+        assert!(like_matches("john", "jo%"));
+        assert!(!like_matches("ajohn", "jo%"));
+        assert!(like_matches("ajohn", "%oh%"));
+        assert!(like_matches("john", "j_h_"));
+        assert!(!like_matches("john", "j_h"));
+        assert!(!like_matches("John", "jo%"));
+        assert!(like_matches("100%", "100\\%"));
+        assert!(!like_matches("1000", "100\\%"));
+        assert!(like_matches("a_b", "a\\_b"));
+        assert!(!like_matches("axb", "a\\_b"));
+        assert!(like_matches("", "%"));
+
+        let mut db = Database::in_memory("like_operator_test");
+        db.query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC").unwrap();
+        for (id, name) in [(1, "joan"), (2, "john"), (3, "mojo")] {
+            db.query(&format!("INSERT INTO t (id, name) VALUES ({}, '{}')", id, name))
+                .unwrap();
+        }
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM t WHERE name LIKE 'jo%' ORDER BY id"),
+            vec![1, 2]
+        );
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM t WHERE name NOT LIKE 'jo%'"), vec![3]);
+    }
+
+    #[test]
+    fn test_in_subquery_filters_outer_rows() {
+        let mut db = Database::in_memory("in_subquery_test");
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, active BOOLEAN) DURABILITY ASYNC")
+            .unwrap();
+        for (id, user_id) in [(1, 10), (2, 20), (3, 10), (4, 30)] {
+            db.query(&format!("INSERT INTO orders (id, user_id) VALUES ({}, {})", id, user_id))
+                .unwrap();
+        }
+        for (id, active) in [(10, true), (20, false), (30, true)] {
+            db.query(&format!("INSERT INTO users (id, active) VALUES ({}, {})", id, active))
+                .unwrap();
+        }
+
+        assert_eq!(
+            ordered_ids(
+                &mut db,
+                "SELECT id FROM orders WHERE user_id IN (SELECT id FROM users WHERE active = true) \
+                 ORDER BY id LIMIT 2"
+            ),
+            vec![1, 3]
+        );
+        assert_eq!(
+            ordered_ids(
+                &mut db,
+                "SELECT id FROM orders WHERE id > 1 AND user_id IN (SELECT id FROM users LIMIT 1)"
+            ),
+            vec![3]
+        );
+
+        db.query("DELETE FROM orders WHERE user_id IN (SELECT id FROM users WHERE active = false)")
+            .unwrap();
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM orders ORDER BY id"), vec![1, 3, 4]);
+
+        assert!(matches!(
+            db.query("SELECT id FROM orders WHERE user_id IN (SELECT * FROM users)"),
+            Err(DatabaseError::InvalidSqlSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_in_list_predicates() {
+        let mut db = Database::in_memory("in_list_test");
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, status TEXT) DURABILITY ASYNC")
+            .unwrap();
+        for (id, status) in [(1, "paid"), (2, "open"), (3, "shipped"), (4, "paid")] {
+            db.query(&format!("INSERT INTO orders (id, status) VALUES ({}, '{}')", id, status))
+                .unwrap();
+        }
+        db.query("INSERT INTO orders (id, status) VALUES (5, NULL)").unwrap();
+
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM orders WHERE status IN ('paid','shipped')"),
+            vec![1, 3, 4]
+        );
+        // NULL matches neither form
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM orders WHERE status NOT IN ('paid', 'shipped')"),
+            vec![2]
+        );
+        assert!(ordered_ids(&mut db, "SELECT id FROM orders WHERE id IN ()").is_empty());
+
+        // `id` has a primary key index; the OR forces the same list through a full scan
+        let indexed = ordered_ids(&mut db, "SELECT id FROM orders WHERE id IN (4, 2, 4, 9)");
+        let scanned = ordered_ids(
+            &mut db,
+            "SELECT id FROM orders WHERE id IN (4, 2, 4, 9) OR id IN ()",
+        );
+        assert_eq!(indexed, vec![2, 4]);
+        assert_eq!(indexed, scanned);
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM orders WHERE id IN (1,2,3,4) LIMIT 2 OFFSET 1"),
+            vec![2, 3]
+        );
+    }
+
+    #[test]
+    fn test_between_indexed_and_scanned_paths_agree() {
+        let mut db = Database::in_memory("between_test");
+        // `id` gets a primary key index; the nullable `price` column has none
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY, price INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        for id in [7, 1, 9, 3, 5, 4] {
+            db.query(&format!("INSERT INTO items (id, price) VALUES ({}, {})", id, id))
+                .unwrap();
+        }
+
+        let indexed = ordered_ids(&mut db, "SELECT id FROM items WHERE id BETWEEN 3 AND 7");
+        let scanned = ordered_ids(&mut db, "SELECT id FROM items WHERE price BETWEEN 3 AND 7");
+        assert_eq!(indexed, vec![7, 3, 5, 4]);
+        assert_eq!(indexed, scanned);
+
+        for column in ["id", "price"] {
+            let inverted = format!("SELECT id FROM items WHERE {} BETWEEN 7 AND 3", column);
+            assert!(ordered_ids(&mut db, &inverted).is_empty());
+
+            let outside = format!(
+                "SELECT id FROM items WHERE {} NOT BETWEEN 3 AND 7 AND id > 0 ORDER BY id",
+                column
+            );
+            assert_eq!(ordered_ids(&mut db, &outside), vec![1, 9]);
+        }
+    }
+
+    #[test]
+    fn test_scalar_subqueries_in_where_and_select_list() {
+        let mut db = Database::in_memory("scalar_subquery_test");
+        db.query("CREATE TABLE products (id INTEGER PRIMARY KEY, price INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        for (id, price) in [(1, 30), (2, 10), (3, 50), (4, 20)] {
+            db.query(&format!("INSERT INTO products (id, price) VALUES ({}, {})", id, price))
+                .unwrap();
+        }
+
+        assert_eq!(
+            ordered_ids(
+                &mut db,
+                "SELECT id FROM products WHERE price > (SELECT price FROM products WHERE id = 4) \
+                 ORDER BY id"
+            ),
+            vec![1, 3]
+        );
+        // An empty subquery is NULL, which no comparison matches
+        assert!(ordered_ids(
+            &mut db,
+            "SELECT id FROM products WHERE price <= (SELECT price FROM products WHERE id = 99)"
+        )
+        .is_empty());
+        assert!(matches!(
+            db.query("SELECT id FROM products WHERE price = (SELECT price FROM products)"),
+            Err(DatabaseError::InvalidSqlSyntax(_))
+        ));
+
+        let rows = db
+            .query(
+                "SELECT id, (SELECT price FROM products WHERE id = 3) AS top, \
+                 (SELECT id FROM products WHERE id = 99) FROM products WHERE id = 2",
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(matches!(rows[0].columns.get("top"), Some(SqlValue::Integer(50))));
+        assert!(matches!(rows[0].columns.get("subquery_2"), Some(SqlValue::Null)));
+        assert!(matches!(rows[0].columns.get("id"), Some(SqlValue::Integer(2))));
+
+        // AVG is a FLOAT, compared with INTEGER columns by value, indexed or not
+        let above_average = "SELECT id FROM products WHERE price > (SELECT AVG(price) FROM products)";
+        assert_eq!(ordered_ids(&mut db, above_average), vec![1, 3]);
+        let below_average = "SELECT id FROM products WHERE id < (SELECT AVG(id) FROM products)";
+        assert_eq!(ordered_ids(&mut db, below_average), vec![1, 2]);
+        let at_average = "SELECT id FROM products WHERE price = (SELECT AVG(price) FROM products)";
+        assert!(ordered_ids(&mut db, at_average).is_empty());
+        db.query("DELETE FROM products WHERE id = 4").unwrap();
+        assert_eq!(ordered_ids(&mut db, at_average), vec![1]);
+        let middle = "SELECT id FROM products WHERE id = (SELECT AVG(id) FROM products)";
+        assert_eq!(ordered_ids(&mut db, middle), vec![2]);
+    }
+
+    #[test]
+    fn test_union_deduplicates_unless_all() {
+        let mut db = Database::in_memory("union_test");
+        db.query("CREATE TABLE staff (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("CREATE TABLE guests (guest_id INTEGER PRIMARY KEY, label TEXT) DURABILITY ASYNC")
+            .unwrap();
+        for (id, name) in [(1, "ann"), (2, "bo")] {
+            db.query(&format!("INSERT INTO staff (id, name) VALUES ({}, '{}')", id, name))
+                .unwrap();
+        }
+        for (id, name) in [(2, "bo"), (3, "cy")] {
+            db.query(&format!(
+                "INSERT INTO guests (guest_id, label) VALUES ({}, '{}')",
+                id, name
+            ))
+            .unwrap();
+        }
+
+        let rows = db
+            .query("SELECT id, name FROM staff UNION SELECT guest_id, label FROM guests")
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        // Result columns take the names of the first branch
+        assert!(rows
+            .iter()
+            .all(|row| row.columns.contains_key("id") && row.columns.contains_key("name")));
+
+        let all = db
+            .query(
+                "SELECT id, name FROM staff UNION ALL SELECT guest_id, label FROM guests \
+                 UNION ALL SELECT id, name FROM staff WHERE id = 1",
+            )
+            .unwrap();
+        assert_eq!(all.len(), 5);
+
+        assert!(matches!(
+            db.query("SELECT id FROM staff UNION SELECT guest_id, label FROM guests"),
+            Err(DatabaseError::InvalidSqlSyntax(_))
+        ));
+        assert!(matches!(
+            db.query("SELECT id FROM staff UNION SELECT label FROM guests"),
+            Err(DatabaseError::InvalidDataType(_))
+        ));
+    }
+
+    #[test]
+    fn test_select_distinct_drops_repeated_rows() {
+        let mut db = Database::in_memory("distinct_test");
+        db.query(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, country TEXT, city TEXT) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+        let users = [
+            ("kr", "seoul"),
+            ("us", "nyc"),
+            ("kr", "busan"),
+            ("kr", "seoul"),
+            ("jp", "osaka"),
+        ];
+        for (id, (country, city)) in users.iter().enumerate() {
+            db.query(&format!(
+                "INSERT INTO users (id, country, city) VALUES ({}, '{}', '{}')",
+                id, country, city
+            ))
+            .unwrap();
+        }
+        let text = |rows: Vec<Row>, column: &str| -> Vec<String> {
+            rows.iter()
+                .map(|row| match &row.columns[column] {
+                    SqlValue::Text(text) => text.to_string(),
+                    other => panic!("expected text, got {:?}", other),
+                })
+                .collect()
+        };
+
+        let rows = db.query("SELECT DISTINCT country FROM users ORDER BY country").unwrap();
+        assert_eq!(text(rows, "country"), ["jp", "kr", "us"]);
+        // Paging applies to the distinct rows, not to the scanned ones
+        let rows = db
+            .query("SELECT DISTINCT country FROM users ORDER BY country LIMIT 2 OFFSET 1")
+            .unwrap();
+        assert_eq!(text(rows, "country"), ["kr", "us"]);
+        let rows = db.query("SELECT DISTINCT country FROM users LIMIT 2").unwrap();
+        assert_eq!(rows.len(), 2);
+
+        // Several columns are deduplicated as a whole
+        let rows = db.query("SELECT DISTINCT country, city FROM users ORDER BY city").unwrap();
+        assert_eq!(text(rows, "city"), ["busan", "nyc", "osaka", "seoul"]);
+        assert_eq!(db.query("SELECT country FROM users").unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_select_aliases_rename_result_columns() {
+        let mut db = Database::in_memory("alias_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, age INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        for (id, age) in [(1, 40), (2, 30), (3, 30)] {
+            db.query(&format!("INSERT INTO users (id, age) VALUES ({}, {})", id, age))
+                .unwrap();
+        }
+        let keys = |rows: &[Row]| {
+            let mut keys: Vec<&str> = rows[0].columns.keys().map(String::as_str).collect();
+            keys.sort();
+            keys.join(",")
+        };
+
+        let rows = db.query("SELECT age AS user_age FROM users WHERE id = 1").unwrap();
+        assert_eq!(keys(&rows), "user_age");
+        assert!(matches!(rows[0].columns["user_age"], SqlValue::Integer(40)));
+        // The implicit form, a qualified column, and the column kept alongside its alias
+        let rows = db.query("SELECT users.id ident, age, age AS a FROM users WHERE id = 2").unwrap();
+        assert_eq!(keys(&rows), "a,age,ident");
+
+        // ORDER BY may name the alias; aggregates take theirs as before
+        let rows = db.query("SELECT id AS n FROM users ORDER BY n DESC").unwrap();
+        let ids: Vec<String> = rows.iter().map(|row| format!("{:?}", row.columns["n"])).collect();
+        assert_eq!(ids, ["Integer(3)", "Integer(2)", "Integer(1)"]);
+        let rows = db
+            .query("SELECT age AS years, COUNT(*) AS n FROM users GROUP BY age ORDER BY years")
+            .unwrap();
+        assert_eq!(keys(&rows), "n,years");
+        assert!(matches!(rows[0].columns["n"], SqlValue::Integer(2)));
+        assert_eq!(db.query("SELECT DISTINCT age AS years FROM users").unwrap().len(), 2);
+
+        assert!(db.query("SELECT * AS everything FROM users").is_err());
+        // An alias may not take a name another result column already has
+        for sql in [
+            "SELECT id AS age, age FROM users",
+            "SELECT id AS x, age AS x FROM users",
+            "SELECT age AS n, COUNT(*) AS n FROM users GROUP BY age",
+        ] {
+            assert!(matches!(db.query(sql), Err(DatabaseError::ParseError(_))), "{}", sql);
+        }
+        assert_eq!(db.query("SELECT age, age FROM users").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_memory_limit_aborts_large_results() {
+        let mut db = Database::in_memory("memory_limit_test");
+        db.query("CREATE TABLE docs (id INTEGER PRIMARY KEY, body TEXT) DURABILITY ASYNC")
+            .unwrap();
+        let body = SqlValue::Text("x".repeat(64 * 1024).into());
+        for id in 0..32 {
+            db.query_with_params(
+                "INSERT INTO docs (id, body) VALUES (?, ?)",
+                &[SqlValue::Integer(id), body.clone()],
+            )
+            .unwrap();
+        }
+
+        // 2 MB of bodies against a 1 MB budget
+        db.set_memory_limit_mb(Some(1));
+        for sql in [
+            "SELECT * FROM docs",
+            "SELECT * FROM docs ORDER BY id DESC",
+            "SELECT body, COUNT(*) FROM docs GROUP BY body",
+        ] {
+            let result = db.query(sql);
+            assert!(matches!(result, Err(DatabaseError::QueryTooComplex)), "{}", sql);
+        }
+        assert_eq!(db.query("SELECT id FROM docs").unwrap().len(), 32);
+        assert_eq!(db.query("SELECT * FROM docs LIMIT 4").unwrap().len(), 4);
+
+        db.set_memory_limit_mb(None);
+        assert_eq!(db.query("SELECT * FROM docs").unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_group_by_aggregates_per_group() {
+        let mut db = Database::in_memory("group_by_test");
+        db.query(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, country TEXT, plan TEXT, age INTEGER) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+        let users = [
+            (1, "kr", "free", 30),
+            (2, "us", "paid", 40),
+            (3, "kr", "paid", 20),
+            (4, "kr", "free", 50),
+            (5, "us", "paid", 60),
+        ];
+        for (id, country, plan, age) in users {
+            db.query(&format!(
+                "INSERT INTO users (id, country, plan, age) VALUES ({}, '{}', '{}', {})",
+                id, country, plan, age
+            ))
+            .unwrap();
+        }
+        db.query("INSERT INTO users (id, country, plan, age) VALUES (6, 'us', 'free', NULL)")
+            .unwrap();
+
+        let text = |row: &Row, column: &str| match row.columns.get(column) {
+            Some(SqlValue::Text(text)) => text.to_string(),
+            other => panic!("expected text in {}, got {:?}", column, other),
+        };
+        let integer = |row: &Row, column: &str| match row.columns.get(column) {
+            Some(SqlValue::Integer(value)) => *value,
+            other => panic!("expected integer in {}, got {:?}", column, other),
+        };
+
+        // Groups come out in key order, so repeated runs agree
+        let rows = db
+            .query(
+                "SELECT country, plan, COUNT(*), MAX(age) AS oldest FROM users \
+                 GROUP BY country, plan",
+            )
+            .unwrap();
+        let summary: Vec<(String, String, i64)> = rows
+            .iter()
+            .map(|row| (text(row, "country"), text(row, "plan"), integer(row, "COUNT(*)")))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("kr".to_string(), "free".to_string(), 2),
+                ("kr".to_string(), "paid".to_string(), 1),
+                ("us".to_string(), "free".to_string(), 1),
+                ("us".to_string(), "paid".to_string(), 2),
+            ]
+        );
+        assert_eq!(integer(&rows[0], "oldest"), 50);
+        assert!(matches!(rows[2].columns.get("oldest"), Some(SqlValue::Null)));
+
+        let rows = db
+            .query(
+                "SELECT country, COUNT(age) AS n, AVG(age) FROM users GROUP BY country \
+                 ORDER BY n DESC, country",
+            )
+            .unwrap();
+        assert_eq!(text(&rows[0], "country"), "kr");
+        assert_eq!(integer(&rows[0], "n"), 3);
+        assert!(matches!(
+            rows[1].columns.get("AVG(age)"),
+            Some(SqlValue::Float(avg)) if *avg == 50.0
+        ));
+
+        // Without GROUP BY the whole table is one group, even when nothing matches
+        let rows = db.query("SELECT COUNT(*), SUM(age) FROM users WHERE age > 100").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(integer(&rows[0], "COUNT(*)"), 0);
+        assert!(matches!(rows[0].columns.get("SUM(age)"), Some(SqlValue::Null)));
+
+        assert!(matches!(
+            db.query("SELECT plan, COUNT(*) FROM users GROUP BY country"),
+            Err(DatabaseError::InvalidSqlSyntax(_))
+        ));
+        assert!(matches!(
+            db.query("SELECT plan, COUNT(*) FROM users"),
+            Err(DatabaseError::InvalidSqlSyntax(message)) if message.contains("UNGROUPED_COLUMNS")
+        ));
+        assert!(matches!(
+            db.query("SELECT SUM(country) FROM users"),
+            Err(DatabaseError::InvalidDataType(_))
+        ));
+        assert!(matches!(
+            db.query("SELECT COUNT(*) FROM users GROUP BY missing"),
+            Err(DatabaseError::ColumnNotFound(_))
+        ));
+
+        // Relaxed mode takes ungrouped columns from the first row of each group
+        db.ungrouped_columns = UngroupedColumns::Relaxed;
+        let rows = db.query("SELECT country, plan, COUNT(*) FROM users GROUP BY country").unwrap();
+        let summary: Vec<(String, String, i64)> = rows
+            .iter()
+            .map(|row| (text(row, "country"), text(row, "plan"), integer(row, "COUNT(*)")))
+            .collect();
+        assert_eq!(
+            summary,
+            [("kr".into(), "free".into(), 3), ("us".into(), "paid".into(), 3)]
+        );
+        let rows = db.query("SELECT plan, COUNT(*) FROM users").unwrap();
+        assert_eq!(text(&rows[0], "plan"), "free");
+        assert!(matches!(
+            db.query("SELECT missing, COUNT(*) FROM users"),
+            Err(DatabaseError::ColumnNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_and_uses_most_selective_index_with_residual_filter() {
+        let mut db = Database::in_memory("residual_filter_test");
+        // `id` has the primary key index, NOT NULL `status` an automatic one, `note` none
+        db.query(
+            "CREATE TABLE tickets (id INTEGER PRIMARY KEY, status TEXT NOT NULL, note TEXT) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+        for id in 1..=6 {
+            let status = if id % 2 == 0 { "open" } else { "closed" };
+            db.query(&format!(
+                "INSERT INTO tickets (id, status, note) VALUES ({}, '{}', 'n{}')",
+                id, status, id
+            ))
+            .unwrap();
+        }
+
+        let equal = |column: &str, value: SqlValue| {
+            WhereExpr::Comparison(WhereClause {
+                column: column.to_string(),
+                operator: ComparisonOperator::Equal,
+                value,
+            })
+        };
+        let table = &db.tables["TICKETS"];
+        let by_status_and_id = WhereExpr::And(vec![
+            equal("status", SqlValue::Text("open".into())),
+            equal("id", SqlValue::Integer(4)),
+        ]);
+        assert_eq!(index_candidates(table, Some(&by_status_and_id)), Some(vec![3]));
+        let by_status_and_note = WhereExpr::And(vec![
+            equal("note", SqlValue::Text("n4".into())),
+            equal("status", SqlValue::Text("open".into())),
+        ]);
+        assert_eq!(index_candidates(table, Some(&by_status_and_note)), Some(vec![1, 3, 5]));
+        // Nullable columns and mismatched literal types fall back to a scan
+        for unindexable in [
+            equal("note", SqlValue::Text("n4".into())),
+            equal("id", SqlValue::Text("4".into())),
+        ] {
+            assert_eq!(index_candidates(table, Some(&unindexable)), None);
+        }
+
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM tickets WHERE status = 'open' AND note = 'n4'"),
+            vec![4]
+        );
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM tickets WHERE note <> 'n2' AND status = 'open'"),
+            vec![4, 6]
+        );
+        assert!(ordered_ids(&mut db, "SELECT id FROM tickets WHERE id = 3 AND status = 'open'")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_bare_boolean_column_conditions() {
+        let mut db = Database::in_memory("boolean_where_test");
+        db.query(
+            "CREATE TABLE flags (id INTEGER PRIMARY KEY, active BOOLEAN, name TEXT) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+        for (id, active) in [(1, "TRUE"), (2, "FALSE"), (3, "NULL"), (4, "TRUE")] {
+            db.query(&format!(
+                "INSERT INTO flags (id, active, name) VALUES ({}, {}, 'f{}')",
+                id, active, id
+            ))
+            .unwrap();
+        }
+
+        assert_eq!(
+            ordered_ids(&mut db, "SELECT id FROM flags WHERE active ORDER BY id"),
+            vec![1, 4]
+        );
+        // NULL is neither active nor NOT active
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM flags WHERE NOT active"), vec![2]);
+        assert_eq!(
+            ordered_ids(
+                &mut db,
+                "SELECT id FROM flags WHERE (active AND id > 1) OR id = 2 ORDER BY id"
+            ),
+            vec![2, 4]
+        );
+        assert!(matches!(
+            db.query("SELECT id FROM flags WHERE name"),
+            Err(DatabaseError::InvalidDataType(_))
+        ));
+    }
+
+    #[test]
+    fn test_count_matches_ignores_paging() {
+        let mut db = Database::in_memory("count_matches_test");
+        db.query("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT) DURABILITY ASYNC")
+            .unwrap();
+        for id in 1..=25 {
+            let kind = if id % 5 == 0 { "error" } else { "info" };
+            db.query(&format!("INSERT INTO events (id, kind) VALUES ({}, '{}')", id, kind))
+                .unwrap();
+        }
+
+        let parser = AnySQL::new();
+        let count = |sql: &str| {
+            let statement = parser.parse(sql).unwrap();
+            db.count_matches(&statement).unwrap()
+        };
+        assert_eq!(count("SELECT * FROM events LIMIT 10 OFFSET 20"), 25);
+        assert_eq!(count("SELECT id FROM events WHERE kind = 'info' LIMIT 3"), 20);
+        // Answered from the primary key index
+        assert_eq!(count("SELECT id FROM events WHERE id BETWEEN 5 AND 14 LIMIT 2"), 10);
+        assert_eq!(count("SELECT kind, COUNT(*) FROM events GROUP BY kind LIMIT 1"), 2);
+        assert_eq!(
+            count(
+                "SELECT id FROM events WHERE kind = 'error' \
+                 UNION SELECT id FROM events WHERE id < 3"
+            ),
+            7
+        );
+    }
+
+    #[test]
+    fn test_rollback_leaves_database_file_unchanged() {
+        let name = "transaction_rollback_test";
+        let path = std::path::Path::new(".mirseoDB").join(format!("{}.mdb", name));
+        let mut db = Database::new(name.to_string());
+        db.query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance INTEGER)")
+            .unwrap();
+        db.query("INSERT INTO accounts (id, balance) VALUES (1, 100)").unwrap();
+        let saved = std::fs::read(&path).unwrap();
+
+        db.query("BEGIN").unwrap();
+        db.query("INSERT INTO accounts (id, balance) VALUES (2, 50)").unwrap();
+        db.query("UPDATE accounts SET balance = 0 WHERE id = 1").unwrap();
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM accounts WHERE balance < 100"), [1, 2]);
+        assert_eq!(std::fs::read(&path).unwrap(), saved);
+        db.query("ROLLBACK").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), saved);
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM accounts WHERE balance = 100"), [1]);
+        assert!(db.query("COMMIT").is_err());
+
+        // The restored primary key index accepts id 2 again; COMMIT saves once
+        db.query("BEGIN").unwrap();
+        db.query("INSERT INTO accounts (id, balance) VALUES (2, 50)").unwrap();
+        assert!(db.query("BEGIN").is_err());
+        db.query("COMMIT").unwrap();
+        let mut reloaded = Database::load(name.to_string()).unwrap();
+        assert_eq!(ordered_ids(&mut reloaded, "SELECT id FROM accounts"), [1, 2]);
+
+        StorageEngine::remove_database_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_writes_async_changes() {
+        let name = "checkpoint_test";
+        let mut db = Database::new(name.to_string());
+        db.query("CREATE TABLE events (id INTEGER PRIMARY KEY) DURABILITY = ASYNC")
+            .unwrap();
+        db.query("INSERT INTO events (id) VALUES (1)").unwrap();
+
+        let rows = db.query("CHECKPOINT").unwrap();
+        // The file now includes both logged statements and the log is gone
+        let (lsn, _) = db.durability_status();
+        assert_eq!(lsn, 2);
+        assert!(!wal::log_path(name).exists());
+        let reported = rows[0].columns.get("lsn");
+        assert!(matches!(reported, Some(SqlValue::Integer(n)) if *n == lsn as i64));
+        let mut reloaded = Database::load(name.to_string()).unwrap();
+        assert_eq!(ordered_ids(&mut reloaded, "SELECT id FROM events"), [1]);
+
+        db.query("BEGIN").unwrap();
+        assert!(db.query("CHECKPOINT").is_err());
+        db.query("ROLLBACK").unwrap();
+
+        StorageEngine::remove_database_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_auto_checkpoint_after_deferred_writes() {
+        let name = "auto_checkpoint_test";
+        let mut db = Database::new(name.to_string());
+        db.checkpoint_every_writes = Some(3);
+        db.query("CREATE TABLE events (id INTEGER PRIMARY KEY) DURABILITY = ASYNC")
+            .unwrap();
+
+        // CREATE TABLE was the first deferred write
+        db.query("INSERT INTO events (id) VALUES (1)").unwrap();
+        assert!(wal::log_path(name).exists());
+        db.query("INSERT INTO events (id) VALUES (2)").unwrap();
+        assert!(!wal::log_path(name).exists());
+        assert!(!db.flush_pending().unwrap());
+
+        let mut reloaded = Database::load(name.to_string()).unwrap();
+        assert_eq!(ordered_ids(&mut reloaded, "SELECT id FROM events"), [1, 2]);
+        StorageEngine::remove_database_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_wal_replays_writes_lost_in_a_crash() {
+        let name = "wal_replay_test";
+        let mut db = Database::new(name.to_string());
+        db.query("CREATE TABLE events (id INTEGER PRIMARY KEY) DURABILITY = ASYNC")
+            .unwrap();
+        db.query("INSERT INTO events (id) VALUES (1)").unwrap();
+        db.query("INSERT INTO events (id) VALUES (2)").unwrap();
+        db.query("BEGIN").unwrap();
+        db.query("INSERT INTO events (id) VALUES (9)").unwrap();
+        db.query("ROLLBACK").unwrap();
+        // Dropped without a flush, like a process that crashed
+        drop(db);
+
+        let mut reloaded = Database::load(name.to_string()).unwrap();
+        assert_eq!(ordered_ids(&mut reloaded, "SELECT id FROM events"), [1, 2]);
+        assert_eq!(reloaded.durability_status().0, 3);
+        // Replay saved the file, so the records are not applied twice
+        assert!(!wal::log_path(name).exists());
+        drop(reloaded);
+
+        // A crash after the record was written but before the statement ran
+        let mut log = WriteAheadLog::new(name, false);
+        log.advance_to(3);
+        log.append(&[
+            LoggedStatement {
+                dialect: None,
+                sql: "INSERT INTO events (id) VALUES (?)".to_string(),
+                params: vec![SqlValue::Integer(3)],
+            },
+            // Replayed with the chosen dialect's types; detection would read TEXT
+            LoggedStatement {
+                dialect: Some(DetectedDialect::Oracle),
+                sql: "CREATE TABLE flags (id NUMBER(10))".to_string(),
+                params: Vec::new(),
+            },
+        ])
+        .unwrap();
+        let mut reloaded = Database::load(name.to_string()).unwrap();
+        assert_eq!(ordered_ids(&mut reloaded, "SELECT id FROM events"), [1, 2, 3]);
+        assert_eq!(reloaded.durability_status().0, 5);
+        let columns = reloaded.query("DESCRIBE flags").unwrap();
+        assert!(matches!(
+            columns[0].columns.get("data_type"),
+            Some(SqlValue::Text(data_type)) if &**data_type == "INTEGER"
+        ));
+
+        StorageEngine::remove_database_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_params_are_bound_as_values() {
+        let mut db = Database::in_memory("query_params_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        let hostile = "'; DROP TABLE users; --";
+        db.query_with_params(
+            "INSERT INTO users (id, name) VALUES (?, ?)",
+            &[SqlValue::Integer(1), SqlValue::Text(hostile.into())],
+        )
+        .unwrap();
+        db.query_with_params(
+            "INSERT INTO users (id, name) VALUES (?, ?)",
+            &[SqlValue::Integer(2), SqlValue::Text("a, b".into())],
+        )
+        .unwrap();
+
+        let rows = db
+            .query_with_params(
+                "SELECT id, name FROM users WHERE name = ?",
+                &[SqlValue::Text(hostile.into())],
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        let name = rows[0].columns.get("name");
+        assert!(matches!(name, Some(SqlValue::Text(name)) if &**name == hostile));
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM users"), [1, 2]);
+
+        // A ? inside a string literal is text, not a placeholder
+        let rows = db.query_with_params("SELECT id FROM users WHERE name = '?'", &[]).unwrap();
+        assert!(rows.is_empty());
+        assert!(matches!(
+            db.query_with_params("SELECT id FROM users WHERE id = ?", &[]),
+            Err(DatabaseError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_schema_diff_against_target_ddl() {
+        let mut db = Database::in_memory("schema_diff_test");
+        db.query(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER) DURABILITY ASYNC",
+        )
+        .unwrap();
+        db.query("CREATE TABLE legacy (id INTEGER PRIMARY KEY) DURABILITY ASYNC")
+            .unwrap();
+        db.query("COMMENT ON TABLE users IS 'People; and their ages'").unwrap();
+
+        // The schema's own export has nothing to change
+        let exported = db.export_ddl(None, &DetectedDialect::Standard).unwrap();
+        assert!(db.schema_diff(&exported).unwrap().is_empty());
+
+        let target = "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age FLOAT, \
+            \"Email\" TEXT);
+            CREATE TABLE orders (id INTEGER PRIMARY KEY, total FLOAT) DURABILITY ASYNC;";
+        let statements = db.schema_diff(target).unwrap();
+        assert_eq!(
+            statements,
+            [
+                "CREATE TABLE orders (id INTEGER PRIMARY KEY, total FLOAT) DURABILITY ASYNC;",
+                "ALTER TABLE \"USERS\" MODIFY COLUMN \"age\" FLOAT;",
+                "ALTER TABLE \"USERS\" ADD COLUMN \"Email\" TEXT;",
+                "DROP TABLE \"LEGACY\";",
+            ]
+        );
+
+        // Applying the statements leaves no difference
+        for statement in &statements {
+            db.query(statement).unwrap();
+        }
+        assert!(db.schema_diff(target).unwrap().is_empty());
+        assert!(matches!(
+            db.schema_diff("DROP TABLE users"),
+            Err(DatabaseError::InvalidSqlSyntax(_))
+        ));
+        // DROP TABLE always saves
+        StorageEngine::remove_database_file("schema_diff_test").unwrap();
+    }
+
+    #[test]
+    fn test_create_table_existence_checks() {
+        let mut db = Database::in_memory("create_exists_test");
+        let create = "CREATE TABLE items (id INTEGER PRIMARY KEY) DURABILITY ASYNC";
+        db.query(create).unwrap();
+        db.query("INSERT INTO items (id) VALUES (1)").unwrap();
+
+        // A second CREATE used to replace the table and its rows
+        assert!(matches!(db.query(create), Err(DatabaseError::TableAlreadyExists(_))));
+        db.query("CREATE TABLE IF NOT EXISTS items (id INTEGER PRIMARY KEY) DURABILITY ASYNC")
+            .unwrap();
+        assert_eq!(db.query("SELECT * FROM items").unwrap().len(), 1);
+
+        assert!(matches!(
+            db.query("DROP TABLE missing"),
+            Err(DatabaseError::TableNotFound(_))
+        ));
+        db.query("DROP TABLE IF EXISTS missing").unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_create_and_drop_stay_consistent() {
+        let db = Arc::new(std::sync::Mutex::new(Database::in_memory("churn_test")));
+        let workers: Vec<_> = (0..8)
+            .map(|worker| {
+                let db = Arc::clone(&db);
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let id = worker * 1000 + i;
+                        let statements = [
+                            "CREATE TABLE IF NOT EXISTS churn (id INTEGER PRIMARY KEY) \
+                             DURABILITY ASYNC"
+                                .to_string(),
+                            format!("INSERT INTO churn (id) VALUES ({})", id),
+                            "CREATE TABLE churn (id INTEGER PRIMARY KEY) DURABILITY ASYNC"
+                                .to_string(),
+                            "DROP TABLE IF EXISTS churn".to_string(),
+                        ];
+                        for sql in &statements {
+                            match db.lock().unwrap().query(sql) {
+                                Ok(_)
+                                | Err(DatabaseError::TableAlreadyExists(_))
+                                | Err(DatabaseError::TableNotFound(_)) => {}
+                                Err(err) => panic!("{} failed: {}", sql, err),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        // Whatever survived still answers index lookups for each of its rows
+        let mut db = db.lock().unwrap();
+        let ids = if db.tables.is_empty() {
+            Vec::new()
+        } else {
+            ordered_ids(&mut db, "SELECT id FROM churn")
+        };
+        for id in ids {
+            let sql = format!("SELECT id FROM churn WHERE id = {}", id);
+            assert_eq!(db.query(&sql).unwrap().len(), 1);
+        }
+        StorageEngine::remove_database_file("churn_test").unwrap();
+    }
+
+    #[test]
+    fn test_reset_database_keeps_schema() {
+        let mut db = Database::in_memory("reset_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("CREATE TABLE tags (id INTEGER AUTO_INCREMENT, label TEXT) DURABILITY ASYNC")
+            .unwrap();
+        for id in 1..=3 {
+            db.query(&format!("INSERT INTO users (id, name) VALUES ({}, 'u{}')", id, id))
+                .unwrap();
+        }
+        db.query("INSERT INTO tags (label) VALUES ('a')").unwrap();
+
+        // The name is the confirmation
+        assert!(db.query("RESET DATABASE").is_err());
+        assert!(db.query("RESET DATABASE other").is_err());
+        assert_eq!(db.query("SELECT * FROM users").unwrap().len(), 3);
+
+        let report = db.query("RESET DATABASE reset_test").unwrap();
+        assert_eq!(report.len(), 1);
+        assert!(matches!(report[0].columns["tables_cleared"], SqlValue::Integer(2)));
+        assert!(matches!(report[0].columns["rows_cleared"], SqlValue::Integer(4)));
+        assert!(db.query("SELECT * FROM users").unwrap().is_empty());
+        assert!(db.tables.values().all(|table| table.next_row_id == 0));
+
+        // The primary key index was emptied with the rows, and ids start over
+        db.query("INSERT INTO users (id, name) VALUES (1, 'again')").unwrap();
+        assert_eq!(db.query("SELECT * FROM users WHERE id = 1").unwrap().len(), 1);
+        let rows = db.query("INSERT INTO tags (label) VALUES ('b')").unwrap();
+        assert!(matches!(rows[0].columns["id"], SqlValue::Integer(1)));
+        StorageEngine::remove_database_file("reset_test").unwrap();
+    }
+
+    #[test]
+    fn test_insert_whitespace_policy() {
+        let mut db = Database::in_memory("whitespace_test");
+        db.query("CREATE TABLE notes (id INTEGER, body TEXT) DURABILITY ASYNC").unwrap();
+        db.query("INSERT INTO notes ( id ,  \" body \" ) VALUES ( 5 ,'  spaced  ')")
+            .unwrap();
+        db.query("INSERT INTO notes (id, body) VALUES (6, '')").unwrap();
+
+        let rows = db.query("SELECT * FROM notes").unwrap();
+        // Column names and unquoted values are trimmed, quoted text is kept as written
+        assert!(matches!(rows[0].columns["id"], SqlValue::Integer(5)));
+        assert!(matches!(
+            &rows[0].columns["body"],
+            SqlValue::Text(text) if &**text == "  spaced  "
+        ));
+        assert!(matches!(&rows[1].columns["body"], SqlValue::Text(text) if text.is_empty()));
+    }
+
+    #[test]
+    fn test_export_ddl_quotes_for_dialect() {
+        let mut db = Database::in_memory("export_ddl_test");
+        db.query(
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, note TEXT, paid BOOLEAN NOT NULL) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+
+        assert_eq!(
+            db.export_ddl(Some("ORDERS"), &DetectedDialect::Standard).unwrap(),
+            "CREATE TABLE \"ORDERS\" (\n    \"id\" INTEGER PRIMARY KEY,\n    \"note\" TEXT,\n    \
+             \"paid\" BOOLEAN NOT NULL\n);\n"
+        );
+        let mysql = db.export_ddl(None, &DetectedDialect::MySQL).unwrap();
+        assert!(mysql.starts_with("CREATE TABLE `ORDERS` (\n    `id` BIGINT PRIMARY KEY"));
+        let mssql = db.export_ddl(None, &DetectedDialect::MsSQL).unwrap();
+        assert!(mssql.contains("[paid] BIT NOT NULL"));
+        assert_eq!(DetectedDialect::MsSQL.quote_identifier("a]b"), "[a]]b]");
+
+        db.query("COMMENT ON TABLE orders IS 'Customer orders'").unwrap();
+        db.query("COMMENT ON COLUMN orders.note IS 'Shown on the invoice'").unwrap();
+        let mysql = db.export_ddl(None, &DetectedDialect::MySQL).unwrap();
+        assert!(mysql.contains("`note` TEXT COMMENT 'Shown on the invoice'"));
+        assert!(mysql.ends_with(") COMMENT='Customer orders';\n"));
+        let oracle = db.export_ddl(None, &DetectedDialect::Oracle).unwrap();
+        assert!(oracle.ends_with(
+            "COMMENT ON TABLE \"ORDERS\" IS 'Customer orders';\n\
+             COMMENT ON COLUMN \"ORDERS\".\"note\" IS 'Shown on the invoice';\n"
+        ));
+        assert!(db.query("COMMENT ON COLUMN orders.missing IS 'x'").is_err());
+        assert!(matches!(
+            db.export_ddl(Some("missing"), &DetectedDialect::Standard),
+            Err(DatabaseError::TableNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_create_index_is_used_until_dropped() {
+        let mut db = Database::in_memory("create_index_test");
+        db.query(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT, score INTEGER) \
+             DURABILITY ASYNC",
+        )
+        .unwrap();
+        for (id, kind) in [(1, "error"), (2, "info"), (3, "error"), (4, "warn")] {
+            db.query(&format!(
+                "INSERT INTO events (id, kind, score) VALUES ({}, '{}', 7)",
+                id, kind
+            ))
+            .unwrap();
+        }
+        let by_kind = "SELECT id FROM events WHERE kind IN ('error')";
+        let strategy = |db: &mut Database| {
+            let statement = AnySQL::new().parse(by_kind).unwrap();
+            db.explain(&statement).unwrap().strategy
+        };
+        assert_eq!(strategy(&mut db), "full_scan");
+
+        db.query("CREATE INDEX idx_kind ON events (kind)").unwrap();
+        assert_eq!(strategy(&mut db), "index_lookup");
+        assert_eq!(ordered_ids(&mut db, by_kind), [1, 3]);
+        assert!(matches!(
+            db.query("CREATE INDEX idx_kind ON events (score)"),
+            Err(DatabaseError::IndexAlreadyExists(_))
+        ));
+        // Existing duplicates reject a unique index, which is not left behind
+        assert!(matches!(
+            db.query("CREATE UNIQUE INDEX idx_score ON events (score)"),
+            Err(DatabaseError::UniqueConstraintViolation(_))
+        ));
+        db.query("CREATE INDEX idx_score ON events (score)").unwrap();
+        db.query("CREATE INDEX idx_kind_score ON events (kind, score)").unwrap();
+
+        db.query("DROP INDEX idx_kind").unwrap();
+        assert_eq!(strategy(&mut db), "full_scan");
+        assert_eq!(ordered_ids(&mut db, by_kind), [1, 3]);
+        db.query("DROP INDEX idx_kind_score ON events").unwrap();
+        assert!(matches!(
+            db.query("DROP INDEX idx_kind"),
+            Err(DatabaseError::IndexNotFound(_))
+        ));
+        assert!(db.query("DROP INDEX pk_id").is_err());
+
+        // Names are unique across tables; the automatic pk_ names need ON
+        db.query("CREATE TABLE logs (id INTEGER PRIMARY KEY, kind TEXT) DURABILITY ASYNC")
+            .unwrap();
+        assert!(matches!(
+            db.query("CREATE INDEX idx_score ON logs (kind)"),
+            Err(DatabaseError::IndexAlreadyExists(_))
+        ));
+        assert!(matches!(
+            db.query("DROP INDEX pk_id"),
+            Err(DatabaseError::InvalidSqlSyntax(message)) if message.contains("EVENTS, LOGS")
+        ));
+    }
+
+    #[test]
+    fn test_nocase_index_folds_unique_text() {
+        let mut db = Database::in_memory("nocase_index_test");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO users (id, email) VALUES (1, 'Ann@Example.com')").unwrap();
+        db.query("CREATE UNIQUE INDEX idx_email ON users (email COLLATE NOCASE)").unwrap();
+        assert!(db.tables["USERS"].index_manager.get_index("idx_email").unwrap().case_insensitive);
+
+        assert!(matches!(
+            db.query("INSERT INTO users (id, email) VALUES (2, 'ann@example.COM')"),
+            Err(DatabaseError::UniqueConstraintViolation(_))
+        ));
+        db.query("INSERT INTO users (id, email) VALUES (2, 'bo@example.com')").unwrap();
+        // Comparisons stay case-sensitive; the index only narrows the rows checked
+        let by_email = |db: &mut Database, email: &str| {
+            ordered_ids(db, &format!("SELECT id FROM users WHERE email = '{}'", email))
+        };
+        assert_eq!(by_email(&mut db, "Ann@Example.com"), [1]);
+        assert!(by_email(&mut db, "ann@example.com").is_empty());
+        assert_eq!(db.check_indexes(false).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_composite_index_serves_leading_column_predicates() {
+        let mut db = Database::in_memory("composite_lookup_test");
+        db.query(
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer TEXT NOT NULL, \
+             status TEXT NOT NULL, amount INTEGER NOT NULL, note TEXT) DURABILITY ASYNC",
+        )
+        .unwrap();
+        let orders = [("a", "open", 10), ("a", "paid", 20), ("b", "open", 15), ("b", "open", 30)];
+        for (id, (customer, status, amount)) in orders.iter().enumerate() {
+            db.query(&format!(
+                "INSERT INTO orders (id, customer, status, amount) VALUES ({}, '{}', '{}', {})",
+                id, customer, status, amount
+            ))
+            .unwrap();
+        }
+        db.query("CREATE INDEX idx_customer_status ON orders (customer, status)")
+            .unwrap();
+        let plan = |db: &Database, sql: &str| {
+            db.explain(&AnySQL::new().parse(sql).unwrap()).unwrap()
+        };
+
+        let both = "SELECT id FROM orders WHERE status = 'open' AND customer = 'b'";
+        let both_plan = plan(&db, both);
+        assert_eq!(both_plan.strategy, "index_lookup");
+        assert!(both_plan
+            .detail
+            .unwrap()
+            .contains("composite index 'idx_customer_status' on (customer, status)"));
+        assert_eq!(ordered_ids(&mut db, both), [2, 3]);
+        // A leading-column prefix is enough; a trailing column alone is not
+        let leading = "SELECT id FROM orders WHERE customer = 'a'";
+        assert!(plan(&db, leading).detail.unwrap().contains("on (customer)"));
+        assert_eq!(ordered_ids(&mut db, leading), [0, 1]);
+        let trailing = plan(&db, "SELECT id FROM orders WHERE status = 'open'");
+        assert!(!trailing.detail.unwrap_or_default().contains("composite"));
+
+        // A BETWEEN on the last column after the equality prefix is a key range
+        db.query("DROP INDEX idx_customer_status").unwrap();
+        db.query("CREATE INDEX idx_customer_amount ON orders (customer, amount)")
+            .unwrap();
+        let range = "SELECT id FROM orders WHERE customer = 'b' AND amount BETWEEN 10 AND 20";
+        assert!(plan(&db, range).detail.unwrap().contains("on (customer, amount)"));
+        assert_eq!(ordered_ids(&mut db, range), [2]);
+
+        // Rows leaving a nullable column out are missing from the index
+        db.query("CREATE INDEX idx_customer_note ON orders (customer, note)").unwrap();
+        db.query("DROP INDEX idx_customer_amount").unwrap();
+        assert!(!plan(&db, leading).detail.unwrap_or_default().contains("composite"));
+        assert_eq!(ordered_ids(&mut db, leading), [0, 1]);
+    }
+
+    #[test]
+    fn test_like_prefix_scans_an_index_range() {
+        let mut db = Database::in_memory("like_prefix_test");
+        db.query("CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        let names = ["Jo", "John", "Joan", "jo", "Jp", "Jn", "Bob", "Jo%e", "Jo%ey"];
+        for (id, name) in names.iter().enumerate() {
+            db.query(&format!("INSERT INTO people (id, name) VALUES ({}, '{}')", id, name))
+                .unwrap();
+        }
+        db.query("CREATE INDEX idx_name ON people (name)").unwrap();
+        let plan = |db: &Database, sql: &str| {
+            db.explain(&AnySQL::new().parse(sql).unwrap()).unwrap()
+        };
+
+        let by_prefix = "SELECT id FROM people WHERE name LIKE 'Jo%'";
+        let prefix_plan = plan(&db, by_prefix);
+        assert_eq!(prefix_plan.strategy, "index_lookup");
+        assert!(prefix_plan.detail.unwrap().contains("prefix range"));
+        assert_eq!(ordered_ids(&mut db, by_prefix), [0, 1, 2, 7, 8]);
+        // An escaped % is part of the prefix
+        let escaped = "SELECT id FROM people WHERE name LIKE 'Jo\\%e%'";
+        assert_eq!(plan(&db, escaped).strategy, "index_lookup");
+        assert_eq!(ordered_ids(&mut db, escaped), [7, 8]);
+
+        for sql in [
+            "SELECT id FROM people WHERE name LIKE '%ohn'",
+            "SELECT id FROM people WHERE name LIKE 'J%n'",
+            "SELECT id FROM people WHERE name LIKE 'J_%'",
+            "SELECT id FROM people WHERE name NOT LIKE 'Jo%'",
+        ] {
+            assert_eq!(plan(&db, sql).strategy, "full_scan", "{}", sql);
+        }
+        assert_eq!(ordered_ids(&mut db, "SELECT id FROM people WHERE name LIKE 'J%n'"), [1, 2, 5]);
+
+        assert_eq!(prefix_successor("Jo").as_deref(), Some("Jp"));
+        assert_eq!(prefix_successor("a\u{d7ff}").as_deref(), Some("a\u{e000}"));
+        assert_eq!(prefix_successor("a\u{10ffff}").as_deref(), Some("b"));
+        assert_eq!(prefix_successor("\u{10ffff}"), None);
+    }
+
+    #[test]
+    fn test_check_indexes_finds_and_repairs_mismatches() {
+        let mut db = Database::in_memory("index_check_test");
+        db.query(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, kind TEXT, size INTEGER) DURABILITY ASYNC",
+        )
+        .unwrap();
+        for id in 0..4 {
+            db.query(&format!(
+                "INSERT INTO items (id, kind, size) VALUES ({}, 'k{}', {})",
+                id,
+                id % 2,
+                id * 10
+            ))
+            .unwrap();
+        }
+        db.query("CREATE INDEX idx_kind_size ON items (kind, size)").unwrap();
+        assert_eq!(db.check_indexes(false).unwrap(), 0);
+
+        // One entry dropped, one filed under a stale value, one past the last row
+        let indexes = &mut db.tables.get_mut("ITEMS").unwrap().index_manager;
+        let primary = indexes.get_index_mut("pk_id").unwrap();
+        primary.remove(&SqlValue::Integer(2), 2);
+        primary.insert(&SqlValue::Integer(7), 3).unwrap();
+        primary.insert(&SqlValue::Integer(9), 9).unwrap();
+        let by_id = "SELECT id FROM items WHERE id = 2";
+        assert!(ordered_ids(&mut db, by_id).is_empty());
+
+        assert_eq!(db.check_indexes(false).unwrap(), 3);
+        assert!(ordered_ids(&mut db, by_id).is_empty());
+        assert_eq!(db.check_indexes(true).unwrap(), 3);
+        assert_eq!(db.check_indexes(false).unwrap(), 0);
+        assert_eq!(ordered_ids(&mut db, by_id), [2]);
+    }
+
+    #[test]
+    fn test_values_take_the_declared_column_type() {
+        let mut db = Database::in_memory("column_type_test");
+        db.query(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, price FLOAT NOT NULL, qty INTEGER, \
+             active BOOLEAN, name TEXT) DURABILITY ASYNC",
+        )
+        .unwrap();
+
+        // Compatible values are stored as the column's type
+        db.query(
+            "INSERT INTO items (id, price, qty, active, name) VALUES ('1', 5, 3.0, 1, 'pen')",
+        )
+        .unwrap();
+        db.query("UPDATE items SET price = '2.5', qty = '7' WHERE id = 1").unwrap();
+        let rows = db.query("SELECT * FROM items WHERE id = 1").unwrap();
+        let columns = &rows[0].columns;
+        assert!(matches!(columns["id"], SqlValue::Integer(1)));
+        assert!(matches!(columns["price"], SqlValue::Float(price) if price == 2.5));
+        assert!(matches!(columns["qty"], SqlValue::Integer(7)));
+        assert!(matches!(columns["active"], SqlValue::Boolean(true)));
+
+        // True mismatches are rejected and leave the row as it was
+        for sql in [
+            "INSERT INTO items (id, price, qty) VALUES (2, 1.0, 'abc')",
+            "INSERT INTO items (id, price, name) VALUES (2, 1.0, 12)",
+            "INSERT INTO items (id, price, active) VALUES (2, 1.0, 2)",
+            "UPDATE items SET qty = 1.5 WHERE id = 1",
+            "UPDATE items SET price = 'cheap' WHERE id = 1",
+        ] {
+            assert!(
+                matches!(db.query(sql), Err(DatabaseError::InvalidDataType(_))),
+                "{} was accepted",
+                sql
+            );
+        }
+
+        // NULL is fine in a nullable column but not in a NOT NULL one
+        db.query("INSERT INTO items (id, price, qty) VALUES (2, 1.0, NULL)").unwrap();
+        for sql in [
+            "INSERT INTO items (id, price) VALUES (3, NULL)",
+            "UPDATE items SET price = NULL WHERE id = 1",
+        ] {
+            assert!(matches!(db.query(sql), Err(DatabaseError::InvalidDataType(_))));
+        }
+        assert_eq!(db.query("SELECT id FROM items").unwrap().len(), 2);
+        let rows = db.query("SELECT qty FROM items WHERE id = 1").unwrap();
+        assert!(matches!(rows[0].columns["qty"], SqlValue::Integer(7)));
+    }
+
+    #[test]
+    fn test_insert_fills_in_column_defaults() {
+        let mut db = Database::in_memory("column_default_test");
+        db.query(
+            "CREATE TABLE tasks (id INTEGER PRIMARY KEY, \
+             status TEXT NOT NULL DEFAULT 'to do' COMMENT 'workflow state', \
+             priority FLOAT DEFAULT 1, done BOOLEAN DEFAULT FALSE, note TEXT DEFAULT NULL, \
+             owner TEXT NOT NULL) DURABILITY ASYNC",
+        )
+        .unwrap();
+
+        db.query("INSERT INTO tasks (id, owner) VALUES (1, 'ann')").unwrap();
+        db.query("INSERT INTO tasks (id, status, owner) VALUES (2, 'done', 'bob')").unwrap();
+        let rows = db.query("SELECT * FROM tasks WHERE id = 1").unwrap();
+        let columns = &rows[0].columns;
+        assert!(matches!(&columns["status"], SqlValue::Text(text) if &**text == "to do"));
+        assert!(matches!(columns["priority"], SqlValue::Float(priority) if priority == 1.0));
+        assert!(matches!(columns["done"], SqlValue::Boolean(false)));
+        assert!(!columns.contains_key("note"));
+
+        // The default goes into the index of the NOT NULL column like any value
+        let status_index = db.tables["TASKS"]
+            .index_manager
+            .list_indexes()
+            .into_iter()
+            .find(|index| index.column_name == "status")
+            .unwrap();
+        assert_eq!(status_index.find_exact(&SqlValue::Text("to do".into())), vec![0]);
+
+        // NOT NULL columns without a default still need a value
+        assert!(matches!(
+            db.query("INSERT INTO tasks (id) VALUES (3)"),
+            Err(DatabaseError::ColumnNotFound(_))
+        ));
+        assert!(matches!(
+            db.query("CREATE TABLE bad (id INTEGER PRIMARY KEY, n INTEGER DEFAULT 'many')"),
+            Err(DatabaseError::InvalidDataType(_))
+        ));
+    }
+
+    #[test]
+    fn test_auto_increment_generates_sequential_ids() {
+        let mut db = Database::in_memory("auto_increment_test");
+        db.query("CREATE TABLE users (id INTEGER AUTO_INCREMENT, name TEXT) DURABILITY ASYNC")
+            .unwrap();
+        let insert = |db: &mut Database, sql: &str| match db.query(sql).unwrap().as_slice() {
+            [row] => match row.columns["id"] {
+                SqlValue::Integer(id) => Some(id),
+                _ => panic!("generated id is not an integer"),
+            },
+            [] => None,
+            rows => panic!("INSERT returned {} rows", rows.len()),
+        };
+
+        for (name, expected) in [("ann", 1), ("bob", 2), ("cy", 3)] {
+            let sql = format!("INSERT INTO users (name) VALUES ('{}')", name);
+            assert_eq!(insert(&mut db, &sql), Some(expected));
+        }
+        // An explicit id is kept and later ids continue after it
+        assert_eq!(insert(&mut db, "INSERT INTO users (id, name) VALUES (10, 'di')"), None);
+        assert_eq!(insert(&mut db, "INSERT INTO users (id, name) VALUES (NULL, 'ed')"), Some(11));
+        let rows = db.query("SELECT name FROM users WHERE id = 2").unwrap();
+        assert!(matches!(&rows[0].columns["name"], SqlValue::Text(name) if &**name == "bob"));
+
+        // The exported DDL reads back as the same column
+        db.query(
+            "CREATE TABLE tickets (id INTEGER GENERATED BY DEFAULT AS IDENTITY, \
+             state TEXT DEFAULT 'open') DURABILITY ASYNC",
+        )
+        .unwrap();
+        let columns = &db.tables["TICKETS"].columns;
+        assert!(columns[0].auto_increment && columns[0].primary_key);
+        assert!(columns[0].default.is_none());
+        assert!(matches!(
+            db.query("CREATE TABLE bad (code TEXT AUTO_INCREMENT)"),
+            Err(DatabaseError::InvalidDataType(_))
+        ));
+        // Only the seed and increment that match the counter are accepted
+        db.query("CREATE TABLE orders (id INTEGER IDENTITY(1, 1), note TEXT) DURABILITY ASYNC")
+            .unwrap();
+        assert!(matches!(
+            db.query("CREATE TABLE bad (id INTEGER IDENTITY(100,5), note TEXT)"),
+            Err(DatabaseError::ParseError(_))
+        ));
+
+        // The last id ends the counter instead of wrapping around
+        let max = format!("INSERT INTO users (id, name) VALUES ({}, 'max')", i64::MAX);
+        assert_eq!(insert(&mut db, &max), None);
+        assert!(db.query("INSERT INTO users (name) VALUES ('over')").is_err());
+        assert_eq!(db.query("SELECT * FROM users").unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_upsert_updates_the_conflicting_row() {
+        let mut db = Database::in_memory("upsert_test");
+        db.query(
+            "CREATE TABLE stock (sku TEXT PRIMARY KEY, qty INTEGER, bin TEXT) DURABILITY ASYNC",
+        )
+        .unwrap();
+        db.query("CREATE UNIQUE INDEX idx_bin ON stock (bin)").unwrap();
+        let qty = |db: &mut Database, sku: &str| {
+            let rows = db.query(&format!("SELECT qty FROM stock WHERE sku = '{}'", sku)).unwrap();
+            format!("{:?}", rows.iter().map(|row| &row.columns["qty"]).collect::<Vec<_>>())
+        };
+
+        // No conflict: a plain insert
+        db.query("INSERT INTO stock (sku, qty, bin) VALUES ('a', 1, 'b1') ON CONFLICT DO NOTHING")
+            .unwrap();
+        db.query(
+            "INSERT INTO stock (sku, qty, bin) VALUES ('b', 2, 'b2') \
+             ON DUPLICATE KEY UPDATE qty = 0",
+        )
+        .unwrap();
+        assert_eq!(qty(&mut db, "a"), "[Integer(1)]");
+        assert_eq!(qty(&mut db, "b"), "[Integer(2)]");
+
+        // Primary key conflicts, in both spellings
+        db.query(
+            "INSERT INTO stock (sku, qty, bin) VALUES ('a', 5, 'b1') \
+             ON DUPLICATE KEY UPDATE qty = VALUES(qty)",
+        )
+        .unwrap();
+        assert_eq!(qty(&mut db, "a"), "[Integer(5)]");
+        db.query(
+            "INSERT INTO stock (sku, qty, bin) VALUES ('a', 7, 'b9') \
+             ON CONFLICT (sku) DO UPDATE SET qty = EXCLUDED.qty, bin = EXCLUDED.bin",
+        )
+        .unwrap();
+        assert_eq!(qty(&mut db, "a"), "[Integer(7)]");
+        db.query("INSERT INTO stock (sku, qty, bin) VALUES ('a', 9, 'b1') ON CONFLICT DO NOTHING")
+            .unwrap();
+        assert_eq!(qty(&mut db, "a"), "[Integer(7)]");
+
+        // A unique index conflict updates the row holding the value
+        db.query(
+            "INSERT INTO stock (sku, qty, bin) VALUES ('c', 3, 'b2') \
+             ON CONFLICT (bin) DO UPDATE SET qty = 20",
+        )
+        .unwrap();
+        assert_eq!(qty(&mut db, "b"), "[Integer(20)]");
+        assert_eq!(qty(&mut db, "c"), "[]");
+        assert_eq!(db.tables["STOCK"].rows.len(), 2);
+
+        // The indexes follow the updated values
+        assert_eq!(db.check_indexes(false).unwrap(), 0);
+        let rows = db.query("SELECT sku FROM stock WHERE bin = 'b9'").unwrap();
+        assert!(matches!(&rows[0].columns["sku"], SqlValue::Text(sku) if &**sku == "a"));
+        assert!(db.query("SELECT sku FROM stock WHERE bin = 'b1'").unwrap().is_empty());
+        assert!(matches!(
+            db.query("INSERT INTO stock (sku, qty, bin) VALUES ('a', 1, 'x') \
+                      ON CONFLICT DO UPDATE SET bin = 'b2'"),
+            Err(DatabaseError::UniqueConstraintViolation(_))
+        ));
+
+        // A target only catches conflicts on its own key
+        assert!(matches!(
+            db.query("INSERT INTO stock (sku, qty, bin) VALUES ('d', 1, 'b2') \
+                      ON CONFLICT (sku) DO NOTHING"),
+            Err(DatabaseError::UniqueConstraintViolation(_))
+        ));
+        assert!(matches!(
+            db.query("INSERT INTO stock (sku, qty, bin) VALUES ('a', 1, 'x') \
+                      ON CONFLICT (qty) DO NOTHING"),
+            Err(DatabaseError::ParseError(_))
+        ));
+        assert_eq!(qty(&mut db, "a"), "[Integer(7)]");
+        assert_eq!(db.check_indexes(false).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_where_compares_with_now() {
+        let mut db = Database::in_memory("now_test");
+        db.query("CREATE TABLE sessions (id INTEGER PRIMARY KEY, expires_at TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO sessions (id, expires_at) VALUES (1, '2000-01-01 00:00:00')")
+            .unwrap();
+        db.query("INSERT INTO sessions (id, expires_at) VALUES (2, '9999-12-31 23:59:59')")
+            .unwrap();
+
+        let ids = |db: &mut Database, sql: &str| -> Vec<String> {
+            let rows = db.query(sql).unwrap();
+            rows.iter().map(|row| format!("{:?}", row.columns["id"])).collect()
+        };
+        let expired = ids(&mut db, "SELECT id FROM sessions WHERE expires_at < NOW()");
+        assert_eq!(expired, ["Integer(1)"]);
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM sessions WHERE CURRENT_TIMESTAMP < expires_at"),
+            ["Integer(2)"]
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM sessions WHERE id > 0 AND NOW() >= expires_at"),
+            ["Integer(1)"]
+        );
+
+        // Writes log the time they ran at, so a replay compares with the same one
+        let now = SqlValue::Text("2026-10-17 10:00:00".into());
+        assert_eq!(
+            with_timestamp_literals(
+                "UPDATE sessions SET note = 'NOW()' WHERE NOW ( ) > expires_at \
+                 OR expires_at < current_timestamp",
+                &now
+            ),
+            "UPDATE sessions SET note = 'NOW()' WHERE TIMESTAMP '2026-10-17 10:00:00' > \
+             expires_at OR expires_at < TIMESTAMP '2026-10-17 10:00:00'"
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM sessions WHERE TIMESTAMP '2026-10-17 10:00:00' > expires_at"
+            ),
+            ["Integer(1)"]
+        );
+        db.query("DELETE FROM sessions WHERE expires_at < NOW()").unwrap();
+        assert_eq!(ids(&mut db, "SELECT id FROM sessions"), ["Integer(2)"]);
+    }
+
+    #[test]
+    fn test_show_tables_and_describe_list_the_schema() {
+        let mut db = Database::in_memory("describe_test");
+        db.query(
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, total FLOAT NOT NULL, note TEXT, \
+             paid BOOLEAN) DURABILITY ASYNC",
+        )
+        .unwrap();
+        db.query("CREATE TABLE customers (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+
+        let text = |row: &Row, column: &str| match row.columns.get(column) {
+            Some(SqlValue::Text(text)) => text.to_string(),
+            other => panic!("expected text in {}, got {:?}", column, other),
+        };
+        let flag = |row: &Row, column: &str| match row.columns.get(column) {
+            Some(SqlValue::Boolean(value)) => *value,
+            other => panic!("expected boolean in {}, got {:?}", column, other),
+        };
+
+        let tables = db.query("SHOW TABLES").unwrap();
+        let tables: Vec<String> = tables.iter().map(|row| text(row, "table_name")).collect();
+        assert_eq!(tables, ["CUSTOMERS", "ORDERS"]);
+
+        // column_name data_type nullable primary_key
+        let describe = |rows: Vec<Row>| -> Vec<String> {
+            rows.iter()
+                .map(|row| {
+                    format!(
+                        "{} {} {} {}",
+                        text(row, "column_name"),
+                        text(row, "data_type"),
+                        flag(row, "nullable"),
+                        flag(row, "primary_key")
+                    )
+                })
+                .collect()
+        };
+        let expected = [
+            "id INTEGER false true",
+            "total FLOAT false false",
+            "note TEXT true false",
+            "paid BOOLEAN true false",
+        ];
+        assert_eq!(describe(db.query("DESCRIBE orders").unwrap()), expected);
+        assert_eq!(describe(db.query("SHOW COLUMNS FROM orders").unwrap()), expected);
+
+        let statement = AnySQL::new().parse("DESC orders").unwrap();
+        assert!(!statement.requires_2fa());
+        assert_eq!(describe(db.execute_read(statement, None).unwrap()), expected);
+        assert!(matches!(
+            db.query("DESCRIBE missing"),
+            Err(DatabaseError::TableNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_keeps_databases_apart() {
+        let default = Arc::new(RwLock::new(Database::in_memory("main_db")));
+        let registry = DatabaseRegistry::new(Arc::clone(&default));
+        registry.register(Database::in_memory("SHOP"));
+        for (name, item) in [("main_db", "pen"), ("shop", "cup")] {
+            let database = registry.get(name).unwrap();
+            let mut db = database.write().unwrap();
+            db.query("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC")
+                .unwrap();
+            db.query(&format!("INSERT INTO items (id, name) VALUES (1, '{}')", item))
+                .unwrap();
+        }
+
+        let names = |name: &str| -> Vec<String> {
+            let database = registry.get(name).unwrap();
+            let mut db = database.write().unwrap();
+            let rows = db.query("SELECT name FROM items").unwrap();
+            rows.iter()
+                .map(|row| match row.columns.get("name") {
+                    Some(SqlValue::Text(text)) => text.to_string(),
+                    other => panic!("unexpected name {:?}", other),
+                })
+                .collect()
+        };
+        assert_eq!(names("MAIN_DB"), ["pen"]);
+        assert_eq!(names("Shop"), ["cup"]);
+        assert!(Arc::ptr_eq(&registry.default_database(), &default));
+        assert!(registry.get("no_such_db").is_err());
+
+        // Alone, a database only accepts USE of its own name
+        let mut db = default.write().unwrap();
+        assert!(db.query("USE main_db").is_ok());
+        assert!(db.query("USE shop").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Dependency-free gzip encoder for HTTP responses. Matches are found with the
+//! same hash-chain search as the `.mdb` codec and written as a single DEFLATE
+//! block with the fixed Huffman codes, which every gzip decoder understands.
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW_SIZE: usize = 32 * 1024;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 32; // candidates checked per position
+
+/// Base length for length codes 257..=285 and their extra bits.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distance for distance codes 0..=29 and their extra bits.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Collects bits least significant first, as DEFLATE packs them.
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
+
+/// Writes a literal/length symbol with the fixed Huffman code.
+fn write_symbol(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap_or(0);
+    write_symbol(writer, 257 + code as u16);
+    writer.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+
+    let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0);
+    writer.write_code(code as u32, 5);
+    writer.write(
+        (distance - DISTANCE_BASE[code] as usize) as u32,
+        DISTANCE_EXTRA[code] as u32,
+    );
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(input: &[u8], position: usize, head: &mut [usize], prev: &mut [usize]) {
+    if position + MIN_MATCH <= input.len() {
+        let slot = hash(&input[position..]);
+        prev[position] = head[slot];
+        head[slot] = position;
+    }
+}
+
+fn deflate(input: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        output: Vec::with_capacity(input.len() / 2 + 16),
+        buffer: 0,
+        bits: 0,
+    };
+    writer.write(1, 1); // final block
+    writer.write(1, 2); // fixed Huffman codes
+
+    // head: most recent position per hash; prev: previous position with the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; input.len()];
+
+    let mut position = 0;
+    while position < input.len() {
+        let mut best_len = 0;
+        let mut best_distance = 0;
+
+        if position + MIN_MATCH <= input.len() {
+            let max_len = MAX_MATCH.min(input.len() - position);
+            let mut candidate = head[hash(&input[position..])];
+            let mut checked = 0;
+
+            while candidate != usize::MAX
+                && position - candidate <= WINDOW_SIZE
+                && checked < MAX_CHAIN
+            {
+                let len = input[candidate..]
+                    .iter()
+                    .zip(&input[position..position + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_distance = position - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                checked += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(&mut writer, best_len, best_distance);
+            for offset in 0..best_len {
+                insert(input, position + offset, &mut head, &mut prev);
+            }
+            position += best_len;
+        } else {
+            write_symbol(&mut writer, input[position] as u16);
+            insert(input, position, &mut head, &mut prev);
+            position += 1;
+        }
+    }
+
+    write_symbol(&mut writer, 256); // end of block
+    writer.finish()
+}
+
+/// `input` as a gzip member (RFC 1952) without a file name or timestamp.
+pub fn gzip(input: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+    output.extend_from_slice(&deflate(input));
+    output.extend_from_slice(&crc32(input).to_le_bytes());
+    output.extend_from_slice(&(input.len() as u32).to_le_bytes());
+    output
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize, // in bits
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for bit in 0..count {
+                let byte = self.data[self.position / 8];
+                value |= (((byte >> (self.position % 8)) & 1) as u32) << bit;
+                self.position += 1;
+            }
+            value
+        }
+
+        fn code(&mut self, length: u32) -> u32 {
+            (0..length).fold(0, |code, _| (code << 1) | self.bits(1))
+        }
+
+        fn symbol(&mut self) -> u16 {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code as u16;
+            }
+            let code = (code << 1) | self.bits(1);
+            match code {
+                0x30..=0xBF => (code - 0x30) as u16,
+                0xC0..=0xC7 => (280 + code - 0xC0) as u16,
+                _ => (144 + ((code << 1) | self.bits(1)) - 0x190) as u16,
+            }
+        }
+    }
+
+    /// Decodes the single fixed-Huffman block `gzip` writes.
+    pub(crate) fn gunzip(data: &[u8]) -> Vec<u8> {
+        assert_eq!(&data[..3], &[0x1F, 0x8B, 8]);
+        let mut reader = BitReader {
+            data: &data[10..data.len() - 8],
+            position: 0,
+        };
+        assert_eq!((reader.bits(1), reader.bits(2)), (1, 1));
+
+        let mut output: Vec<u8> = Vec::new();
+        loop {
+            let symbol = reader.symbol();
+            match symbol {
+                0..=255 => output.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let code = (symbol - 257) as usize;
+                    let length =
+                        LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as u32) as usize;
+                    let code = reader.code(5) as usize;
+                    let distance = DISTANCE_BASE[code] as usize
+                        + reader.bits(DISTANCE_EXTRA[code] as u32) as usize;
+                    let start = output.len() - distance;
+                    for offset in 0..length {
+                        output.push(output[start + offset]);
+                    }
+                }
+            }
+        }
+
+        let trailer = &data[data.len() - 8..];
+        assert_eq!(u32::from_le_bytes(trailer[..4].try_into().unwrap()), crc32(&output));
+        assert_eq!(u32::from_le_bytes(trailer[4..].try_into().unwrap()), output.len() as u32);
+        output
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let long_run = vec![b'a'; 5000];
+        let mixed: Vec<u8> = (0..70_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let all_bytes: Vec<u8> = (0..=255u8).collect();
+
+        for input in [&b""[..], b"abc", b"abcabcabcabcabcabc", &long_run, &mixed, &all_bytes] {
+            assert_eq!(gunzip(&gzip(input)), input);
+        }
+        assert!(gzip(&long_run).len() < 100);
+        // Check value for "123456789" from the CRC-32 specification
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
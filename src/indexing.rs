@@ -1,6 +1,30 @@
-use super::core_types::{DatabaseError, SqlValue, ComparisonOperator, WhereClause};
+use super::core_types::{DatabaseError, Row, SqlValue, ComparisonOperator, WhereClause};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+/// What loading a database does about indexes that disagree with the rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexCheck {
+    #[default]
+    Off,
+    /// Log every mismatch and leave the indexes as they are.
+    Check,
+    /// Log the mismatches, then rebuild the indexes of the affected tables.
+    Repair,
+}
+
+impl IndexCheck {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "0" => Some(Self::Off),
+            "check" => Some(Self::Check),
+            "repair" => Some(Self::Repair),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct BTreeIndex {
@@ -8,6 +32,8 @@ pub struct BTreeIndex {
     pub column_name: String,
     pub is_unique: bool,
     pub is_primary: bool,
+    /// Text keys are stored case-folded so lookups ignore case.
+    pub case_insensitive: bool,
     tree: BTreeMap<IndexKey, Vec<usize>>,
 }
 
@@ -101,13 +127,24 @@ impl From<&SqlValue> for IndexKey {
         match value {
             SqlValue::Integer(i) => IndexKey::Integer(*i),
             SqlValue::Float(f) => IndexKey::Float(OrderedFloat(*f)),
-            SqlValue::Text(s) => IndexKey::Text(s.clone()),
+            SqlValue::Text(s) => IndexKey::Text(s.to_string()),
             SqlValue::Boolean(b) => IndexKey::Boolean(*b),
             SqlValue::Null => IndexKey::Null,
         }
     }
 }
 
+impl IndexKey {
+    /// Builds the key used by case-insensitive indexes: text is lowercased,
+    /// every other type maps exactly like `IndexKey::from`.
+    pub fn folded(value: &SqlValue) -> Self {
+        match value {
+            SqlValue::Text(s) => IndexKey::Text(s.to_lowercase()),
+            other => IndexKey::from(other),
+        }
+    }
+}
+
 impl CompositeKey {
     pub fn new(keys: Vec<IndexKey>) -> Self {
         Self { keys }
@@ -217,6 +254,12 @@ impl CompositeIndex {
     pub fn size(&self) -> usize {
         self.tree.len()
     }
+
+    fn key_of_row(&self, row: &Row) -> Option<CompositeKey> {
+        let values: Vec<&SqlValue> =
+            self.column_names.iter().filter_map(|col| row.columns.get(col)).collect();
+        (values.len() == self.column_names.len()).then(|| CompositeKey::from_values(&values))
+    }
 }
 
 impl BTreeIndex {
@@ -226,12 +269,33 @@ impl BTreeIndex {
             column_name,
             is_unique,
             is_primary,
+            case_insensitive: false,
             tree: BTreeMap::new(),
         }
     }
 
+    pub fn new_case_insensitive(
+        name: String,
+        column_name: String,
+        is_unique: bool,
+        is_primary: bool,
+    ) -> Self {
+        Self {
+            case_insensitive: true,
+            ..Self::new(name, column_name, is_unique, is_primary)
+        }
+    }
+
+    fn key_for(&self, value: &SqlValue) -> IndexKey {
+        if self.case_insensitive {
+            IndexKey::folded(value)
+        } else {
+            IndexKey::from(value)
+        }
+    }
+
     pub fn insert(&mut self, key: &SqlValue, row_id: usize) -> Result<(), DatabaseError> {
-        let index_key = IndexKey::from(key);
+        let index_key = self.key_for(key);
 
         if self.is_unique {
             if let Some(existing_rows) = self.tree.get(&index_key) {
@@ -252,7 +316,7 @@ impl BTreeIndex {
     }
 
     pub fn remove(&mut self, key: &SqlValue, row_id: usize) {
-        let index_key = IndexKey::from(key);
+        let index_key = self.key_for(key);
         if let Some(row_ids) = self.tree.get_mut(&index_key) {
             row_ids.retain(|&id| id != row_id);
             if row_ids.is_empty() {
@@ -262,17 +326,19 @@ impl BTreeIndex {
     }
 
     pub fn find_exact(&self, key: &SqlValue) -> Vec<usize> {
-        let index_key = IndexKey::from(key);
+        let index_key = self.key_for(key);
         self.tree.get(&index_key).cloned().unwrap_or_default()
     }
 
     pub fn find_range(&self, start: Option<&SqlValue>, end: Option<&SqlValue>) -> Vec<usize> {
         let mut result = Vec::new();
 
-        let start_key = start.map(IndexKey::from);
-        let end_key = end.map(IndexKey::from);
+        let start_key = start.map(|value| self.key_for(value));
+        let end_key = end.map(|value| self.key_for(value));
 
         let iter = match (start_key.as_ref(), end_key.as_ref()) {
+            // `BTreeMap::range` panics on an inverted range
+            (Some(start), Some(end)) if start > end => return result,
             (Some(start), Some(end)) => self.tree.range(start..=end),
             (Some(start), None) => self.tree.range(start..),
             (None, Some(end)) => self.tree.range(..=end),
@@ -287,7 +353,7 @@ impl BTreeIndex {
     }
 
     pub fn find_greater_than(&self, key: &SqlValue) -> Vec<usize> {
-        let index_key = IndexKey::from(key);
+        let index_key = self.key_for(key);
         let mut result = Vec::new();
 
         for (_, row_ids) in self.tree.range((
@@ -301,7 +367,7 @@ impl BTreeIndex {
     }
 
     pub fn find_less_than(&self, key: &SqlValue) -> Vec<usize> {
-        let index_key = IndexKey::from(key);
+        let index_key = self.key_for(key);
         let mut result = Vec::new();
 
         for (_, row_ids) in self.tree.range(..&index_key) {
@@ -319,17 +385,64 @@ impl BTreeIndex {
         self.tree.len()
     }
 
-    pub fn rebuild(&mut self, data: Vec<(&SqlValue, usize)>) -> Result<(), DatabaseError> {
+    pub fn rebuild(
+        &mut self,
+        data: Vec<(&SqlValue, usize)>,
+        progress: &mut RebuildProgress,
+    ) -> Result<(), DatabaseError> {
         self.tree.clear();
 
         for (value, row_id) in data {
             self.insert(value, row_id)?;
+            progress.advance(1);
         }
 
         Ok(())
     }
 }
 
+/// Lists the entries of `tree` that do not match `rows`: ids past the end of the
+/// table, ids filed under a value the row does not hold, and rows left out.
+fn tree_mismatches<K: Ord + Debug>(
+    index_name: &str,
+    tree: &BTreeMap<K, Vec<usize>>,
+    rows: &[Row],
+    key_of_row: impl Fn(&Row) -> Option<K>,
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+
+    for (key, row_ids) in tree {
+        for &row_id in row_ids {
+            match rows.get(row_id) {
+                None => mismatches.push(format!(
+                    "index '{}' points at row {} but the table has {} rows",
+                    index_name,
+                    row_id,
+                    rows.len()
+                )),
+                Some(row) if key_of_row(row).as_ref() != Some(key) => mismatches.push(format!(
+                    "index '{}' lists row {} under {:?}, which the row does not hold",
+                    index_name, row_id, key
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    for (row_id, row) in rows.iter().enumerate() {
+        if let Some(key) = key_of_row(row) {
+            if !tree.get(&key).is_some_and(|row_ids| row_ids.contains(&row_id)) {
+                mismatches.push(format!(
+                    "index '{}' is missing row {} ({:?})",
+                    index_name, row_id, key
+                ));
+            }
+        }
+    }
+
+    mismatches
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexManager {
     indexes: Vec<BTreeIndex>,
@@ -428,6 +541,13 @@ impl QueryOptimizer {
     }
 }
 
+/// `(row_id, old_values, new_values)` for one row touched by an UPDATE.
+pub type RowChange<'a> = (
+    usize,
+    &'a HashMap<String, SqlValue>,
+    &'a HashMap<String, SqlValue>,
+);
+
 impl IndexManager {
     pub fn new() -> Self {
         Self {
@@ -453,14 +573,33 @@ impl IndexManager {
         Ok(())
     }
 
-    pub fn drop_index(&mut self, name: &str) -> Result<(), DatabaseError> {
-        let pos = self
-            .indexes
-            .iter()
-            .position(|idx| idx.name == name)
-            .ok_or_else(|| DatabaseError::IndexNotFound(name.to_string()))?;
+    /// Creates an index whose text keys are case-folded, for columns compared
+    /// case-insensitively. Case-sensitive indexes keep using `create_index`.
+    pub fn create_case_insensitive_index(
+        &mut self,
+        name: String,
+        column_name: String,
+        is_unique: bool,
+        is_primary: bool,
+    ) -> Result<(), DatabaseError> {
+        if self.indexes.iter().any(|idx| idx.name == name) {
+            return Err(DatabaseError::IndexAlreadyExists(name));
+        }
+
+        let index = BTreeIndex::new_case_insensitive(name, column_name, is_unique, is_primary);
+        self.indexes.push(index);
+        Ok(())
+    }
 
-        self.indexes.remove(pos);
+    /// Drops the plain or composite index called `name`.
+    pub fn drop_index(&mut self, name: &str) -> Result<(), DatabaseError> {
+        if let Some(pos) = self.indexes.iter().position(|idx| idx.name == name) {
+            self.indexes.remove(pos);
+        } else if let Some(pos) = self.composite_indexes.iter().position(|idx| idx.name == name) {
+            self.composite_indexes.remove(pos);
+        } else {
+            return Err(DatabaseError::IndexNotFound(name.to_string()));
+        }
         Ok(())
     }
 
@@ -492,14 +631,65 @@ impl IndexManager {
         column_values: &HashMap<String, SqlValue>,
         row_id: usize,
     ) -> Result<(), DatabaseError> {
-        for index in &mut self.indexes {
-            if let Some(value) = column_values.get(&index.column_name) {
-                index.insert(value, row_id)?;
-            }
+        let inserted = self
+            .indexes
+            .iter_mut()
+            .filter_map(|index| Some((column_values.get(&index.column_name)?, index)))
+            .try_for_each(|(value, index)| index.insert(value, row_id))
+            .and_then(|()| self.insert_into_composite_indexes(column_values, row_id));
+        // A unique index that refused the row must not leave it in the others
+        if inserted.is_err() {
+            self.remove_from_indexes(column_values, row_id);
         }
+        inserted
+    }
 
-        self.insert_into_composite_indexes(column_values, row_id)?;
-        Ok(())
+    /// Whether a primary key or unique index covers exactly `columns`, in any order.
+    pub fn has_unique_key(&self, columns: &[String]) -> bool {
+        self.indexes
+            .iter()
+            .filter(|index| index.is_unique || index.is_primary)
+            .any(|index| same_columns(std::slice::from_ref(&index.column_name), columns))
+            || self
+                .composite_indexes
+                .iter()
+                .filter(|index| index.is_unique)
+                .any(|index| same_columns(&index.column_names, columns))
+    }
+
+    /// The row already holding one of `column_values` in a unique index, if any.
+    /// A non-empty `target` limits the check to the index on those columns.
+    pub fn find_conflict(
+        &self,
+        column_values: &HashMap<String, SqlValue>,
+        target: &[String],
+    ) -> Option<usize> {
+        let single = self
+            .indexes
+            .iter()
+            .filter(|index| index.is_unique || index.is_primary)
+            .filter(|index| {
+                target.is_empty() || same_columns(std::slice::from_ref(&index.column_name), target)
+            })
+            .find_map(|index| {
+                let value = column_values.get(&index.column_name)?;
+                index.find_exact(value).first().copied()
+            });
+
+        single.or_else(|| {
+            self.composite_indexes
+                .iter()
+                .filter(|index| index.is_unique)
+                .filter(|index| target.is_empty() || same_columns(&index.column_names, target))
+                .find_map(|index| {
+                    let values: Option<Vec<&SqlValue>> = index
+                        .column_names
+                        .iter()
+                        .map(|column| column_values.get(column))
+                        .collect();
+                    index.find_exact(&values?).first().copied()
+                })
+        })
     }
 
     pub fn remove_from_indexes(
@@ -537,10 +727,43 @@ impl IndexManager {
         Ok(())
     }
 
+    /// Replaces the index entries of several rows as one unit. All old entries are
+    /// removed before any new one is inserted, so a row may keep its own unique value
+    /// and rows may swap values. On a conflict every change is undone.
+    pub fn update_rows(
+        &mut self,
+        changes: &[RowChange<'_>],
+    ) -> Result<(), DatabaseError> {
+        for (row_id, old_values, _) in changes {
+            self.remove_from_indexes(old_values, *row_id);
+        }
+
+        for (position, (row_id, _, new_values)) in changes.iter().enumerate() {
+            if let Err(err) = self.insert_into_indexes(new_values, *row_id) {
+                // The failing row may be partially inserted; removal only drops its own ids
+                for (row_id, _, new_values) in &changes[..=position] {
+                    self.remove_from_indexes(new_values, *row_id);
+                }
+                for (row_id, old_values, _) in changes {
+                    let _ = self.insert_into_indexes(old_values, *row_id);
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn rebuild_all_indexes(
         &mut self,
+        table_name: &str,
         table_data: &[(HashMap<String, SqlValue>, usize)],
     ) -> Result<(), DatabaseError> {
+        let index_count = self.indexes.len() + self.composite_indexes.len();
+        let mut progress = RebuildProgress::new(
+            format!("the indexes of table '{}'", table_name),
+            table_data.len() * index_count,
+        );
         for index in &mut self.indexes {
             let index_data: Vec<(&SqlValue, usize)> = table_data
                 .iter()
@@ -549,7 +772,7 @@ impl IndexManager {
                 })
                 .collect();
 
-            index.rebuild(index_data)?;
+            index.rebuild(index_data, &mut progress)?;
         }
 
         for composite_idx in &mut self.composite_indexes {
@@ -564,11 +787,33 @@ impl IndexManager {
                 if values.len() == composite_idx.column_names.len() {
                     composite_idx.insert(&values, *row_id)?;
                 }
+                progress.advance(1);
             }
         }
+        progress.finish();
         Ok(())
     }
 
+    /// Compares every index with `rows`, whose positions are the row ids, and
+    /// describes each entry that is wrong or missing.
+    pub fn find_mismatches(&self, rows: &[Row]) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        for index in &self.indexes {
+            mismatches.extend(tree_mismatches(&index.name, &index.tree, rows, |row| {
+                row.columns.get(&index.column_name).map(|value| index.key_for(value))
+            }));
+        }
+        for composite_idx in &self.composite_indexes {
+            mismatches.extend(tree_mismatches(
+                &composite_idx.name,
+                &composite_idx.tree,
+                rows,
+                |row| composite_idx.key_of_row(row),
+            ));
+        }
+        mismatches
+    }
+
     pub fn create_composite_index(
         &mut self,
         name: String,
@@ -746,3 +991,131 @@ impl Default for IndexManager {
         Self::new()
     }
 }
+
+/// Rows between two looks at the clock, which would cost more than the rows
+/// themselves if taken for every one.
+const PROGRESS_CHECK_ROWS: usize = 10_000;
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Logs how far a rebuild of indexes or bloom filters has got, once a second,
+/// so that rebuilding a table of millions of rows does not look like a hang.
+/// Rebuilds finishing within the first second log nothing.
+pub struct RebuildProgress {
+    what: String,
+    total_rows: usize,
+    done_rows: usize,
+    started: Instant,
+    last_logged: Instant,
+    logged: bool,
+}
+
+impl RebuildProgress {
+    pub fn new(what: String, total_rows: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            what,
+            total_rows,
+            done_rows: 0,
+            started: now,
+            last_logged: now,
+            logged: false,
+        }
+    }
+
+    pub fn advance(&mut self, rows: usize) {
+        let before = self.done_rows;
+        self.done_rows += rows;
+        if before / PROGRESS_CHECK_ROWS == self.done_rows / PROGRESS_CHECK_ROWS {
+            return;
+        }
+        let now = Instant::now();
+        if now.duration_since(self.last_logged) >= PROGRESS_LOG_INTERVAL {
+            let elapsed = self.started.elapsed();
+            let message = progress_message(&self.what, self.done_rows, self.total_rows, elapsed);
+            println!("[MirseoDB] {}", message);
+            self.last_logged = now;
+            self.logged = true;
+        }
+    }
+
+    /// Closes the progress lines with the total time, if any were logged.
+    pub fn finish(&self) {
+        if self.logged {
+            println!(
+                "[MirseoDB] Rebuilt {} ({} rows) in {:.1}s",
+                self.what,
+                self.total_rows,
+                self.started.elapsed().as_secs_f64()
+            );
+        }
+    }
+}
+
+fn same_columns(index_columns: &[String], columns: &[String]) -> bool {
+    index_columns.len() == columns.len()
+        && index_columns.iter().all(|column| columns.contains(column))
+}
+
+/// The percentage done and the time left, assuming the remaining rows go as
+/// fast as the ones done so far.
+fn progress_message(what: &str, done_rows: usize, total_rows: usize, elapsed: Duration) -> String {
+    let done_rows = done_rows.min(total_rows);
+    let percent = (done_rows * 100).checked_div(total_rows).unwrap_or(100);
+    let time_left = if done_rows == 0 {
+        "time left unknown".to_string()
+    } else {
+        let seconds = elapsed.as_secs_f64() * (total_rows - done_rows) as f64 / done_rows as f64;
+        format!("about {:.0}s left", seconds)
+    };
+    format!(
+        "Rebuilding {}: {}/{} rows ({}%), {}",
+        what, done_rows, total_rows, percent, time_left
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_insensitive_index_folds_text_keys() {
+        let mut index = BTreeIndex::new_case_insensitive(
+            "idx_users_email".to_string(),
+            "email".to_string(),
+            true,
+            false,
+        );
+        index
+            .insert(&SqlValue::Text("Alice@Example.com".into()), 0)
+            .unwrap();
+
+        assert_eq!(
+            index.find_exact(&SqlValue::Text("alice@example.COM".into())),
+            vec![0]
+        );
+        assert!(index
+            .insert(&SqlValue::Text("ALICE@example.com".into()), 1)
+            .is_err());
+
+        let mut sensitive =
+            BTreeIndex::new("idx_users_name".to_string(), "name".to_string(), false, false);
+        sensitive.insert(&SqlValue::Text("Alice".into()), 0).unwrap();
+        assert!(sensitive
+            .find_exact(&SqlValue::Text("alice".into()))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_progress_message_estimates_the_time_left() {
+        let what = "the indexes of table 'EVENTS'";
+        assert_eq!(
+            progress_message(what, 250_000, 1_000_000, Duration::from_secs(3)),
+            "Rebuilding the indexes of table 'EVENTS': 250000/1000000 rows (25%), about 9s left"
+        );
+        assert_eq!(
+            progress_message(what, 0, 1_000_000, Duration::from_secs(1)),
+            "Rebuilding the indexes of table 'EVENTS': 0/1000000 rows (0%), time left unknown"
+        );
+        assert!(progress_message(what, 10, 0, Duration::ZERO).contains("0/0 rows (100%)"));
+    }
+}
@@ -1,6 +1,6 @@
 use super::core_types::{
-    ColumnDefinition, ComparisonOperator, DataType, DatabaseError, SqlStatement, SqlValue,
-    WhereClause,
+    ColumnDefinition, ComparisonOperator, DataType, DatabaseError, Durability, SqlStatement,
+    SqlValue, WhereClause,
 };
 
 #[derive(Debug, Clone)]
@@ -83,6 +83,9 @@ impl Parser {
             return Ok(SqlStatement::CreateTable {
                 table_name,
                 columns: Vec::new(),
+                durability: Durability::default(),
+                comment: None,
+                if_not_exists: false,
             });
         }
 
@@ -131,18 +134,27 @@ impl Parser {
                     data_type,
                     nullable,
                     primary_key,
+                    comment: None,
+                    default: None,
+                    auto_increment: false,
                 });
             }
 
             Ok(SqlStatement::CreateTable {
                 table_name,
                 columns,
+                durability: Durability::default(),
+                comment: None,
+                if_not_exists: false,
             })
         } else {
             // No parentheses found, create empty table
             Ok(SqlStatement::CreateTable {
                 table_name,
                 columns: Vec::new(),
+                durability: Durability::default(),
+                comment: None,
+                if_not_exists: false,
             })
         }
     }
@@ -201,6 +213,8 @@ impl Parser {
             table_name,
             columns,
             values,
+            on_conflict: None,
+            conflict_target: Vec::new(),
         })
     }
 
@@ -226,7 +240,7 @@ impl Parser {
 
         let where_clause =
             if let Some(where_pos) = tokens.iter().position(|&token| token == "WHERE") {
-                Some(self.parse_where_clause(&tokens[where_pos + 1..])?)
+                Some(self.parse_where_clause(&tokens[where_pos + 1..])?.into())
             } else {
                 None
             };
@@ -234,8 +248,14 @@ impl Parser {
         Ok(SqlStatement::Select {
             table_name,
             columns,
+            scalar_subqueries: Vec::new(),
+            column_aliases: Vec::new(),
+            distinct: false,
+            aggregates: Vec::new(),
+            group_by: Vec::new(),
             where_clause,
             optimization_hint: None,
+            order_by: None,
             limit: None,
             offset: None,
         })
@@ -276,7 +296,7 @@ impl Parser {
         }
 
         let where_clause = if let Some(where_pos) = where_pos {
-            Some(self.parse_where_clause(&tokens[where_pos + 1..])?)
+            Some(self.parse_where_clause(&tokens[where_pos + 1..])?.into())
         } else {
             None
         };
@@ -299,7 +319,7 @@ impl Parser {
 
         let where_clause =
             if let Some(where_pos) = tokens.iter().position(|&token| token == "WHERE") {
-                Some(self.parse_where_clause(&tokens[where_pos + 1..])?)
+                Some(self.parse_where_clause(&tokens[where_pos + 1..])?.into())
             } else {
                 None
             };
@@ -402,12 +422,12 @@ impl Parser {
 
         if value_str.starts_with('\'') && value_str.ends_with('\'') {
             let text = value_str[1..value_str.len() - 1].to_string();
-            return Ok(SqlValue::Text(text));
+            return Ok(SqlValue::Text(text.into()));
         }
 
         if value_str.starts_with('"') && value_str.ends_with('"') {
             let text = value_str[1..value_str.len() - 1].to_string();
-            return Ok(SqlValue::Text(text));
+            return Ok(SqlValue::Text(text.into()));
         }
 
         if value_str.contains('.') {
@@ -420,6 +440,6 @@ impl Parser {
             return Ok(SqlValue::Integer(int_val));
         }
 
-        Ok(SqlValue::Text(value_str.to_string()))
+        Ok(SqlValue::Text(value_str.into()))
     }
 }
@@ -0,0 +1,30 @@
+//! MirseoDB as a library: the storage engine and AnySQL parser can be used
+//! in-process, and `run_server` starts the HTTP API without the web console.
+
+mod auth;
+pub mod bootstrap;
+mod bloom_filter;
+mod compression;
+mod configuration;
+pub mod core_types;
+mod dictionary;
+mod engine;
+mod gzip;
+mod indexing;
+mod legacy_parser;
+mod persistence;
+mod routing;
+mod security;
+mod server;
+mod smart_parser;
+mod statistics;
+mod two_factor_auth;
+mod wal;
+
+pub use bootstrap::{run_server, ServerConfig, ServerHandle};
+pub use core_types::{
+    ColumnDefinition, ComparisonOperator, DataType, DatabaseError, Durability, Row, SqlStatement,
+    SqlValue, Table, WhereClause,
+};
+pub use engine::{Database, DatabaseRegistry};
+pub use smart_parser::AnySQL;
@@ -1,35 +1,13 @@
-mod auth;
-mod bloom_filter;
-mod configuration;
-mod core_types;
-mod engine;
-mod indexing;
-mod legacy_parser;
-mod persistence;
-mod routing;
-mod security;
-mod server;
-mod smart_parser;
-mod two_factor_auth;
-
-use auth::AuthConfig;
-use configuration::ConfigManager;
-use core_types::DatabaseError;
-use engine::Database;
-use routing::RouteConfig;
-use server::start_health_server;
-use smart_parser::AnySQL;
+use mirseodb::{run_server, DatabaseRegistry, ServerConfig};
 use std::env;
 use std::path::Path;
 use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
-use std::time::Duration;
 
 static SVELTEKIT_PROCESS: OnceLock<Arc<Mutex<Option<Child>>>> = OnceLock::new();
+static SHUTDOWN_DATABASES: OnceLock<Arc<DatabaseRegistry>> = OnceLock::new();
 
-const DEFAULT_HEALTH_PORT: u16 = 3306;
-const HEARTBEAT_INTERVAL_SECS: u64 = 60;
 const CONSOLE_DIR: &str = "console";
 
 fn register_shutdown_handler() {
@@ -47,6 +25,13 @@ fn register_shutdown_handler() {
             }
         }
 
+        // Write out ASYNC table changes that the heartbeat has not flushed yet
+        if let Some(databases) = SHUTDOWN_DATABASES.get() {
+            if let Err(e) = databases.flush_pending() {
+                eprintln!("[MirseoDB] Failed to flush pending changes on shutdown: {:?}", e);
+            }
+        }
+
         std::process::exit(0);
     }) {
         eprintln!(
@@ -114,113 +99,41 @@ fn main() {
     println!("[MirseoDB] Starting MirseoDB Server...");
     spawn_console_server();
 
-    let (database, database_name) = match initialize_database() {
-        Ok(pair) => {
-            println!("[MirseoDB] Database initialized successfully");
-            pair
-        }
+    let server = match run_server(ServerConfig::from_env()) {
+        Ok(server) => server,
         Err(e) => {
-            eprintln!("[MirseoDB] Database initialization failed: {:?}", e);
+            eprintln!("[MirseoDB] Server startup failed: {:?}", e);
             return;
         }
     };
 
-    let parser = Arc::new(AnySQL::new());
-    println!(
-        "[MirseoDB] AnySQL HYPERTHINKING engine initialized - All SQL dialects supported automatically!"
-    );
-
-    let route_config = match RouteConfig::load() {
-        Ok(config) => {
-            println!("[MirseoDB] Route configuration loaded successfully");
-            Arc::new(config)
-        }
-        Err(e) => {
-            eprintln!("[MirseoDB] Route configuration failed to load: {}", e);
-            println!("[MirseoDB] Continuing without route forwarding...");
-            Arc::new(RouteConfig {
-                routes: std::collections::HashMap::new(),
-            })
-        }
-    };
-
-    let security_config = ConfigManager::load();
-    if security_config.sql_injection_protect {
-        println!("[MirseoDB] SQL injection protection enabled (SQL_INJECTON_PROTECT=1)");
-    } else {
-        println!("[MirseoDB] SQL injection protection disabled (SQL_INJECTON_PROTECT=0)");
-    }
-
-    let api_token = env::var("MIRSEODB_API_TOKEN").ok();
-    if api_token.is_some() {
-        println!("[MirseoDB] API authentication enabled via MIRSEODB_API_TOKEN");
-    } else {
-        println!("[MirseoDB] API authentication disabled (set MIRSEODB_API_TOKEN to enable)");
-    }
-
-    let health_port = match start_health_server(
-        DEFAULT_HEALTH_PORT,
-        Arc::clone(&database),
-        Arc::clone(&parser),
-        Arc::clone(&route_config),
-        api_token,
-    ) {
-        Ok(port) => {
-            println!(
-                "[MirseoDB] HTTP endpoint ready: http://127.0.0.1:{}/health (and /query)",
-                port
-            );
-            Some(port)
-        }
-        Err(err) => {
-            eprintln!("[MirseoDB] HTTP endpoint failed to start: {}", err);
-            None
-        }
-    };
-
-    if let Some(port) = health_port {
-        println!("[MirseoDB] Server is running on HTTP port: {}", port);
-    } else {
-        println!("[MirseoDB] Server is running without an HTTP endpoint.");
-    }
+    let database = server.database();
+    let databases = server.databases();
+    let _ = SHUTDOWN_DATABASES.set(Arc::clone(&databases));
 
+    println!("[MirseoDB] Server is running on HTTP port: {}", server.port());
     println!("[MirseoDB] Server startup complete. Ready to accept connections.");
 
+    let heartbeat_interval = match database.read() {
+        Ok(db) => db.checkpoint_interval(),
+        Err(poisoned) => poisoned.into_inner().checkpoint_interval(),
+    };
     loop {
-        thread::sleep(Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
+        thread::sleep(heartbeat_interval);
 
-        match database.lock() {
+        match database.read() {
             Ok(db) => println!(
                 "[MirseoDB] Heartbeat: database='{}' tables={}",
-                database_name,
+                server.database_name(),
                 db.tables.len()
             ),
             Err(_) => eprintln!("[MirseoDB] Heartbeat failed: database lock poisoned"),
         }
-    }
-}
 
-fn initialize_database() -> Result<(Arc<Mutex<Database>>, String), DatabaseError> {
-    let db_name = "mirseodb".to_string();
-
-    ConfigManager::ensure_exists()?;
-    AuthConfig::ensure_exists().map_err(|e| DatabaseError::IoError(e))?;
-
-    println!("[MirseoDB] Loading database '{}'...", db_name);
-
-    let database = match Database::load(db_name.clone()) {
-        Ok(db) => {
-            println!(
-                "[MirseoDB] Existing database '{}' loaded successfully",
-                db_name
-            );
-            db
+        match databases.flush_pending() {
+            Ok(true) => println!("[MirseoDB] Heartbeat: flushed ASYNC table changes"),
+            Ok(false) => {}
+            Err(e) => eprintln!("[MirseoDB] Heartbeat flush failed: {:?}", e),
         }
-        Err(_) => {
-            println!("[MirseoDB] Creating new database '{}'", db_name);
-            Database::new(db_name.clone())
-        }
-    };
-
-    Ok((Arc::new(Mutex::new(database)), db_name))
+    }
 }
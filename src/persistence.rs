@@ -1,41 +1,129 @@
-use super::core_types::{ColumnDefinition, DataType, DatabaseError, Row, SqlValue, Table};
+use super::compression::{self, Codec};
+use super::configuration::ConfigManager;
+use super::core_types::{
+    ColumnDefinition, DataType, DatabaseError, Durability, Row, SqlValue, Table,
+};
+use super::dictionary::TableDictionaries;
 use super::indexing::IndexManager;
+use super::wal::remove_log;
 use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
+/// Files written since row values became positional start with this magic and a
+/// format version. Older files start directly with the table count.
+const FILE_MAGIC: &[u8; 4] = b"MSDB";
+// Versions 2 to 4 are a single byte after the magic, later ones a u16 LE. No u16
+// version has 2 to 4 as its low byte, so builds that read the one-byte header
+// report newer files as an unsupported version instead of misreading them.
+const FORMAT_LEGACY: u8 = 1; // every row value prefixed with its column name
+const FORMAT_POSITIONAL: u8 = 2; // row values in ColumnDefinition order behind a presence bitmap
+const FORMAT_ENCODED: u8 = 3; // codec id byte, then the positional layout encoded with it
+const FORMAT_INDEXES: u8 = 4; // version 3 followed by the index trailer
+const FORMAT_WIDE_HEADER: u16 = 5; // version 4 behind a u16 version
+// 6: adds the comment trailer
+// 7: adds the write-ahead log LSN
+// 8: adds the column default trailer
+// 9: adds the AUTO_INCREMENT trailer
+const FORMAT_VERSION: u16 = 9; // the version this build writes
+
 pub struct StorageEngine {
     db_name: String,
+    fsync_on_save: bool,
+    compression: Codec,
+    repair_on_load: bool, // salvage the tables before a corrupt one instead of failing
 }
 
 impl StorageEngine {
     pub fn new(db_name: String) -> Self {
-        Self { db_name }
+        let config = ConfigManager::load();
+        Self {
+            db_name,
+            fsync_on_save: config.fsync_on_save,
+            compression: config.compression,
+            repair_on_load: config.repair_on_load,
+        }
+    }
+
+    /// Writes `tables` along with `lsn`, the last write-ahead log record they include.
+    pub fn save_tables(
+        &self,
+        tables: &HashMap<String, Table>,
+        lsn: u64,
+    ) -> Result<(), DatabaseError> {
+        self.write_tables(tables, lsn, self.fsync_on_save).map(|_| ())
     }
 
-    pub fn save_tables(&self, tables: &HashMap<String, Table>) -> Result<(), DatabaseError> {
+    /// Saves like `save_tables` but always fsyncs, whatever `FSYNC_ON_SAVE` says.
+    /// Returns the bytes written.
+    pub fn checkpoint(
+        &self,
+        tables: &HashMap<String, Table>,
+        lsn: u64,
+    ) -> Result<usize, DatabaseError> {
+        self.write_tables(tables, lsn, true)
+    }
+
+    /// Writes the file beside the database and renames it into place, so a crash
+    /// mid-write leaves the previous file intact.
+    fn write_tables(
+        &self,
+        tables: &HashMap<String, Table>,
+        lsn: u64,
+        fsync: bool,
+    ) -> Result<usize, DatabaseError> {
         let filepath = self.db_file_path()?;
+        let (temp_path, written) = self.write_temp_file(&filepath, tables, lsn, fsync)?;
+
+        fs::rename(&temp_path, &filepath).map_err(|e| {
+            DatabaseError::IoError(format!("Failed to replace database file: {}", e))
+        })?;
+        // The rename itself is only durable once the directory entry is flushed
+        if fsync {
+            if let Some(dir) = filepath.parent() {
+                File::open(dir)
+                    .and_then(|dir| dir.sync_all())
+                    .map_err(|e| DatabaseError::IoError(e.to_string()))?;
+            }
+        }
 
+        Ok(written)
+    }
+
+    fn write_temp_file(
+        &self,
+        filepath: &Path,
+        tables: &HashMap<String, Table>,
+        lsn: u64,
+        fsync: bool,
+    ) -> Result<(PathBuf, usize), DatabaseError> {
+        let temp_path = filepath.with_extension("mdb.tmp");
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(&filepath)
+            .open(&temp_path)
             .map_err(|e| DatabaseError::IoError(e.to_string()))?;
 
-        let serialized = self.serialize_tables(tables)?;
+        let serialized = self.serialize_tables(tables, lsn)?;
         file.write_all(&serialized)
             .map_err(|e| DatabaseError::IoError(e.to_string()))?;
+        // Success must mean the data reached the disk, not just the OS page cache
+        if fsync {
+            file.sync_all()
+                .map_err(|e| DatabaseError::IoError(e.to_string()))?;
+        }
 
-        Ok(())
+        Ok((temp_path, serialized.len()))
     }
 
-    pub fn load_tables(&self) -> Result<HashMap<String, Table>, DatabaseError> {
+    /// The saved tables and the write-ahead log LSN the file includes.
+    pub fn load_tables(&self) -> Result<(HashMap<String, Table>, u64), DatabaseError> {
         let filepath = self.db_file_path()?;
 
         if !filepath.exists() {
-            return Ok(HashMap::new());
+            return Ok((HashMap::new(), 0));
         }
 
         let mut file = File::open(&filepath).map_err(|e| DatabaseError::IoError(e.to_string()))?;
@@ -47,6 +135,52 @@ impl StorageEngine {
         self.deserialize_tables(&buffer)
     }
 
+    /// The name `db_name` is stored under, matched case-insensitively like
+    /// `remove_database_file`, if it has a `.mdb` file.
+    pub fn stored_database_name(db_name: &str) -> Option<String> {
+        fs::read_dir(".mirseoDB").ok()?.flatten().find_map(|entry| {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "mdb") {
+                return None;
+            }
+            let stem = path.file_stem()?.to_str()?;
+            stem.eq_ignore_ascii_case(db_name).then(|| stem.to_string())
+        })
+    }
+
+    /// Deletes the `.mdb` file of `db_name` (matched case-insensitively, since
+    /// unquoted names are uppercased by the parser) and its write-ahead log.
+    /// Returns whether a database file existed.
+    pub fn remove_database_file(db_name: &str) -> Result<bool, DatabaseError> {
+        let entries = match fs::read_dir(".mirseoDB") {
+            Ok(entries) => entries,
+            Err(_) => return Ok(false),
+        };
+
+        let mut removed = false;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_match = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| stem.eq_ignore_ascii_case(db_name));
+            if !is_match {
+                continue;
+            }
+
+            if path.extension().is_some_and(|ext| ext == "mdb") {
+                fs::remove_file(&path).map_err(|e| {
+                    DatabaseError::IoError(format!("Failed to remove database file: {}", e))
+                })?;
+                removed = true;
+            } else if path.extension().is_some_and(|ext| ext == "wal") {
+                remove_log(&path)?;
+            }
+        }
+
+        Ok(removed)
+    }
+
     fn db_file_path(&self) -> Result<PathBuf, DatabaseError> {
         let dir = Path::new(".mirseoDB");
         fs::create_dir_all(dir).map_err(|e| DatabaseError::IoError(e.to_string()))?;
@@ -54,32 +188,152 @@ impl StorageEngine {
         Ok(dir.join(format!("{}.mdb", self.db_name)))
     }
 
-    fn serialize_tables(&self, tables: &HashMap<String, Table>) -> Result<Vec<u8>, DatabaseError> {
+    fn serialize_tables(
+        &self,
+        tables: &HashMap<String, Table>,
+        lsn: u64,
+    ) -> Result<Vec<u8>, DatabaseError> {
+        let body = self.serialize_table_section(tables, lsn)?;
+
+        let mut buffer = Vec::with_capacity(body.len() + FILE_MAGIC.len() + 3);
+        buffer.extend_from_slice(FILE_MAGIC);
+        buffer.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        buffer.push(self.compression.id());
+        match self.compression {
+            Codec::None => buffer.extend_from_slice(&body),
+            Codec::Lz => {
+                // The codec's own length header is a u32 too
+                checked_len(body.len(), "compressed section length")?;
+                buffer.extend_from_slice(&compression::compress(&body));
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    fn serialize_table_section(
+        &self,
+        tables: &HashMap<String, Table>,
+        lsn: u64,
+    ) -> Result<Vec<u8>, DatabaseError> {
         let mut buffer = Vec::new();
 
-        let table_count = tables.len() as u32;
-        buffer.extend_from_slice(&table_count.to_le_bytes());
+        push_len(&mut buffer, tables.len(), "table count")?;
 
-        for table in tables.values() {
+        // Tables and row values are written in a fixed order so identical data
+        // always produces identical bytes
+        let mut ordered_tables: Vec<&Table> = tables.values().collect();
+        ordered_tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for table in &ordered_tables {
             self.serialize_table(table, &mut buffer)?;
         }
 
+        // Trailer listing ASYNC tables. Files without it load every table as SYNC,
+        // and older readers stop after the table section, so the format stays compatible.
+        let async_tables: Vec<&Table> = ordered_tables
+            .iter()
+            .copied()
+            .filter(|table| table.durability == Durability::Async)
+            .collect();
+        push_len(&mut buffer, async_tables.len(), "ASYNC table count")?;
+        for table in async_tables {
+            push_string(&mut buffer, &table.name)?;
+        }
+
+        // Second trailer listing every secondary index, so CREATE INDEX and DROP
+        // INDEX survive a reload. Without it tables get the automatic NOT NULL indexes.
+        let mut index_entries = Vec::new();
+        for table in &ordered_tables {
+            let (indexes, composite_indexes) = table.index_manager.list_all_indexes();
+            for index in indexes.into_iter().filter(|index| !index.is_primary) {
+                let flags = index.is_unique as u8 | (index.case_insensitive as u8) << 1;
+                index_entries.push((&table.name, &index.name, flags, vec![&index.column_name]));
+            }
+            for index in composite_indexes {
+                let columns = index.column_names.iter().collect();
+                index_entries.push((&table.name, &index.name, index.is_unique as u8, columns));
+            }
+        }
+        push_len(&mut buffer, index_entries.len(), "index count")?;
+        for (table_name, index_name, flags, columns) in index_entries {
+            push_string(&mut buffer, table_name)?;
+            push_string(&mut buffer, index_name)?;
+            buffer.push(flags);
+            push_len(&mut buffer, columns.len(), "index column count")?;
+            for column in columns {
+                push_string(&mut buffer, column)?;
+            }
+        }
+
+        // Third trailer: table and column comments, with an empty column name
+        // standing for the table itself
+        let mut comments = Vec::new();
+        for table in &ordered_tables {
+            if let Some(comment) = &table.comment {
+                comments.push((&table.name, "", comment));
+            }
+            for column in &table.columns {
+                if let Some(comment) = &column.comment {
+                    comments.push((&table.name, column.name.as_str(), comment));
+                }
+            }
+        }
+        push_len(&mut buffer, comments.len(), "comment count")?;
+        for (table_name, column_name, comment) in comments {
+            push_string(&mut buffer, table_name)?;
+            push_string(&mut buffer, column_name)?;
+            push_string(&mut buffer, comment)?;
+        }
+
+        buffer.extend_from_slice(&lsn.to_le_bytes());
+
+        // Fifth trailer: column defaults. It follows the LSN so that files
+        // without it still end with their LSN.
+        let defaults: Vec<(&String, &ColumnDefinition, &SqlValue)> = ordered_tables
+            .iter()
+            .flat_map(|table| {
+                table.columns.iter().filter_map(move |column| {
+                    column.default.as_ref().map(|default| (&table.name, column, default))
+                })
+            })
+            .collect();
+        push_len(&mut buffer, defaults.len(), "default count")?;
+        for (table_name, column, default) in defaults {
+            push_string(&mut buffer, table_name)?;
+            push_string(&mut buffer, &column.name)?;
+            self.serialize_sql_value(default, &mut buffer)?;
+        }
+
+        // Sixth trailer: each AUTO_INCREMENT column with the value it gives next
+        let auto_increments: Vec<(&Table, &ColumnDefinition)> = ordered_tables
+            .iter()
+            .filter_map(|table| {
+                let column = table.columns.iter().find(|column| column.auto_increment)?;
+                Some((*table, column))
+            })
+            .collect();
+        push_len(&mut buffer, auto_increments.len(), "AUTO_INCREMENT count")?;
+        for (table, column) in auto_increments {
+            push_string(&mut buffer, &table.name)?;
+            push_string(&mut buffer, &column.name)?;
+            buffer.extend_from_slice(&table.next_auto_increment.to_le_bytes());
+        }
+
         Ok(buffer)
     }
 
     fn serialize_table(&self, table: &Table, buffer: &mut Vec<u8>) -> Result<(), DatabaseError> {
-        let name_bytes = table.name.as_bytes();
-        buffer.extend_from_slice(&(name_bytes.len() as u32).to_le_bytes());
-        buffer.extend_from_slice(name_bytes);
+        push_string(buffer, &table.name)?;
 
-        buffer.extend_from_slice(&(table.columns.len() as u32).to_le_bytes());
+        push_len(buffer, table.columns.len(), "column count")?;
         for column in &table.columns {
             self.serialize_column_definition(column, buffer)?;
         }
 
-        buffer.extend_from_slice(&(table.rows.len() as u32).to_le_bytes());
+        push_len(buffer, table.rows.len(), "row count")?;
         for row in &table.rows {
-            self.serialize_row(row, buffer)?;
+            self.serialize_row(row, &table.columns, buffer)?;
         }
 
         Ok(())
@@ -90,9 +344,7 @@ impl StorageEngine {
         column: &ColumnDefinition,
         buffer: &mut Vec<u8>,
     ) -> Result<(), DatabaseError> {
-        let name_bytes = column.name.as_bytes();
-        buffer.extend_from_slice(&(name_bytes.len() as u32).to_le_bytes());
-        buffer.extend_from_slice(name_bytes);
+        push_string(buffer, &column.name)?;
 
         let data_type_id = match column.data_type {
             DataType::Integer => 0u8,
@@ -108,15 +360,45 @@ impl StorageEngine {
         Ok(())
     }
 
-    fn serialize_row(&self, row: &Row, buffer: &mut Vec<u8>) -> Result<(), DatabaseError> {
-        buffer.extend_from_slice(&(row.columns.len() as u32).to_le_bytes());
+    /// Writes a presence bitmap (one bit per column, plus one flagging named extras)
+    /// followed by the present values in `ColumnDefinition` order. Values for columns
+    /// the table does not define follow with their names, sorted by name.
+    fn serialize_row(
+        &self,
+        row: &Row,
+        columns: &[ColumnDefinition],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), DatabaseError> {
+        let mut bitmap = vec![0u8; columns.len() / 8 + 1];
+        for (position, column) in columns.iter().enumerate() {
+            if row.columns.contains_key(&column.name) {
+                bitmap[position / 8] |= 1 << (position % 8);
+            }
+        }
 
-        for (column_name, value) in &row.columns {
-            let name_bytes = column_name.as_bytes();
-            buffer.extend_from_slice(&(name_bytes.len() as u32).to_le_bytes());
-            buffer.extend_from_slice(name_bytes);
+        let mut extra: Vec<(&String, &SqlValue)> = row
+            .columns
+            .iter()
+            .filter(|(name, _)| !columns.iter().any(|column| &column.name == *name))
+            .collect();
+        extra.sort_by(|a, b| a.0.cmp(b.0));
+        if !extra.is_empty() {
+            bitmap[columns.len() / 8] |= 1 << (columns.len() % 8);
+        }
+        buffer.extend_from_slice(&bitmap);
 
-            self.serialize_sql_value(value, buffer)?;
+        for column in columns {
+            if let Some(value) = row.columns.get(&column.name) {
+                self.serialize_sql_value(value, buffer)?;
+            }
+        }
+
+        if !extra.is_empty() {
+            push_len(buffer, extra.len(), "extra column count")?;
+            for (column_name, value) in extra {
+                push_string(buffer, column_name)?;
+                self.serialize_sql_value(value, buffer)?;
+            }
         }
 
         Ok(())
@@ -138,9 +420,7 @@ impl StorageEngine {
             }
             SqlValue::Text(s) => {
                 buffer.push(2);
-                let text_bytes = s.as_bytes();
-                buffer.extend_from_slice(&(text_bytes.len() as u32).to_le_bytes());
-                buffer.extend_from_slice(text_bytes);
+                push_string(buffer, s)?;
             }
             SqlValue::Boolean(b) => {
                 buffer.push(3);
@@ -153,30 +433,391 @@ impl StorageEngine {
         Ok(())
     }
 
-    fn deserialize_tables(&self, buffer: &[u8]) -> Result<HashMap<String, Table>, DatabaseError> {
+    /// The tables in a database file and the write-ahead log LSN it was saved at.
+    fn deserialize_tables(
+        &self,
+        buffer: &[u8],
+    ) -> Result<(HashMap<String, Table>, u64), DatabaseError> {
+        if !buffer.starts_with(FILE_MAGIC) {
+            return self.deserialize_table_section(buffer, FORMAT_LEGACY);
+        }
+
+        let header_len = FILE_MAGIC.len() + 1;
+        match buffer.get(FILE_MAGIC.len()) {
+            Some(&FORMAT_POSITIONAL) => {
+                return self.deserialize_table_section(&buffer[header_len..], FORMAT_POSITIONAL);
+            }
+            Some(&(FORMAT_ENCODED..=FORMAT_INDEXES)) => {
+                return self.deserialize_encoded(&buffer[header_len..]);
+            }
+            _ => {}
+        }
+
+        let version = buffer
+            .get(FILE_MAGIC.len()..FILE_MAGIC.len() + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(|| DatabaseError::IoError("Invalid file header".to_string()))?;
+        if !(FORMAT_WIDE_HEADER..=FORMAT_VERSION).contains(&version) {
+            return Err(DatabaseError::IoError(format!(
+                "Unsupported database file format version {} (this build reads up to {}); \
+                 the file was probably written by a newer MirseoDB",
+                version, FORMAT_VERSION
+            )));
+        }
+        self.deserialize_encoded(&buffer[FILE_MAGIC.len() + 2..])
+    }
+
+    /// A codec id byte followed by the positional table section encoded with it.
+    fn deserialize_encoded(
+        &self,
+        buffer: &[u8],
+    ) -> Result<(HashMap<String, Table>, u64), DatabaseError> {
+        let codec = buffer.first().and_then(|id| Codec::from_id(*id)).ok_or_else(|| {
+            DatabaseError::IoError("Unknown database file compression".to_string())
+        })?;
+        let payload = &buffer[1..];
+        match codec {
+            Codec::None => self.deserialize_table_section(payload, FORMAT_POSITIONAL),
+            Codec::Lz => {
+                let body = compression::decompress(payload).map_err(|e| {
+                    DatabaseError::IoError(format!("Failed to decompress database: {}", e))
+                })?;
+                self.deserialize_table_section(&body, FORMAT_POSITIONAL)
+            }
+        }
+    }
+
+    /// Reads the tables and their trailers, then fills every table's indexes in a
+    /// single pass once the index trailer has settled which indexes exist.
+    fn deserialize_table_section(
+        &self,
+        buffer: &[u8],
+        format_version: u8,
+    ) -> Result<(HashMap<String, Table>, u64), DatabaseError> {
+        let (mut tables, lsn) = self.read_table_section(buffer, format_version)?;
+        for table in tables.values_mut() {
+            rebuild_indexes(table)?;
+        }
+        Ok((tables, lsn))
+    }
+
+    fn read_table_section(
+        &self,
+        buffer: &[u8],
+        format_version: u8,
+    ) -> Result<(HashMap<String, Table>, u64), DatabaseError> {
         let mut cursor = 0;
         let mut tables = HashMap::new();
 
-        if buffer.len() < 4 {
-            return Ok(tables);
+        if cursor + 4 > buffer.len() {
+            return Ok((tables, 0));
         }
 
-        let table_count = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let table_count = u32::from_le_bytes([
+            buffer[cursor],
+            buffer[cursor + 1],
+            buffer[cursor + 2],
+            buffer[cursor + 3],
+        ]);
         cursor += 4;
 
-        for _ in 0..table_count {
-            let (table, new_cursor) = self.deserialize_table(buffer, cursor)?;
+        for table_number in 1..=table_count {
+            match self.deserialize_table(buffer, cursor, format_version) {
+                Ok((table, new_cursor)) => {
+                    cursor = new_cursor;
+                    tables.insert(table.name.clone(), table);
+                }
+                Err(e) if self.repair_on_load => {
+                    // Without this table's length the rest of the file cannot be located
+                    let table_name = peek_table_name(buffer, cursor)
+                        .unwrap_or_else(|| "name unreadable".to_string());
+                    eprintln!(
+                        "[MirseoDB] Repair mode: table {} of {} ({}) failed to load: {}; keeping {} earlier table(s)",
+                        table_number,
+                        table_count,
+                        table_name,
+                        describe_error(&e),
+                        tables.len()
+                    );
+                    return Ok((tables, 0));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let cursor = match self.apply_durability_trailer(buffer, cursor, &mut tables) {
+            Ok(cursor) => cursor,
+            Err(e) if self.repair_on_load => {
+                eprintln!(
+                    "[MirseoDB] Repair mode: durability metadata unreadable ({}); affected tables load as SYNC",
+                    describe_error(&e)
+                );
+                return Ok((tables, 0));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let cursor = match self.apply_index_trailer(buffer, cursor, &mut tables) {
+            Ok(cursor) => cursor,
+            Err(e) if self.repair_on_load => {
+                eprintln!(
+                    "[MirseoDB] Repair mode: index metadata unreadable ({}); tables keep their automatic indexes",
+                    describe_error(&e)
+                );
+                return Ok((tables, 0));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let cursor = match self.apply_comment_trailer(buffer, cursor, &mut tables) {
+            Ok(cursor) => cursor,
+            Err(e) if self.repair_on_load => {
+                eprintln!(
+                    "[MirseoDB] Repair mode: comments unreadable ({}); tables load without them",
+                    describe_error(&e)
+                );
+                return Ok((tables, 0));
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Fourth trailer: the write-ahead log LSN the file includes. Files written
+        // before the log existed have none, and every logged record is newer.
+        let lsn = buffer
+            .get(cursor..cursor + 8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(0);
+
+        let column_trailers = self
+            .apply_default_trailer(buffer, cursor + 8, &mut tables)
+            .and_then(|cursor| self.apply_auto_increment_trailer(buffer, cursor, &mut tables));
+        if let Err(e) = column_trailers {
+            if !self.repair_on_load {
+                return Err(e);
+            }
+            eprintln!(
+                "[MirseoDB] Repair mode: column defaults or AUTO_INCREMENT counters unreadable \
+                 ({}); columns load without them",
+                describe_error(&e)
+            );
+        }
+
+        Ok((tables, lsn))
+    }
+
+    /// Marks the tables named in the trailer after the table section as ASYNC.
+    /// Returns the position after the trailer.
+    fn apply_durability_trailer(
+        &self,
+        buffer: &[u8],
+        mut cursor: usize,
+        tables: &mut HashMap<String, Table>,
+    ) -> Result<usize, DatabaseError> {
+        if cursor + 4 <= buffer.len() {
+            let async_count = u32::from_le_bytes([
+                buffer[cursor],
+                buffer[cursor + 1],
+                buffer[cursor + 2],
+                buffer[cursor + 3],
+            ]);
+            cursor += 4;
+
+            for _ in 0..async_count {
+                if cursor + 4 > buffer.len() {
+                    return Err(DatabaseError::IoError(
+                        "Invalid durability metadata".to_string(),
+                    ));
+                }
+
+                let name_len = u32::from_le_bytes([
+                    buffer[cursor],
+                    buffer[cursor + 1],
+                    buffer[cursor + 2],
+                    buffer[cursor + 3],
+                ]) as usize;
+                cursor += 4;
+
+                if cursor + name_len > buffer.len() {
+                    return Err(DatabaseError::IoError(
+                        "Invalid durability metadata".to_string(),
+                    ));
+                }
+
+                let name = String::from_utf8_lossy(&buffer[cursor..cursor + name_len]);
+                cursor += name_len;
+
+                if let Some(table) = tables.get_mut(name.as_ref()) {
+                    table.durability = Durability::Async;
+                }
+            }
+        }
+
+        Ok(cursor)
+    }
+
+    /// Replaces each table's secondary indexes with those listed in the index
+    /// trailer, when the file has one. The indexes are left empty. Returns the
+    /// position after the trailer.
+    fn apply_index_trailer(
+        &self,
+        buffer: &[u8],
+        mut cursor: usize,
+        tables: &mut HashMap<String, Table>,
+    ) -> Result<usize, DatabaseError> {
+        let invalid = || DatabaseError::IoError("Invalid index metadata".to_string());
+        let Some(index_count) = read_u32(buffer, &mut cursor) else {
+            return Ok(cursor);
+        };
+
+        // Read every entry first so a damaged trailer leaves the tables untouched
+        let mut entries = Vec::new();
+        for _ in 0..index_count {
+            let table_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let index_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let flags = *buffer.get(cursor).ok_or_else(invalid)?;
+            cursor += 1;
+            let column_count = read_u32(buffer, &mut cursor).ok_or_else(invalid)?;
+            let columns = (0..column_count)
+                .map(|_| read_string(buffer, &mut cursor))
+                .collect::<Option<Vec<String>>>()
+                .ok_or_else(invalid)?;
+            entries.push((table_name, index_name, flags, columns));
+        }
+
+        for table in tables.values_mut() {
+            let secondary: Vec<String> = table
+                .index_manager
+                .list_indexes()
+                .into_iter()
+                .filter(|index| !index.is_primary)
+                .map(|index| index.name.clone())
+                .collect();
+            for name in secondary {
+                table.index_manager.drop_index(&name)?;
+            }
+        }
+
+        for (table_name, index_name, flags, mut columns) in entries {
+            let Some(table) = tables.get_mut(&table_name) else {
+                continue;
+            };
+            let is_unique = flags & 1 != 0;
+            let index_manager = &mut table.index_manager;
+            if columns.len() != 1 {
+                index_manager.create_composite_index(index_name, columns, is_unique)?;
+            } else if flags & 2 != 0 {
+                let column = columns.remove(0);
+                index_manager.create_case_insensitive_index(index_name, column, is_unique, false)?;
+            } else {
+                index_manager.create_index(index_name, columns.remove(0), is_unique, false)?;
+            }
+        }
+
+        Ok(cursor)
+    }
+
+    /// Attaches the table and column comments listed in the comment trailer.
+    /// Returns the position after the trailer.
+    fn apply_comment_trailer(
+        &self,
+        buffer: &[u8],
+        mut cursor: usize,
+        tables: &mut HashMap<String, Table>,
+    ) -> Result<usize, DatabaseError> {
+        let invalid = || DatabaseError::IoError("Invalid comment metadata".to_string());
+        let Some(comment_count) = read_u32(buffer, &mut cursor) else {
+            return Ok(cursor);
+        };
+
+        for _ in 0..comment_count {
+            let table_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let column_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let comment = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+
+            let Some(table) = tables.get_mut(&table_name) else {
+                continue;
+            };
+            if column_name.is_empty() {
+                table.comment = Some(comment);
+            } else if let Some(column) =
+                table.columns.iter_mut().find(|column| column.name == column_name)
+            {
+                column.comment = Some(comment);
+            }
+        }
+
+        Ok(cursor)
+    }
+
+    /// Sets the column defaults listed in the trailer after the LSN. Returns the
+    /// position after the trailer.
+    fn apply_default_trailer(
+        &self,
+        buffer: &[u8],
+        mut cursor: usize,
+        tables: &mut HashMap<String, Table>,
+    ) -> Result<usize, DatabaseError> {
+        let invalid = || DatabaseError::IoError("Invalid column default metadata".to_string());
+        let Some(default_count) = read_u32(buffer, &mut cursor) else {
+            return Ok(cursor);
+        };
+
+        for _ in 0..default_count {
+            let table_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let column_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let (default, new_cursor) = self.deserialize_sql_value(buffer, cursor)?;
             cursor = new_cursor;
-            tables.insert(table.name.clone(), table);
+
+            if let Some(column) = tables.get_mut(&table_name).and_then(|table| {
+                table.columns.iter_mut().find(|column| column.name == column_name)
+            }) {
+                column.default = Some(default);
+            }
         }
 
-        Ok(tables)
+        Ok(cursor)
+    }
+
+    /// Marks the AUTO_INCREMENT columns and restores their tables' counters.
+    fn apply_auto_increment_trailer(
+        &self,
+        buffer: &[u8],
+        mut cursor: usize,
+        tables: &mut HashMap<String, Table>,
+    ) -> Result<(), DatabaseError> {
+        let invalid = || DatabaseError::IoError("Invalid AUTO_INCREMENT metadata".to_string());
+        let Some(count) = read_u32(buffer, &mut cursor) else {
+            return Ok(());
+        };
+
+        for _ in 0..count {
+            let table_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let column_name = read_string(buffer, &mut cursor).ok_or_else(invalid)?;
+            let next_value = buffer
+                .get(cursor..cursor + 8)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(i64::from_le_bytes)
+                .ok_or_else(invalid)?;
+            cursor += 8;
+
+            let Some(table) = tables.get_mut(&table_name) else {
+                continue;
+            };
+            if let Some(column) = table.columns.iter_mut().find(|c| c.name == column_name) {
+                column.auto_increment = true;
+                table.next_auto_increment = next_value;
+            }
+        }
+
+        Ok(())
     }
 
     fn deserialize_table(
         &self,
         buffer: &[u8],
         mut cursor: usize,
+        format_version: u8,
     ) -> Result<(Table, usize), DatabaseError> {
         if cursor + 4 > buffer.len() {
             return Err(DatabaseError::IoError("Invalid table data".to_string()));
@@ -235,11 +876,16 @@ impl StorageEngine {
 
         let mut rows = Vec::new();
         for _ in 0..row_count {
-            let (row, new_cursor) = self.deserialize_row(buffer, cursor)?;
+            let (row, new_cursor) = if format_version == FORMAT_LEGACY {
+                self.deserialize_named_row(buffer, cursor)?
+            } else {
+                self.deserialize_row(buffer, cursor, &columns)?
+            };
             cursor = new_cursor;
             rows.push(row);
         }
 
+        let dictionaries = TableDictionaries::build(&mut rows);
         let mut index_manager = IndexManager::new();
 
         for column in &columns {
@@ -252,25 +898,20 @@ impl StorageEngine {
             }
         }
 
-        let mut table = Table {
+        // The automatic indexes stay empty until `deserialize_table_section` fills
+        // whichever ones the index trailer keeps
+        let table = Table {
             name,
             columns,
+            next_row_id: rows.len(),
+            next_auto_increment: 1,
             rows,
             index_manager,
-            next_row_id: row_count as usize,
+            durability: Durability::Sync,
+            dictionaries,
+            comment: None,
         };
 
-        let table_snapshot: Vec<(HashMap<String, SqlValue>, usize)> = table
-            .rows
-            .iter()
-            .enumerate()
-            .map(|(row_id, row)| (row.columns.clone(), row_id))
-            .collect();
-
-        table.index_manager.rebuild_all_indexes(&table_snapshot)?;
-
-        table.next_row_id = table.rows.len();
-
         Ok((table, cursor))
     }
 
@@ -323,6 +964,9 @@ impl StorageEngine {
             data_type,
             nullable,
             primary_key,
+            comment: None,
+            default: None,
+            auto_increment: false,
         };
 
         Ok((column, cursor))
@@ -332,7 +976,50 @@ impl StorageEngine {
         &self,
         buffer: &[u8],
         mut cursor: usize,
+        definitions: &[ColumnDefinition],
+    ) -> Result<(Row, usize), DatabaseError> {
+        let bitmap_len = definitions.len() / 8 + 1;
+        if cursor + bitmap_len > buffer.len() {
+            return Err(DatabaseError::IoError("Invalid row data".to_string()));
+        }
+
+        let bitmap = &buffer[cursor..cursor + bitmap_len];
+        cursor += bitmap_len;
+        let is_set = |position: usize| bitmap[position / 8] & (1 << (position % 8)) != 0;
+
+        let mut columns = HashMap::new();
+        for (position, definition) in definitions.iter().enumerate() {
+            if is_set(position) {
+                let (value, new_cursor) = self.deserialize_sql_value(buffer, cursor)?;
+                cursor = new_cursor;
+                columns.insert(definition.name.clone(), value);
+            }
+        }
+
+        if is_set(definitions.len()) {
+            cursor = self.deserialize_named_values(buffer, cursor, &mut columns)?;
+        }
+
+        Ok((Row { columns }, cursor))
+    }
+
+    /// Row layout of files without a format header: a count and name/value pairs.
+    fn deserialize_named_row(
+        &self,
+        buffer: &[u8],
+        cursor: usize,
     ) -> Result<(Row, usize), DatabaseError> {
+        let mut columns = HashMap::new();
+        let cursor = self.deserialize_named_values(buffer, cursor, &mut columns)?;
+        Ok((Row { columns }, cursor))
+    }
+
+    fn deserialize_named_values(
+        &self,
+        buffer: &[u8],
+        mut cursor: usize,
+        columns: &mut HashMap<String, SqlValue>,
+    ) -> Result<usize, DatabaseError> {
         if cursor + 4 > buffer.len() {
             return Err(DatabaseError::IoError("Invalid row data".to_string()));
         }
@@ -345,8 +1032,6 @@ impl StorageEngine {
         ]);
         cursor += 4;
 
-        let mut columns = HashMap::new();
-
         for _ in 0..column_count {
             if cursor + 4 > buffer.len() {
                 return Err(DatabaseError::IoError(
@@ -378,8 +1063,7 @@ impl StorageEngine {
             columns.insert(column_name, value);
         }
 
-        let row = Row { columns };
-        Ok((row, cursor))
+        Ok(cursor)
     }
 
     fn deserialize_sql_value(
@@ -452,7 +1136,7 @@ impl StorageEngine {
                         DatabaseError::IoError("Invalid UTF-8 in text value".to_string())
                     })?;
                 cursor += text_len;
-                SqlValue::Text(text_val)
+                SqlValue::Text(text_val.into())
             }
             3 => {
                 if cursor >= buffer.len() {
@@ -469,3 +1153,419 @@ impl StorageEngine {
         Ok((value, cursor))
     }
 }
+
+/// Writes a `u32` length prefix, refusing lengths the format cannot represent
+/// rather than truncating them into an unreadable file.
+fn push_len(buffer: &mut Vec<u8>, len: usize, what: &str) -> Result<(), DatabaseError> {
+    buffer.extend_from_slice(&checked_len(len, what)?.to_le_bytes());
+    Ok(())
+}
+
+fn checked_len(len: usize, what: &str) -> Result<u32, DatabaseError> {
+    u32::try_from(len).map_err(|_| {
+        DatabaseError::IoError(format!(
+            "Cannot save database: {} of {} exceeds the format limit of {}",
+            what,
+            len,
+            u32::MAX
+        ))
+    })
+}
+
+fn rebuild_indexes(table: &mut Table) -> Result<(), DatabaseError> {
+    let table_snapshot: Vec<(HashMap<String, SqlValue>, usize)> = table
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(row_id, row)| (row.columns.clone(), row_id))
+        .collect();
+    table.index_manager.rebuild_all_indexes(&table.name, &table_snapshot)
+}
+
+fn push_string(buffer: &mut Vec<u8>, value: &str) -> Result<(), DatabaseError> {
+    push_len(buffer, value.len(), "string length")?;
+    buffer.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn read_u32(buffer: &[u8], cursor: &mut usize) -> Option<u32> {
+    let bytes = buffer.get(*cursor..*cursor + 4)?;
+    *cursor += 4;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_string(buffer: &[u8], cursor: &mut usize) -> Option<String> {
+    let len = read_u32(buffer, cursor)? as usize;
+    let bytes = buffer.get(*cursor..*cursor + len)?;
+    *cursor += len;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn peek_table_name(buffer: &[u8], cursor: usize) -> Option<String> {
+    let len_bytes = buffer.get(cursor..cursor + 4)?;
+    let name_len = u32::from_le_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]);
+    let name = buffer.get(cursor + 4..cursor + 4 + name_len as usize)?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+fn describe_error(error: &DatabaseError) -> String {
+    match error {
+        DatabaseError::IoError(msg) => msg.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_table(values: &[(&str, SqlValue)]) -> Table {
+        let column = |name: &str, data_type| ColumnDefinition {
+            name: name.to_string(),
+            data_type,
+            nullable: true,
+            primary_key: false,
+            comment: None,
+            default: None,
+            auto_increment: false,
+        };
+
+        Table {
+            name: "T".to_string(),
+            columns: vec![
+                column("id", DataType::Integer),
+                column("name", DataType::Text),
+                column("score", DataType::Float),
+            ],
+            rows: vec![Row {
+                columns: values
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            }],
+            index_manager: IndexManager::new(),
+            next_row_id: 1,
+            next_auto_increment: 1,
+            durability: Durability::Sync,
+            dictionaries: TableDictionaries::default(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_rows_serialize_in_column_definition_order() {
+        let storage = StorageEngine::new("serialization_order_test".to_string());
+        let values = [
+            ("score", SqlValue::Float(1.5)),
+            ("id", SqlValue::Integer(7)),
+            ("name", SqlValue::Text("kim".into())),
+        ];
+
+        let mut forward = Vec::new();
+        storage.serialize_table(&sample_table(&values), &mut forward).unwrap();
+
+        let reversed: Vec<(&str, SqlValue)> = values.iter().rev().cloned().collect();
+        let mut backward = Vec::new();
+        storage.serialize_table(&sample_table(&reversed), &mut backward).unwrap();
+
+        assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn test_positional_rows_round_trip() {
+        let storage = StorageEngine::new("positional_rows_test".to_string());
+        // `score` omitted, `name` explicitly NULL, `note` not part of the table definition
+        let table = sample_table(&[
+            ("id", SqlValue::Integer(1)),
+            ("name", SqlValue::Null),
+            ("note", SqlValue::Text("extra".into())),
+        ]);
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+
+        let bytes = storage.serialize_tables(&tables, 0).unwrap();
+        assert!(bytes.starts_with(FILE_MAGIC));
+        assert_eq!(bytes.windows(2).filter(|window| window == b"id").count(), 1);
+
+        let loaded = storage.deserialize_tables(&bytes).unwrap().0;
+        let row = &loaded["T"].rows[0];
+        assert!(matches!(row.columns.get("id"), Some(SqlValue::Integer(1))));
+        assert!(matches!(row.columns.get("name"), Some(SqlValue::Null)));
+        assert!(!row.columns.contains_key("score"));
+        assert!(matches!(row.columns.get("note"), Some(SqlValue::Text(note)) if &**note == "extra"));
+        // Text is stored in full and re-interned on load
+        assert_eq!(loaded["T"].dictionaries.dictionary_size("note"), Some(1));
+    }
+
+    #[test]
+    fn test_created_and_dropped_indexes_survive_reload() {
+        let mut storage = StorageEngine::new("index_trailer_test".to_string());
+        storage.compression = Codec::None;
+        let mut table = sample_table(&[("id", SqlValue::Integer(1)), ("name", SqlValue::Null)]);
+        table
+            .index_manager
+            .create_index("idx_name".to_string(), "name".to_string(), false, false)
+            .unwrap();
+        table
+            .index_manager
+            .create_composite_index(
+                "idx_name_score".to_string(),
+                vec!["name".to_string(), "score".to_string()],
+                true,
+            )
+            .unwrap();
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+
+        let loaded = storage
+            .deserialize_tables(&storage.serialize_tables(&tables, 0).unwrap())
+            .unwrap()
+            .0;
+        let (indexes, composite_indexes) = loaded["T"].index_manager.list_all_indexes();
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].column_name, "name");
+        assert_eq!(loaded["T"].index_manager.get_index("idx_name").unwrap().size(), 1);
+        assert_eq!(composite_indexes.len(), 1);
+        assert!(composite_indexes[0].is_unique);
+
+        // A dropped automatic index is not recreated from the NOT NULL column
+        let mut table = sample_table(&[("id", SqlValue::Integer(1))]);
+        table.columns[0].nullable = false;
+        tables.insert(table.name.clone(), table);
+        let bytes = storage.serialize_tables(&tables, 0).unwrap();
+        let loaded = storage.deserialize_tables(&bytes).unwrap().0;
+        assert!(loaded["T"].index_manager.list_indexes().is_empty());
+
+        // Version 3 files end after the ASYNC table trailer and keep the automatic index
+        let mut section = 1u32.to_le_bytes().to_vec();
+        storage.serialize_table(&tables["T"], &mut section).unwrap();
+        section.extend_from_slice(&0u32.to_le_bytes());
+        let bytes = [&FILE_MAGIC[..], &[FORMAT_ENCODED, 0], &section].concat();
+        let loaded = storage.deserialize_tables(&bytes).unwrap().0;
+        assert_eq!(loaded["T"].index_manager.get_index("idx_T_id").unwrap().size(), 1);
+    }
+
+    #[test]
+    fn test_comments_survive_reload() {
+        let storage = StorageEngine::new("comment_trailer_test".to_string());
+        let mut table = sample_table(&[("id", SqlValue::Integer(1))]);
+        table.comment = Some("Scores per player".to_string());
+        table.columns[2].comment = Some("Best of three".to_string());
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+
+        let loaded = storage
+            .deserialize_tables(&storage.serialize_tables(&tables, 0).unwrap())
+            .unwrap()
+            .0;
+        assert_eq!(loaded["T"].comment.as_deref(), Some("Scores per player"));
+        assert_eq!(loaded["T"].columns[2].comment.as_deref(), Some("Best of three"));
+        assert_eq!(loaded["T"].columns[0].comment, None);
+    }
+
+    #[test]
+    fn test_column_defaults_survive_reload() {
+        let storage = StorageEngine::new("default_trailer_test".to_string());
+        let mut table = sample_table(&[("id", SqlValue::Integer(1))]);
+        table.columns[1].default = Some(SqlValue::Text("anonymous".into()));
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+
+        let (loaded, lsn) = storage
+            .deserialize_tables(&storage.serialize_tables(&tables, 42).unwrap())
+            .unwrap();
+        assert_eq!(lsn, 42);
+        assert!(matches!(
+            &loaded["T"].columns[1].default,
+            Some(SqlValue::Text(name)) if &**name == "anonymous"
+        ));
+        assert!(loaded["T"].columns[0].default.is_none());
+    }
+
+    #[test]
+    fn test_auto_increment_counter_survives_reload() {
+        let storage = StorageEngine::new("auto_increment_trailer_test".to_string());
+        let mut table = sample_table(&[("id", SqlValue::Integer(3))]);
+        table.columns[0].auto_increment = true;
+        table.next_auto_increment = 4;
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+
+        let loaded = storage
+            .deserialize_tables(&storage.serialize_tables(&tables, 7).unwrap())
+            .unwrap()
+            .0;
+        assert!(loaded["T"].columns[0].auto_increment);
+        assert!(!loaded["T"].columns[1].auto_increment);
+        assert_eq!(loaded["T"].next_auto_increment, 4);
+    }
+
+    #[test]
+    fn test_legacy_named_rows_still_load() {
+        let storage = StorageEngine::new("legacy_rows_test".to_string());
+        let mut table = sample_table(&[]);
+        table.columns[0].nullable = false;
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&(table.name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(table.name.as_bytes());
+        bytes.extend_from_slice(&(table.columns.len() as u32).to_le_bytes());
+        for column in &table.columns {
+            storage.serialize_column_definition(column, &mut bytes).unwrap();
+        }
+        bytes.extend_from_slice(&1u32.to_le_bytes()); // one row
+        bytes.extend_from_slice(&1u32.to_le_bytes()); // with one named value
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(b"id");
+        storage.serialize_sql_value(&SqlValue::Integer(42), &mut bytes).unwrap();
+
+        let loaded = storage.deserialize_tables(&bytes).unwrap().0;
+        assert!(matches!(
+            loaded["T"].rows[0].columns.get("id"),
+            Some(SqlValue::Integer(42))
+        ));
+        // Files without an index trailer keep the automatic index, filled on load
+        let index = loaded["T"].index_manager.get_index("idx_T_id").unwrap();
+        assert_eq!(index.size(), 1);
+    }
+
+    #[test]
+    fn test_unknown_format_version_is_reported() {
+        let mut storage = StorageEngine::new("format_version_test".to_string());
+        let mut bytes = FILE_MAGIC.to_vec();
+        bytes.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+
+        let expected = format!("format version {}", FORMAT_VERSION + 1);
+        match storage.deserialize_tables(&bytes) {
+            Err(DatabaseError::IoError(msg)) => assert!(msg.contains(&expected), "{}", msg),
+            Err(other) => panic!("unexpected error: {:?}", other),
+            Ok(_) => panic!("an unknown format version loaded"),
+        }
+
+        // Files with the one-byte version header still load
+        storage.compression = Codec::None;
+        let table = sample_table(&[("id", SqlValue::Integer(1))]);
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+        let current = storage.serialize_tables(&tables, 0).unwrap();
+        assert_eq!(current[FILE_MAGIC.len()..FILE_MAGIC.len() + 2], FORMAT_VERSION.to_le_bytes());
+        let section = &current[FILE_MAGIC.len() + 3..];
+        for header in [&[FORMAT_POSITIONAL][..], &[FORMAT_ENCODED, 0], &[FORMAT_INDEXES, 0]] {
+            let bytes = [&FILE_MAGIC[..], header, section].concat();
+            let loaded = storage.deserialize_tables(&bytes).unwrap().0;
+            assert_eq!(loaded["T"].rows.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_lengths_beyond_u32_are_rejected() {
+        let mut buffer = Vec::new();
+        push_len(&mut buffer, u32::MAX as usize, "row count").unwrap();
+        assert_eq!(buffer, u32::MAX.to_le_bytes());
+
+        if let Some(too_long) = (u32::MAX as usize).checked_add(1) {
+            let error = push_len(&mut buffer, too_long, "row count").unwrap_err();
+            assert!(matches!(error, DatabaseError::IoError(msg) if msg.contains("row count")));
+            assert_eq!(buffer.len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_compression_shrinks_text_heavy_tables() {
+        let cities = ["Seoul", "Busan", "Incheon", "Daegu", "Daejeon", "Gwangju"];
+        let mut table = sample_table(&[]);
+        table.rows = (0..5000)
+            .map(|i| Row {
+                columns: [
+                    ("id".to_string(), SqlValue::Integer(i)),
+                    (
+                        "name".to_string(),
+                        SqlValue::Text(format!(
+                            "customer-{} lives in {} and ordered item #{}",
+                            i,
+                            cities[i as usize % cities.len()],
+                            i % 97
+                        ).into()),
+                    ),
+                    ("score".to_string(), SqlValue::Float((i % 100) as f64 / 4.0)),
+                ]
+                .into_iter()
+                .collect(),
+            })
+            .collect();
+        let mut tables = HashMap::new();
+        tables.insert(table.name.clone(), table);
+
+        let mut storage = StorageEngine::new("compression_tradeoff_test".to_string());
+        let mut measure = |codec: Codec| {
+            storage.compression = codec;
+            let bytes = storage.serialize_tables(&tables, 0).unwrap();
+            assert_eq!(bytes[FILE_MAGIC.len() + 2], codec.id());
+            let loaded = storage.deserialize_tables(&bytes).unwrap().0;
+            assert_eq!(loaded["T"].rows.len(), 5000);
+            (bytes, loaded)
+        };
+
+        let (plain, _) = measure(Codec::None);
+        let (compressed, loaded) = measure(Codec::Lz);
+
+        assert!(compressed.len() * 2 < plain.len());
+        assert!(matches!(
+            loaded["T"].rows[4999].columns.get("name"),
+            Some(SqlValue::Text(name)) if name.starts_with("customer-4999 lives in")
+        ));
+    }
+
+    #[test]
+    fn test_interrupted_save_keeps_previous_file() {
+        let name = "atomic_save_test";
+        let storage = StorageEngine::new(name.to_string());
+        let tables_with_id = |id| {
+            HashMap::from([("T".to_string(), sample_table(&[("id", SqlValue::Integer(id))]))])
+        };
+        let saved = tables_with_id(1);
+        storage.save_tables(&saved, 0).unwrap();
+
+        // A crash after the new contents were written but before the rename
+        let filepath = storage.db_file_path().unwrap();
+        let newer = tables_with_id(2);
+        let (temp_path, _) = storage.write_temp_file(&filepath, &newer, 1, false).unwrap();
+        let (loaded, lsn) = storage.load_tables().unwrap();
+        assert!(matches!(loaded["T"].rows[0].columns.get("id"), Some(SqlValue::Integer(1))));
+        assert_eq!(lsn, 0);
+
+        // The next save replaces the leftover temporary file
+        storage.save_tables(&newer, 1).unwrap();
+        assert!(!temp_path.exists());
+        let (loaded, lsn) = storage.load_tables().unwrap();
+        assert!(matches!(loaded["T"].rows[0].columns.get("id"), Some(SqlValue::Integer(2))));
+        assert_eq!(lsn, 1);
+
+        StorageEngine::remove_database_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_repair_mode_keeps_tables_before_truncation() {
+        let mut storage = StorageEngine::new("repair_on_load_test".to_string());
+        let mut tables = HashMap::new();
+        for name in ["A", "B"] {
+            let mut table = sample_table(&[("id", SqlValue::Integer(1))]);
+            table.name = name.to_string();
+            tables.insert(table.name.clone(), table);
+        }
+
+        let bytes = storage.serialize_tables(&tables, 0).unwrap();
+        // Cut past the five empty trailers and the LSN into table B, as a crash
+        // during the write would
+        let truncated = &bytes[..bytes.len() - 36];
+
+        assert!(storage.deserialize_tables(truncated).is_err());
+
+        storage.repair_on_load = true;
+        let (salvaged, _) = storage.deserialize_tables(truncated).unwrap();
+        assert_eq!(salvaged.len(), 1);
+        assert_eq!(salvaged["A"].rows.len(), 1);
+    }
+}
@@ -109,6 +109,17 @@ pub fn should_forward_request(headers: &HashMap<String, String>) -> bool {
 pub fn forward_request(
     target_url: &str,
     request: &ForwardRequest,
+) -> Result<ForwardResponse, String> {
+    send_request(target_url, request, false)
+}
+
+/// Sends `request` and reads the reply until the peer closes the connection.
+/// With `strict` unset a read timeout ends the reply early; with it set the
+/// timeout, or a reply over the size limit, is an error instead.
+fn send_request(
+    target_url: &str,
+    request: &ForwardRequest,
+    strict: bool,
 ) -> Result<ForwardResponse, String> {
     // Parse the target URL
     let url = if target_url.starts_with("http://") || target_url.starts_with("https://") {
@@ -174,21 +185,91 @@ pub fn forward_request(
         match stream.read(&mut buffer) {
             Ok(0) => break, // Connection closed
             Ok(n) => response_data.extend_from_slice(&buffer[..n]),
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
-            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => break,
+            Err(e)
+                if !strict
+                    && matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+            {
+                break
+            }
             Err(e) => return Err(format!("Failed to read response: {}", e)),
         }
 
         // Prevent infinite reading
         if response_data.len() > 1024 * 1024 {
+            if strict {
+                return Err("Response exceeds 1 MiB".to_string());
+            }
             break;
         }
     }
 
+    if strict && !is_complete_response(&response_data) {
+        return Err("Response was cut off".to_string());
+    }
+
     // Parse HTTP response
     parse_http_response(&response_data)
 }
 
+/// Whether `data` holds the full header block and as much body as
+/// `Content-Length` announces.
+fn is_complete_response(data: &[u8]) -> bool {
+    let Some(header_end) = data.windows(4).position(|window| window == b"\r\n\r\n") else {
+        return false;
+    };
+    let head = String::from_utf8_lossy(&data[..header_end]);
+    let content_length = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse::<usize>().ok())?
+    });
+    data.len() - (header_end + 4) >= content_length.unwrap_or(0)
+}
+
+/// Checks that `url` can be used as `AUTH_WEBHOOK_URL`. Only plain `http://` is
+/// supported: without TLS an `https://` URL would be sent unencrypted to port 80.
+pub fn check_auth_webhook_url(url: &str) -> Result<(), String> {
+    match url.split_once("://") {
+        Some((scheme, _)) if scheme.eq_ignore_ascii_case("http") => Ok(()),
+        None => Ok(()),
+        Some((scheme, _)) => Err(format!(
+            "AUTH_WEBHOOK_URL '{}' uses {}://, which is not supported because credentials \
+             would be sent in plaintext; use an http:// URL on a trusted network or a local \
+             TLS-terminating proxy",
+            url, scheme
+        )),
+    }
+}
+
+/// Asks an external identity provider whether the credentials in `body` (JSON)
+/// are valid. Any 2xx response allows the request; a reply that times out or
+/// is cut off is an error, which rejects it.
+pub fn verify_with_auth_webhook(webhook_url: &str, body: String) -> Result<bool, String> {
+    check_auth_webhook_url(webhook_url)?;
+    let without_scheme = webhook_url.strip_prefix("http://").unwrap_or(webhook_url);
+    let path = match without_scheme.find('/') {
+        Some(pos) => without_scheme[pos..].to_string(),
+        None => "/".to_string(),
+    };
+
+    let mut headers = HashMap::new();
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+
+    let request = ForwardRequest {
+        method: "POST".to_string(),
+        path,
+        headers,
+        body: body.into_bytes(),
+    };
+
+    let response = send_request(webhook_url, &request, true)?;
+    Ok((200..300).contains(&response.status_code))
+}
+
 fn parse_url(url: &str) -> Result<(String, u16), String> {
     let url = url.strip_prefix("http://").unwrap_or(url);
     let url = url.strip_prefix("https://").unwrap_or(url);
@@ -278,3 +359,37 @@ fn parse_status_code(status_line: &str) -> Result<u16, String> {
 
 // Add Read trait import
 use std::io::Read;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_auth_webhook_rejects_https_and_cut_off_replies() {
+        assert!(check_auth_webhook_url("http://auth.internal:8080/verify").is_ok());
+        let https = verify_with_auth_webhook("https://auth.internal/verify", "{}".to_string());
+        assert!(https.unwrap_err().contains("plaintext"));
+
+        let reply = |response: &'static [u8]| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let server = thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                stream.write_all(response).unwrap();
+            });
+            let url = format!("http://127.0.0.1:{}/verify", port);
+            let verdict = verify_with_auth_webhook(&url, "{}".to_string());
+            server.join().unwrap();
+            verdict
+        };
+        assert_eq!(reply(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"), Ok(true));
+        assert_eq!(reply(b"HTTP/1.1 403 Forbidden\r\n\r\n"), Ok(false));
+        // Replies that end before the headers or the announced body are complete
+        assert!(reply(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n").is_err());
+        assert!(reply(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nok").is_err());
+    }
+}
@@ -1,7 +1,13 @@
+use super::configuration::ConfigOptions;
+use super::core_types::{DatabaseError, SqlValue};
+
+/// Strips quoting from an identifier. Surrounding whitespace is never part of a
+/// name, inside the quotes or out: `" name "` and `name` are the same column.
 pub fn normalize_identifier(token: &str) -> String {
     token
         .trim()
         .trim_matches(|ch| matches!(ch, '[' | ']' | '`' | '"' | '\'' | ';'))
+        .trim()
         .to_string()
 }
 
@@ -32,3 +38,140 @@ fn is_quoted_identifier(token: &str) -> bool {
         ('"', '"') | ('`', '`') | ('[', ']') | ('\'', '\'')
     )
 }
+
+/// What to do with control characters (other than tab, CR and LF) in stored text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ControlCharPolicy {
+    #[default]
+    Allow,
+    Reject,
+    Strip,
+}
+
+impl ControlCharPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "allow" | "0" => Some(Self::Allow),
+            "reject" | "1" => Some(Self::Reject),
+            "strip" => Some(Self::Strip),
+            _ => None,
+        }
+    }
+}
+
+/// Checks text payloads before they are stored by INSERT or UPDATE. Unlike the SQL
+/// injection filter this looks at data values, not query structure.
+#[derive(Clone, Debug)]
+pub struct ValueValidator {
+    pub reject_nul_bytes: bool,
+    pub control_chars: ControlCharPolicy,
+    /// Maximum text length in characters; `None` means unlimited.
+    pub max_text_length: Option<usize>,
+}
+
+impl Default for ValueValidator {
+    fn default() -> Self {
+        Self {
+            reject_nul_bytes: true,
+            control_chars: ControlCharPolicy::Allow,
+            max_text_length: None,
+        }
+    }
+}
+
+impl ValueValidator {
+    pub fn from_config(config: &ConfigOptions) -> Self {
+        Self {
+            reject_nul_bytes: config.reject_nul_bytes,
+            control_chars: config.text_control_chars,
+            max_text_length: config.max_text_length,
+        }
+    }
+
+    /// Validates `value` in place; with `ControlCharPolicy::Strip` offending
+    /// characters are removed instead of rejected.
+    pub fn check(&self, column: &str, value: &mut SqlValue) -> Result<(), DatabaseError> {
+        let text = match value {
+            SqlValue::Text(text) => text,
+            _ => return Ok(()),
+        };
+
+        if self.reject_nul_bytes && text.contains('\0') {
+            return Err(DatabaseError::InvalidDataType(format!(
+                "Text value for column '{}' contains a NUL byte",
+                column
+            )));
+        }
+
+        match self.control_chars {
+            ControlCharPolicy::Allow => {}
+            ControlCharPolicy::Reject => {
+                if text.chars().any(is_disallowed_control) {
+                    return Err(DatabaseError::InvalidDataType(format!(
+                        "Text value for column '{}' contains control characters",
+                        column
+                    )));
+                }
+            }
+            ControlCharPolicy::Strip => {
+                if text.chars().any(is_disallowed_control) {
+                    let stripped: String =
+                        text.chars().filter(|&ch| !is_disallowed_control(ch)).collect();
+                    *text = stripped.into();
+                }
+            }
+        }
+
+        if let Some(max_length) = self.max_text_length {
+            let length = text.chars().count();
+            if length > max_length {
+                return Err(DatabaseError::InvalidDataType(format!(
+                    "Text value for column '{}' is {} characters long (limit {})",
+                    column, length, max_length
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_disallowed_control(ch: char) -> bool {
+    ch.is_control() && !matches!(ch, '\t' | '\n' | '\r')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_validator_defaults_reject_only_nul() {
+        let validator = ValueValidator::default();
+
+        let mut value = SqlValue::Text("a\u{0}b".into());
+        assert!(validator.check("name", &mut value).is_err());
+
+        let mut value = SqlValue::Text("bell\u{7}".into());
+        assert!(validator.check("name", &mut value).is_ok());
+    }
+
+    #[test]
+    fn test_value_validator_control_char_policies() {
+        let mut validator = ValueValidator {
+            control_chars: ControlCharPolicy::Strip,
+            max_text_length: Some(5),
+            ..ValueValidator::default()
+        };
+
+        let mut value = SqlValue::Text("a\u{1b}b\tc".into());
+        validator.check("name", &mut value).unwrap();
+        assert!(matches!(value, SqlValue::Text(ref text) if &**text == "ab\tc"));
+
+        let mut value = SqlValue::Text("toolong".into());
+        assert!(validator.check("name", &mut value).is_err());
+
+        validator.control_chars = ControlCharPolicy::Reject;
+        let mut value = SqlValue::Text("a\u{1b}".into());
+        assert!(validator.check("name", &mut value).is_err());
+    }
+}
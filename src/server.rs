@@ -1,24 +1,53 @@
 use super::auth::AuthConfig;
-use super::configuration::ConfigManager;
-use super::core_types::{DatabaseError, Row, SqlValue};
-use super::engine::Database;
-use super::routing::{forward_request, should_forward_request, ForwardRequest, RouteConfig};
-use super::smart_parser::AnySQL;
-use super::two_factor_auth::TwoFactorAuth;
+use super::configuration::{ConfigManager, ConfigOptions};
+use super::core_types::{DatabaseError, Durability, Row, SqlStatement, SqlValue, Table};
+use super::engine::{CacheStats, Database, DatabaseRegistry, QueryPlan};
+use super::gzip::gzip;
+use super::routing::{
+    forward_request, should_forward_request, verify_with_auth_webhook, ForwardRequest,
+    RouteConfig,
+};
+use super::security::normalize_table_name;
+use super::smart_parser::{
+    split_statements, AnySQL, DetectedDialect, DialectExplanation, ParserMetricsSnapshot,
+};
+use super::statistics::TableStatistics;
+use super::two_factor_auth::{
+    TotpAlgorithm, TwoFactorAuth, DEFAULT_TOTP_DIGITS, TOTP_DIGIT_RANGE,
+};
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::net::{Shutdown, TcpListener, TcpStream};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::hash::{BuildHasher, Hasher};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::ops::Deref;
+use std::sync::{Arc, LockResult, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use time::format_description::well_known::Rfc3339;
-use time::OffsetDateTime;
+use time::{OffsetDateTime, UtcOffset};
 
 const CONSOLE_PROXY_ADDR: &str = "127.0.0.1:5173";
 
 const MAX_PORT: u16 = 65535;
 const MAX_REQUEST_SIZE: usize = 64 * 1024;
 const READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a kept-alive connection may wait for its next request.
+const KEEP_ALIVE_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests served on one connection before it is closed.
+const KEEP_ALIVE_MAX_REQUESTS: usize = 100;
+const AUTH_WEBHOOK_CACHE_TTL: Duration = Duration::from_secs(30);
+const ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// How often idle rate-limit buckets are dropped.
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+// A missing console dev server fails every proxied request; log it once per interval
+static CONSOLE_PROXY_ERROR_LOG: ThrottledLog = ThrottledLog::new(ERROR_LOG_INTERVAL);
+static ACCEPT_ERROR_LOG: ThrottledLog = ThrottledLog::new(ERROR_LOG_INTERVAL);
 
 const SUSPICIOUS_PATTERNS: &[(&str, &str)] = &[
     ("' or '1'='1", "'"),
@@ -33,25 +62,22 @@ const SUSPICIOUS_PATTERNS: &[(&str, &str)] = &[
 struct HealthServerState {
     start_time: Instant,
     version: &'static str,
-    last_checkpoint_ms: u128,
 }
 
 impl HealthServerState {
     fn new() -> Self {
-        let start_time = Instant::now();
-        let last_checkpoint_ms = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis();
-
         Self {
-            start_time,
+            start_time: Instant::now(),
             version: env!("CARGO_PKG_VERSION"),
-            last_checkpoint_ms,
         }
     }
 
-    fn health_payload(&self) -> String {
+    /// `lsn` and `last_checkpoint` come from `Database::durability_status`.
+    fn health_payload(&self, lsn: u64, last_checkpoint: SystemTime) -> String {
+        let last_checkpoint_ms = last_checkpoint
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
         let uptime = self.start_time.elapsed().as_millis();
         let mut body = String::from("{");
         body.push_str("\"status\":\"200 OK\"");
@@ -62,39 +88,522 @@ impl HealthServerState {
         body.push_str(&escape_json_string(self.version));
         body.push_str("\"");
         body.push_str(",\"transactions_active\":0");
-        body.push_str(",\"wal_lsn\":\"0/0\"");
+        body.push_str(",\"wal_lsn\":\"0/");
+        body.push_str(&format!("{:X}", lsn));
+        body.push('"');
         body.push_str(",\"last_checkpoint\":");
-        body.push_str(&self.last_checkpoint_ms.to_string());
+        body.push_str(&last_checkpoint_ms.to_string());
         body.push('}');
         body
     }
 }
 
+/// Rate-limited stderr logging for errors that can repeat on every request. At
+/// most one line is written per `interval`; the lines dropped in between are
+/// counted and reported with the next one that gets through.
+struct ThrottledLog {
+    interval: Duration,
+    state: Mutex<(Option<Instant>, u64)>, // last line written, lines suppressed since
+}
+
+impl ThrottledLog {
+    const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            state: Mutex::new((None, 0)),
+        }
+    }
+
+    fn log(&self, message: &str) {
+        if let Some(suppressed) = self.admit(Instant::now()) {
+            if suppressed == 0 {
+                eprintln!("{}", message);
+            } else {
+                eprintln!("{} ({} similar errors suppressed)", message, suppressed);
+            }
+        }
+    }
+
+    /// Whether a line arriving at `now` should be written, with the number of
+    /// lines suppressed before it.
+    fn admit(&self, now: Instant) -> Option<u64> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (last_logged, suppressed) = &mut *state;
+
+        match last_logged {
+            Some(last) if now.duration_since(*last) < self.interval => {
+                *suppressed += 1;
+                None
+            }
+            _ => {
+                *last_logged = Some(now);
+                Some(std::mem::take(suppressed))
+            }
+        }
+    }
+}
+
+/// Upper bounds (exclusive) of the lock-wait histogram buckets; the last bucket is open-ended.
+const LOCK_WAIT_BUCKETS_MS: [u64; 3] = [1, 10, 100];
+
+/// Contention counters for the shared database lock. Only lock acquisition is
+/// timed, so the overhead is a couple of atomic operations per query.
+struct LockMetrics {
+    acquisitions: AtomicU64,
+    total_wait_ns: AtomicU64,
+    max_wait_ns: AtomicU64,
+    waiting: AtomicUsize,
+    wait_buckets: [AtomicU64; LOCK_WAIT_BUCKETS_MS.len() + 1],
+}
+
+impl LockMetrics {
+    fn new() -> Self {
+        Self {
+            acquisitions: AtomicU64::new(0),
+            total_wait_ns: AtomicU64::new(0),
+            max_wait_ns: AtomicU64::new(0),
+            waiting: AtomicUsize::new(0),
+            wait_buckets: Default::default(),
+        }
+    }
+
+    fn record_wait(&self, wait: Duration) {
+        let wait_ns = wait.as_nanos().min(u64::MAX as u128) as u64;
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_ns.fetch_add(wait_ns, Ordering::Relaxed);
+        self.max_wait_ns.fetch_max(wait_ns, Ordering::Relaxed);
+
+        let wait_ms = wait.as_millis();
+        let bucket = LOCK_WAIT_BUCKETS_MS
+            .iter()
+            .position(|&bound| wait_ms < bound as u128)
+            .unwrap_or(LOCK_WAIT_BUCKETS_MS.len());
+        self.wait_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn to_json(&self) -> String {
+        let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+        let total_wait_ns = self.total_wait_ns.load(Ordering::Relaxed);
+        let avg_wait_ms = if acquisitions == 0 {
+            0.0
+        } else {
+            total_wait_ns as f64 / acquisitions as f64 / 1_000_000.0
+        };
+        let max_wait_ms = self.max_wait_ns.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+
+        let mut body = String::from("{");
+        body.push_str("\"acquisitions\":");
+        body.push_str(&acquisitions.to_string());
+        body.push_str(",\"waiting\":");
+        body.push_str(&self.waiting.load(Ordering::Relaxed).to_string());
+        body.push_str(",\"avg_wait_ms\":");
+        body.push_str(&format!("{:.3}", avg_wait_ms));
+        body.push_str(",\"max_wait_ms\":");
+        body.push_str(&format!("{:.3}", max_wait_ms));
+        body.push_str(",\"wait_histogram_ms\":{");
+        for (index, bucket) in self.wait_buckets.iter().enumerate() {
+            if index > 0 {
+                body.push(',');
+            }
+            let label = match LOCK_WAIT_BUCKETS_MS.get(index) {
+                Some(bound) => format!("lt_{}", bound),
+                None => format!("ge_{}", LOCK_WAIT_BUCKETS_MS[LOCK_WAIT_BUCKETS_MS.len() - 1]),
+            };
+            body.push('"');
+            body.push_str(&label);
+            body.push_str("\":");
+            body.push_str(&bucket.load(Ordering::Relaxed).to_string());
+        }
+        body.push_str("}}");
+        body
+    }
+}
+
+/// External auth configured through `AUTH_WEBHOOK_URL`. Approvals are cached
+/// for `AUTH_WEBHOOK_CACHE_TTL`; denials are not cached.
+struct AuthWebhook {
+    url: String,
+    approved: Mutex<HashMap<String, Instant>>, // credentials -> approval expiry
+}
+
+impl AuthWebhook {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            approved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_authorized(&self, token: Option<&str>, email: Option<&str>) -> bool {
+        let cache_key = format!("{}\n{}", token.unwrap_or(""), email.unwrap_or(""));
+        let now = Instant::now();
+
+        if let Ok(mut approved) = self.approved.lock() {
+            approved.retain(|_, expires_at| *expires_at > now);
+            if approved.contains_key(&cache_key) {
+                return true;
+            }
+        }
+
+        let mut body = String::from("{");
+        body.push_str("\"token\":");
+        push_json_string_or_null(&mut body, token);
+        body.push_str(",\"email\":");
+        push_json_string_or_null(&mut body, email);
+        body.push('}');
+
+        match verify_with_auth_webhook(&self.url, body) {
+            Ok(true) => {
+                if let Ok(mut approved) = self.approved.lock() {
+                    approved.insert(cache_key, now + AUTH_WEBHOOK_CACHE_TTL);
+                }
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                eprintln!("[MirseoDB][auth] Auth webhook request failed: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Per-client token buckets set through `MIRSEODB_RATE_LIMIT_RPS` (requests per
+/// second) and `MIRSEODB_RATE_LIMIT_BURST` (default: one second's worth). Clients
+/// are told apart by IP address, since each connection has its own port.
+struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    state: Mutex<(HashMap<IpAddr, TokenBucket>, Instant)>, // buckets, last pruned
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1.0),
+            state: Mutex::new((HashMap::new(), Instant::now())),
+        }
+    }
+
+    /// `None` when `MIRSEODB_RATE_LIMIT_RPS` is unset or not a positive number.
+    fn from_env() -> Option<Self> {
+        let rate = env::var("MIRSEODB_RATE_LIMIT_RPS").ok()?;
+        let rate = match rate.trim().parse::<f64>() {
+            Ok(rate) if rate.is_finite() && rate > 0.0 => rate,
+            _ => {
+                eprintln!(
+                    "[MirseoDB] Ignoring MIRSEODB_RATE_LIMIT_RPS='{}': expected a positive number",
+                    rate
+                );
+                return None;
+            }
+        };
+        let burst = match env::var("MIRSEODB_RATE_LIMIT_BURST") {
+            Ok(burst) => match burst.trim().parse::<u32>() {
+                Ok(burst) if burst > 0 => f64::from(burst),
+                _ => {
+                    eprintln!(
+                        "[MirseoDB] Ignoring MIRSEODB_RATE_LIMIT_BURST='{}': expected a positive \
+                         integer",
+                        burst
+                    );
+                    rate.ceil()
+                }
+            },
+            Err(_) => rate.ceil(),
+        };
+
+        println!(
+            "[MirseoDB] Rate limit: {} requests per second per client, burst {}",
+            rate, burst
+        );
+        Some(Self::new(rate, burst))
+    }
+
+    /// Takes a token for `client`, or returns how long until one is available.
+    fn acquire(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (buckets, last_pruned) = &mut *state;
+
+        if now.duration_since(*last_pruned) >= RATE_LIMIT_PRUNE_INTERVAL {
+            // A bucket that has refilled completely is the same as a new one
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst
+            });
+            *last_pruned = now;
+        }
+
+        let bucket = buckets.entry(client).or_insert(TokenBucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+/// The 429 for a client over the rate limit; `None` when the request may proceed.
+/// Only API paths are limited, and health checks never are; the console's
+/// pages and assets are left to it.
+fn rate_limit_response(
+    state: &ApiServerState,
+    peer_addr: Option<SocketAddr>,
+    route_path: &str,
+) -> Option<HttpResponse> {
+    let limiter = state.rate_limiter.as_ref()?;
+    if !is_api_path(route_path) || matches!(route_path, "/health" | "/api/health") {
+        return None;
+    }
+    let wait = limiter.acquire(peer_addr?.ip(), Instant::now()).err()?;
+
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    let mut body = String::from("{\"error\":\"Too many requests\",\"retry_after_secs\":");
+    body.push_str(&retry_after.to_string());
+    body.push('}');
+    Some(
+        HttpResponse::json("429 Too Many Requests", body)
+            .with_header("Retry-After", retry_after.to_string()),
+    )
+}
+
+fn push_json_string_or_null(body: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            body.push('"');
+            body.push_str(&escape_json_string(value));
+            body.push('"');
+        }
+        None => body.push_str("null"),
+    }
+}
+
 struct ApiServerState {
     health: HealthServerState,
-    database: Arc<Mutex<Database>>,
+    database: Arc<RwLock<Database>>, // the default one, which the other endpoints work on
+    databases: Arc<DatabaseRegistry>,
     parser: Arc<AnySQL>,
     route_config: Arc<RouteConfig>,
     auth_token: Option<String>,
+    cors_origin: String,
+    config: RwLock<Arc<ConfigOptions>>, // read at startup and by POST /config/reload
+    auth_config: RwLock<Arc<AuthConfig>>, // also replaced by setup, GRANT and REVOKE
+    auth_webhook: Option<AuthWebhook>,
+    rate_limiter: Option<RateLimiter>,
     two_factor_auth: Arc<Mutex<TwoFactorAuth>>,
+    lock_metrics: LockMetrics,
 }
 
 impl ApiServerState {
     fn new(
-        database: Arc<Mutex<Database>>,
+        databases: Arc<DatabaseRegistry>,
         parser: Arc<AnySQL>,
         route_config: Arc<RouteConfig>,
         auth_token: Option<String>,
+        cors_origin: String,
+        two_factor_auth: TwoFactorAuth,
     ) -> Self {
-        let two_factor_auth = TwoFactorAuth::load().unwrap_or_else(|_| TwoFactorAuth::new());
+        let config = ConfigManager::load();
+        let auth_webhook = config.auth_webhook_url.clone().map(|url| {
+            println!("[MirseoDB] API authentication delegated to auth webhook {}", url);
+            AuthWebhook::new(url)
+        });
+        let auth_config = AuthConfig::load().unwrap_or_else(|e| {
+            eprintln!("[MirseoDB] {}; starting with setup not completed", e);
+            AuthConfig::default()
+        });
 
         Self {
             health: HealthServerState::new(),
-            database,
+            database: databases.default_database(),
+            databases,
             parser,
             route_config,
             auth_token,
+            cors_origin,
+            auth_webhook,
+            rate_limiter: RateLimiter::from_env(),
             two_factor_auth: Arc::new(Mutex::new(two_factor_auth)),
+            lock_metrics: LockMetrics::new(),
+            config: RwLock::new(Arc::new(config)),
+            auth_config: RwLock::new(Arc::new(auth_config)),
+        }
+    }
+
+    /// The configuration as last read, so requests do not each re-read the file.
+    fn config(&self) -> Arc<ConfigOptions> {
+        Arc::clone(&self.config.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// The users and permissions as last read, which queries check against.
+    fn auth_config(&self) -> Arc<AuthConfig> {
+        Arc::clone(&self.auth_config.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn set_auth_config(&self, auth_config: AuthConfig) -> Arc<AuthConfig> {
+        let auth_config = Arc::new(auth_config);
+        let mut cached = self.auth_config.write().unwrap_or_else(PoisonError::into_inner);
+        *cached = Arc::clone(&auth_config);
+        auth_config
+    }
+
+    /// Re-reads `.mirseoDB/auth_config.json` after something changed it on disk.
+    fn reload_auth_config(&self) -> Result<Arc<AuthConfig>, String> {
+        AuthConfig::load().map(|auth_config| self.set_auth_config(auth_config))
+    }
+
+    /// Checks the presented credentials against the auth webhook when one is
+    /// configured, otherwise against the built-in API token (if any).
+    fn is_authorized(&self, token: Option<&str>, email: Option<&str>) -> bool {
+        if let Some(webhook) = &self.auth_webhook {
+            return webhook.is_authorized(token, email);
+        }
+
+        match &self.auth_token {
+            Some(expected) => token == Some(expected.as_str()),
+            None => true,
+        }
+    }
+
+    /// Acquires the database lock exclusively while recording how long the
+    /// caller waited for it.
+    fn lock_database(&self) -> LockResult<RwLockWriteGuard<'_, Database>> {
+        self.timed_lock(|| self.database.write())
+    }
+
+    /// Shares the database lock with other readers, recording the wait like
+    /// `lock_database`.
+    fn read_database(&self) -> LockResult<RwLockReadGuard<'_, Database>> {
+        self.timed_lock(|| self.database.read())
+    }
+
+    /// The database `statement` runs against: the one USE names, else the
+    /// request's or connection's database, else the default one.
+    fn target_database(
+        &self,
+        statement: &SqlStatement,
+        database: Option<&str>,
+    ) -> Result<Arc<RwLock<Database>>, DatabaseError> {
+        match statement {
+            SqlStatement::Use { database_name } => self.databases.get(database_name),
+            // An open database may not have a file yet, which the engine checks
+            SqlStatement::CreateDatabase { database_name }
+                if self.databases.is_open(database_name) =>
+            {
+                Err(DatabaseError::DatabaseAlreadyExists(database_name.clone()))
+            }
+            _ => self.databases.get(database.unwrap_or(self.databases.default_name())),
+        }
+    }
+
+    /// The lock `statement` runs under. SELECTs share it unless a transaction is
+    /// open: statements then reset its idle timer, and the session check may roll
+    /// it back, so they run exclusively like every other statement.
+    fn lock_database_for<'a>(
+        &self,
+        database: &'a RwLock<Database>,
+        statement: &SqlStatement,
+    ) -> LockResult<DatabaseGuard<'a>> {
+        if statement.is_read_only() {
+            let db = self
+                .timed_lock(|| database.read())
+                .map_err(|poisoned| PoisonError::new(DatabaseGuard::Read(poisoned.into_inner())))?;
+            if db.open_transaction().is_none() {
+                return Ok(DatabaseGuard::Read(db));
+            }
+        }
+        self.timed_lock(|| database.write())
+            .map(DatabaseGuard::Write)
+            .map_err(|poisoned| PoisonError::new(DatabaseGuard::Write(poisoned.into_inner())))
+    }
+
+    /// Applies what a successful statement changed about the databases: CREATE
+    /// and DROP DATABASE add or remove registry entries, USE switches the
+    /// connection's database, and GRANT and REVOKE reload the permissions.
+    fn finish_database_change(
+        &self,
+        change: DatabaseChange,
+        connection_database: &mut Option<String>,
+    ) {
+        match change {
+            DatabaseChange::None => {}
+            DatabaseChange::Refresh(name) => {
+                if let Err(e) = self.databases.refresh(&name) {
+                    eprintln!("[MirseoDB] Failed to reload database '{}': {:?}", name, e);
+                }
+            }
+            DatabaseChange::Use(name) => *connection_database = Some(name),
+            DatabaseChange::Permissions => {
+                if let Err(e) = self.reload_auth_config() {
+                    eprintln!("[MirseoDB] Failed to reload permissions: {}", e);
+                }
+            }
+        }
+    }
+
+    fn timed_lock<G>(&self, acquire: impl FnOnce() -> G) -> G {
+        self.lock_metrics.waiting.fetch_add(1, Ordering::Relaxed);
+        let wait_start = Instant::now();
+        let guard = acquire();
+        self.lock_metrics.record_wait(wait_start.elapsed());
+        self.lock_metrics.waiting.fetch_sub(1, Ordering::Relaxed);
+        guard
+    }
+}
+
+/// What a statement changes about the databases a connection sees, taken
+/// before the statement is executed.
+enum DatabaseChange {
+    None,
+    Refresh(String),
+    Use(String),
+    Permissions,
+}
+
+impl DatabaseChange {
+    fn of(statement: &SqlStatement) -> Self {
+        match statement {
+            SqlStatement::CreateDatabase { database_name }
+            | SqlStatement::DropDatabase { database_name } => {
+                DatabaseChange::Refresh(database_name.clone())
+            }
+            SqlStatement::Use { database_name } => DatabaseChange::Use(database_name.clone()),
+            SqlStatement::Grant { .. } | SqlStatement::Revoke { .. } => {
+                DatabaseChange::Permissions
+            }
+            _ => DatabaseChange::None,
+        }
+    }
+}
+
+/// The database lock held for one statement: shared for reads, exclusive for
+/// everything else.
+enum DatabaseGuard<'a> {
+    Read(RwLockReadGuard<'a, Database>),
+    Write(RwLockWriteGuard<'a, Database>),
+}
+
+impl Deref for DatabaseGuard<'_> {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        match self {
+            DatabaseGuard::Read(db) => db,
+            DatabaseGuard::Write(db) => db,
         }
     }
 }
@@ -104,47 +613,180 @@ struct QueryRequest {
     auth_token: Option<String>,
     totp_token: Option<String>, // 2차 인증 토큰
     email: Option<String>,      // 사용자 이메일
+    include_schema: bool,       // ?include_schema=1: add column types to SELECT results
+    with_count: bool,           // ?with_count=1: add the unpaged match count to SELECT results
+    dialect: Option<String>,    // "dialect" field or ?dialect=: skip dialect detection
+    debug: bool,                // ?debug=1: add connection details to the response
+    transaction_id: Option<String>, // returned by BEGIN; runs the query inside that transaction
+    params: Vec<SqlValue>,      // "params": values for the ? placeholders in `sql`
+    database: Option<String>,   // "database" field or ?database=: overrides the connection's
+    statements: Vec<String>,    // "statements" or a `;`-separated `sql`: run as a batch
+    continue_on_error: bool,    // keep running a batch after a statement fails
+}
+
+/// Where a query came from, for the audit log and `?debug=1` responses.
+struct ConnectionInfo {
+    peer_addr: Option<SocketAddr>,
+    email: Option<String>,
+    dialect: Option<DetectedDialect>,
+    forwarded: bool,
+    /// Time spent waiting for the database lock, then executing under it; the
+    /// wall-clock `execution_time_ms` includes both.
+    timing: Option<(Duration, Duration)>,
+}
+
+impl ConnectionInfo {
+    fn new(peer_addr: Option<SocketAddr>, email: Option<String>, forwarded: bool) -> Self {
+        Self {
+            peer_addr,
+            email,
+            dialect: None,
+            forwarded,
+            timing: None,
+        }
+    }
+
+    /// Adds one statement's timing; a batch reports the sum over its statements.
+    fn record_timing(&mut self, lock_wait: Duration, execution: Duration) {
+        let (total_wait, total_execution) = self.timing.get_or_insert_default();
+        *total_wait += lock_wait;
+        *total_execution += execution;
+    }
+
+    fn log_audit(&self, operation: &str, outcome: &str) {
+        println!(
+            "[MirseoDB][audit] {} {} peer={} email={} dialect={} forwarded={}",
+            operation,
+            outcome,
+            self.peer_addr
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            self.email.as_deref().unwrap_or("-"),
+            self.dialect.as_ref().map(|d| d.name()).unwrap_or("-"),
+            self.forwarded
+        );
+    }
+
+    fn insert_debug(&self, body: &mut String) {
+        let mut debug = String::from(",\"debug\":{\"peer_addr\":");
+        let peer_addr = self.peer_addr.map(|addr| addr.to_string());
+        push_json_string_or_null(&mut debug, peer_addr.as_deref());
+        debug.push_str(",\"email\":");
+        push_json_string_or_null(&mut debug, self.email.as_deref());
+        debug.push_str(",\"dialect\":");
+        push_json_string_or_null(&mut debug, self.dialect.as_ref().map(|d| d.name()));
+        debug.push_str(",\"forwarded\":");
+        debug.push_str(if self.forwarded { "true" } else { "false" });
+        if let Some((lock_wait, execution)) = self.timing {
+            debug.push_str(",\"lock_wait_ms\":");
+            debug.push_str(&format!("{:.3}", lock_wait.as_secs_f64() * 1000.0));
+            debug.push_str(",\"execute_ms\":");
+            debug.push_str(&format!("{:.3}", execution.as_secs_f64() * 1000.0));
+        }
+        debug.push('}');
+
+        if let Some(pos) = body.rfind('}') {
+            body.insert_str(pos, &debug);
+        }
+    }
+}
+
+/// Running HTTP listener. Dropping the handle leaves the server running;
+/// call `shutdown` to stop accepting connections.
+pub struct HttpServerHandle {
+    local_addr: SocketAddr,
+    stop_requested: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServerHandle {
+    pub fn port(&self) -> u16 {
+        self.local_addr.port()
+    }
+
+    /// Address the listener is bound to, with the port it settled on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops the accept loop and waits for it to exit. Requests already being
+    /// handled finish on their own threads.
+    pub fn shutdown(mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        // The accept loop is blocked in `incoming()`; a throwaway connection wakes it up
+        let mut wake_addr = self.local_addr;
+        if wake_addr.ip().is_unspecified() {
+            wake_addr.set_ip(match wake_addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect(wake_addr);
+
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+    }
 }
 
 pub fn start_health_server(
+    bind_addr: IpAddr,
     start_port: u16,
-    database: Arc<Mutex<Database>>,
+    databases: Arc<DatabaseRegistry>,
     parser: Arc<AnySQL>,
     route_config: Arc<RouteConfig>,
     auth_token: Option<String>,
-) -> std::io::Result<u16> {
-    let listener = bind_available_port(start_port)?;
-    let port = listener.local_addr()?.port();
+    cors_origin: String,
+) -> std::io::Result<HttpServerHandle> {
+    // Starting without the stored secrets would let the next save erase them
+    let two_factor_auth = TwoFactorAuth::load()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let listener = bind_available_port(bind_addr, start_port)?;
+    let local_addr = listener.local_addr()?;
     let state = Arc::new(ApiServerState::new(
-        database,
+        databases,
         parser,
         route_config,
         auth_token,
+        cors_origin,
+        two_factor_auth,
     ));
+    let stop_requested = Arc::new(AtomicBool::new(false));
 
-    thread::spawn({
+    let accept_thread = thread::spawn({
         let state = Arc::clone(&state);
+        let stop_requested = Arc::clone(&stop_requested);
         move || {
             for stream in listener.incoming() {
+                if stop_requested.load(Ordering::SeqCst) {
+                    break;
+                }
+
                 match stream {
                     Ok(stream) => {
                         let state = Arc::clone(&state);
                         thread::spawn(move || handle_client(stream, state));
                     }
-                    Err(e) => eprintln!("[MirseoDB][api] Connection error: {}", e),
+                    Err(e) => {
+                        ACCEPT_ERROR_LOG.log(&format!("[MirseoDB][api] Connection error: {}", e))
+                    }
                 }
             }
         }
     });
 
-    Ok(port)
+    Ok(HttpServerHandle {
+        local_addr,
+        stop_requested,
+        accept_thread: Some(accept_thread),
+    })
 }
 
-fn bind_available_port(start_port: u16) -> std::io::Result<TcpListener> {
+fn bind_available_port(bind_addr: IpAddr, start_port: u16) -> std::io::Result<TcpListener> {
     let mut port = start_port;
 
     loop {
-        match TcpListener::bind(("127.0.0.1", port)) {
+        match TcpListener::bind((bind_addr, port)) {
             Ok(listener) => return Ok(listener),
             Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
                 if port == MAX_PORT {
@@ -161,28 +803,83 @@ fn bind_available_port(start_port: u16) -> std::io::Result<TcpListener> {
 }
 
 fn handle_client(mut stream: TcpStream, state: Arc<ApiServerState>) {
-    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let peer_addr = stream.peer_addr().ok();
+    // Bytes read past the end of the previous request, when requests are pipelined
+    let mut pending = Vec::new();
+    // Set by USE, for the later queries on this connection
+    let mut connection_database = None;
+
+    for served in 1..=KEEP_ALIVE_MAX_REQUESTS {
+        let idle_timeout = if served == 1 { READ_TIMEOUT } else { KEEP_ALIVE_IDLE_TIMEOUT };
+        let request_bytes = match read_full_request(&mut stream, &mut pending, idle_timeout) {
+            Ok(bytes) => bytes,
+            Err(RequestReadError::TooLarge) => {
+                let response = HttpResponse::text("413 Payload Too Large", "Request too large");
+                let _ = write_http_response(&mut stream, &response);
+                return;
+            }
+            Err(RequestReadError::InvalidFraming(reason)) => {
+                let response = HttpResponse::text("400 Bad Request", &reason);
+                let _ = write_http_response(&mut stream, &response);
+                return;
+            }
+            Err(RequestReadError::Io(e)) => {
+                eprintln!("[MirseoDB][api] Failed to read request: {}", e);
+                let response = HttpResponse::text("400 Bad Request", "Malformed request");
+                let _ = write_http_response(&mut stream, &response);
+                return;
+            }
+        };
 
-    let request_bytes = match read_full_request(&mut stream) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            eprintln!("[MirseoDB][api] Failed to read request: {}", e);
-            let response = HttpResponse::text("400 Bad Request", "Malformed request");
-            let _ = write_http_response(&mut stream, &response);
+        if request_bytes.is_empty() {
             return;
         }
-    };
 
-    if request_bytes.is_empty() {
-        return;
+        let may_keep_alive = served < KEEP_ALIVE_MAX_REQUESTS;
+        let step = handle_request(
+            &mut stream,
+            &state,
+            &request_bytes,
+            peer_addr,
+            may_keep_alive,
+            &mut connection_database,
+        );
+        match step {
+            NextStep::KeepAlive => {}
+            NextStep::Close => return,
+            NextStep::ProxyToConsole => {
+                // Pipelined requests already read belong to the console too
+                let mut request_bytes = request_bytes;
+                request_bytes.append(&mut pending);
+                proxy_to_console(stream, request_bytes);
+                return;
+            }
+        }
     }
+}
+
+/// What `handle_client` does with the connection after a request.
+enum NextStep {
+    KeepAlive,
+    Close,
+    /// Not an API path; the console proxy takes over the connection.
+    ProxyToConsole,
+}
 
-    let (header_text, body_bytes) = match split_request(&request_bytes) {
+fn handle_request(
+    stream: &mut TcpStream,
+    state: &Arc<ApiServerState>,
+    request_bytes: &[u8],
+    peer_addr: Option<SocketAddr>,
+    may_keep_alive: bool,
+    connection_database: &mut Option<String>,
+) -> NextStep {
+    let (header_text, body_bytes) = match split_request(request_bytes) {
         Some(parts) => parts,
         None => {
             let response = HttpResponse::text("400 Bad Request", "Invalid HTTP request");
-            let _ = write_http_response(&mut stream, &response);
-            return;
+            let _ = write_http_response(stream, &response);
+            return NextStep::Close;
         }
     };
 
@@ -191,25 +888,107 @@ fn handle_client(mut stream: TcpStream, state: Arc<ApiServerState>) {
     let mut request_parts = request_line.split_whitespace();
     let method = request_parts.next().unwrap_or("");
     let path = request_parts.next().unwrap_or("");
-    let headers = parse_headers(lines);
+    let version = request_parts.next().unwrap_or("");
+    let headers = match parse_headers(lines) {
+        Ok(headers) => headers,
+        Err(reason) => {
+            let response = HttpResponse::text("400 Bad Request", &reason);
+            let _ = write_http_response(stream, &response);
+            return NextStep::Close;
+        }
+    };
+    let (route_path, query_string) = match path.split_once('?') {
+        Some((route, query)) => (route, Some(query)),
+        None => (path, None),
+    };
+
+    // HEAD is served by the GET handlers; only the body is dropped when writing.
+    let is_head = method == "HEAD";
+    let method = if is_head { "GET" } else { method };
+
+    let keep_alive = may_keep_alive && wants_keep_alive(version, &headers);
+    let next_step = if keep_alive { NextStep::KeepAlive } else { NextStep::Close };
+
+    if method == "OPTIONS" {
+        let response = preflight_response(&state.cors_origin);
+        return match write_http_message(stream, &response, true, false, keep_alive) {
+            Ok(()) => next_step,
+            Err(_) => NextStep::Close,
+        };
+    }
+
+    if let Some(response) = rate_limit_response(state, peer_addr, route_path) {
+        let response =
+            response.with_header("Access-Control-Allow-Origin", state.cors_origin.clone());
+        return match write_http_message(stream, &response, !is_head, false, keep_alive) {
+            Ok(()) => next_step,
+            Err(_) => NextStep::Close,
+        };
+    }
 
-    let response = match (method, path) {
+    let response = match (method, route_path) {
         ("GET", "/health") | ("GET", "/heatlh") | ("GET", "/api/health") => {
-            Some(HttpResponse::json("200 OK", state.health.health_payload()))
-        }
-        ("GET", "/time") => Some(handle_time_request()),
-        ("GET", "/setup/status") => Some(handle_setup_status()),
-        ("POST", "/setup/init") => Some(handle_setup_init(&state, &headers, body_bytes)),
-        ("POST", "/setup/complete") => Some(handle_setup_complete(&state, &headers, body_bytes)),
-        ("POST", "/2fa/setup") => Some(handle_2fa_setup(&state, &headers, body_bytes)),
-        ("GET", "/2fa/qr") => Some(handle_2fa_qr(&state, &headers)),
-        ("POST", "/2fa/verify") => Some(handle_2fa_verify(&state, &headers, body_bytes)),
+            // Like /metrics, read without `lock_database` so health checks stay out of them
+            let (lsn, last_checkpoint) = match state.database.read() {
+                Ok(db) => db.durability_status(),
+                Err(poisoned) => poisoned.into_inner().durability_status(),
+            };
+            Some(HttpResponse::json(
+                "200 OK",
+                state.health.health_payload(lsn, last_checkpoint),
+            ))
+        }
+        ("GET", "/time") => Some(handle_time_request(query_string)),
+        ("GET", "/version") => Some(handle_version_request()),
+        ("GET", "/metrics") => Some(handle_metrics_request(state, &headers, false)),
+        ("POST", "/metrics/reset") => Some(handle_metrics_request(state, &headers, true)),
+        ("GET", "/explain") => Some(handle_explain_request(state, &headers, query_string)),
+        ("GET", "/explain/dialect") => {
+            Some(handle_explain_dialect_request(state, &headers, query_string))
+        }
+        ("GET", "/stats/columns") => {
+            Some(handle_column_stats_request(state, &headers, query_string))
+        }
+        ("GET", "/export/ddl") => Some(handle_export_ddl_request(state, &headers, query_string)),
+        ("POST", "/export/file") => {
+            Some(handle_export_file_request(
+                state,
+                &headers,
+                query_string,
+                connection_database.as_deref(),
+            ))
+        }
+        ("POST", "/checkpoint") => Some(handle_checkpoint_request(state, &headers)),
+        ("POST", "/config/reload") => {
+            Some(handle_config_reload_request(state, &headers, body_bytes))
+        }
+        ("GET", "/schema") => Some(handle_schema_request(state, &headers, query_string)),
+        ("POST", "/schema/diff") => Some(handle_schema_diff_request(state, &headers, body_bytes)),
+        ("GET", "/setup/status") => Some(handle_setup_status(state)),
+        ("POST", "/setup/init") => Some(handle_setup_init(state, &headers, body_bytes)),
+        ("POST", "/setup/complete") => Some(handle_setup_complete(state, &headers, body_bytes)),
+        ("POST", "/2fa/setup") => Some(handle_2fa_setup(state, &headers, body_bytes)),
+        ("GET", "/2fa/qr") => Some(handle_2fa_qr(state, &headers)),
+        ("POST", "/2fa/verify") => Some(handle_2fa_verify(state, &headers, body_bytes)),
         _ => {
             // Check if this is a query endpoint (any path ending with /query or containing /query)
             if path == "/query" || path == "/api/query" || path.ends_with("/query") || path.contains("/query") {
                 match method {
-                    "GET" => Some(handle_get_query_request(&state, &headers, path)),
-                    "POST" | "PUT" | "DELETE" | "PATCH" => Some(handle_query_request(&state, &headers, body_bytes)),
+                    "GET" => Some(handle_get_query_request(
+                        state,
+                        &headers,
+                        path,
+                        peer_addr,
+                        connection_database,
+                    )),
+                    "POST" | "PUT" | "DELETE" | "PATCH" => Some(handle_query_request(
+                        state,
+                        &headers,
+                        body_bytes,
+                        query_string,
+                        peer_addr,
+                        connection_database,
+                    )),
                     _ => None,
                 }
             } else {
@@ -217,23 +996,105 @@ fn handle_client(mut stream: TcpStream, state: Arc<ApiServerState>) {
             }
         }
     };
+    let response = response.or_else(|| unknown_api_path_response(method, route_path));
 
-    if let Some(response) = response {
-        let _ = write_http_response(&mut stream, &response);
-        return;
+    if let Some(mut response) = response {
+        if response.content_type == "application/json" {
+            let origin = state.cors_origin.clone();
+            response = response.with_header("Access-Control-Allow-Origin", origin);
+        }
+        let compressible = state
+            .config()
+            .gzip_min_bytes
+            .is_some_and(|min_bytes| response.body.len() >= min_bytes);
+        if compressible {
+            response = response.with_header("Vary", "Accept-Encoding".to_string());
+        }
+        let compress = compressible && accepts_gzip(&headers);
+        return match write_http_message(stream, &response, !is_head, compress, keep_alive) {
+            Ok(()) => next_step,
+            Err(_) => NextStep::Close,
+        };
+    }
+
+    NextStep::ProxyToConsole
+}
+
+/// HTTP/1.1 connections stay open unless the client sends `Connection: close`;
+/// older versions only with `Connection: keep-alive`.
+fn wants_keep_alive(version: &str, headers: &HashMap<String, String>) -> bool {
+    let connection = find_header(headers, "connection").unwrap_or("");
+    let has_option = |option: &str| {
+        connection
+            .split(',')
+            .any(|value| value.trim().eq_ignore_ascii_case(option))
+    };
+
+    if version == "HTTP/1.1" {
+        !has_option("close")
+    } else {
+        has_option("keep-alive")
+    }
+}
+
+const CORS_ALLOWED_METHODS: &str = "GET, HEAD, POST, PUT, DELETE, PATCH, OPTIONS";
+const CORS_ALLOWED_HEADERS: &str =
+    "Content-Type, Authorization, If-None-Match, X-Memory-Limit-MB";
+
+/// Answers a CORS preflight for any path, so browsers may send API requests
+/// from `origin`.
+fn preflight_response(origin: &str) -> HttpResponse {
+    HttpResponse::text("204 No Content", "")
+        .with_header("Access-Control-Allow-Origin", origin.to_string())
+        .with_header("Access-Control-Allow-Methods", CORS_ALLOWED_METHODS.to_string())
+        .with_header("Access-Control-Allow-Headers", CORS_ALLOWED_HEADERS.to_string())
+        .with_header("Access-Control-Max-Age", "86400".to_string())
+}
+
+/// Path prefixes served by this API. Unmatched requests under them get a 404
+/// instead of being proxied to the console, which would answer 502 when the
+/// console is not running.
+const API_PATH_PREFIXES: &[&str] = &[
+    "/api", "/query", "/health", "/metrics", "/time", "/explain", "/stats", "/export",
+    "/checkpoint", "/schema", "/setup", "/2fa", "/config", "/version",
+];
+
+fn is_api_path(route_path: &str) -> bool {
+    API_PATH_PREFIXES.iter().any(|prefix| {
+        route_path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// A JSON 404 naming the attempted method and path, for requests in an API
+/// namespace that no handler matched; `None` for paths the console serves.
+fn unknown_api_path_response(method: &str, route_path: &str) -> Option<HttpResponse> {
+    if !is_api_path(route_path) {
+        return None;
     }
 
-    proxy_to_console(stream, request_bytes);
+    let mut body = String::from("{\"error\":\"");
+    body.push_str(&escape_json_string(&format!(
+        "No API endpoint for {} {}",
+        method, route_path
+    )));
+    body.push_str("\",\"method\":\"");
+    body.push_str(&escape_json_string(method));
+    body.push_str("\",\"path\":\"");
+    body.push_str(&escape_json_string(route_path));
+    body.push_str("\"}");
+    Some(HttpResponse::json("404 Not Found", body))
 }
 
 fn proxy_to_console(mut client_stream: TcpStream, request_bytes: Vec<u8>) {
     match TcpStream::connect(CONSOLE_PROXY_ADDR) {
         Ok(mut console_stream) => {
             if let Err(err) = console_stream.write_all(&request_bytes) {
-                eprintln!(
+                CONSOLE_PROXY_ERROR_LOG.log(&format!(
                     "[MirseoDB][console-proxy] Failed to write request to console server: {}",
                     err
-                );
+                ));
                 let response = HttpResponse::text(
                     "502 Bad Gateway",
                     "Console dev server unavailable (could not write request)",
@@ -243,10 +1104,10 @@ fn proxy_to_console(mut client_stream: TcpStream, request_bytes: Vec<u8>) {
             }
 
             if let Err(err) = console_stream.flush() {
-                eprintln!(
+                CONSOLE_PROXY_ERROR_LOG.log(&format!(
                     "[MirseoDB][console-proxy] Failed to flush request to console server: {}",
                     err
-                );
+                ));
                 let response = HttpResponse::text(
                     "502 Bad Gateway",
                     "Console dev server unavailable (flush failed)",
@@ -258,10 +1119,10 @@ fn proxy_to_console(mut client_stream: TcpStream, request_bytes: Vec<u8>) {
             let mut console_reader = match console_stream.try_clone() {
                 Ok(stream) => stream,
                 Err(err) => {
-                    eprintln!(
+                    CONSOLE_PROXY_ERROR_LOG.log(&format!(
                         "[MirseoDB][console-proxy] Failed to clone console stream: {}",
                         err
-                    );
+                    ));
                     let response = HttpResponse::text(
                         "502 Bad Gateway",
                         "Console dev server unavailable (clone failed)",
@@ -274,10 +1135,10 @@ fn proxy_to_console(mut client_stream: TcpStream, request_bytes: Vec<u8>) {
             let mut client_writer = match client_stream.try_clone() {
                 Ok(stream) => stream,
                 Err(err) => {
-                    eprintln!(
+                    CONSOLE_PROXY_ERROR_LOG.log(&format!(
                         "[MirseoDB][console-proxy] Failed to clone client stream: {}",
                         err
-                    );
+                    ));
                     let response = HttpResponse::text(
                         "502 Bad Gateway",
                         "Console dev server unavailable (clone failed)",
@@ -330,10 +1191,10 @@ fn proxy_to_console(mut client_stream: TcpStream, request_bytes: Vec<u8>) {
             let _ = console_to_client.join();
         }
         Err(err) => {
-            eprintln!(
+            CONSOLE_PROXY_ERROR_LOG.log(&format!(
                 "[MirseoDB][console-proxy] Failed to connect to console server at {}: {}",
                 CONSOLE_PROXY_ADDR, err
-            );
+            ));
             let response = HttpResponse::text(
                 "502 Bad Gateway",
                 "Console dev server unavailable (connection failed)",
@@ -363,13 +1224,24 @@ fn handle_query_request(
     state: &Arc<ApiServerState>,
     headers: &HashMap<String, String>,
     body: &[u8],
+    query_string: Option<&str>,
+    peer_addr: Option<SocketAddr>,
+    connection_database: &mut Option<String>,
 ) -> HttpResponse {
     let start_time = Instant::now();
 
     // Check if this is a forwarded request that should be ignored
     if should_forward_request(headers) {
         // This is a forwarded request, process normally but add forward mode indicator
-        return handle_forwarded_query_request(state, headers, body, start_time);
+        return handle_forwarded_query_request(
+            state,
+            headers,
+            body,
+            query_string,
+            peer_addr,
+            start_time,
+            connection_database,
+        );
     }
 
     if body.is_empty() {
@@ -400,7 +1272,7 @@ fn handle_query_request(
         .map(|ct| ct.contains("application/sql"))
         .unwrap_or(false);
 
-    let request = match parse_query_payload(body, allow_raw_sql) {
+    let mut request = match parse_query_payload(body, allow_raw_sql) {
         Ok(req) => req,
         Err(message) => {
             return HttpResponse::json(
@@ -409,18 +1281,43 @@ fn handle_query_request(
             );
         }
     };
+    if let Some(query_string) = query_string {
+        let mut params = parse_url_query_params(query_string);
+        request.include_schema = query_flag(&params, "include_schema");
+        request.with_count = query_flag(&params, "with_count");
+        request.debug = query_flag(&params, "debug");
+        request.continue_on_error |= query_flag(&params, "continue_on_error");
+        if let Some(dialect) = params.remove("dialect") {
+            request.dialect = Some(dialect);
+        }
+        if let Some(transaction_id) = params.remove("transaction_id") {
+            request.transaction_id = Some(transaction_id);
+        }
+        if let Some(database) = params.remove("database") {
+            request.database = Some(database);
+        }
+    }
 
     let QueryRequest {
         sql: mut sql_text,
         auth_token: request_token,
         totp_token: request_totp,
         email: request_email,
+        include_schema,
+        with_count,
+        dialect: requested_dialect,
+        debug,
+        transaction_id,
+        params,
+        database: request_database,
+        statements,
+        continue_on_error,
     } = request;
 
     let provided_token = extract_auth_token(headers, request_token.clone());
 
     let mut sanitized_applied = false;
-    let config = ConfigManager::load();
+    let config = state.config();
     if config.sql_injection_protect {
         if let Some(filtered) = sanitize_sql_input(&sql_text) {
             sanitized_applied = true;
@@ -429,26 +1326,12 @@ fn handle_query_request(
         }
     }
 
-    if let Some(expected) = state.auth_token.as_ref() {
-        match provided_token {
-            Some(ref token) if token == expected => {}
-            _ => {
-                return generate_random_auth_error();
-            }
-        }
+    if !state.is_authorized(provided_token.as_deref(), request_email.as_deref()) {
+        return generate_random_auth_error();
     }
 
     // Check if setup is completed first
-    let auth_config = match AuthConfig::load() {
-        Ok(config) => config,
-        Err(e) => {
-            let mut body = error_json(&format!("Auth config error: {}", e), start_time.elapsed());
-            if sanitized_applied {
-                insert_sanitized_flag(&mut body);
-            }
-            return HttpResponse::json("500 Internal Server Error", body);
-        }
-    };
+    let auth_config = state.auth_config();
 
     if !auth_config.is_setup_completed() {
         let mut body = error_json(
@@ -461,8 +1344,8 @@ fn handle_query_request(
         return HttpResponse::json("503 Service Unavailable", body);
     }
 
-    // Check email-based SQL permissions
-    if let Some(email) = request_email.as_ref() {
+    // Check email-based SQL permissions; a batch is checked statement by statement
+    if let Some(email) = request_email.as_ref().filter(|_| statements.is_empty()) {
         if !auth_config.check_sql_permission(email, &sql_text) {
             let user_role = auth_config.get_user_role(email).unwrap_or("unknown");
             let mut body = error_json(
@@ -479,8 +1362,58 @@ fn handle_query_request(
         }
     }
 
-    let statement = match state.parser.parse(&sql_text) {
-        Ok(stmt) => stmt,
+    let dialect = match resolve_dialect(requested_dialect.as_deref()) {
+        Ok(dialect) => dialect,
+        Err(message) => {
+            let mut body = error_json(&message, start_time.elapsed());
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json("400 Bad Request", body);
+        }
+    };
+    let memory_limit_mb = match resolve_memory_limit(headers) {
+        Ok(limit_mb) => limit_mb,
+        Err(message) => {
+            let mut body = error_json(&message, start_time.elapsed());
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json("400 Bad Request", body);
+        }
+    };
+
+    if !statements.is_empty() {
+        if !params.is_empty() {
+            return HttpResponse::json(
+                "400 Bad Request",
+                error_json(
+                    "'params' cannot be used with a batch of statements",
+                    start_time.elapsed(),
+                ),
+            );
+        }
+        let batch = QueryBatch {
+            state,
+            auth_config: &auth_config,
+            connection: ConnectionInfo::new(peer_addr, request_email.clone(), false),
+            totp: request_totp.as_deref(),
+            transaction_id,
+            database: request_database.or_else(|| connection_database.clone()),
+            connection_database,
+            dialect,
+            memory_limit_mb,
+            sanitize: config.sql_injection_protect,
+        };
+        return batch.run(&statements, continue_on_error, debug, start_time);
+    }
+
+    let mut connection = ConnectionInfo::new(peer_addr, request_email.clone(), false);
+    let statement = match state.parser.parse_with_params(&sql_text, dialect.clone(), &params) {
+        Ok((stmt, dialect)) => {
+            connection.dialect = Some(dialect);
+            stmt
+        }
         Err(err) => {
             let mut body = error_json(&format!("SQL parse error: {:?}", err), start_time.elapsed());
             if sanitized_applied {
@@ -490,55 +1423,69 @@ fn handle_query_request(
         }
     };
 
-    // 민감한 작업인지 확인하고 2차 인증 검사
-    if statement.requires_2fa() {
-        let user_id = "default"; // 실제 구현에서는 적절한 사용자 ID를 사용해야 함
-
-        // TOTP 토큰 확인
-        match request_totp {
-            Some(totp) if !totp.is_empty() => {
-                let two_factor_auth = match state.two_factor_auth.lock() {
-                    Ok(guard) => guard,
-                    Err(_) => {
-                        return HttpResponse::json(
-                            "500 Internal Server Error",
-                            error_json("2FA system error", start_time.elapsed()),
-                        );
-                    }
-                };
+    if let Some(message) = admin_only_denial(&statement, &auth_config, request_email.as_deref()) {
+        let mut body = error_json(&message, start_time.elapsed());
+        if sanitized_applied {
+            insert_sanitized_flag(&mut body);
+        }
+        return HttpResponse::json("403 Forbidden", body);
+    }
 
-                if !two_factor_auth.verify_token(user_id, &totp) {
-                    let mut body = error_json(
-                        &format!(
-                            "2FA required for {} operation. Invalid or expired TOTP token.",
-                            statement.get_operation_name()
-                        ),
-                        start_time.elapsed(),
-                    );
-                    if sanitized_applied {
-                        insert_sanitized_flag(&mut body);
-                    }
-                    insert_2fa_required_flag(&mut body);
-                    return HttpResponse::json("403 Forbidden", body);
-                }
-            }
-            _ => {
-                let mut body = error_json(
-                    &format!("2FA required for {} operation. Please provide 'authtoken' field with your TOTP code.", 
-                            statement.get_operation_name()),
-                    start_time.elapsed(),
-                );
-                if sanitized_applied {
-                    insert_sanitized_flag(&mut body);
-                }
-                insert_2fa_required_flag(&mut body);
-                return HttpResponse::json("403 Forbidden", body);
+    // 민감한 작업인지 확인하고 2차 인증 검사
+    if let Err((status, message)) =
+        check_two_factor(state, &statement, request_totp.as_deref())
+    {
+        let mut body = error_json(&message, start_time.elapsed());
+        if status == "403 Forbidden" {
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
             }
+            insert_2fa_required_flag(&mut body);
         }
+        return HttpResponse::json(status, body);
     }
 
-    let execution_result = {
-        let mut db = match state.database.lock() {
+    let database_name = request_database.or_else(|| connection_database.clone());
+    let database = match state.target_database(&statement, database_name.as_deref()) {
+        Ok(database) => database,
+        Err(err) => {
+            let status = error_status(&err);
+            let mut body = error_json(&database_error_to_string(err), start_time.elapsed());
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json(status, body);
+        }
+    };
+    let database_change = DatabaseChange::of(&statement);
+
+    let is_select = matches!(
+        statement,
+        SqlStatement::Select { .. }
+            | SqlStatement::ComplexSelect { .. }
+            | SqlStatement::Union { .. }
+    );
+
+    let schema_target = match &statement {
+        SqlStatement::Select {
+            table_name,
+            columns,
+            column_aliases,
+            aggregates,
+            ..
+        } if include_schema => {
+            let mut columns = aliased_columns(columns, column_aliases);
+            columns.extend(aggregates.iter().map(|aggregate| aggregate.name.clone()));
+            Some((table_name.clone(), columns))
+        }
+        _ => None,
+    };
+
+    let count_target = (with_count && is_select).then(|| statement.clone());
+
+    let (execution_result, schema, total_count, began_transaction) = {
+        let lock_start = Instant::now();
+        let mut db = match state.lock_database_for(&database, &statement) {
             Ok(guard) => guard,
             Err(poisoned) => {
                 return HttpResponse::json(
@@ -550,20 +1497,76 @@ fn handle_query_request(
                 );
             }
         };
+        if let Err(message) = check_transaction_session(&mut db, transaction_id.as_deref()) {
+            return HttpResponse::json("409 Conflict", error_json(&message, start_time.elapsed()));
+        }
 
-        db.execute(statement)
+        let lock_wait = lock_start.elapsed();
+        let execute_start = Instant::now();
+        let operation = statement.get_operation_name();
+        let (result, began_transaction) = execute_for_session(
+            &mut db,
+            statement,
+            &sql_text,
+            &params,
+            dialect.as_ref(),
+            memory_limit_mb,
+        );
+        connection.log_audit(operation, if result.is_ok() { "ok" } else { "error" });
+        let schema = match (&result, schema_target) {
+            (Ok(rows), Some((table_name, columns))) => {
+                Some(db.result_schema(&table_name, &columns, rows))
+            }
+            _ => None,
+        };
+        let total_count = match (&result, count_target) {
+            (Ok(_), Some(statement)) => db.count_matches(&statement).ok(),
+            _ => None,
+        };
+        connection.record_timing(lock_wait, execute_start.elapsed());
+        (result, schema, total_count, began_transaction)
     };
+    if execution_result.is_ok() {
+        state.finish_database_change(database_change, connection_database);
+    }
 
     match execution_result {
         Ok(rows) => {
             let elapsed = start_time.elapsed();
+            let rows_json = rows_to_json(&rows);
+            let etag = if is_select {
+                // A new total must not be answered with 304 for an unchanged page
+                let etag = match total_count {
+                    Some(total_count) => result_etag(&format!("{}{}", rows_json, total_count)),
+                    None => result_etag(&rows_json),
+                };
+                if etag_matches(headers, &etag) {
+                    return HttpResponse::not_modified(etag);
+                }
+                Some(etag)
+            } else {
+                None
+            };
+
             let mut body = String::from("{");
             body.push_str("\"status\":\"ok\"");
             body.push_str(",\"status_code\":200");
             body.push_str(",\"row_count\":");
             body.push_str(&rows.len().to_string());
+            if let Some(total_count) = total_count {
+                body.push_str(",\"total_count\":");
+                body.push_str(&total_count.to_string());
+            }
             body.push_str(",\"rows\":");
-            body.push_str(&rows_to_json(&rows));
+            body.push_str(&rows_json);
+            if let Some(schema) = schema.as_ref() {
+                body.push_str(",\"schema\":");
+                body.push_str(&schema_json(schema));
+            }
+            if let Some(transaction_id) = began_transaction.as_deref() {
+                body.push_str(",\"transaction_id\":");
+                push_json_string_or_null(&mut body, Some(transaction_id));
+            }
             if rows.is_empty() {
                 body.push_str(",\"message\":\"Command executed successfully\"");
             }
@@ -572,8 +1575,15 @@ fn handle_query_request(
             if sanitized_applied {
                 insert_sanitized_flag(&mut body);
             }
+            if debug {
+                connection.insert_debug(&mut body);
+            }
 
-            HttpResponse::json("200 OK", body)
+            let response = HttpResponse::json("200 OK", body);
+            match etag {
+                Some(etag) => response.with_header("ETag", etag),
+                None => response,
+            }
         }
         Err(err) => {
             let elapsed = start_time.elapsed();
@@ -587,16 +1597,250 @@ fn handle_query_request(
                 }
             }
 
+            let status = error_status(&err);
             let mut body = error_json(&database_error_to_string(err), elapsed);
             if sanitized_applied {
                 insert_sanitized_flag(&mut body);
             }
+            if debug {
+                connection.insert_debug(&mut body);
+            }
+
+            HttpResponse::json(status, body)
+        }
+    }
+}
+
+/// The TOTP check of statements that require 2FA. Errors carry the HTTP status.
+fn check_two_factor(
+    state: &ApiServerState,
+    statement: &SqlStatement,
+    totp: Option<&str>,
+) -> Result<(), (&'static str, String)> {
+    if !statement.requires_2fa() {
+        return Ok(());
+    }
+    let user_id = "default"; // 실제 구현에서는 적절한 사용자 ID를 사용해야 함
+
+    // TOTP 토큰 확인
+    match totp {
+        Some(totp) if !totp.is_empty() => {
+            let two_factor_auth = state
+                .two_factor_auth
+                .lock()
+                .map_err(|_| ("500 Internal Server Error", "2FA system error".to_string()))?;
+            if two_factor_auth.verify_token(user_id, totp) {
+                return Ok(());
+            }
+            Err((
+                "403 Forbidden",
+                format!(
+                    "2FA required for {} operation. Invalid or expired TOTP token.",
+                    statement.get_operation_name()
+                ),
+            ))
+        }
+        _ => Err((
+            "403 Forbidden",
+            format!(
+                "2FA required for {} operation. Please provide 'authtoken' field with your TOTP code.",
+                statement.get_operation_name()
+            ),
+        )),
+    }
+}
+
+/// The statements of one `/query` request run in order. Each one gets the
+/// permission, admin and 2FA checks of a single query and takes the database
+/// lock on its own; a BEGIN in the batch carries its transaction to the
+/// statements after it.
+struct QueryBatch<'a> {
+    state: &'a ApiServerState,
+    auth_config: &'a AuthConfig,
+    connection: ConnectionInfo,
+    totp: Option<&'a str>,
+    transaction_id: Option<String>,
+    database: Option<String>, // switched by USE, which also sets `connection_database`
+    connection_database: &'a mut Option<String>,
+    dialect: Option<DetectedDialect>,
+    memory_limit_mb: Option<usize>,
+    sanitize: bool,
+}
+
+/// Outcome of one batch statement: its result fields, or the HTTP status and
+/// message of its error.
+type BatchStatementResult = Result<String, (&'static str, String)>;
+
+impl QueryBatch<'_> {
+    /// Stops at the first failing statement unless `continue_on_error` is set.
+    /// The response is 200 when every statement succeeded or the client chose
+    /// to continue past errors, and the failing statement's status otherwise.
+    fn run(
+        mut self,
+        statements: &[String],
+        continue_on_error: bool,
+        debug: bool,
+        start_time: Instant,
+    ) -> HttpResponse {
+        let mut results = String::from("[");
+        let mut executed = 0;
+        let mut failures: Vec<(usize, &'static str)> = Vec::new();
+        let mut sanitized_applied = false;
+
+        for (index, sql) in statements.iter().enumerate() {
+            let sql = match sanitize_sql_input(sql).filter(|_| self.sanitize) {
+                Some(filtered) => {
+                    sanitized_applied = true;
+                    filtered
+                }
+                None => sql.clone(),
+            };
+
+            if index > 0 {
+                results.push(',');
+            }
+            results.push_str("{\"index\":");
+            results.push_str(&index.to_string());
+            executed += 1;
+            match self.run_statement(&sql) {
+                Ok(fields) => {
+                    results.push_str(",\"status\":\"ok\"");
+                    results.push_str(&fields);
+                }
+                Err((status, message)) => {
+                    results.push_str(",\"status\":\"error\",\"status_code\":");
+                    results.push_str(status.split(' ').next().unwrap_or("500"));
+                    results.push_str(",\"error\":\"");
+                    results.push_str(&escape_json_string(&message));
+                    results.push('"');
+                    failures.push((index, status));
+                }
+            }
+            results.push('}');
+
+            if !failures.is_empty() && !continue_on_error {
+                break;
+            }
+        }
+        results.push(']');
+
+        let status = match failures.first() {
+            Some((_, status)) if !continue_on_error => *status,
+            _ => "200 OK",
+        };
+        let mut body = String::from("{");
+        body.push_str(if failures.is_empty() {
+            "\"status\":\"ok\""
+        } else {
+            "\"status\":\"error\""
+        });
+        body.push_str(",\"status_code\":");
+        body.push_str(status.split(' ').next().unwrap_or("200"));
+        body.push_str(",\"statement_count\":");
+        body.push_str(&statements.len().to_string());
+        body.push_str(",\"executed\":");
+        body.push_str(&executed.to_string());
+        body.push_str(",\"error_count\":");
+        body.push_str(&failures.len().to_string());
+        if let Some((index, _)) = failures.first() {
+            body.push_str(",\"failed_index\":");
+            body.push_str(&index.to_string());
+        }
+        body.push_str(",\"results\":");
+        body.push_str(&results);
+        if let Some(transaction_id) = self.transaction_id.as_deref() {
+            body.push_str(",\"transaction_id\":");
+            push_json_string_or_null(&mut body, Some(transaction_id));
+        }
+        append_execution_time(&mut body, start_time.elapsed());
+        body.push('}');
+        if sanitized_applied {
+            insert_sanitized_flag(&mut body);
+        }
+        if debug {
+            self.connection.insert_debug(&mut body);
+        }
+
+        HttpResponse::json(status, body)
+    }
+
+    fn run_statement(&mut self, sql: &str) -> BatchStatementResult {
+        if let Some(email) = self.connection.email.as_deref() {
+            if !self.auth_config.check_sql_permission(email, sql) {
+                let user_role = self.auth_config.get_user_role(email).unwrap_or("unknown");
+                return Err((
+                    "403 Forbidden",
+                    format!(
+                        "SQL permission denied for user '{}' with role '{}'",
+                        email, user_role
+                    ),
+                ));
+            }
+        }
+
+        let (statement, dialect) = self
+            .state
+            .parser
+            .parse_with_params(sql, self.dialect.clone(), &[])
+            .map_err(|err| ("400 Bad Request", format!("SQL parse error: {:?}", err)))?;
+        self.connection.dialect = Some(dialect);
+        let email = self.connection.email.as_deref();
+        if let Some(message) = admin_only_denial(&statement, self.auth_config, email) {
+            return Err(("403 Forbidden", message));
+        }
+        check_two_factor(self.state, &statement, self.totp)?;
+        let database = self
+            .state
+            .target_database(&statement, self.database.as_deref())
+            .map_err(|err| (error_status(&err), database_error_to_string(err)))?;
+        let database_change = DatabaseChange::of(&statement);
+
+        let lock_start = Instant::now();
+        let mut db = self.state.lock_database_for(&database, &statement).map_err(|poisoned| {
+            (
+                "500 Internal Server Error",
+                format!("Database lock poisoned: {}", poisoned),
+            )
+        })?;
+        check_transaction_session(&mut db, self.transaction_id.as_deref())
+            .map_err(|message| ("409 Conflict", message))?;
+
+        let lock_wait = lock_start.elapsed();
+        let execute_start = Instant::now();
+        let operation = statement.get_operation_name();
+        let ends_transaction = matches!(statement, SqlStatement::Commit | SqlStatement::Rollback);
+        let (result, began_transaction) = execute_for_session(
+            &mut db,
+            statement,
+            sql,
+            &[],
+            self.dialect.as_ref(),
+            self.memory_limit_mb,
+        );
+        drop(db);
+        self.connection.record_timing(lock_wait, execute_start.elapsed());
+        self.connection.log_audit(operation, if result.is_ok() { "ok" } else { "error" });
+        let rows = result.map_err(|err| (error_status(&err), database_error_to_string(err)))?;
 
-            HttpResponse::json("400 Bad Request", body)
+        if let DatabaseChange::Use(name) = &database_change {
+            self.database = Some(name.clone());
         }
+        self.state.finish_database_change(database_change, self.connection_database);
+        if began_transaction.is_some() {
+            self.transaction_id = began_transaction;
+        } else if ends_transaction {
+            self.transaction_id = None;
+        }
+
+        let mut fields = String::from(",\"row_count\":");
+        fields.push_str(&rows.len().to_string());
+        fields.push_str(",\"rows\":");
+        fields.push_str(&rows_to_json(&rows));
+        Ok(fields)
     }
 }
 
+
 fn parse_query_payload(body: &[u8], allow_raw_sql: bool) -> Result<QueryRequest, String> {
     let text = std::str::from_utf8(body)
         .map_err(|_| "Request body must be valid UTF-8".to_string())?
@@ -612,19 +1856,73 @@ fn parse_query_payload(body: &[u8], allow_raw_sql: bool) -> Result<QueryRequest,
 
     if allow_raw_sql {
         return Ok(QueryRequest {
+            statements: batch_statements(text),
+            continue_on_error: false,
             sql: text.to_string(),
             auth_token: None,
             totp_token: None,
             email: None,
+            include_schema: false,
+            with_count: false,
+            dialect: None,
+            debug: false,
+            transaction_id: None,
+            params: Vec::new(),
+            database: None,
         });
     }
 
     Err("Body must be a JSON object with a 'sql' field".to_string())
 }
 
+/// Maps the optional `dialect` request value onto a parser dialect.
+fn resolve_dialect(name: Option<&str>) -> Result<Option<DetectedDialect>, String> {
+    match name {
+        None => Ok(None),
+        Some(name) if name.trim().is_empty() => Ok(None),
+        Some(name) => DetectedDialect::from_name(name).map(Some).ok_or_else(|| {
+            let expected: Vec<&str> = DetectedDialect::ALL.iter().map(|d| d.name()).collect();
+            format!(
+                "Unknown dialect '{}'; expected one of {}",
+                name,
+                expected.join(", ")
+            )
+        }),
+    }
+}
+
+/// The optional `X-Memory-Limit-MB` header, which lowers the memory budget of
+/// the request's query.
+fn resolve_memory_limit(headers: &HashMap<String, String>) -> Result<Option<usize>, String> {
+    let Some(value) = find_header(headers, "x-memory-limit-mb") else {
+        return Ok(None);
+    };
+    match value.trim().parse::<usize>() {
+        Ok(mb) if mb > 0 => Ok(Some(mb)),
+        _ => Err(format!(
+            "X-Memory-Limit-MB must be a positive number of megabytes, not '{}'",
+            value
+        )),
+    }
+}
+
 fn parse_query_request_json(text: &str) -> Result<QueryRequest, String> {
-    let sql =
-        extract_json_string_field(text, "sql").ok_or_else(|| "Missing 'sql' field".to_string())?;
+    let statements = match extract_json_array(text, "statements")? {
+        Some(values) => values
+            .into_iter()
+            .map(|value| match value {
+                SqlValue::Text(sql) => Ok(sql.to_string()),
+                _ => Err("'statements' must be an array of SQL strings".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    let sql = match extract_json_string_field(text, "sql") {
+        Some(sql) => sql,
+        None if !statements.is_empty() => String::new(),
+        None => return Err("Missing 'sql' field".to_string()),
+    };
+    let statements = if statements.is_empty() { batch_statements(&sql) } else { statements };
 
     let auth_token = extract_json_string_field(text, "auth_token")
         .or_else(|| extract_json_string_field(text, "token"))
@@ -638,14 +1936,148 @@ fn parse_query_request_json(text: &str) -> Result<QueryRequest, String> {
         .or_else(|| extract_json_string_field(text, "user_email"))
         .or_else(|| extract_json_string_field(text, "user"));
 
+    let dialect = extract_json_string_field(text, "dialect");
+
     Ok(QueryRequest {
         sql,
         auth_token,
         totp_token,
         email,
+        include_schema: false,
+        with_count: false,
+        dialect,
+        debug: false,
+        transaction_id: extract_json_string_field(text, "transaction_id"),
+        params: extract_json_array(text, "params")?.unwrap_or_default(),
+        database: extract_json_string_field(text, "database"),
+        statements,
+        continue_on_error: extract_json_bool_field(text, "continue_on_error").unwrap_or(false),
+    })
+}
+
+/// The statements of a `;`-separated script when it holds more than one; a
+/// single statement (with or without a trailing `;`) is not a batch.
+fn batch_statements(sql: &str) -> Vec<String> {
+    let statements = split_statements(sql);
+    if statements.len() > 1 {
+        statements.into_iter().map(str::to_string).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// The non-negative integer under `field`; `None` when missing, an error when
+/// the value is not such a number or does not fit a u32.
+fn extract_json_u32_field(text: &str, field: &str) -> Result<Option<u32>, String> {
+    let pattern = format!("\"{}\"", field);
+    let Some(rest) = text.find(&pattern).and_then(|key| {
+        text[key + pattern.len()..].trim_start().strip_prefix(':')
+    }) else {
+        return Ok(None);
+    };
+    let digits: String = rest.trim_start().chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().map(Some).map_err(|_| {
+        format!("'{}' must be a whole number from 0 to {}", field, u32::MAX)
     })
 }
 
+/// `true` or `false` given for `field`, if any.
+fn extract_json_bool_field(text: &str, field: &str) -> Option<bool> {
+    let pattern = format!("\"{}\"", field);
+    let key = text.find(&pattern)?;
+    let rest = text[key + pattern.len()..].trim_start().strip_prefix(':')?.trim_start();
+    if rest.starts_with("true") {
+        Some(true)
+    } else if rest.starts_with("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// The optional array under `field` (`"params"`, `"statements"`), typed from
+/// JSON. Numbers with a fraction or exponent become FLOAT.
+fn extract_json_array(text: &str, field: &str) -> Result<Option<Vec<SqlValue>>, String> {
+    let pattern = format!("\"{}\"", field);
+    let Some(start) = text.find(&pattern).and_then(|key| {
+        let rest = text[key + pattern.len()..].trim_start().strip_prefix(':')?;
+        Some(text.len() - rest.trim_start().len())
+    }) else {
+        return Ok(None);
+    };
+
+    let invalid = || {
+        format!("'{}' must be an array of numbers, strings, booleans or null", field)
+    };
+    let mut chars = text[start..].chars().peekable();
+    if chars.next() != Some('[') {
+        return Err(invalid());
+    }
+
+    let mut params = Vec::new();
+    loop {
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        if params.is_empty() && chars.next_if_eq(&']').is_some() {
+            return Ok(Some(params));
+        }
+
+        let value = match chars.next().ok_or_else(invalid)? {
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next().ok_or_else(invalid)? {
+                        '"' => break,
+                        '\\' => match chars.next().ok_or_else(invalid)? {
+                            'n' => value.push('\n'),
+                            't' => value.push('\t'),
+                            'r' => value.push('\r'),
+                            'b' => value.push('\u{0008}'),
+                            'f' => value.push('\u{000C}'),
+                            'u' => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                let ch = u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(invalid)?;
+                                value.push(ch);
+                            }
+                            other => value.push(other),
+                        },
+                        ch => value.push(ch),
+                    }
+                }
+                SqlValue::Text(value.into())
+            }
+            first if first.is_ascii_alphanumeric() || first == '-' => {
+                let mut token = first.to_string();
+                while let Some(ch) =
+                    chars.next_if(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '.' | '+' | '-'))
+                {
+                    token.push(ch);
+                }
+                match token.as_str() {
+                    "null" => SqlValue::Null,
+                    "true" => SqlValue::Boolean(true),
+                    "false" => SqlValue::Boolean(false),
+                    _ if token.contains(['.', 'e', 'E']) => {
+                        SqlValue::Float(token.parse().map_err(|_| invalid())?)
+                    }
+                    _ => SqlValue::Integer(token.parse().map_err(|_| invalid())?),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        params.push(value);
+
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        match chars.next() {
+            Some(',') => {}
+            Some(']') => return Ok(Some(params)),
+            _ => return Err(invalid()),
+        }
+    }
+}
+
 fn extract_json_string_field(text: &str, field: &str) -> Option<String> {
     let pattern = format!("\"{}\"", field);
     let bytes = text.as_bytes();
@@ -709,6 +2141,18 @@ fn extract_json_string_field(text: &str, field: &str) -> Option<String> {
     None
 }
 
+/// The select list as the result rows name it, with aliases in place of columns.
+fn aliased_columns(columns: &[String], column_aliases: &[(String, String)]) -> Vec<String> {
+    let mut column_aliases = column_aliases.to_vec();
+    columns
+        .iter()
+        .map(|column| match column_aliases.iter().position(|(c, _)| c == column) {
+            Some(position) => column_aliases.remove(position).1,
+            None => column.clone(),
+        })
+        .collect()
+}
+
 fn rows_to_json(rows: &[Row]) -> String {
     let mut out = String::from("[");
 
@@ -738,6 +2182,24 @@ fn rows_to_json(rows: &[Row]) -> String {
     out
 }
 
+fn schema_json(schema: &[(String, &'static str)]) -> String {
+    let mut out = String::from("[");
+
+    for (index, (column, type_name)) in schema.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":\"");
+        out.push_str(&escape_json_string(column));
+        out.push_str("\",\"type\":\"");
+        out.push_str(type_name);
+        out.push_str("\"}");
+    }
+
+    out.push(']');
+    out
+}
+
 fn append_sql_value(out: &mut String, value: &SqlValue) {
     match value {
         SqlValue::Integer(v) => out.push_str(&v.to_string()),
@@ -819,29 +2281,62 @@ fn insert_2fa_required_flag(body: &mut String) {
     }
 }
 
+/// Creates a new 2FA secret. The optional `"algorithm"` (`"SHA1"` or `"SHA256"`)
+/// and `"digits"` (6 to 8) of the JSON body choose how codes are computed.
 fn handle_2fa_setup(
     state: &Arc<ApiServerState>,
     headers: &HashMap<String, String>,
-    _body: &[u8],
+    body: &[u8],
 ) -> HttpResponse {
     let start_time = Instant::now();
 
     // Basic API token 인증 확인
-    if let Some(expected) = state.auth_token.as_ref() {
-        let provided_token = extract_auth_token(headers, None);
-        match provided_token {
-            Some(ref token) if token == expected => {}
-            _ => {
-                return HttpResponse::json(
-                    "401 Unauthorized",
-                    error_json("Invalid or missing auth token", start_time.elapsed()),
-                );
-            }
-        }
-    }
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
 
     let user_id = "default"; // 실제 구현에서는 적절한 사용자 ID를 사용해야 함
 
+    let text = String::from_utf8_lossy(body);
+    let algorithm = match extract_json_string_field(&text, "algorithm") {
+        Some(name) => match TotpAlgorithm::from_name(&name) {
+            Some(algorithm) => algorithm,
+            None => {
+                return HttpResponse::json(
+                    "400 Bad Request",
+                    error_json(
+                        &format!("Unsupported 2FA algorithm '{}'; use SHA1 or SHA256", name),
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+        },
+        None => TotpAlgorithm::Sha1,
+    };
+    let digits = match extract_json_u32_field(&text, "digits") {
+        Ok(digits) => digits.unwrap_or(DEFAULT_TOTP_DIGITS),
+        Err(e) => {
+            return HttpResponse::json("400 Bad Request", error_json(&e, start_time.elapsed()));
+        }
+    };
+    if !TOTP_DIGIT_RANGE.contains(&digits) {
+        return HttpResponse::json(
+            "400 Bad Request",
+            error_json(
+                &format!(
+                    "2FA codes must have {} to {} digits",
+                    TOTP_DIGIT_RANGE.start(),
+                    TOTP_DIGIT_RANGE.end()
+                ),
+                start_time.elapsed(),
+            ),
+        );
+    }
+
     let mut two_factor_auth = match state.two_factor_auth.lock() {
         Ok(guard) => guard,
         Err(_) => {
@@ -852,7 +2347,7 @@ fn handle_2fa_setup(
         }
     };
 
-    match two_factor_auth.generate_secret_for_user(user_id) {
+    match two_factor_auth.generate_secret_with_options(user_id, algorithm, digits) {
         Ok(secret) => {
             let mut body = String::from("{");
             body.push_str("\"status\":\"ok\"");
@@ -860,6 +2355,10 @@ fn handle_2fa_setup(
             body.push_str(",\"secret\":\"");
             body.push_str(&escape_json_string(&secret));
             body.push_str("\"");
+            body.push_str(",\"algorithm\":\"");
+            body.push_str(algorithm.name());
+            body.push_str("\",\"digits\":");
+            body.push_str(&digits.to_string());
             body.push_str(",\"user_id\":\"");
             body.push_str(&escape_json_string(user_id));
             body.push_str("\"");
@@ -882,17 +2381,12 @@ fn handle_2fa_qr(state: &Arc<ApiServerState>, headers: &HashMap<String, String>)
     let start_time = Instant::now();
 
     // Basic API token 인증 확인
-    if let Some(expected) = state.auth_token.as_ref() {
-        let provided_token = extract_auth_token(headers, None);
-        match provided_token {
-            Some(ref token) if token == expected => {}
-            _ => {
-                return HttpResponse::json(
-                    "401 Unauthorized",
-                    error_json("Invalid or missing auth token", start_time.elapsed()),
-                );
-            }
-        }
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
     }
 
     let user_id = "default"; // 실제 구현에서는 적절한 사용자 ID를 사용해야 함
@@ -943,17 +2437,12 @@ fn handle_2fa_verify(
     let start_time = Instant::now();
 
     // Basic API token 인증 확인
-    if let Some(expected) = state.auth_token.as_ref() {
-        let provided_token = extract_auth_token(headers, None);
-        match provided_token {
-            Some(ref token) if token == expected => {}
-            _ => {
-                return HttpResponse::json(
-                    "401 Unauthorized",
-                    error_json("Invalid or missing auth token", start_time.elapsed()),
-                );
-            }
-        }
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
     }
 
     if body.is_empty() {
@@ -1036,6 +2525,9 @@ fn append_execution_time(body: &mut String, elapsed: Duration) {
     let elapsed_ms = elapsed_secs * 1000.0;
     body.push_str(",\"execution_time_ms\":");
     body.push_str(&format!("{:.3}", elapsed_ms));
+    // Raw nanoseconds, since sub-microsecond lookups all round to 0.000 ms
+    body.push_str(",\"execution_time_ns\":");
+    body.push_str(&elapsed.as_nanos().to_string());
     body.push_str(",\"execution_time\":\"");
     body.push_str(&format!("{:.3} sec", elapsed_secs));
     body.push('"');
@@ -1072,6 +2564,18 @@ fn escape_json_string(input: &str) -> String {
     escaped
 }
 
+/// Status for a failed statement: table and index name clashes and missing
+/// indexes get their own codes, anything else is the client's query.
+fn error_status(error: &DatabaseError) -> &'static str {
+    match error {
+        DatabaseError::IndexAlreadyExists(_)
+        | DatabaseError::TableAlreadyExists(_)
+        | DatabaseError::DatabaseAlreadyExists(_) => "409 Conflict",
+        DatabaseError::IndexNotFound(_) | DatabaseError::DatabaseNotFound(_) => "404 Not Found",
+        _ => "400 Bad Request",
+    }
+}
+
 fn database_error_to_string(error: DatabaseError) -> String {
     match error {
         DatabaseError::TableNotFound(name) => format!("Table not found: {}", name),
@@ -1086,6 +2590,7 @@ fn database_error_to_string(error: DatabaseError) -> String {
             format!("Index already exists: {}", name)
         }
         DatabaseError::IndexNotFound(name) => format!("Index not found: {}", name),
+        DatabaseError::TableAlreadyExists(name) => format!("Table already exists: {}", name),
         DatabaseError::PrimaryKeyViolation(msg) => format!("Primary key violation: {}", msg),
         DatabaseError::PermissionDenied(msg) => format!("Permission denied: {}", msg),
         DatabaseError::InvalidCredentials(msg) => format!("Invalid credentials: {}", msg),
@@ -1096,9 +2601,137 @@ fn database_error_to_string(error: DatabaseError) -> String {
         DatabaseError::SqlInjectionDetected => format!("SQL injection attempt detected"),
         DatabaseError::QueryTooComplex => format!("Query too complex"),
         DatabaseError::InvalidIndexHint(msg) => format!("Invalid index hint: {}", msg),
+        DatabaseError::DatabaseNotFound(name) => format!("Database '{}' does not exist", name),
+        DatabaseError::DatabaseAlreadyExists(name) => {
+            format!("Database '{}' already exists", name)
+        }
+    }
+}
+
+/// How long a transaction may sit unused before another session may roll it
+/// back, so a client that never sends COMMIT cannot hold the database forever.
+const TRANSACTION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Only the session that began the open transaction may run statements while it
+/// lasts. Returns the message for a 409 response otherwise.
+fn check_transaction_session(
+    db: &mut DatabaseGuard<'_>,
+    transaction_id: Option<&str>,
+) -> Result<(), String> {
+    let db = match db {
+        // Reads share the lock only while no transaction is open
+        DatabaseGuard::Read(_) => {
+            return match transaction_id {
+                Some(id) => Err(format!("Transaction '{}' is not open", id)),
+                None => Ok(()),
+            }
+        }
+        DatabaseGuard::Write(db) => db,
+    };
+    let idle = match db.open_transaction() {
+        Some((session, _)) if session == transaction_id => return Ok(()),
+        Some((_, idle)) => idle,
+        None => {
+            return match transaction_id {
+                Some(id) => Err(format!("Transaction '{}' is not open", id)),
+                None => Ok(()),
+            }
+        }
+    };
+
+    if idle < TRANSACTION_IDLE_TIMEOUT {
+        return Err("Another session's transaction is in progress".to_string());
+    }
+    if let Err(err) = db.rollback_transaction() {
+        return Err(database_error_to_string(err));
+    }
+    println!(
+        "[MirseoDB] Rolled back a transaction left idle for {}s",
+        idle.as_secs()
+    );
+    match transaction_id {
+        Some(id) => Err(format!("Transaction '{}' is not open", id)),
+        None => Ok(()),
+    }
+}
+
+/// Executes `statement`, giving a BEGIN a new transaction id for the client to
+/// send with the statements that follow it. Returns that id alongside the rows.
+fn execute_for_session(
+    db: &mut DatabaseGuard<'_>,
+    statement: SqlStatement,
+    sql: &str,
+    params: &[SqlValue],
+    dialect: Option<&DetectedDialect>,
+    memory_limit_mb: Option<usize>,
+) -> (Result<Vec<Row>, DatabaseError>, Option<String>) {
+    let db = match db {
+        DatabaseGuard::Read(db) => return (db.execute_read(statement, memory_limit_mb), None),
+        DatabaseGuard::Write(db) => db,
+    };
+    if !matches!(statement, SqlStatement::Begin) {
+        db.set_memory_limit_mb(memory_limit_mb);
+        let result = db.execute_logged(statement, sql, params, dialect);
+        db.set_memory_limit_mb(None);
+        return (result, None);
+    }
+
+    let transaction_id = new_transaction_id();
+    match db.begin_transaction(Some(transaction_id.clone())) {
+        Ok(()) => (Ok(vec![]), Some(transaction_id)),
+        Err(err) => (Err(err), None),
     }
 }
 
+fn new_transaction_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0);
+
+    // RandomState keys are random per process, so ids cannot be derived from the clock
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(nanos);
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("tx-{:016x}", hasher.finish())
+}
+
+/// DROP DATABASE, RESET DATABASE, GRANT and REVOKE need an admin caller on top
+/// of the 2FA check every sensitive statement gets.
+fn admin_only_denial(
+    statement: &SqlStatement,
+    auth_config: &AuthConfig,
+    email: Option<&str>,
+) -> Option<String> {
+    if !is_admin_only(statement) {
+        return None;
+    }
+
+    admin_denial(statement.get_operation_name(), auth_config, email)
+}
+
+fn admin_denial(operation: &str, auth_config: &AuthConfig, email: Option<&str>) -> Option<String> {
+    match email {
+        Some(email) if auth_config.get_user_role(email) == Some("admin") => None,
+        Some(email) => Some(format!(
+            "{} requires an admin user; '{}' is not one",
+            operation, email
+        )),
+        None => Some(format!("{} requires an admin user; provide 'email'", operation)),
+    }
+}
+
+fn is_admin_only(statement: &SqlStatement) -> bool {
+    matches!(
+        statement,
+        SqlStatement::DropDatabase { .. }
+            | SqlStatement::ResetDatabase { .. }
+            | SqlStatement::Grant { .. }
+            | SqlStatement::Revoke { .. }
+    )
+}
+
 fn extract_auth_token(
     headers: &HashMap<String, String>,
     request_token: Option<String>,
@@ -1125,46 +2758,232 @@ fn extract_auth_token(
     })
 }
 
-fn read_full_request(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
-    let mut data = Vec::new();
+enum RequestReadError {
+    Io(std::io::Error),
+    TooLarge,
+    InvalidFraming(String),
+}
+
+impl From<std::io::Error> for RequestReadError {
+    fn from(err: std::io::Error) -> Self {
+        RequestReadError::Io(err)
+    }
+}
+
+/// Reads the next request on the connection. `pending` holds bytes already read
+/// past the previous request and keeps whatever follows this one. An empty result
+/// means the client closed the connection or sent nothing within `idle_timeout`.
+fn read_full_request(
+    stream: &mut TcpStream,
+    pending: &mut Vec<u8>,
+    idle_timeout: Duration,
+) -> Result<Vec<u8>, RequestReadError> {
     let mut buffer = [0u8; 1024];
 
     loop {
-        let bytes_read = stream.read(&mut buffer)?;
+        if !pending.is_empty() {
+            match request_progress(pending)? {
+                RequestProgress::Incomplete => {}
+                RequestProgress::Complete(len) => return Ok(pending.drain(..len).collect()),
+                RequestProgress::Dechunked(request, len) => {
+                    pending.drain(..len);
+                    return Ok(request);
+                }
+            }
+        }
+
+        let waiting_for_request = pending.is_empty();
+        let timeout = if waiting_for_request { idle_timeout } else { READ_TIMEOUT };
+        stream.set_read_timeout(Some(timeout))?;
+        let bytes_read = match stream.read(&mut buffer) {
+            Ok(bytes_read) => bytes_read,
+            Err(e)
+                if waiting_for_request
+                    && matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+            {
+                0
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         if bytes_read == 0 {
             break;
         }
 
-        data.extend_from_slice(&buffer[..bytes_read]);
+        pending.extend_from_slice(&buffer[..bytes_read]);
+    }
+
+    let chunked = match find_double_crlf(pending) {
+        Some(header_end) => is_chunked(&pending[..header_end - 4])?,
+        None => false,
+    };
+    if chunked {
+        return Err(RequestReadError::InvalidFraming(
+            "Chunked body ended before its last chunk".to_string(),
+        ));
+    }
+    Ok(std::mem::take(pending))
+}
+
+/// How much of a request has arrived.
+enum RequestProgress {
+    Incomplete,
+    /// The first `len` bytes are a whole request.
+    Complete(usize),
+    /// A chunked request, rewritten with its decoded body and a Content-Length
+    /// so the handlers and the console proxy see an ordinary request, and the
+    /// number of raw bytes it took up.
+    Dechunked(Vec<u8>, usize),
+}
 
+fn request_progress(data: &[u8]) -> Result<RequestProgress, RequestReadError> {
+    let Some(header_end) = find_double_crlf(data) else {
         if data.len() > MAX_REQUEST_SIZE {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Request too large",
-            ));
+            return Err(RequestReadError::TooLarge);
         }
+        return Ok(RequestProgress::Incomplete);
+    };
+    let header_bytes = &data[..header_end - 4];
 
-        if request_complete(&data) {
-            break;
+    if !is_chunked(header_bytes)? {
+        return match expected_request_len(data)? {
+            // Reject from the declared length, before waiting for a body we would refuse anyway
+            Some(total_expected) if total_expected > MAX_REQUEST_SIZE => {
+                Err(RequestReadError::TooLarge)
+            }
+            Some(total_expected) if data.len() >= total_expected => {
+                Ok(RequestProgress::Complete(total_expected))
+            }
+            _ => Ok(RequestProgress::Incomplete),
+        };
+    }
+    if parse_content_length(header_bytes)?.is_some() {
+        return Err(RequestReadError::InvalidFraming(
+            "Both Content-Length and Transfer-Encoding are present".to_string(),
+        ));
+    }
+
+    let body_limit = MAX_REQUEST_SIZE.saturating_sub(header_end);
+    match decode_chunked(&data[header_end..], body_limit)? {
+        Some((body, body_len)) => Ok(RequestProgress::Dechunked(
+            dechunked_request(header_bytes, &body),
+            header_end + body_len,
+        )),
+        // Chunk framing adds to the body size, but not without bound
+        None if data.len() > 2 * MAX_REQUEST_SIZE => Err(RequestReadError::TooLarge),
+        None => Ok(RequestProgress::Incomplete),
+    }
+}
+
+/// Whether the body uses the chunked transfer coding, the only one accepted.
+fn is_chunked(header_bytes: &[u8]) -> Result<bool, RequestReadError> {
+    let header_text = String::from_utf8_lossy(header_bytes);
+    let mut chunked = false;
+
+    for line in header_text.lines() {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Transfer-Encoding") {
+                if !value.trim().eq_ignore_ascii_case("chunked") {
+                    return Err(RequestReadError::InvalidFraming(format!(
+                        "Unsupported Transfer-Encoding '{}'; only chunked is accepted",
+                        value.trim()
+                    )));
+                }
+                chunked = true;
+            }
         }
     }
 
-    Ok(data)
+    Ok(chunked)
 }
 
-fn request_complete(data: &[u8]) -> bool {
-    if let Some(split_index) = find_double_crlf(data) {
-        let header_bytes = &data[..split_index - 4];
-        if let Some(content_length) = parse_content_length(header_bytes) {
-            let total_expected = split_index + content_length;
-            return data.len() >= total_expected;
+/// Decodes a chunked body: hex-sized chunks ending with a zero-sized one, then
+/// optional trailer fields, which are dropped, and an empty line. Returns the body
+/// and the raw bytes it spanned, or `None` until all of it has arrived; a decoded
+/// size over `limit` is `TooLarge`.
+fn decode_chunked(
+    raw: &[u8],
+    limit: usize,
+) -> Result<Option<(Vec<u8>, usize)>, RequestReadError> {
+    let invalid = || RequestReadError::InvalidFraming("Invalid chunked body".to_string());
+    let mut body = Vec::new();
+    let mut cursor = 0;
+
+    loop {
+        let Some(line_len) = find_crlf(&raw[cursor..]) else {
+            return Ok(None);
+        };
+        let line = String::from_utf8_lossy(&raw[cursor..cursor + line_len]);
+        // Chunk extensions after ';' are ignored
+        let size_text = line.split(';').next().unwrap_or("").trim();
+        if size_text.is_empty() || !size_text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        // Too many digits for usize is certainly beyond the size limit
+        let size = usize::from_str_radix(size_text, 16).unwrap_or(usize::MAX);
+        cursor += line_len + 2;
+
+        if size == 0 {
+            break;
+        }
+        if body.len().saturating_add(size) > limit {
+            return Err(RequestReadError::TooLarge);
+        }
+        let Some(chunk) = raw.get(cursor..cursor + size) else {
+            return Ok(None);
+        };
+        body.extend_from_slice(chunk);
+        cursor += size;
+        match raw.get(cursor..cursor + 2) {
+            None => return Ok(None),
+            Some(b"\r\n") => cursor += 2,
+            Some(_) => return Err(invalid()),
+        }
+    }
+
+    loop {
+        let Some(line_len) = find_crlf(&raw[cursor..]) else {
+            return Ok(None);
+        };
+        cursor += line_len + 2;
+        if line_len == 0 {
+            return Ok(Some((body, cursor)));
         }
+    }
+}
 
-        return true;
+fn dechunked_request(header_bytes: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(header_bytes.len() + body.len() + 32);
+    for line in String::from_utf8_lossy(header_bytes).lines() {
+        let is_transfer_encoding = line
+            .split_once(':')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case("Transfer-Encoding"));
+        if !is_transfer_encoding {
+            request.extend_from_slice(line.as_bytes());
+            request.extend_from_slice(b"\r\n");
+        }
     }
+    request.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+    request.extend_from_slice(body);
+    request
+}
+
+/// Total request size (headers plus declared body) once the header block is complete.
+fn expected_request_len(data: &[u8]) -> Result<Option<usize>, RequestReadError> {
+    let Some(split_index) = find_double_crlf(data) else {
+        return Ok(None);
+    };
+
+    let header_bytes = &data[..split_index - 4];
+    let content_length = parse_content_length(header_bytes)?.unwrap_or(0);
+    Ok(Some(split_index.saturating_add(content_length)))
+}
 
-    false
+fn find_crlf(data: &[u8]) -> Option<usize> {
+    data.windows(2).position(|window| window == b"\r\n")
 }
 
 fn find_double_crlf(data: &[u8]) -> Option<usize> {
@@ -1173,20 +2992,35 @@ fn find_double_crlf(data: &[u8]) -> Option<usize> {
         .map(|pos| pos + 4)
 }
 
-fn parse_content_length(header_bytes: &[u8]) -> Option<usize> {
+fn parse_content_length(header_bytes: &[u8]) -> Result<Option<usize>, RequestReadError> {
     let header_text = String::from_utf8_lossy(header_bytes);
+    let mut content_length: Option<usize> = None;
 
     for line in header_text.lines() {
         if let Some((name, value)) = line.split_once(':') {
             if name.trim().eq_ignore_ascii_case("Content-Length") {
-                if let Ok(length) = value.trim().parse::<usize>() {
-                    return Some(length);
+                let value = value.trim();
+                if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(RequestReadError::InvalidFraming(
+                        "Invalid Content-Length header".to_string(),
+                    ));
+                }
+
+                // Too many digits for usize is certainly beyond the size limit
+                let length = value.parse::<usize>().unwrap_or(usize::MAX);
+                match content_length {
+                    Some(existing) if existing != length => {
+                        return Err(RequestReadError::InvalidFraming(
+                            "Conflicting Content-Length headers".to_string(),
+                        ));
+                    }
+                    _ => content_length = Some(length),
                 }
             }
         }
     }
 
-    None
+    Ok(content_length)
 }
 
 fn split_request(data: &[u8]) -> Option<(String, &[u8])> {
@@ -1196,11 +3030,14 @@ fn split_request(data: &[u8]) -> Option<(String, &[u8])> {
     Some((header_text, body))
 }
 
-fn parse_headers<'a, I>(lines: I) -> HashMap<String, String>
+/// Parses request headers, rejecting ambiguous message framing. Requests that
+/// reach the console proxy are forwarded verbatim, so a downstream server must
+/// never be able to read a different body length than we did.
+fn parse_headers<'a, I>(lines: I) -> Result<HashMap<String, String>, String>
 where
     I: Iterator<Item = &'a str>,
 {
-    let mut headers = HashMap::new();
+    let mut headers: HashMap<String, String> = HashMap::new();
 
     for line in lines {
         if line.trim().is_empty() {
@@ -1213,16 +3050,31 @@ where
                 .filter(|c| !c.is_ascii_whitespace())
                 .collect::<String>()
                 .to_ascii_lowercase();
-            headers.insert(normalized_name, value.trim().to_string());
+            let value = value.trim().to_string();
+
+            if normalized_name == "content-length" || normalized_name == "transfer-encoding" {
+                if let Some(existing) = headers.get(&normalized_name) {
+                    if *existing != value {
+                        return Err(format!("Conflicting {} headers", name.trim()));
+                    }
+                }
+            }
+
+            headers.insert(normalized_name, value);
         }
     }
 
-    headers
+    if headers.contains_key("content-length") && headers.contains_key("transfer-encoding") {
+        return Err("Both Content-Length and Transfer-Encoding are present".to_string());
+    }
+
+    Ok(headers)
 }
 
 struct HttpResponse {
     status: &'static str,
     content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
     body: String,
 }
 
@@ -1231,6 +3083,7 @@ impl HttpResponse {
         Self {
             status,
             content_type: "application/json",
+            headers: Vec::new(),
             body,
         }
     }
@@ -1239,21 +3092,107 @@ impl HttpResponse {
         Self {
             status,
             content_type: "text/plain",
+            headers: Vec::new(),
             body: body.to_string(),
         }
     }
+
+    fn not_modified(etag: String) -> Self {
+        Self::json("304 Not Modified", String::new()).with_header("ETag", etag)
+    }
+
+    fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
 }
 
 fn write_http_response(stream: &mut TcpStream, response: &HttpResponse) -> std::io::Result<()> {
-    let message = format!(
-        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {length}\r\nConnection: close\r\n\r\n{body}",
+    write_http_message(stream, response, true, false, false)
+}
+
+/// Writes the response; with `include_body` unset (HEAD requests) the headers,
+/// including `Content-Length`, still describe the body that GET would return.
+/// `compress` sends the body gzip-encoded; `keep_alive` leaves the connection
+/// open for the client's next request.
+fn write_http_message(
+    stream: &mut TcpStream,
+    response: &HttpResponse,
+    include_body: bool,
+    compress: bool,
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    let mut extra_headers = String::new();
+    for (name, value) in &response.headers {
+        extra_headers.push_str(name);
+        extra_headers.push_str(": ");
+        extra_headers.push_str(value);
+        extra_headers.push_str("\r\n");
+    }
+
+    let compressed = compress.then(|| gzip(response.body.as_bytes()));
+    if compressed.is_some() {
+        extra_headers.push_str("Content-Encoding: gzip\r\n");
+    }
+    let body = compressed.as_deref().unwrap_or(response.body.as_bytes());
+
+    let mut message = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {length}\r\n{extra_headers}Connection: {connection}\r\n\r\n",
         status = response.status,
         content_type = response.content_type,
-        length = response.body.as_bytes().len(),
-        body = response.body,
-    );
+        length = body.len(),
+        extra_headers = extra_headers,
+        connection = if keep_alive { "keep-alive" } else { "close" },
+    )
+    .into_bytes();
+    if include_body {
+        message.extend_from_slice(body);
+    }
+
+    stream.write_all(&message)
+}
+
+/// Whether `Accept-Encoding` lists gzip with a non-zero quality. Only an explicit
+/// `gzip` coding counts; `*` or a missing header keeps the identity encoding.
+fn accepts_gzip(headers: &HashMap<String, String>) -> bool {
+    let Some(accept_encoding) = find_header(headers, "accept-encoding") else {
+        return false;
+    };
+
+    accept_encoding.split(',').any(|coding| {
+        let mut params = coding.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let quality = params
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+            .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        name.eq_ignore_ascii_case("gzip") && quality > 0.0
+    })
+}
 
-    stream.write_all(message.as_bytes())
+/// Strong ETag for a SELECT result. It is derived from the serialized rows, so it
+/// changes whenever the result content changes, not when the table is merely written.
+fn result_etag(rows_json: &str) -> String {
+    // FNV-1a keeps the tag stable across restarts and Rust versions
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in rows_json.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("\"{:016x}\"", hash)
+}
+
+fn etag_matches(headers: &HashMap<String, String>, etag: &str) -> bool {
+    let Some(if_none_match) = find_header(headers, "if-none-match") else {
+        return false;
+    };
+
+    if_none_match.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        let candidate = candidate.strip_prefix("W/").unwrap_or(candidate);
+        candidate == "*" || candidate == etag
+    })
 }
 
 fn normalize_content_type(value: &str) -> String {
@@ -1293,7 +3232,10 @@ fn handle_forwarded_query_request(
     state: &Arc<ApiServerState>,
     headers: &HashMap<String, String>,
     body: &[u8],
+    query_string: Option<&str>,
+    peer_addr: Option<SocketAddr>,
     start_time: Instant,
+    connection_database: &mut Option<String>,
 ) -> HttpResponse {
     // This is a forwarded request, process it normally but indicate it's in forward mode
     if body.is_empty() {
@@ -1340,12 +3282,24 @@ fn handle_forwarded_query_request(
         auth_token: request_token,
         totp_token: _request_totp, // 포워드 모드에서는 2FA 검사하지 않음
         email: request_email,
+        include_schema: _,
+        with_count: _,
+        dialect: requested_dialect,
+        debug: _,
+        transaction_id,
+        params,
+        database: request_database,
+        statements,
+        continue_on_error: _,
     } = request;
+    let debug = query_string
+        .map(|query| query_flag(&parse_url_query_params(query), "debug"))
+        .unwrap_or(false);
 
     let provided_token = extract_auth_token(headers, request_token.clone());
 
     let mut sanitized_applied = false;
-    let config = ConfigManager::load();
+    let config = state.config();
     if config.sql_injection_protect {
         if let Some(filtered) = sanitize_sql_input(&sql_text) {
             sanitized_applied = true;
@@ -1356,25 +3310,54 @@ fn handle_forwarded_query_request(
         }
     }
 
-    if let Some(expected) = state.auth_token.as_ref() {
-        match provided_token {
-            Some(ref token) if token == expected => {}
-            _ => {
-                let mut body = error_json_with_mode(
-                    "Invalid or missing auth token",
-                    start_time.elapsed(),
-                    true,
-                );
-                if sanitized_applied {
-                    insert_sanitized_flag(&mut body);
-                }
-                return HttpResponse::json("401 Unauthorized", body);
+    if !state.is_authorized(provided_token.as_deref(), request_email.as_deref()) {
+        let mut body =
+            error_json_with_mode("Invalid or missing auth token", start_time.elapsed(), true);
+        if sanitized_applied {
+            insert_sanitized_flag(&mut body);
+        }
+        return HttpResponse::json("401 Unauthorized", body);
+    }
+
+    let dialect = match resolve_dialect(requested_dialect.as_deref()) {
+        Ok(dialect) => dialect,
+        Err(message) => {
+            let mut body = error_json_with_mode(&message, start_time.elapsed(), true);
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json("400 Bad Request", body);
+        }
+    };
+    let memory_limit_mb = match resolve_memory_limit(headers) {
+        Ok(limit_mb) => limit_mb,
+        Err(message) => {
+            let mut body = error_json_with_mode(&message, start_time.elapsed(), true);
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
             }
+            return HttpResponse::json("400 Bad Request", body);
+        }
+    };
+
+    if !statements.is_empty() {
+        let mut body = error_json_with_mode(
+            "Batches of statements are not supported on forwarded requests",
+            start_time.elapsed(),
+            true,
+        );
+        if sanitized_applied {
+            insert_sanitized_flag(&mut body);
         }
+        return HttpResponse::json("400 Bad Request", body);
     }
 
-    let statement = match state.parser.parse(&sql_text) {
-        Ok(stmt) => stmt,
+    let mut connection = ConnectionInfo::new(peer_addr, request_email.clone(), true);
+    let statement = match state.parser.parse_with_params(&sql_text, dialect.clone(), &params) {
+        Ok((stmt, dialect)) => {
+            connection.dialect = Some(dialect);
+            stmt
+        }
         Err(err) => {
             let mut body = error_json_with_mode(
                 &format!("SQL parse error: {:?}", err),
@@ -1388,8 +3371,40 @@ fn handle_forwarded_query_request(
         }
     };
 
-    let execution_result = {
-        let mut db = match state.database.lock() {
+    // Forwarded requests carry no TOTP code, so they cannot pass the admin-only checks
+    if is_admin_only(&statement) {
+        let mut body = error_json_with_mode(
+            &format!(
+                "{} is not allowed on forwarded requests",
+                statement.get_operation_name()
+            ),
+            start_time.elapsed(),
+            true,
+        );
+        if sanitized_applied {
+            insert_sanitized_flag(&mut body);
+        }
+        return HttpResponse::json("403 Forbidden", body);
+    }
+
+    let database_name = request_database.or_else(|| connection_database.clone());
+    let database = match state.target_database(&statement, database_name.as_deref()) {
+        Ok(database) => database,
+        Err(err) => {
+            let status = error_status(&err);
+            let message = database_error_to_string(err);
+            let mut body = error_json_with_mode(&message, start_time.elapsed(), true);
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json(status, body);
+        }
+    };
+    let database_change = DatabaseChange::of(&statement);
+
+    let (execution_result, began_transaction) = {
+        let lock_start = Instant::now();
+        let mut db = match state.lock_database_for(&database, &statement) {
             Ok(guard) => guard,
             Err(poisoned) => {
                 return HttpResponse::json(
@@ -1402,9 +3417,31 @@ fn handle_forwarded_query_request(
                 );
             }
         };
+        if let Err(message) = check_transaction_session(&mut db, transaction_id.as_deref()) {
+            return HttpResponse::json(
+                "409 Conflict",
+                error_json_with_mode(&message, start_time.elapsed(), true),
+            );
+        }
 
-        db.execute(statement)
+        let lock_wait = lock_start.elapsed();
+        let execute_start = Instant::now();
+        let operation = statement.get_operation_name();
+        let (result, began_transaction) = execute_for_session(
+            &mut db,
+            statement,
+            &sql_text,
+            &params,
+            dialect.as_ref(),
+            memory_limit_mb,
+        );
+        connection.log_audit(operation, if result.is_ok() { "ok" } else { "error" });
+        connection.record_timing(lock_wait, execute_start.elapsed());
+        (result, began_transaction)
     };
+    if execution_result.is_ok() {
+        state.finish_database_change(database_change, connection_database);
+    }
 
     match execution_result {
         Ok(rows) => {
@@ -1417,6 +3454,10 @@ fn handle_forwarded_query_request(
             body.push_str(&rows.len().to_string());
             body.push_str(",\"rows\":");
             body.push_str(&rows_to_json(&rows));
+            if let Some(transaction_id) = began_transaction.as_deref() {
+                body.push_str(",\"transaction_id\":");
+                push_json_string_or_null(&mut body, Some(transaction_id));
+            }
             if rows.is_empty() {
                 body.push_str(",\"message\":\"Command executed successfully\"");
             }
@@ -1425,16 +3466,23 @@ fn handle_forwarded_query_request(
             if sanitized_applied {
                 insert_sanitized_flag(&mut body);
             }
+            if debug {
+                connection.insert_debug(&mut body);
+            }
 
             HttpResponse::json("200 OK", body)
         }
         Err(err) => {
             let elapsed = start_time.elapsed();
+            let status = error_status(&err);
             let mut body = error_json_with_mode(&database_error_to_string(err), elapsed, true);
             if sanitized_applied {
                 insert_sanitized_flag(&mut body);
             }
-            HttpResponse::json("400 Bad Request", body)
+            if debug {
+                connection.insert_debug(&mut body);
+            }
+            HttpResponse::json(status, body)
         }
     }
 }
@@ -1500,16 +3548,8 @@ fn insert_sanitized_flag(body: &mut String) {
     }
 }
 
-fn handle_setup_status() -> HttpResponse {
-    let auth_config = match AuthConfig::load() {
-        Ok(config) => config,
-        Err(e) => {
-            return HttpResponse::json(
-                "500 Internal Server Error",
-                format!("{{\"error\":\"Failed to load auth config: {}\"}}", e),
-            );
-        }
-    };
+fn handle_setup_status(state: &Arc<ApiServerState>) -> HttpResponse {
+    let auth_config = state.auth_config();
 
     let mut body = String::from("{");
     body.push_str("\"setup_completed\":");
@@ -1569,17 +3609,7 @@ fn handle_setup_init(
         }
     };
 
-    let auth_config = match AuthConfig::load() {
-        Ok(config) => config,
-        Err(e) => {
-            return HttpResponse::json(
-                "500 Internal Server Error",
-                error_json(&format!("Auth config error: {}", e), start_time.elapsed()),
-            );
-        }
-    };
-
-    if auth_config.is_setup_completed() {
+    if state.auth_config().is_setup_completed() {
         return HttpResponse::json(
             "400 Bad Request",
             error_json("Setup already completed", start_time.elapsed()),
@@ -1684,15 +3714,7 @@ fn handle_setup_complete(
         }
     };
 
-    let mut auth_config = match AuthConfig::load() {
-        Ok(config) => config,
-        Err(e) => {
-            return HttpResponse::json(
-                "500 Internal Server Error",
-                error_json(&format!("Auth config error: {}", e), start_time.elapsed()),
-            );
-        }
-    };
+    let mut auth_config = AuthConfig::clone(&state.auth_config());
 
     if auth_config.is_setup_completed() {
         return HttpResponse::json(
@@ -1738,6 +3760,7 @@ fn handle_setup_complete(
             error_json(&format!("Failed to complete setup: {}", e), start_time.elapsed()),
         );
     }
+    state.set_auth_config(auth_config);
 
     let mut response_body = String::from("{");
     response_body.push_str("\"status\":\"ok\"");
@@ -1751,10 +3774,1038 @@ fn handle_setup_complete(
     append_execution_time(&mut response_body, start_time.elapsed());
     response_body.push('}');
 
-    HttpResponse::json("200 OK", response_body)
+    HttpResponse::json("200 OK", response_body)
+}
+
+/// `reset` returns the parser counters and zeroes them atomically so monitoring
+/// can compute per-interval rates; lock metrics are cumulative and never reset.
+fn handle_metrics_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    reset: bool,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    // Taken without `lock_database` so reading metrics does not show up in them
+    let cache_stats = match state.database.read() {
+        Ok(db) => Some(db.cache_stats()),
+        Err(_) => None,
+    };
+
+    let mut body = String::from("{");
+    body.push_str("\"status\":\"ok\"");
+    body.push_str(",\"database_lock\":");
+    body.push_str(&state.lock_metrics.to_json());
+    if let Some(cache_stats) = cache_stats {
+        body.push_str(",\"database\":");
+        body.push_str(&cache_stats_json(&cache_stats));
+    }
+
+    let snapshot = if reset {
+        state.parser.take_performance_snapshot()
+    } else {
+        state.parser.performance_snapshot()
+    };
+    if let Some(snapshot) = snapshot {
+        body.push_str(",\"parser\":");
+        body.push_str(&parser_metrics_json(&snapshot));
+    }
+    if reset {
+        body.push_str(",\"reset\":true");
+    }
+    body.push('}');
+
+    HttpResponse::json("200 OK", body)
+}
+
+fn handle_explain_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    query_string: Option<&str>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let sql = match query_string.and_then(|query| parse_url_query_params(query).remove("sql")) {
+        Some(sql) if !sql.trim().is_empty() => sql,
+        _ => {
+            return HttpResponse::json(
+                "400 Bad Request",
+                error_json("Missing 'sql' parameter", start_time.elapsed()),
+            );
+        }
+    };
+
+    let statement = match state.parser.parse(&sql) {
+        Ok(statement) => statement,
+        Err(err) => {
+            return HttpResponse::json(
+                "400 Bad Request",
+                error_json(&format!("SQL parse error: {:?}", err), start_time.elapsed()),
+            );
+        }
+    };
+
+    let plan = match state.read_database() {
+        Ok(db) => db.explain(&statement),
+        Err(poisoned) => {
+            return HttpResponse::json(
+                "500 Internal Server Error",
+                error_json(
+                    &format!("Database lock poisoned: {}", poisoned),
+                    start_time.elapsed(),
+                ),
+            );
+        }
+    };
+
+    let plan = match plan {
+        Ok(plan) => plan,
+        Err(DatabaseError::TableNotFound(name)) => {
+            return HttpResponse::json(
+                "404 Not Found",
+                error_json(&format!("Table '{}' not found", name), start_time.elapsed()),
+            );
+        }
+        Err(err) => {
+            return HttpResponse::json(
+                "400 Bad Request",
+                error_json(&format!("{:?}", err), start_time.elapsed()),
+            );
+        }
+    };
+
+    let mut body = String::from("{");
+    body.push_str("\"status\":\"ok\",\"sql\":\"");
+    body.push_str(&escape_json_string(&sql));
+    body.push('"');
+    body.push_str(&query_plan_json(&plan));
+    append_execution_time(&mut body, start_time.elapsed());
+    body.push('}');
+
+    HttpResponse::json("200 OK", body)
+}
+
+/// Fields (with leading comma) describing the plan `Database::explain` chose.
+fn query_plan_json(plan: &QueryPlan) -> String {
+    let mut body = String::new();
+    body.push_str(",\"table\":\"");
+    body.push_str(&escape_json_string(&plan.table_name));
+    body.push_str("\",\"strategy\":\"");
+    body.push_str(plan.strategy);
+    body.push_str("\",\"detail\":");
+    push_json_string_or_null(&mut body, plan.detail.as_deref());
+    body
+}
+
+fn handle_explain_dialect_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    query_string: Option<&str>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let sql = match query_string.and_then(|query| parse_url_query_params(query).remove("sql")) {
+        Some(sql) if !sql.trim().is_empty() => sql,
+        _ => {
+            return HttpResponse::json(
+                "400 Bad Request",
+                error_json("Missing 'sql' parameter", start_time.elapsed()),
+            );
+        }
+    };
+
+    let mut body = String::from("{");
+    body.push_str("\"status\":\"ok\"");
+    body.push_str(",\"sql\":\"");
+    body.push_str(&escape_json_string(&sql));
+    body.push('"');
+    body.push_str(&dialect_explanation_json(&state.parser.explain_dialect(&sql)));
+    append_execution_time(&mut body, start_time.elapsed());
+    body.push('}');
+
+    HttpResponse::json("200 OK", body)
+}
+
+fn handle_column_stats_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    query_string: Option<&str>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let table_name =
+        match query_string.and_then(|query| parse_url_query_params(query).remove("table")) {
+            Some(table) if !table.trim().is_empty() => normalize_table_name(&table),
+            _ => {
+                return HttpResponse::json(
+                    "400 Bad Request",
+                    error_json("Missing 'table' parameter", start_time.elapsed()),
+                );
+            }
+        };
+
+    let statistics = {
+        let mut db = match state.lock_database() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                return HttpResponse::json(
+                    "500 Internal Server Error",
+                    error_json(
+                        &format!("Database lock poisoned: {}", poisoned),
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+        };
+        db.column_statistics(&table_name).cloned()
+    };
+
+    let statistics = match statistics {
+        Ok(statistics) => statistics,
+        Err(DatabaseError::TableNotFound(name)) => {
+            return HttpResponse::json(
+                "404 Not Found",
+                error_json(&format!("Table '{}' not found", name), start_time.elapsed()),
+            );
+        }
+        Err(err) => {
+            return HttpResponse::json(
+                "500 Internal Server Error",
+                error_json(&format!("{:?}", err), start_time.elapsed()),
+            );
+        }
+    };
+
+    let mut body = String::from("{");
+    body.push_str("\"status\":\"ok\",\"table\":\"");
+    body.push_str(&escape_json_string(&table_name));
+    body.push('"');
+    body.push_str(&column_statistics_json(&statistics));
+    append_execution_time(&mut body, start_time.elapsed());
+    body.push('}');
+
+    HttpResponse::json("200 OK", body)
+}
+
+/// The statements that migrate the current schema to the target DDL in the body,
+/// given as plain SQL or as `{"ddl": "..."}`.
+fn handle_schema_diff_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let text = String::from_utf8_lossy(body);
+    let text = text.trim();
+    let target_ddl = if text.starts_with('{') {
+        extract_json_string_field(text, "ddl")
+    } else {
+        Some(text.to_string()).filter(|ddl| !ddl.is_empty())
+    };
+    let Some(target_ddl) = target_ddl else {
+        return HttpResponse::json(
+            "400 Bad Request",
+            error_json("Body must contain the target DDL", start_time.elapsed()),
+        );
+    };
+
+    let diff = match state.read_database() {
+        Ok(db) => db.schema_diff(&target_ddl),
+        Err(poisoned) => {
+            return HttpResponse::json(
+                "500 Internal Server Error",
+                error_json(
+                    &format!("Database lock poisoned: {}", poisoned),
+                    start_time.elapsed(),
+                ),
+            );
+        }
+    };
+
+    match diff {
+        Ok(statements) => {
+            let mut body = String::from("{\"success\":true,\"statements\":[");
+            for (i, statement) in statements.iter().enumerate() {
+                if i > 0 {
+                    body.push(',');
+                }
+                body.push('"');
+                body.push_str(&escape_json_string(statement));
+                body.push('"');
+            }
+            body.push_str("],\"count\":");
+            body.push_str(&statements.len().to_string());
+            append_execution_time(&mut body, start_time.elapsed());
+            body.push('}');
+            HttpResponse::json("200 OK", body)
+        }
+        Err(err) => HttpResponse::json(
+            "400 Bad Request",
+            error_json(&format!("Invalid target DDL: {:?}", err), start_time.elapsed()),
+        ),
+    }
+}
+
+/// Column definitions and comments for `?table=`, or for every table.
+fn handle_schema_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    query_string: Option<&str>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let table_name = query_string
+        .and_then(|query| parse_url_query_params(query).remove("table"))
+        .filter(|table| !table.trim().is_empty())
+        .map(|table| normalize_table_name(&table));
+
+    let tables_json = {
+        let db = match state.read_database() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                return HttpResponse::json(
+                    "500 Internal Server Error",
+                    error_json(
+                        &format!("Database lock poisoned: {}", poisoned),
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+        };
+
+        let mut tables: Vec<&Table> = match &table_name {
+            Some(name) => match db.tables.get(name) {
+                Some(table) => vec![table],
+                None => {
+                    return HttpResponse::json(
+                        "404 Not Found",
+                        error_json(&format!("Table '{}' not found", name), start_time.elapsed()),
+                    );
+                }
+            },
+            None => db.tables.values().collect(),
+        };
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        tables.into_iter().map(table_schema_json).collect::<Vec<_>>().join(",")
+    };
+
+    let mut body = String::from("{");
+    body.push_str("\"status\":\"ok\",\"tables\":[");
+    body.push_str(&tables_json);
+    body.push(']');
+    append_execution_time(&mut body, start_time.elapsed());
+    body.push('}');
+
+    HttpResponse::json("200 OK", body)
+}
+
+fn table_schema_json(table: &Table) -> String {
+    let mut body = String::from("{\"name\":\"");
+    body.push_str(&escape_json_string(&table.name));
+    body.push_str("\",\"comment\":");
+    push_json_string_or_null(&mut body, table.comment.as_deref());
+    body.push_str(",\"durability\":\"");
+    body.push_str(match table.durability {
+        Durability::Sync => "SYNC",
+        Durability::Async => "ASYNC",
+    });
+    body.push_str("\",\"columns\":[");
+    for (i, column) in table.columns.iter().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        body.push_str("{\"name\":\"");
+        body.push_str(&escape_json_string(&column.name));
+        body.push_str("\",\"type\":\"");
+        body.push_str(column.data_type.as_str());
+        body.push_str("\",\"nullable\":");
+        body.push_str(&column.nullable.to_string());
+        body.push_str(",\"primary_key\":");
+        body.push_str(&column.primary_key.to_string());
+        body.push_str(",\"comment\":");
+        push_json_string_or_null(&mut body, column.comment.as_deref());
+        body.push('}');
+    }
+    body.push_str("]}");
+    body
+}
+
+/// `CREATE TABLE` statements for `?table=` (or every table) as plain SQL, quoted
+/// for `?dialect=` (standard by default) so they load into that database.
+fn handle_export_ddl_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    query_string: Option<&str>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let params = query_string.map(parse_url_query_params).unwrap_or_default();
+    let dialect = match resolve_dialect(params.get("dialect").map(String::as_str)) {
+        Ok(dialect) => dialect.unwrap_or(DetectedDialect::Standard),
+        Err(message) => {
+            return HttpResponse::json("400 Bad Request", error_json(&message, start_time.elapsed()));
+        }
+    };
+    let table_name = params
+        .get("table")
+        .filter(|table| !table.trim().is_empty())
+        .map(|table| normalize_table_name(table));
+
+    let ddl = match state.read_database() {
+        Ok(db) => db.export_ddl(table_name.as_deref(), &dialect),
+        Err(poisoned) => {
+            return HttpResponse::json(
+                "500 Internal Server Error",
+                error_json(
+                    &format!("Database lock poisoned: {}", poisoned),
+                    start_time.elapsed(),
+                ),
+            );
+        }
+    };
+
+    match ddl {
+        Ok(ddl) => HttpResponse::text("200 OK", &ddl),
+        Err(DatabaseError::TableNotFound(name)) => HttpResponse::json(
+            "404 Not Found",
+            error_json(&format!("Table '{}' not found", name), start_time.elapsed()),
+        ),
+        Err(err) => HttpResponse::json(
+            "500 Internal Server Error",
+            error_json(&format!("{:?}", err), start_time.elapsed()),
+        ),
+    }
+}
+
+const EXPORT_DIR: &str = ".mirseoDB/exports";
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Writes `?table=` to a CSV or JSON file under `.mirseoDB/exports/` for jobs on
+/// the same host, instead of sending every row over HTTP. Files older than
+/// `EXPORT_RETENTION_SECS` are removed first.
+fn handle_export_file_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    query_string: Option<&str>,
+    connection_database: Option<&str>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let auth_config = state.auth_config();
+    if !auth_config.is_setup_completed() {
+        return HttpResponse::json(
+            "503 Service Unavailable",
+            error_json(
+                "Database setup not completed. Please complete initial setup at /setup/init",
+                start_time.elapsed(),
+            ),
+        );
+    }
+
+    let params = query_string.map(parse_url_query_params).unwrap_or_default();
+    let Some(table_name) = params
+        .get("table")
+        .filter(|table| !table.trim().is_empty())
+        .map(|table| normalize_table_name(table))
+    else {
+        return HttpResponse::json(
+            "400 Bad Request",
+            error_json("Missing 'table' parameter", start_time.elapsed()),
+        );
+    };
+    let format = match params.get("format") {
+        None => ExportFormat::Csv,
+        Some(value) => match ExportFormat::parse(value) {
+            Some(format) => format,
+            None => {
+                return HttpResponse::json(
+                    "400 Bad Request",
+                    error_json(
+                        &format!("Unknown export format '{}' (use csv or json)", value),
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+        },
+    };
+
+    // The same email-based permission check a SELECT through /query gets
+    let sql = format!("SELECT * FROM {}", table_name);
+    if let Some(email) = params.get("email") {
+        if !auth_config.check_sql_permission(email, &sql) {
+            return HttpResponse::json(
+                "403 Forbidden",
+                error_json(
+                    &format!("SQL permission denied for user '{}'", email),
+                    start_time.elapsed(),
+                ),
+            );
+        }
+    }
+
+    if let Some(retention_secs) = state.config().export_retention_secs {
+        remove_expired_exports(Path::new(EXPORT_DIR), Duration::from_secs(retention_secs));
+    }
+
+    // Exports from the database that query would read, like /query does
+    let database_name = params.get("database").map(String::as_str).or(connection_database);
+    let database = match state
+        .parser
+        .parse(&sql)
+        .and_then(|statement| state.target_database(&statement, database_name))
+    {
+        Ok(database) => database,
+        Err(err) => {
+            let status = error_status(&err);
+            return HttpResponse::json(
+                status,
+                error_json(&database_error_to_string(err), start_time.elapsed()),
+            );
+        }
+    };
+
+    let export = {
+        let db = match state.timed_lock(|| database.read()) {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                return HttpResponse::json(
+                    "500 Internal Server Error",
+                    error_json(
+                        &format!("Database lock poisoned: {}", poisoned),
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+        };
+        match db.tables.get(&table_name) {
+            Some(table) => write_export_file(Path::new(EXPORT_DIR), table, format),
+            None => {
+                return HttpResponse::json(
+                    "404 Not Found",
+                    error_json(
+                        &format!("Table '{}' not found", table_name),
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+        }
+    };
+
+    match export {
+        Ok((path, row_count)) => {
+            println!(
+                "[MirseoDB] Exported {} rows of '{}' to {}",
+                row_count,
+                table_name,
+                path.display()
+            );
+            let mut body = String::from("{");
+            body.push_str("\"success\":true");
+            body.push_str(",\"path\":\"");
+            body.push_str(&escape_json_string(&path.display().to_string()));
+            body.push_str("\",\"format\":\"");
+            body.push_str(format.extension());
+            body.push_str("\",\"row_count\":");
+            body.push_str(&row_count.to_string());
+            append_execution_time(&mut body, start_time.elapsed());
+            body.push('}');
+            HttpResponse::json("200 OK", body)
+        }
+        Err(e) => HttpResponse::json(
+            "500 Internal Server Error",
+            error_json(&format!("Failed to write export: {}", e), start_time.elapsed()),
+        ),
+    }
+}
+
+/// Writes every row of `table` to a new file in `dir`, one line per row, and
+/// returns its path and the row count.
+fn write_export_file(
+    dir: &Path,
+    table: &Table,
+    format: ExportFormat,
+) -> std::io::Result<(PathBuf, usize)> {
+    fs::create_dir_all(dir)?;
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or(0);
+    // Quoted table names may hold `/` or `..`; only a safe stem reaches the path
+    let stem: String = table
+        .name
+        .to_lowercase()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() || ch == '-' { ch } else { '_' })
+        .collect();
+
+    // An export of the same table in the same millisecond gets a suffix
+    let (path, file) = (0..)
+        .find_map(|attempt: u32| {
+            let suffix = if attempt == 0 { String::new() } else { format!("_{}", attempt) };
+            let path = dir.join(format!(
+                "{}_{}{}.{}",
+                stem,
+                millis,
+                suffix,
+                format.extension()
+            ));
+            if path.parent() != Some(dir) {
+                return Some(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Export path leaves the export directory",
+                )));
+            }
+            match File::options().write(true).create_new(true).open(&path) {
+                Ok(file) => Some(Ok((path, file))),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .unwrap_or_else(|| Err(std::io::ErrorKind::AlreadyExists.into()))?;
+
+    let mut writer = BufWriter::new(file);
+    let mut line = String::new();
+    match format {
+        ExportFormat::Csv => {
+            for (i, column) in table.columns.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                push_csv_field(&mut line, &column.name);
+            }
+            writeln!(writer, "{}", line)?;
+            for row in &table.rows {
+                line.clear();
+                for (i, column) in table.columns.iter().enumerate() {
+                    if i > 0 {
+                        line.push(',');
+                    }
+                    match row.columns.get(&column.name) {
+                        None | Some(SqlValue::Null) => {}
+                        Some(SqlValue::Text(text)) => push_csv_field(&mut line, text),
+                        Some(value) => append_sql_value(&mut line, value),
+                    }
+                }
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        ExportFormat::Json => {
+            writer.write_all(b"[")?;
+            for (row_index, row) in table.rows.iter().enumerate() {
+                line.clear();
+                if row_index > 0 {
+                    line.push(',');
+                }
+                line.push_str("\n{");
+                for (i, column) in table.columns.iter().enumerate() {
+                    if i > 0 {
+                        line.push(',');
+                    }
+                    line.push('"');
+                    line.push_str(&escape_json_string(&column.name));
+                    line.push_str("\":");
+                    let value = row.columns.get(&column.name).unwrap_or(&SqlValue::Null);
+                    append_sql_value(&mut line, value);
+                }
+                line.push('}');
+                writer.write_all(line.as_bytes())?;
+            }
+            writer.write_all(b"\n]\n")?;
+        }
+    }
+    writer.flush()?;
+
+    Ok((path, table.rows.len()))
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn push_csv_field(out: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&value.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(value);
+    }
+}
+
+fn remove_expired_exports(dir: &Path, retention: Duration) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > retention);
+        if expired && fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        println!("[MirseoDB] Removed {} expired export files", removed);
+    }
+}
+
+fn handle_checkpoint_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), None) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let checkpoint = match state.lock_database() {
+        Ok(mut db) => {
+            if db.open_transaction().is_some() {
+                return HttpResponse::json(
+                    "409 Conflict",
+                    error_json(
+                        "CHECKPOINT cannot run while a transaction is open",
+                        start_time.elapsed(),
+                    ),
+                );
+            }
+            db.checkpoint()
+        }
+        Err(poisoned) => {
+            return HttpResponse::json(
+                "500 Internal Server Error",
+                error_json(
+                    &format!("Database lock poisoned: {}", poisoned),
+                    start_time.elapsed(),
+                ),
+            );
+        }
+    };
+
+    match checkpoint {
+        Ok(checkpoint) => {
+            println!(
+                "[MirseoDB] Checkpoint written: lsn {}, {} bytes",
+                checkpoint.lsn, checkpoint.bytes_written
+            );
+            let mut body = String::from("{");
+            body.push_str("\"success\":true");
+            body.push_str(",\"lsn\":");
+            body.push_str(&checkpoint.lsn.to_string());
+            body.push_str(",\"bytes_written\":");
+            body.push_str(&checkpoint.bytes_written.to_string());
+            append_execution_time(&mut body, start_time.elapsed());
+            body.push('}');
+            HttpResponse::json("200 OK", body)
+        }
+        Err(err) => HttpResponse::json(
+            "500 Internal Server Error",
+            error_json(&format!("{:?}", err), start_time.elapsed()),
+        ),
+    }
+}
+
+/// Re-reads `.mirseoDB/config.cfg`. Needs the auth token when one is configured
+/// and an admin `email` in the JSON body.
+fn handle_config_reload_request(
+    state: &Arc<ApiServerState>,
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> HttpResponse {
+    let start_time = Instant::now();
+
+    let email = std::str::from_utf8(body)
+        .ok()
+        .and_then(|text| extract_json_string_field(text, "email"));
+    let provided_token = extract_auth_token(headers, None);
+    if !state.is_authorized(provided_token.as_deref(), email.as_deref()) {
+        return HttpResponse::json(
+            "401 Unauthorized",
+            error_json("Invalid or missing auth token", start_time.elapsed()),
+        );
+    }
+
+    let auth_config = match state.reload_auth_config() {
+        Ok(config) => config,
+        Err(e) => {
+            return HttpResponse::json(
+                "500 Internal Server Error",
+                error_json(&format!("Auth config error: {}", e), start_time.elapsed()),
+            );
+        }
+    };
+    if let Some(message) = admin_denial("Configuration reload", &auth_config, email.as_deref()) {
+        return HttpResponse::json("403 Forbidden", error_json(&message, start_time.elapsed()));
+    }
+
+    let config = ConfigManager::load();
+    let sql_injection_protect = config.sql_injection_protect;
+    *state.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+    println!("[MirseoDB] Configuration reloaded");
+
+    let mut body = String::from("{");
+    body.push_str("\"success\":true");
+    body.push_str(",\"sql_injection_protect\":");
+    body.push_str(if sql_injection_protect { "true" } else { "false" });
+    append_execution_time(&mut body, start_time.elapsed());
+    body.push('}');
+    HttpResponse::json("200 OK", body)
+}
+
+/// Fields (with leading comma) for a table's row count and per-column statistics.
+fn column_statistics_json(statistics: &TableStatistics) -> String {
+    let mut body = String::new();
+    body.push_str(",\"row_count\":");
+    body.push_str(&statistics.row_count.to_string());
+
+    body.push_str(",\"columns\":[");
+    for (i, column) in statistics.columns.iter().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        body.push_str("{\"name\":\"");
+        body.push_str(&escape_json_string(&column.name));
+        body.push_str("\",\"null_count\":");
+        body.push_str(&column.null_count.to_string());
+        body.push_str(",\"distinct_estimate\":");
+        body.push_str(&column.distinct_estimate.to_string());
+        body.push_str(",\"dictionary_size\":");
+        match column.dictionary_size {
+            Some(size) => body.push_str(&size.to_string()),
+            None => body.push_str("null"),
+        }
+        for (key, bound) in [("min", &column.min), ("max", &column.max)] {
+            body.push_str(",\"");
+            body.push_str(key);
+            body.push_str("\":");
+            append_sql_value(&mut body, bound.as_ref().unwrap_or(&SqlValue::Null));
+        }
+        body.push('}');
+    }
+    body.push(']');
+    body
+}
+
+/// Fields (with leading comma) describing how the dialect of a query was chosen.
+fn dialect_explanation_json(explanation: &DialectExplanation) -> String {
+    let mut body = String::new();
+    body.push_str(",\"dialect\":\"");
+    body.push_str(explanation.dialect.name());
+    body.push_str("\",\"confidence\":");
+    body.push_str(&format!("{:.3}", explanation.confidence));
+
+    body.push_str(",\"scores\":{");
+    for (i, (dialect, score)) in explanation.scores.iter().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        body.push('"');
+        body.push_str(dialect.name());
+        body.push_str("\":");
+        body.push_str(&format!("{:.3}", score));
+    }
+    body.push('}');
+
+    body.push_str(",\"matches\":[");
+    for (i, keyword_match) in explanation.matches.iter().enumerate() {
+        if i > 0 {
+            body.push(',');
+        }
+        body.push_str("{\"keyword\":\"");
+        body.push_str(&escape_json_string(&keyword_match.keyword));
+        body.push_str("\",\"dialect\":\"");
+        body.push_str(keyword_match.dialect.name());
+        body.push_str("\",\"weight\":");
+        body.push_str(&format!("{:.3}", keyword_match.weight));
+        body.push('}');
+    }
+    body.push(']');
+    body
+}
+
+fn parser_metrics_json(snapshot: &ParserMetricsSnapshot) -> String {
+    let metrics = &snapshot.metrics;
+    let mut body = String::from("{");
+    body.push_str("\"total_queries\":");
+    body.push_str(&metrics.total_queries.to_string());
+    body.push_str(",\"cache_hits\":");
+    body.push_str(&metrics.cache_hits.to_string());
+    body.push_str(",\"cache_misses\":");
+    body.push_str(&metrics.cache_misses.to_string());
+    body.push_str(",\"avg_parse_time_ns\":");
+    body.push_str(&metrics.avg_parse_time_ns.to_string());
+    body.push_str(",\"dialect_cache_hits\":");
+    body.push_str(&snapshot.dialect_cache_hits.to_string());
+    body.push_str(",\"dialect_cache_misses\":");
+    body.push_str(&snapshot.dialect_cache_misses.to_string());
+    body.push_str(",\"dialect_cache_entries\":");
+    body.push_str(&snapshot.dialect_cache_entries.to_string());
+    let dialect_lookups = snapshot.dialect_cache_hits + snapshot.dialect_cache_misses;
+    let hit_rate = if dialect_lookups == 0 {
+        0.0
+    } else {
+        snapshot.dialect_cache_hits as f64 / dialect_lookups as f64
+    };
+    body.push_str(",\"dialect_cache_hit_rate\":");
+    body.push_str(&format!("{:.3}", hit_rate));
+    body.push('}');
+    body
+}
+
+fn cache_stats_json(stats: &CacheStats) -> String {
+    let mut body = String::from("{");
+    body.push_str("\"tables\":");
+    body.push_str(&stats.tables.to_string());
+    body.push_str(",\"column_cache_entries\":");
+    body.push_str(&stats.column_cache_entries.to_string());
+    body.push_str(",\"query_cache_entries\":");
+    body.push_str(&stats.query_cache_entries.to_string());
+    body.push_str(",\"bloom_filters\":");
+    body.push_str(&stats.bloom_filters.to_string());
+    body.push('}');
+    body
+}
+
+/// The version and the commit and build time `build.rs` recorded.
+fn handle_version_request() -> HttpResponse {
+    let build_timestamp: i64 = env!("MIRSEODB_BUILD_TIMESTAMP").parse().unwrap_or(0);
+    let build_time = OffsetDateTime::from_unix_timestamp(build_timestamp)
+        .ok()
+        .and_then(|dt| dt.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "1970-01-01T00:00:00Z".to_string());
+
+    let mut body = String::from("{");
+    body.push_str("\"version\":\"");
+    body.push_str(&escape_json_string(env!("CARGO_PKG_VERSION")));
+    body.push_str("\",\"git_commit\":\"");
+    body.push_str(&escape_json_string(env!("MIRSEODB_GIT_COMMIT")));
+    body.push_str("\",\"build_timestamp\":");
+    body.push_str(&build_timestamp.to_string());
+    body.push_str(",\"build_time\":\"");
+    body.push_str(&build_time);
+    body.push('"');
+    // There are no optional cargo features: both codecs are always compiled in,
+    // and the server speaks plain HTTP only
+    body.push_str(",\"features\":{\"tls\":false,\"file_compression\":[\"none\",\"lz\"]");
+    body.push_str(",\"response_gzip\":true}");
+    body.push('}');
+
+    HttpResponse::json("200 OK", body)
 }
 
-fn handle_time_request() -> HttpResponse {
+fn handle_time_request(query_string: Option<&str>) -> HttpResponse {
+    let requested_offset = match query_string
+        .and_then(|query| parse_url_query_params(query).get("tz").cloned())
+    {
+        Some(raw) => match parse_utc_offset(&raw) {
+            Some(offset) => Some(offset),
+            None => {
+                return HttpResponse::json(
+                    "400 Bad Request",
+                    error_json(
+                        "Invalid 'tz' parameter; expected an offset like +09:00, -05:30 or Z",
+                        Duration::ZERO,
+                    ),
+                );
+            }
+        },
+        None => None,
+    };
+
     let now = SystemTime::now();
     let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
     let unix_seconds = since_epoch.as_secs();
@@ -1763,9 +4814,11 @@ fn handle_time_request() -> HttpResponse {
     const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
     let ntp_timestamp = unix_seconds + NTP_UNIX_OFFSET;
 
-    let iso8601 = OffsetDateTime::from_unix_timestamp(unix_seconds as i64)
+    let utc_now = OffsetDateTime::from_unix_timestamp(unix_seconds as i64)
         .ok()
-        .and_then(|dt| dt.replace_nanosecond(nano_offset).ok())
+        .and_then(|dt| dt.replace_nanosecond(nano_offset).ok());
+
+    let iso8601 = utc_now
         .and_then(|dt| dt.format(&Rfc3339).ok())
         .unwrap_or_else(|| "1970-01-01T00:00:00Z".to_string());
 
@@ -1782,26 +4835,82 @@ fn handle_time_request() -> HttpResponse {
     body.push_str(",\"iso8601\":\"");
     body.push_str(&iso8601);
     body.push_str("\"");
+
+    if let Some(offset) = requested_offset {
+        let local_iso8601 = utc_now
+            .map(|dt| dt.to_offset(offset))
+            .and_then(|dt| dt.format(&Rfc3339).ok())
+            .unwrap_or_default();
+
+        body.push_str(",\"tz_offset\":\"");
+        body.push_str(&format_utc_offset(offset));
+        body.push('"');
+        body.push_str(",\"local_iso8601\":\"");
+        body.push_str(&local_iso8601);
+        body.push('"');
+    }
+
     body.push('}');
 
     HttpResponse::json("200 OK", body)
 }
 
+fn parse_utc_offset(value: &str) -> Option<UtcOffset> {
+    let trimmed = value.trim_end();
+    if trimmed.eq_ignore_ascii_case("Z") || trimmed.eq_ignore_ascii_case("UTC") {
+        return Some(UtcOffset::UTC);
+    }
+
+    // An unencoded '+' in the query string arrives here as a space after url_decode
+    let (sign, digits) = match trimmed.chars().next()? {
+        '+' | ' ' => (1i8, &trimmed[1..]),
+        '-' => (-1i8, &trimmed[1..]),
+        _ => return None,
+    };
+
+    let (hours_str, minutes_str) = match digits.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+
+    if hours_str.is_empty() || !hours_str.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    if !minutes_str.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+
+    let hours: i8 = hours_str.parse().ok()?;
+    let minutes: i8 = minutes_str.parse().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+fn format_utc_offset(offset: UtcOffset) -> String {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, hours.abs(), minutes.abs())
+}
+
 fn handle_get_query_request(
     state: &Arc<ApiServerState>,
     headers: &HashMap<String, String>,
     path: &str,
+    peer_addr: Option<SocketAddr>,
+    connection_database: &mut Option<String>,
 ) -> HttpResponse {
     let start_time = Instant::now();
 
-    let sql = if let Some(query_start) = path.find('?') {
-        let query_string = &path[query_start + 1..];
-        parse_url_query_params(query_string).get("sql").cloned()
-    } else {
-        None
+    let params = match path.find('?') {
+        Some(query_start) => parse_url_query_params(&path[query_start + 1..]),
+        None => HashMap::new(),
     };
 
-    let sql = match sql {
+    let sql = match params.get("sql").cloned() {
         Some(s) if !s.is_empty() => s,
         _ => {
             return HttpResponse::json(
@@ -1812,52 +4921,83 @@ fn handle_get_query_request(
     };
 
     let request = QueryRequest {
-        sql: url_decode(&sql),
+        statements: batch_statements(&sql),
+        sql,
         auth_token: extract_auth_token(headers, None),
         totp_token: None,
         email: None,
+        include_schema: query_flag(&params, "include_schema"),
+        with_count: query_flag(&params, "with_count"),
+        dialect: params.get("dialect").cloned(),
+        debug: query_flag(&params, "debug"),
+        transaction_id: params.get("transaction_id").cloned(),
+        params: Vec::new(),
+        database: params.get("database").cloned(),
+        continue_on_error: query_flag(&params, "continue_on_error"),
     };
 
-    execute_query_request(state, request, start_time, false, headers)
+    execute_query_request(
+        state,
+        request,
+        start_time,
+        false,
+        headers,
+        peer_addr,
+        connection_database,
+    )
 }
 
+fn query_flag(params: &HashMap<String, String>, name: &str) -> bool {
+    params
+        .get(name)
+        .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Splits a raw query string into decoded key/value pairs. Pairs are split on the
+/// first `=` only and decoded afterwards, so encoded `&`/`=` inside values survive.
 fn parse_url_query_params(query_string: &str) -> HashMap<String, String> {
     let mut params = HashMap::new();
 
     for pair in query_string.split('&') {
-        if let Some((key, value)) = pair.split_once('=') {
-            params.insert(key.to_string(), value.to_string());
+        if pair.is_empty() {
+            continue;
         }
+
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(url_decode(key), url_decode(value));
     }
 
     params
 }
 
 fn url_decode(input: &str) -> String {
-    let mut result = String::new();
-    let mut chars = input.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        if ch == '%' {
-            if let (Some(hex1), Some(hex2)) = (chars.next(), chars.next()) {
-                if let Ok(byte) = u8::from_str_radix(&format!("{}{}", hex1, hex2), 16) {
-                    result.push(byte as char);
-                } else {
-                    result.push(ch);
-                    result.push(hex1);
-                    result.push(hex2);
-                }
-            } else {
-                result.push(ch);
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len()
+                && bytes[index + 1].is_ascii_hexdigit()
+                && bytes[index + 2].is_ascii_hexdigit() =>
+            {
+                let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or("0");
+                decoded.push(u8::from_str_radix(hex, 16).unwrap_or(0));
+                index += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            other => {
+                decoded.push(other);
+                index += 1;
             }
-        } else if ch == '+' {
-            result.push(' ');
-        } else {
-            result.push(ch);
         }
     }
 
-    result
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 fn execute_query_request(
@@ -1866,18 +5006,29 @@ fn execute_query_request(
     start_time: Instant,
     sanitized_applied: bool,
     headers: &HashMap<String, String>,
+    peer_addr: Option<SocketAddr>,
+    connection_database: &mut Option<String>,
 ) -> HttpResponse {
     let QueryRequest {
         sql: mut sql_text,
         auth_token: request_token,
         totp_token: request_totp,
         email: request_email,
+        include_schema,
+        with_count,
+        dialect: requested_dialect,
+        debug,
+        transaction_id,
+        params,
+        database: request_database,
+        statements,
+        continue_on_error,
     } = request;
 
     let provided_token = extract_auth_token(headers, request_token.clone());
 
     let mut sanitized_applied = sanitized_applied;
-    let config = ConfigManager::load();
+    let config = state.config();
     if config.sql_injection_protect {
         if let Some(filtered) = sanitize_sql_input(&sql_text) {
             sanitized_applied = true;
@@ -1886,25 +5037,11 @@ fn execute_query_request(
         }
     }
 
-    if let Some(expected) = state.auth_token.as_ref() {
-        match provided_token {
-            Some(ref token) if token == expected => {}
-            _ => {
-                return generate_random_auth_error();
-            }
-        }
+    if !state.is_authorized(provided_token.as_deref(), request_email.as_deref()) {
+        return generate_random_auth_error();
     }
 
-    let auth_config = match AuthConfig::load() {
-        Ok(config) => config,
-        Err(e) => {
-            let mut body = error_json(&format!("Auth config error: {}", e), start_time.elapsed());
-            if sanitized_applied {
-                insert_sanitized_flag(&mut body);
-            }
-            return HttpResponse::json("500 Internal Server Error", body);
-        }
-    };
+    let auth_config = state.auth_config();
 
     if !auth_config.is_setup_completed() {
         let mut body = error_json(
@@ -1917,7 +5054,7 @@ fn execute_query_request(
         return HttpResponse::json("503 Service Unavailable", body);
     }
 
-    if let Some(email) = request_email.as_ref() {
+    if let Some(email) = request_email.as_ref().filter(|_| statements.is_empty()) {
         if !auth_config.check_sql_permission(email, &sql_text) {
             let user_role = auth_config.get_user_role(email).unwrap_or("unknown");
             let mut body = error_json(
@@ -1934,8 +5071,58 @@ fn execute_query_request(
         }
     }
 
-    let statement = match state.parser.parse(&sql_text) {
-        Ok(stmt) => stmt,
+    let dialect = match resolve_dialect(requested_dialect.as_deref()) {
+        Ok(dialect) => dialect,
+        Err(message) => {
+            let mut body = error_json(&message, start_time.elapsed());
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json("400 Bad Request", body);
+        }
+    };
+    let memory_limit_mb = match resolve_memory_limit(headers) {
+        Ok(limit_mb) => limit_mb,
+        Err(message) => {
+            let mut body = error_json(&message, start_time.elapsed());
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json("400 Bad Request", body);
+        }
+    };
+
+    if !statements.is_empty() {
+        if !params.is_empty() {
+            return HttpResponse::json(
+                "400 Bad Request",
+                error_json(
+                    "'params' cannot be used with a batch of statements",
+                    start_time.elapsed(),
+                ),
+            );
+        }
+        let batch = QueryBatch {
+            state,
+            auth_config: &auth_config,
+            connection: ConnectionInfo::new(peer_addr, request_email.clone(), false),
+            totp: request_totp.as_deref(),
+            transaction_id,
+            database: request_database.or_else(|| connection_database.clone()),
+            connection_database,
+            dialect,
+            memory_limit_mb,
+            sanitize: config.sql_injection_protect,
+        };
+        return batch.run(&statements, continue_on_error, debug, start_time);
+    }
+
+    let mut connection = ConnectionInfo::new(peer_addr, request_email.clone(), false);
+    let statement = match state.parser.parse_with_params(&sql_text, dialect.clone(), &params) {
+        Ok((stmt, dialect)) => {
+            connection.dialect = Some(dialect);
+            stmt
+        }
         Err(err) => {
             let mut body = error_json(&format!("SQL parse error: {:?}", err), start_time.elapsed());
             if sanitized_applied {
@@ -1945,6 +5132,14 @@ fn execute_query_request(
         }
     };
 
+    if let Some(message) = admin_only_denial(&statement, &auth_config, request_email.as_deref()) {
+        let mut body = error_json(&message, start_time.elapsed());
+        if sanitized_applied {
+            insert_sanitized_flag(&mut body);
+        }
+        return HttpResponse::json("403 Forbidden", body);
+    }
+
     if statement.requires_2fa() {
         let user_id = "default";
 
@@ -1990,8 +5185,47 @@ fn execute_query_request(
         }
     }
 
-    let execution_result = {
-        let mut db = match state.database.lock() {
+    let database_name = request_database.or_else(|| connection_database.clone());
+    let database = match state.target_database(&statement, database_name.as_deref()) {
+        Ok(database) => database,
+        Err(err) => {
+            let status = error_status(&err);
+            let mut body = error_json(&database_error_to_string(err), start_time.elapsed());
+            if sanitized_applied {
+                insert_sanitized_flag(&mut body);
+            }
+            return HttpResponse::json(status, body);
+        }
+    };
+    let database_change = DatabaseChange::of(&statement);
+
+    let is_select = matches!(
+        statement,
+        SqlStatement::Select { .. }
+            | SqlStatement::ComplexSelect { .. }
+            | SqlStatement::Union { .. }
+    );
+
+    let schema_target = match &statement {
+        SqlStatement::Select {
+            table_name,
+            columns,
+            column_aliases,
+            aggregates,
+            ..
+        } if include_schema => {
+            let mut columns = aliased_columns(columns, column_aliases);
+            columns.extend(aggregates.iter().map(|aggregate| aggregate.name.clone()));
+            Some((table_name.clone(), columns))
+        }
+        _ => None,
+    };
+
+    let count_target = (with_count && is_select).then(|| statement.clone());
+
+    let (execution_result, schema, total_count, began_transaction) = {
+        let lock_start = Instant::now();
+        let mut db = match state.lock_database_for(&database, &statement) {
             Ok(guard) => guard,
             Err(poisoned) => {
                 return HttpResponse::json(
@@ -2003,20 +5237,76 @@ fn execute_query_request(
                 );
             }
         };
+        if let Err(message) = check_transaction_session(&mut db, transaction_id.as_deref()) {
+            return HttpResponse::json("409 Conflict", error_json(&message, start_time.elapsed()));
+        }
 
-        db.execute(statement)
+        let lock_wait = lock_start.elapsed();
+        let execute_start = Instant::now();
+        let operation = statement.get_operation_name();
+        let (result, began_transaction) = execute_for_session(
+            &mut db,
+            statement,
+            &sql_text,
+            &params,
+            dialect.as_ref(),
+            memory_limit_mb,
+        );
+        connection.log_audit(operation, if result.is_ok() { "ok" } else { "error" });
+        let schema = match (&result, schema_target) {
+            (Ok(rows), Some((table_name, columns))) => {
+                Some(db.result_schema(&table_name, &columns, rows))
+            }
+            _ => None,
+        };
+        let total_count = match (&result, count_target) {
+            (Ok(_), Some(statement)) => db.count_matches(&statement).ok(),
+            _ => None,
+        };
+        connection.record_timing(lock_wait, execute_start.elapsed());
+        (result, schema, total_count, began_transaction)
     };
+    if execution_result.is_ok() {
+        state.finish_database_change(database_change, connection_database);
+    }
 
     match execution_result {
         Ok(rows) => {
             let elapsed = start_time.elapsed();
+            let rows_json = rows_to_json(&rows);
+            let etag = if is_select {
+                // A new total must not be answered with 304 for an unchanged page
+                let etag = match total_count {
+                    Some(total_count) => result_etag(&format!("{}{}", rows_json, total_count)),
+                    None => result_etag(&rows_json),
+                };
+                if etag_matches(headers, &etag) {
+                    return HttpResponse::not_modified(etag);
+                }
+                Some(etag)
+            } else {
+                None
+            };
+
             let mut body = String::from("{");
             body.push_str("\"status\":\"ok\"");
             body.push_str(",\"status_code\":200");
             body.push_str(",\"row_count\":");
             body.push_str(&rows.len().to_string());
+            if let Some(total_count) = total_count {
+                body.push_str(",\"total_count\":");
+                body.push_str(&total_count.to_string());
+            }
             body.push_str(",\"rows\":");
-            body.push_str(&rows_to_json(&rows));
+            body.push_str(&rows_json);
+            if let Some(schema) = schema.as_ref() {
+                body.push_str(",\"schema\":");
+                body.push_str(&schema_json(schema));
+            }
+            if let Some(transaction_id) = began_transaction.as_deref() {
+                body.push_str(",\"transaction_id\":");
+                push_json_string_or_null(&mut body, Some(transaction_id));
+            }
             if rows.is_empty() {
                 body.push_str(",\"message\":\"Command executed successfully\"");
             }
@@ -2025,8 +5315,15 @@ fn execute_query_request(
             if sanitized_applied {
                 insert_sanitized_flag(&mut body);
             }
+            if debug {
+                connection.insert_debug(&mut body);
+            }
 
-            HttpResponse::json("200 OK", body)
+            let response = HttpResponse::json("200 OK", body);
+            match etag {
+                Some(etag) => response.with_header("ETag", etag),
+                None => response,
+            }
         }
         Err(err) => {
             let elapsed = start_time.elapsed();
@@ -2039,12 +5336,1005 @@ fn execute_query_request(
                 }
             }
 
+            let status = error_status(&err);
             let mut body = error_json(&database_error_to_string(err), elapsed);
             if sanitized_applied {
                 insert_sanitized_flag(&mut body);
             }
+            if debug {
+                connection.insert_debug(&mut body);
+            }
+
+            HttpResponse::json(status, body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bootstrap::{run_server, ServerConfig, DEFAULT_CORS_ORIGIN};
+    use crate::configuration::DEFAULT_GZIP_MIN_BYTES;
+    use crate::gzip::tests::gunzip;
+    use crate::persistence::StorageEngine;
+
+    fn json_number(body: &str, key: &str) -> u64 {
+        let start = body.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3;
+        let digits: String = body[start..].chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().unwrap()
+    }
+
+    #[test]
+    fn test_metrics_counters_advance_with_queries() {
+        let mut db = Database::in_memory("metrics_test");
+        db.query("CREATE TABLE hits (id INTEGER PRIMARY KEY) DURABILITY ASYNC")
+            .unwrap();
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            Some("secret".to_string()),
+            "*".to_string(),
+            TwoFactorAuth::new(),
+        ));
+        let headers: HashMap<String, String> =
+            [("authorization".to_string(), "Bearer secret".to_string())].into();
+
+        assert!(handle_metrics_request(&state, &HashMap::new(), false)
+            .status
+            .starts_with("401"));
+        let before = handle_metrics_request(&state, &headers, false).body;
+        for _ in 0..3 {
+            state.parser.parse("SELECT id FROM hits WHERE id = 1").unwrap();
+        }
+        let after = handle_metrics_request(&state, &headers, false).body;
+
+        assert_eq!(
+            json_number(&after, "total_queries"),
+            json_number(&before, "total_queries") + 3
+        );
+        assert!(json_number(&after, "cache_hits") > json_number(&before, "cache_hits"));
+        assert_eq!(json_number(&after, "tables"), 1);
+        assert!(after.contains("\"dialect_cache_hit_rate\":"));
+    }
+
+    #[test]
+    fn test_lock_metrics_count_waiters_and_bucket_waits() {
+        let metrics = LockMetrics::new();
+        for wait_ms in [0, 5, 50, 500] {
+            metrics.record_wait(Duration::from_millis(wait_ms));
+        }
+        let json = metrics.to_json();
+        assert!(json.contains("\"acquisitions\":4,\"waiting\":0"), "{}", json);
+        assert!(json.contains("\"avg_wait_ms\":138.750,\"max_wait_ms\":500.000"), "{}", json);
+        assert!(json.contains("{\"lt_1\":1,\"lt_10\":1,\"lt_100\":1,\"ge_100\":1}"), "{}", json);
+
+        // A caller blocked on the lock counts as waiting until it gets the lock
+        let state = ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(Database::in_memory("lock"))))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        );
+        let held = state.database.write().unwrap();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| drop(state.lock_database().unwrap()));
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while state.lock_metrics.waiting.load(Ordering::Relaxed) == 0 {
+                assert!(Instant::now() < deadline, "the waiter never blocked");
+                thread::sleep(Duration::from_millis(1));
+            }
+            drop(held);
+            waiter.join().unwrap();
+        });
+        assert_eq!(state.lock_metrics.waiting.load(Ordering::Relaxed), 0);
+        assert_eq!(state.lock_metrics.acquisitions.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_throttled_log_counts_suppressed_lines() {
+        let log = ThrottledLog::new(Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert_eq!(log.admit(start), Some(0));
+        assert_eq!(log.admit(start + Duration::from_secs(1)), None);
+        assert_eq!(log.admit(start + Duration::from_secs(9)), None);
+        assert_eq!(log.admit(start + Duration::from_secs(10)), Some(2));
+        assert_eq!(log.admit(start + Duration::from_secs(11)), None);
+    }
+
+    #[test]
+    fn test_query_params_keep_encoded_separators() {
+        let params = parse_url_query_params(
+            "sql=SELECT%20*%20FROM%20notes%20WHERE%20note%20%3D%20%27a%26b%27&x=1",
+        );
+        assert_eq!(
+            params.get("sql").map(String::as_str),
+            Some("SELECT * FROM notes WHERE note = 'a&b'")
+        );
+        assert_eq!(params.get("x").map(String::as_str), Some("1"));
+    }
+
+    #[test]
+    fn test_query_params_split_on_first_equals() {
+        let params = parse_url_query_params("sql=SELECT+*+FROM+t+WHERE+a+=+b%3Dc");
+        assert_eq!(
+            params.get("sql").map(String::as_str),
+            Some("SELECT * FROM t WHERE a = b=c")
+        );
+    }
+
+    #[test]
+    fn test_url_decode_utf8_and_invalid_escapes() {
+        assert_eq!(url_decode("%ED%95%9C%EA%B8%80"), "한글");
+        assert_eq!(url_decode("100%25+done"), "100% done");
+        assert_eq!(url_decode("50%+off%2"), "50% off%2");
+    }
+
+    #[test]
+    fn test_content_length_rejects_oversized_and_conflicting_values() {
+        let oversized = b"POST /query HTTP/1.1\r\nContent-Length: 999999999999999999999\r\n\r\n";
+        assert!(matches!(
+            expected_request_len(oversized),
+            Ok(Some(len)) if len > MAX_REQUEST_SIZE
+        ));
+
+        assert!(matches!(
+            parse_content_length(b"Content-Length: 10\r\nContent-Length: 12"),
+            Err(RequestReadError::InvalidFraming(_))
+        ));
+        assert!(matches!(
+            parse_content_length(b"Content-Length: +10"),
+            Err(RequestReadError::InvalidFraming(_))
+        ));
+        assert!(matches!(
+            parse_content_length(b"Content-Length: 10\r\nContent-Length: 10"),
+            Ok(Some(10))
+        ));
+    }
+
+    #[test]
+    fn test_chunked_query_request_is_decoded() {
+        let request = "POST /query HTTP/1.1\r\nContent-Type: application/json\r\n\
+                       Transfer-Encoding: chunked\r\n\r\n\
+                       9\r\n{\"sql\": \"\r\n\
+                       15;ext=1\r\nSELECT * FROM users\"}\r\n\
+                       0\r\nX-Trailer: dropped\r\n\r\n";
+        let bytes = request.as_bytes();
+        let last_chunk = request.find("0\r\n").unwrap();
+        assert!(matches!(
+            request_progress(&bytes[..last_chunk]),
+            Ok(RequestProgress::Incomplete)
+        ));
+
+        let Ok(RequestProgress::Dechunked(decoded, len)) = request_progress(bytes) else {
+            panic!("chunked request was not decoded");
+        };
+        assert_eq!(len, bytes.len());
+        let (header_text, body) = split_request(&decoded).unwrap();
+        let headers = parse_headers(header_text.lines().skip(1)).unwrap();
+        assert_eq!(headers.get("content-length").map(String::as_str), Some("30"));
+        assert!(!headers.contains_key("transfer-encoding"));
+        let query = parse_query_payload(body, false).unwrap();
+        assert_eq!(query.sql, "SELECT * FROM users");
+    }
+
+    #[test]
+    fn test_chunked_body_limits_and_framing_errors() {
+        let head = "POST /query HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let oversized = format!("{}{:x}\r\n", head, MAX_REQUEST_SIZE);
+        assert!(matches!(
+            request_progress(oversized.as_bytes()),
+            Err(RequestReadError::TooLarge)
+        ));
+        for body in ["zz\r\n", "2\r\nabcd\r\n"] {
+            let request = format!("{}{}", head, body);
+            assert!(matches!(
+                request_progress(request.as_bytes()),
+                Err(RequestReadError::InvalidFraming(_))
+            ));
+        }
+
+        let gzip = b"POST /query HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n";
+        assert!(matches!(request_progress(gzip), Err(RequestReadError::InvalidFraming(_))));
+    }
+
+    #[test]
+    fn test_headers_reject_ambiguous_framing() {
+        let smuggled = "Content-Length: 4\r\nTransfer-Encoding: chunked";
+        assert!(parse_headers(smuggled.lines()).is_err());
+
+        let conflicting = "Content-Length: 4\r\ncontent-length : 5";
+        assert!(parse_headers(conflicting.lines()).is_err());
+
+        let headers = parse_headers("Content-Length: 4\r\nContent-Length: 4".lines()).unwrap();
+        assert_eq!(headers.get("content-length").map(String::as_str), Some("4"));
+    }
+
+    #[test]
+    fn test_export_file_writes_csv_and_json() {
+        let mut db = Database::in_memory("export_file_test");
+        db.query("CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO notes (id, body) VALUES (1, 'plain')").unwrap();
+        db.query("INSERT INTO notes (id, body) VALUES (2, 'say \"hi\"')").unwrap();
+        db.query("INSERT INTO notes (id) VALUES (3)").unwrap();
+        let dir = std::env::temp_dir().join("mirseodb_export_file_test");
+        let table = &db.tables["NOTES"];
+
+        let (path, row_count) = write_export_file(&dir, table, ExportFormat::Csv).unwrap();
+        assert_eq!(row_count, 3);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "id,body\n1,plain\n2,\"say \"\"hi\"\"\"\n3,\n"
+        );
+
+        let (path, _) = write_export_file(&dir, table, ExportFormat::Json).unwrap();
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(json.contains("{\"id\":3,\"body\":null}"));
+        // Exports never overwrite each other
+        let (again, _) = write_export_file(&dir, table, ExportFormat::Json).unwrap();
+        assert_ne!(again, path);
+        assert!(fs::read_to_string(&path).unwrap().contains("null"));
+
+        // A quoted name cannot climb out of the export directory
+        let mut escaping = table.clone();
+        escaping.name = "../../x/y".to_string();
+        let (path, _) = write_export_file(&dir, &escaping, ExportFormat::Csv).unwrap();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        assert!(path.file_name().unwrap().to_str().unwrap().starts_with("______x_y_"));
+
+        remove_expired_exports(&dir, Duration::ZERO);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_params_are_typed() {
+        let body = r#"{"sql":"INSERT INTO t VALUES (?, ?, ?, ?, ?)",
+            "params": [7, -1.5e2, "it's \"x\" \u00e9", true, null]}"#;
+        let request = parse_query_request_json(body).unwrap();
+        assert!(matches!(
+            request.params.as_slice(),
+            [
+                SqlValue::Integer(7),
+                SqlValue::Float(f),
+                SqlValue::Text(text),
+                SqlValue::Boolean(true),
+                SqlValue::Null,
+            ] if *f == -150.0 && &**text == "it's \"x\" \u{e9}"
+        ));
+
+        assert!(parse_query_request_json(r#"{"sql":"SELECT 1"}"#).unwrap().params.is_empty());
+        assert!(parse_query_request_json(r#"{"sql":"SELECT 1","params":[]}"#).is_ok());
+        assert!(parse_query_request_json(r#"{"sql":"SELECT 1","params":[[1]]}"#).is_err());
+        assert!(parse_query_request_json(r#"{"sql":"SELECT 1","params":"1"}"#).is_err());
+
+        assert_eq!(extract_json_u32_field(r#"{"digits": 8}"#, "digits"), Ok(Some(8)));
+        assert_eq!(extract_json_u32_field(r#"{"user_id":"a"}"#, "digits"), Ok(None));
+        assert!(extract_json_u32_field(r#"{"digits":4294967302}"#, "digits").is_err());
+        assert!(extract_json_u32_field(r#"{"digits":"8"}"#, "digits").is_err());
+    }
+
+    #[test]
+    fn test_batch_stops_at_failure_unless_continuing() {
+        let request = parse_query_request_json(
+            r#"{"sql":"INSERT INTO t VALUES ('a;b'); SELECT * FROM t;","continue_on_error":true}"#,
+        )
+        .unwrap();
+        assert_eq!(request.statements, ["INSERT INTO t VALUES ('a;b')", "SELECT * FROM t"]);
+        assert!(request.continue_on_error);
+        let request = parse_query_request_json(r#"{"statements":["SELECT 1","SELECT 2"]}"#);
+        assert_eq!(request.unwrap().statements.len(), 2);
+        assert!(parse_query_request_json(r#"{"sql":"SELECT * FROM t;"}"#)
+            .unwrap()
+            .statements
+            .is_empty());
+        assert!(parse_query_request_json(r#"{"statements":["SELECT 1", 2]}"#).is_err());
+
+        let statements: Vec<String> = [
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC",
+            "INSERT INTO items (id, name) VALUES (1, 'a')",
+            "CREATE TABLE items (id INTEGER) DURABILITY ASYNC",
+            "INSERT INTO items (id, name) VALUES (2, 'b')",
+            "DROP TABLE items",
+        ]
+        .iter()
+        .map(|sql| sql.to_string())
+        .collect();
+        let auth_config = AuthConfig::default();
+        let run = |continue_on_error: bool| {
+            let db = Database::in_memory("batch_test");
+            let state = ApiServerState::new(
+                Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+                Arc::new(AnySQL::new()),
+                Arc::new(RouteConfig {
+                    routes: HashMap::new(),
+                }),
+                None,
+                DEFAULT_CORS_ORIGIN.to_string(),
+                TwoFactorAuth::new(),
+            );
+            let batch = QueryBatch {
+                state: &state,
+                auth_config: &auth_config,
+                connection: ConnectionInfo::new(None, None, false),
+                totp: None,
+                transaction_id: None,
+                database: None,
+                connection_database: &mut None,
+                dialect: None,
+                memory_limit_mb: None,
+                sanitize: true,
+            };
+            let response = batch.run(&statements, continue_on_error, false, Instant::now());
+            let rows = state.database.write().unwrap().query("SELECT * FROM items").unwrap();
+            (response, rows.len())
+        };
+
+        // The duplicate CREATE stops the batch before the second INSERT
+        let (response, rows) = run(false);
+        assert_eq!(response.status, "409 Conflict");
+        assert_eq!(json_number(&response.body, "executed"), 3);
+        assert_eq!(json_number(&response.body, "failed_index"), 2);
+        assert!(response.body.contains(
+            "{\"index\":2,\"status\":\"error\",\"status_code\":409,\
+             \"error\":\"Table already exists: ITEMS\"}"
+        ));
+        assert_eq!(rows, 1);
+
+        // Every statement runs; DROP TABLE still needs 2FA
+        let (response, rows) = run(true);
+        assert_eq!(response.status, "200 OK");
+        assert!(response.body.starts_with("{\"status\":\"error\",\"status_code\":200"));
+        assert_eq!(json_number(&response.body, "executed"), 5);
+        assert_eq!(json_number(&response.body, "error_count"), 2);
+        assert_eq!(json_number(&response.body, "failed_index"), 2);
+        assert!(response.body.contains("{\"index\":3,\"status\":\"ok\",\"row_count\":0"));
+        assert!(response.body.contains("\"status_code\":403,\"error\":\"2FA required for DROP"));
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn test_get_runs_batches_and_forwarding_rejects_them() {
+        let mut db = Database::in_memory("get_batch");
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        ));
+        state.set_auth_config(AuthConfig {
+            setup_completed: true,
+            ..Default::default()
+        });
+
+        let path = "/query?sql=INSERT%20INTO%20items%20(id)%20VALUES%20(1);\
+                    INSERT%20INTO%20items%20(id)%20VALUES%20(2)";
+        let response = handle_get_query_request(&state, &HashMap::new(), path, None, &mut None);
+        assert_eq!(response.status, "200 OK");
+        assert_eq!(json_number(&response.body, "executed"), 2);
+        let rows = state.database.write().unwrap().query("SELECT * FROM items").unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        headers.insert("x-mirseodb-mode".to_string(), "fd".to_string());
+        let body = br#"{"statements":["INSERT INTO items (id) VALUES (3)","SELECT * FROM items"]}"#;
+        let response = handle_query_request(&state, &headers, body, None, None, &mut None);
+        assert_eq!(response.status, "400 Bad Request");
+        assert!(response.body.contains("Batches of statements are not supported"));
+        let rows = state.database.write().unwrap().query("SELECT * FROM items").unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn test_use_switches_the_database_of_later_statements() {
+        let registry = DatabaseRegistry::new(Arc::new(RwLock::new(Database::in_memory("main_db"))));
+        registry.register(Database::in_memory("SHOP"));
+        let state = ApiServerState::new(
+            Arc::new(registry),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        );
+        let auth_config = AuthConfig::default();
+        let mut connection_database = None;
+        let mut run = |database: Option<&str>, statements: &[&str]| {
+            let statements: Vec<String> = statements.iter().map(|sql| sql.to_string()).collect();
+            let batch = QueryBatch {
+                state: &state,
+                auth_config: &auth_config,
+                connection: ConnectionInfo::new(None, None, false),
+                totp: None,
+                transaction_id: None,
+                database: database.map(str::to_string).or_else(|| connection_database.clone()),
+                connection_database: &mut connection_database,
+                dialect: None,
+                memory_limit_mb: None,
+                sanitize: true,
+            };
+            batch.run(&statements, false, false, Instant::now()).body
+        };
+
+        let create = "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT) DURABILITY ASYNC";
+        run(None, &[create, "INSERT INTO items (id, name) VALUES (1, 'main')"]);
+        let body = run(
+            None,
+            &[
+                "USE shop",
+                create,
+                "INSERT INTO items (id, name) VALUES (1, 'shop')",
+                "INSERT INTO items (id, name) VALUES (2, 'shop')",
+            ],
+        );
+        assert_eq!(json_number(&body, "error_count"), 0);
+
+        // USE carries over to the connection's next request; "database" overrides it
+        let body = run(None, &["SELECT name FROM items"]);
+        assert!(body.contains("\"row_count\":2"));
+        let body = run(Some("MAIN_DB"), &["SELECT name FROM items"]);
+        assert!(body.contains("\"rows\":[{\"name\":\"main\"}]"));
+        let body = run(Some("no_such_db"), &["SELECT name FROM items"]);
+        assert!(body.contains("Database 'no_such_db' does not exist"));
+        // An open database is never recreated over its data
+        let body = run(None, &["CREATE DATABASE shop"]);
+        assert!(body.contains("Database 'SHOP' already exists"));
+        let body = run(None, &["SELECT name FROM items"]);
+        assert!(body.contains("\"row_count\":2"));
+        assert_eq!(connection_database.as_deref(), Some("SHOP"));
+    }
+
+    #[test]
+    fn test_export_file_waits_for_setup_and_reads_the_selected_database() {
+        let registry = DatabaseRegistry::new(Arc::new(RwLock::new(Database::in_memory("main_db"))));
+        let mut shop = Database::in_memory("SHOP");
+        shop.query("CREATE TABLE orders (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+        shop.query("INSERT INTO orders (id) VALUES (1)").unwrap();
+        registry.register(shop);
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(registry),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        ));
+        let headers = HashMap::new();
+
+        let response = handle_export_file_request(&state, &headers, Some("table=orders"), None);
+        assert_eq!(response.status, "503 Service Unavailable");
+
+        state.set_auth_config(AuthConfig {
+            setup_completed: true,
+            ..Default::default()
+        });
+        let response = handle_export_file_request(&state, &headers, Some("table=orders"), None);
+        assert_eq!(response.status, "404 Not Found");
+        let query = Some("table=orders&database=no_such_db");
+        let response = handle_export_file_request(&state, &headers, query, None);
+        assert_eq!(response.status, "404 Not Found");
+        assert!(response.body.contains("Database 'no_such_db' does not exist"));
+
+        // The database field and the connection's USE select the database to export
+        for (query, connection_database) in
+            [("table=orders&database=shop", None), ("table=orders", Some("SHOP"))]
+        {
+            let response =
+                handle_export_file_request(&state, &headers, Some(query), connection_database);
+            assert_eq!(response.status, "200 OK", "{}", response.body);
+            assert_eq!(json_number(&response.body, "row_count"), 1);
+            let path = response.body.split("\"path\":\"").nth(1).unwrap().split('"').next();
+            fs::remove_file(path.unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_config_reload_requires_an_admin() {
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(Database::in_memory("reload"))))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        ));
+        let before = state.config();
+
+        let response = handle_config_reload_request(&state, &HashMap::new(), b"{}");
+        assert_eq!(response.status, "403 Forbidden");
+        assert!(response.body.contains("Configuration reload requires an admin user"));
+        let body = br#"{"email":"nobody@example.com"}"#;
+        let response = handle_config_reload_request(&state, &HashMap::new(), body);
+        assert_eq!(response.status, "403 Forbidden");
+        assert!(Arc::ptr_eq(&before, &state.config()));
+    }
+
+    #[test]
+    fn test_queries_check_the_cached_auth_config() {
+        let mut db = Database::in_memory("auth_cache");
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        ));
+        // Start from a fresh setup whatever .mirseoDB/auth_config.json holds
+        state.set_auth_config(AuthConfig::default());
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        let body = br#"{"sql":"SELECT id FROM items","email":"admin@example.com"}"#;
+        let mut connection_database = None;
+
+        let response =
+            handle_query_request(&state, &headers, body, None, None, &mut connection_database);
+        assert_eq!(response.status, "503 Service Unavailable");
+
+        // The query sees the new setup through the cache, not the file
+        let mut auth_config = AuthConfig::default();
+        auth_config.emails.insert("admin@example.com".to_string(), "admin".to_string());
+        auth_config.setup_completed = true;
+        state.set_auth_config(auth_config);
+        let response =
+            handle_query_request(&state, &headers, body, None, None, &mut connection_database);
+        assert_eq!(response.status, "200 OK");
+        assert!(handle_setup_status(&state).body.contains("\"setup_completed\":true"));
+    }
+
+    #[test]
+    fn test_column_aliases_name_the_json_keys() {
+        let mut db = Database::in_memory("alias_json");
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, age INTEGER) DURABILITY ASYNC")
+            .unwrap();
+        db.query("INSERT INTO users (id, age) VALUES (1, 30)").unwrap();
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        ));
+        let mut auth_config = AuthConfig::default();
+        auth_config.emails.insert("admin@example.com".to_string(), "admin".to_string());
+        auth_config.setup_completed = true;
+        state.set_auth_config(auth_config);
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        let body = br#"{"sql":"SELECT id, age AS user_age FROM users","email":"admin@example.com"}"#;
+
+        let response = handle_query_request(
+            &state,
+            &headers,
+            body,
+            Some("include_schema=1"),
+            None,
+            &mut None,
+        );
+        assert_eq!(response.status, "200 OK");
+        assert!(response.body.contains(r#""rows":[{"id":1,"user_age":30}]"#), "{}", response.body);
+        assert!(response.body.contains(r#"{"name":"user_age","type":"INTEGER"}"#));
+        assert!(!response.body.contains(r#""age""#));
+    }
+
+    /// Compares reading the auth file per query with the cached copy; run with
+    /// `cargo test --release auth_config_cache_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn auth_config_cache_benchmark() {
+        const ITERATIONS: u32 = 10_000;
+        let mut auth_config = AuthConfig::default();
+        for i in 0..50 {
+            auth_config.emails.insert(format!("user{}@example.com", i), "user".to_string());
+        }
+        let path = std::env::temp_dir().join("mirseodb_auth_config_benchmark.json");
+        fs::write(&path, serde_json::to_string_pretty(&auth_config).unwrap()).unwrap();
+        let cache = RwLock::new(Arc::new(auth_config));
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let content = fs::read_to_string(&path).unwrap();
+            let loaded: AuthConfig = serde_json::from_str(&content).unwrap();
+            assert!(!loaded.check_sql_permission("user7@example.com", "DROP TABLE items"));
+        }
+        let from_disk = start.elapsed() / ITERATIONS;
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let cached = Arc::clone(&cache.read().unwrap());
+            assert!(!cached.check_sql_permission("user7@example.com", "DROP TABLE items"));
+        }
+        let from_cache = start.elapsed() / ITERATIONS;
+        let _ = fs::remove_file(&path);
+
+        println!("auth config per query: from disk {:?}, cached {:?}", from_disk, from_cache);
+        assert!(from_cache < from_disk);
+    }
+
+    #[test]
+    fn test_selects_share_the_database_lock() {
+        let mut db = Database::in_memory("read_lock_test");
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY) DURABILITY ASYNC").unwrap();
+        let state = Arc::new(ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        ));
+        let run = |state: &ApiServerState, sql: &str| {
+            let statement = state.parser.parse(sql).unwrap();
+            let mut db = state.lock_database_for(&state.database, &statement).unwrap();
+            execute_for_session(&mut db, statement, sql, &[], None, None).0.unwrap().len()
+        };
+
+        // A slow SELECT holding the lock does not hold up the others
+        let select = state.parser.parse("SELECT id FROM items").unwrap();
+        let slow_select = state.lock_database_for(&state.database, &select).unwrap();
+        assert!(matches!(slow_select, DatabaseGuard::Read(_)));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let state = Arc::clone(&state);
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let mut last_seen = 0;
+                    for _ in 0..50 {
+                        let seen = run(&state, "SELECT id FROM items");
+                        assert!(seen >= last_seen);
+                        last_seen = seen;
+                        sender.send(seen).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..20 {
+            let seen = receiver.recv_timeout(Duration::from_secs(10)).expect("SELECT blocked");
+            assert_eq!(seen, 0);
+        }
+
+        // Readers keep making progress while one writer inserts
+        drop(slow_select);
+        let writer = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                for id in 0..100 {
+                    run(&state, &format!("INSERT INTO items (id) VALUES ({})", id));
+                }
+            })
+        };
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(receiver.try_iter().count(), 4 * 50 - 20);
+        assert_eq!(run(&state, "SELECT id FROM items"), 100);
+
+        // Inside a transaction even a SELECT takes the lock exclusively
+        run(&state, "BEGIN");
+        let guard = state.lock_database_for(&state.database, &select).unwrap();
+        assert!(matches!(guard, DatabaseGuard::Write(_)));
+    }
+
+    #[test]
+    fn test_unknown_api_paths_get_json_404() {
+        let response = unknown_api_path_response("GET", "/api/bogus").unwrap();
+        assert!(response.status.starts_with("404"));
+        assert_eq!(
+            response.body,
+            "{\"error\":\"No API endpoint for GET /api/bogus\",\
+             \"method\":\"GET\",\"path\":\"/api/bogus\"}"
+        );
+        assert!(unknown_api_path_response("GET", "/checkpoint").is_some());
+        assert!(unknown_api_path_response("DELETE", "/2fa/verify").is_some());
+
+        // Console pages are still proxied
+        assert!(unknown_api_path_response("GET", "/").is_none());
+        assert!(unknown_api_path_response("GET", "/dashboard").is_none());
+        assert!(unknown_api_path_response("GET", "/apidocs").is_none());
+    }
+
+    #[test]
+    fn test_version_reports_the_build() {
+        let response = handle_version_request();
+        assert_eq!(response.status, "200 OK");
+        let expected = format!("{{\"version\":\"{}\",", env!("CARGO_PKG_VERSION"));
+        assert!(response.body.starts_with(&expected));
+        assert!(response.body.contains("\"git_commit\":\""));
+        assert!(!response.body.contains("\"build_time\":\"1970-01-01T00:00:00Z\""));
+        assert!(serde_json::from_str::<serde_json::Value>(&response.body).is_ok());
+    }
+
+    fn send_raw_request(port: u16, request: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        // Half-close so the server ends the kept-alive connection after responding
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_options_preflight_returns_cors_headers() {
+        let database = Arc::new(RwLock::new(Database::in_memory("cors_test")));
+        let server = start_health_server(
+            Ipv4Addr::LOCALHOST.into(),
+            38306,
+            Arc::new(DatabaseRegistry::new(database)),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            "https://app.example.com".to_string(),
+        )
+        .unwrap();
+
+        let preflight = send_raw_request(
+            server.port(),
+            "OPTIONS /query HTTP/1.1\r\nHost: localhost\r\n\
+             Access-Control-Request-Method: POST\r\n\r\n",
+        );
+        assert!(preflight.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(preflight.contains("Access-Control-Allow-Origin: https://app.example.com\r\n"));
+        assert!(preflight.contains(&format!(
+            "Access-Control-Allow-Methods: {}\r\n",
+            CORS_ALLOWED_METHODS
+        )));
+        assert!(preflight.contains("Access-Control-Allow-Headers: Content-Type, Authorization"));
+
+        let health = send_raw_request(server.port(), "GET /health HTTP/1.1\r\n\r\n");
+        assert!(health.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(health.contains("Access-Control-Allow-Origin: https://app.example.com\r\n"));
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_rapid_requests_are_throttled_per_client() {
+        let mut state = ApiServerState::new(
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(Database::in_memory(
+                "rate_limit_test",
+            ))))),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+            TwoFactorAuth::new(),
+        );
+        state.rate_limiter = Some(RateLimiter::new(1.0, 3.0));
+        let client: SocketAddr = "10.0.0.7:51234".parse().unwrap();
+
+        let throttled = (0..10)
+            .position(|_| rate_limit_response(&state, Some(client), "/query").is_some());
+        assert_eq!(throttled, Some(3));
+        let response = rate_limit_response(&state, Some(client), "/query").unwrap();
+        assert_eq!(response.status, "429 Too Many Requests");
+        assert!(response.headers.contains(&("Retry-After", "1".to_string())));
+        assert!(response.body.starts_with("{\"error\":\"Too many requests\""));
+        // Health checks, other clients and the same client on a new connection
+        assert!(rate_limit_response(&state, Some(client), "/health").is_none());
+        assert!(rate_limit_response(&state, Some(client), "/assets/app.js").is_none());
+        let other: SocketAddr = "10.0.0.8:51234".parse().unwrap();
+        assert!(rate_limit_response(&state, Some(other), "/query").is_none());
+        let reconnected: SocketAddr = "10.0.0.7:51235".parse().unwrap();
+        assert!(rate_limit_response(&state, Some(reconnected), "/query").is_some());
+
+        // Tokens refill at the configured rate, and full buckets are pruned
+        let limiter = RateLimiter::new(2.0, 2.0);
+        let start = Instant::now();
+        let ip = client.ip();
+        assert!(limiter.acquire(ip, start).is_ok());
+        assert!(limiter.acquire(ip, start).is_ok());
+        assert_eq!(limiter.acquire(ip, start), Err(Duration::from_millis(500)));
+        assert!(limiter.acquire(ip, start + Duration::from_millis(500)).is_ok());
+        let later = start + RATE_LIMIT_PRUNE_INTERVAL + Duration::from_secs(1);
+        assert!(limiter.acquire(other.ip(), later).is_ok());
+        assert_eq!(limiter.state.lock().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn test_server_binds_the_configured_address() {
+        let database = Arc::new(RwLock::new(Database::in_memory("bind_addr_test")));
+        let server = start_health_server(
+            Ipv4Addr::UNSPECIFIED.into(),
+            0,
+            Arc::new(DatabaseRegistry::new(database)),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+        )
+        .unwrap();
+        assert_eq!(server.local_addr().ip(), Ipv4Addr::UNSPECIFIED);
+        assert_ne!(server.port(), 0);
+        let health = send_raw_request(server.port(), "GET /health HTTP/1.1\r\n\r\n");
+        assert!(health.starts_with("HTTP/1.1 200 OK\r\n"));
+        server.shutdown();
+
+        let config = ServerConfig {
+            bind_addr: "localhost:3306".to_string(),
+            ..ServerConfig::default()
+        };
+        match run_server(config) {
+            Err(DatabaseError::NetworkError(message)) => {
+                assert!(message.contains("Invalid bind address 'localhost:3306'"))
+            }
+            other => panic!("expected a bind address error, got {:?}", other.err()),
+        }
+    }
 
-            HttpResponse::json("400 Bad Request", body)
+    fn split_raw_response(response: &[u8]) -> (String, &[u8]) {
+        let end = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+        (String::from_utf8_lossy(&response[..end]).into_owned(), &response[end + 4..])
+    }
+
+    #[test]
+    fn test_large_responses_are_gzipped_when_accepted() {
+        let mut db = Database::in_memory("gzip_test");
+        let parser = AnySQL::new();
+        for table in 0..20 {
+            let sql = format!(
+                "CREATE TABLE notes_{} (id INTEGER, title TEXT, body TEXT) DURABILITY ASYNC",
+                table
+            );
+            db.execute(parser.parse(&sql).unwrap()).unwrap();
         }
+
+        let server = start_health_server(
+            Ipv4Addr::LOCALHOST.into(),
+            38307,
+            Arc::new(DatabaseRegistry::new(Arc::new(RwLock::new(db)))),
+            Arc::new(parser),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+        )
+        .unwrap();
+        let query = |accept_encoding: &str| {
+            let request = format!("GET /schema HTTP/1.1\r\n{}\r\n", accept_encoding);
+            let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            response
+        };
+
+        let plain = query("");
+        let (plain_head, plain_body) = split_raw_response(&plain);
+        assert!(plain_head.starts_with("HTTP/1.1 200 OK"));
+        assert!(plain_head.contains("Vary: Accept-Encoding"));
+        assert!(!plain_head.contains("Content-Encoding"));
+        assert!(plain_body.len() > DEFAULT_GZIP_MIN_BYTES);
+
+        let compressed = query("Accept-Encoding: deflate, gzip;q=0.8\r\n");
+        let (head, body) = split_raw_response(&compressed);
+        assert!(head.contains("Content-Encoding: gzip\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(body.len() < plain_body.len());
+        let decoded = String::from_utf8(gunzip(body)).unwrap();
+        // Only the timing differs between the two responses
+        let tables = |json: &str| json[..json.find("\"execution_time").unwrap()].to_string();
+        assert!(decoded.contains("NOTES_19"));
+        assert_eq!(tables(&decoded), tables(&String::from_utf8_lossy(plain_body)));
+
+        let refused = query("Accept-Encoding: gzip;q=0, *\r\n");
+        assert!(!split_raw_response(&refused).0.contains("Content-Encoding"));
+
+        // Small bodies stay uncompressed even when gzip is accepted
+        let health = send_raw_request(
+            server.port(),
+            "GET /health HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+        );
+        assert!(health.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!health.contains("Content-Encoding") && !health.contains("Vary"));
+        server.shutdown();
+        let _ = StorageEngine::remove_database_file("gzip_test");
+    }
+
+    #[test]
+    fn test_pipelined_requests_share_a_connection() {
+        let database = Arc::new(RwLock::new(Database::in_memory("keep_alive_test")));
+        let server = start_health_server(
+            Ipv4Addr::LOCALHOST.into(),
+            38308,
+            Arc::new(DatabaseRegistry::new(database)),
+            Arc::new(AnySQL::new()),
+            Arc::new(RouteConfig {
+                routes: HashMap::new(),
+            }),
+            None,
+            DEFAULT_CORS_ORIGIN.to_string(),
+        )
+        .unwrap();
+
+        // Both requests in one write; the second one asks to close afterwards
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream
+            .write_all(
+                b"GET /time?tz=%2B09:00 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+                  GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).unwrap();
+
+        let parts: Vec<&str> = responses.split("HTTP/1.1 ").skip(1).collect();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].starts_with("200 OK\r\n"));
+        assert!(parts[0].contains("Connection: keep-alive\r\n"));
+        assert!(parts[0].contains("+09:00"));
+        assert!(parts[1].starts_with("200 OK\r\n"));
+        assert!(parts[1].contains("Connection: close\r\n"));
+        assert!(parts[1].contains("\"status\""));
+
+        // HTTP/1.0 closes unless the client asks for keep-alive
+        let response = send_raw_request(server.port(), "GET /health HTTP/1.0\r\n\r\n");
+        assert!(response.contains("Connection: close\r\n"));
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_debug_output_includes_connection_details() {
+        let mut connection = ConnectionInfo::new(
+            Some("10.0.0.7:51234".parse().unwrap()),
+            Some("ops@example.com".to_string()),
+            true,
+        );
+        connection.dialect = Some(DetectedDialect::MySQL);
+
+        let mut body = String::from("{\"status\":\"ok\"}");
+        connection.insert_debug(&mut body);
+        assert_eq!(
+            body,
+            "{\"status\":\"ok\",\"debug\":{\"peer_addr\":\"10.0.0.7:51234\",\
+             \"email\":\"ops@example.com\",\"dialect\":\"mysql\",\"forwarded\":true}}"
+        );
+
+        // A batch reports the lock wait and execution time summed over its statements
+        connection.record_timing(Duration::from_micros(1500), Duration::from_micros(250));
+        connection.record_timing(Duration::from_micros(500), Duration::from_micros(4000));
+        let mut body = String::from("{\"status\":\"ok\"}");
+        connection.insert_debug(&mut body);
+        assert!(body.ends_with(
+            "\"forwarded\":true,\"lock_wait_ms\":2.000,\"execute_ms\":4.250}}"
+        ));
     }
 }
@@ -1,14 +1,25 @@
 use super::core_types::{
-    ColumnDefinition, ComparisonOperator, DataType, DatabaseError, SqlStatement, SqlValue,
-    WhereClause,
+    Aggregate, AggregateFunction, ColumnDefinition, ComparisonOperator, DataType, DatabaseError,
+    Durability, InSource, NullsOrder, OnConflict, OrderBy, SortDirection, SqlStatement, SqlValue,
+    WhereClause, WhereExpr,
 };
+use super::configuration::DEFAULT_MAX_QUERY_DEPTH;
 use super::security::{normalize_identifier, normalize_table_name};
+use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+/// A parsed select list: plain columns, aggregate calls and named `(SELECT ...)`
+/// items, each in select-list order.
+struct SelectList {
+    columns: Vec<String>,
+    column_aliases: Vec<(String, String)>,
+    aggregates: Vec<Aggregate>,
+    scalar_subqueries: Vec<(String, SqlStatement)>,
+}
 
 #[derive(Debug, Clone)]
 pub struct DialectCache {
@@ -29,7 +40,7 @@ pub struct CachedDialectResult {
 
 #[derive(Debug, Clone)]
 pub struct CachedSQLAnalysis {
-    statement_type: StatementType,
+    statement_type: &'static StatementType,
     detected_dialect: DetectedDialect,
     original_sql: String,
     preprocessed_sql: String,      // 미리 계산된 uppercase SQL
@@ -45,13 +56,22 @@ pub struct KeywordHashMatcher {
     keyword_to_dialects: HashMap<String, Vec<(DetectedDialect, f32)>>, // 역 인덱스
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct PerformanceMetrics {
-    total_queries: u64,
-    cache_hits: u64,
-    cache_misses: u64,
-    avg_parse_time_ns: u64,
-    dialect_accuracy: f32,
+    pub total_queries: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub avg_parse_time_ns: u64,
+    pub dialect_accuracy: f32,
+}
+
+/// Parser counters together with the dialect cache's own hit/miss counts.
+#[derive(Debug, Clone)]
+pub struct ParserMetricsSnapshot {
+    pub metrics: PerformanceMetrics,
+    pub dialect_cache_hits: u64,
+    pub dialect_cache_misses: u64,
+    pub dialect_cache_entries: usize,
 }
 
 impl DialectCache {
@@ -177,6 +197,41 @@ impl KeywordHashMatcher {
     }
 
     pub fn detect_dialect_optimized(&self, sql_upper: &str) -> (DetectedDialect, f32) {
+        best_dialect(&self.score_dialects(sql_upper, None))
+    }
+
+    /// Same scoring as `detect_dialect_optimized`, keeping every dialect's score
+    /// and the keywords that contributed to it.
+    pub fn explain_dialect(&self, sql_upper: &str) -> DialectExplanation {
+        let mut matches = Vec::new();
+        let scores = self.score_dialects(sql_upper, Some(&mut matches));
+        let (dialect, confidence) = best_dialect(&scores);
+
+        matches.sort_by(|a, b| {
+            b.weight
+                .partial_cmp(&a.weight)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.keyword.cmp(&b.keyword))
+        });
+        let mut scores: Vec<(DetectedDialect, f32)> = DetectedDialect::ALL
+            .iter()
+            .map(|dialect| (dialect.clone(), scores.get(dialect).copied().unwrap_or(0.0)))
+            .collect();
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        DialectExplanation {
+            dialect,
+            confidence,
+            scores,
+            matches,
+        }
+    }
+
+    fn score_dialects(
+        &self,
+        sql_upper: &str,
+        mut matches: Option<&mut Vec<KeywordMatch>>,
+    ) -> HashMap<DetectedDialect, f32> {
         let mut dialect_scores: HashMap<DetectedDialect, f32> = HashMap::new();
 
         // 초기화
@@ -190,17 +245,927 @@ impl KeywordHashMatcher {
             if sql_upper.contains(keyword) {
                 for (dialect, weight) in dialect_weights {
                     *dialect_scores.entry(dialect.clone()).or_insert(0.0) += weight;
+                    if let Some(matches) = matches.as_deref_mut() {
+                        matches.push(KeywordMatch {
+                            keyword: keyword.clone(),
+                            dialect: dialect.clone(),
+                            weight: *weight,
+                        });
+                    }
+                }
+            }
+        }
+
+        dialect_scores
+    }
+}
+
+/// Highest-scoring dialect. Ties go to the dialect listed first in
+/// `DetectedDialect::ALL`, so the result does not depend on map order.
+fn best_dialect(scores: &HashMap<DetectedDialect, f32>) -> (DetectedDialect, f32) {
+    let mut best = (DetectedDialect::Standard, 1.0);
+    let mut best_score = f32::MIN;
+
+    for dialect in DetectedDialect::ALL {
+        if let Some(&score) = scores.get(&dialect) {
+            if score > best_score {
+                best_score = score;
+                best = (dialect, score);
+            }
+        }
+    }
+
+    best
+}
+
+/// Whether `tokens` start with `keywords`, in any case (`IF NOT EXISTS`).
+fn has_keywords(tokens: &[&str], keywords: &[&str]) -> bool {
+    tokens.len() >= keywords.len()
+        && keywords
+            .iter()
+            .zip(tokens)
+            .all(|(keyword, token)| token.eq_ignore_ascii_case(keyword))
+}
+
+/// Row count for LIMIT/OFFSET/FETCH at `tokens[pos]`, ignoring a trailing `;`.
+/// Values that are not plain digits or do not fit in `usize` are rejected.
+fn paging_value(tokens: &[&str], pos: usize, keyword: &str) -> Result<usize, DatabaseError> {
+    let token = tokens
+        .get(pos)
+        .map(|token| token.trim_end_matches(';'))
+        .ok_or_else(|| DatabaseError::ParseError(format!("Missing {} value", keyword)))?;
+
+    if token.is_empty() || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(DatabaseError::ParseError(format!(
+            "Invalid {} value '{}'",
+            keyword, token
+        )));
+    }
+
+    token.parse::<usize>().map_err(|_| {
+        DatabaseError::ParseError(format!("{} value '{}' is too large", keyword, token))
+    })
+}
+
+/// Result name after a select-list item: nothing (`default`), `AS name` or `name`.
+fn select_alias(text: &str, default: String, item: &str) -> Result<String, DatabaseError> {
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [] => Ok(default),
+        [keyword, name] if keyword.eq_ignore_ascii_case("AS") => Ok(normalize_identifier(name)),
+        [name] => Ok(normalize_identifier(name)),
+        _ => Err(DatabaseError::ParseError(format!(
+            "Invalid alias '{}' after {}",
+            text.trim(),
+            item
+        ))),
+    }
+}
+
+/// Parses `FUNC(column) [[AS] alias]` (or `COUNT(*)`) for the aggregate functions.
+/// `Ok(None)` when `item` is not an aggregate call. Without an alias the result
+/// column is named after the call, e.g. `COUNT(*)` or `SUM(price)`.
+fn parse_aggregate_item(item: &str) -> Result<Option<Aggregate>, DatabaseError> {
+    let Some((function_name, rest)) = item.split_once('(') else {
+        return Ok(None);
+    };
+    let Some(function) = AggregateFunction::from_name(function_name.trim()) else {
+        return Ok(None);
+    };
+    let Some((argument, alias)) = rest.split_once(')') else {
+        return Err(DatabaseError::ParseError(format!(
+            "Missing closing parenthesis after {}(",
+            function.as_str()
+        )));
+    };
+
+    let column = match argument.trim() {
+        "*" if function == AggregateFunction::Count => None,
+        "" | "*" => {
+            return Err(DatabaseError::ParseError(format!(
+                "{} needs a column argument",
+                function.as_str()
+            )))
+        }
+        column => Some(normalize_identifier(column)),
+    };
+    let default_name = format!(
+        "{}({})",
+        function.as_str(),
+        column.as_deref().unwrap_or("*")
+    );
+
+    Ok(Some(Aggregate {
+        name: select_alias(alias, default_name, function.as_str())?,
+        function,
+        column,
+    }))
+}
+
+/// Grouping columns after GROUP BY, comma separated.
+fn parse_group_by(tokens: &[&str]) -> Result<Vec<String>, DatabaseError> {
+    let clause = tokens.join(" ");
+    clause
+        .trim()
+        .trim_end_matches(';')
+        .split(',')
+        .map(|item| match item.trim() {
+            "" => Err(DatabaseError::ParseError("Empty GROUP BY item".to_string())),
+            column => Ok(normalize_identifier(column)),
+        })
+        .collect()
+}
+
+/// Parses what follows LIMIT: `count`, or MySQL's `offset, count`. Returns the
+/// count and, for the two-value form, the offset.
+fn parse_limit_clause(tokens: &[&str]) -> Result<(usize, Option<usize>), DatabaseError> {
+    let clause = tokens.join(" ");
+    let clause = clause.trim_end_matches(';');
+    match clause.split_once(',') {
+        Some((offset, count)) => Ok((
+            paging_value(&[count.trim()], 0, "LIMIT")?,
+            Some(paging_value(&[offset.trim()], 0, "OFFSET")?),
+        )),
+        None => Ok((paging_value(&[clause], 0, "LIMIT")?, None)),
+    }
+}
+
+/// Parses what follows `FETCH FIRST|NEXT`: `[n] ROW|ROWS ONLY`. The count defaults to 1.
+fn parse_fetch_count(tokens: &[&str]) -> Result<usize, DatabaseError> {
+    let invalid = || {
+        DatabaseError::ParseError("Expected FETCH FIRST [n] ROWS ONLY".to_string())
+    };
+
+    let (count, rest) = match tokens.first() {
+        Some(token) if token.eq_ignore_ascii_case("ROW") || token.eq_ignore_ascii_case("ROWS") => {
+            (1, tokens)
+        }
+        Some(_) => (paging_value(tokens, 0, "FETCH FIRST")?, &tokens[1..]),
+        None => return Err(invalid()),
+    };
+
+    let unit = rest.first().ok_or_else(invalid)?;
+    let only = rest.get(1).ok_or_else(invalid)?.trim_end_matches(';');
+    if !(unit.eq_ignore_ascii_case("ROW") || unit.eq_ignore_ascii_case("ROWS"))
+        || !only.eq_ignore_ascii_case("ONLY")
+    {
+        return Err(invalid());
+    }
+
+    Ok(count)
+}
+
+thread_local! {
+    /// Values for the `?N` placeholders of the statement `parse_with_params` is
+    /// parsing on this thread. The parser is shared between connections, so the
+    /// values cannot live on it.
+    static BOUND_PARAMS: RefCell<Option<Vec<SqlValue>>> = const { RefCell::new(None) };
+    /// The dialect chosen for the statement being parsed on this thread, whose
+    /// column type names are then the only ones accepted. None while detecting.
+    static PARSE_DIALECT: RefCell<Option<DetectedDialect>> = const { RefCell::new(None) };
+}
+
+/// Numbers the `?` placeholders outside string literals as `?1`, `?2`, ... so
+/// each binds to its own value however the statement is split up. Returns the
+/// rewritten SQL and the placeholder count.
+fn number_placeholders(sql: &str) -> (String, usize) {
+    let mut numbered = String::with_capacity(sql.len());
+    let mut count = 0;
+    let mut quote: Option<char> = None;
+
+    for ch in sql.chars() {
+        match (quote, ch) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            (None, '?') => {
+                count += 1;
+                numbered.push('?');
+                numbered.push_str(&count.to_string());
+                continue;
+            }
+            _ => {}
+        }
+        numbered.push(ch);
+    }
+
+    (numbered, count)
+}
+
+/// Splits a script on the semicolons outside string literals, dropping empty
+/// statements.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (index, ch) in sql.char_indices() {
+        match (quote, ch) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            (None, ';') => {
+                statements.push(&sql[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&sql[start..]);
+
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// The value bound to a `?N` token, when a statement with params is being parsed.
+fn bound_param(token: &str) -> Option<SqlValue> {
+    let position: usize = token.strip_prefix('?')?.parse().ok()?;
+    BOUND_PARAMS.with(|bound| {
+        bound
+            .borrow()
+            .as_ref()
+            .and_then(|params| params.get(position.checked_sub(1)?).cloned())
+    })
+}
+
+/// Parenthesis depth at the start of each token, ignoring parentheses in quoted
+/// literals, so keywords inside a subquery are not taken for the outer query's.
+fn token_depths(tokens: &[&str]) -> Vec<usize> {
+    let mut depths = Vec::with_capacity(tokens.len());
+    let mut depth: usize = 0;
+    let mut quote: Option<char> = None;
+
+    for token in tokens {
+        depths.push(depth);
+        for ch in token.chars() {
+            match (quote, ch) {
+                (Some(open), c) if c == open => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"' | '`') => quote = Some(ch),
+                (None, '(') => depth += 1,
+                (None, ')') => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    depths
+}
+
+/// Splits `sql` at whitespace outside quoted literals, so a literal such as
+/// `'x LIMIT 1'` stays one word and keeps its spacing.
+fn sql_words(sql: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote: Option<char> = None;
+
+    for (i, ch) in sql.char_indices() {
+        match (quote, ch) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, c) if c.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    words.push(&sql[start..i]);
+                }
+                continue;
+            }
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(start) = start {
+        words.push(&sql[start..]);
+    }
+
+    words
+}
+
+/// The original text of `sql` from the first to the last of `words`, which are
+/// slices of it as `sql_words` returns them.
+fn words_text<'a>(sql: &'a str, words: &[&'a str]) -> &'a str {
+    let (Some(first), Some(last)) = (words.first(), words.last()) else {
+        return "";
+    };
+    let offset = |word: &str| word.as_ptr() as usize - sql.as_ptr() as usize;
+    &sql[offset(first)..offset(last) + last.len()]
+}
+
+/// Rejects identifiers quoted the way `dialect` does not quote them: backticks
+/// are MySQL's and brackets MS-SQL's. Double quotes are accepted for every
+/// dialect, as MySQL does with ANSI_QUOTES.
+fn check_identifier_quotes(sql: &str, dialect: &DetectedDialect) -> Result<(), DatabaseError> {
+    let mut quote: Option<char> = None;
+
+    for ch in sql.chars() {
+        match (quote, ch) {
+            (Some(close), c) if c == close => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(ch),
+            (None, '`') if *dialect == DetectedDialect::MySQL => quote = Some('`'),
+            (None, '[') if *dialect == DetectedDialect::MsSQL => quote = Some(']'),
+            (None, '`' | '[') => {
+                let (style, owner) = if ch == '`' {
+                    ("Backtick", "mysql")
+                } else {
+                    ("Bracket", "mssql")
+                };
+                return Err(DatabaseError::ParseError(format!(
+                    "{}-quoted identifiers are {} syntax, not {}",
+                    style,
+                    owner,
+                    dialect.name()
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The column type `type_str` names in `dialect`. Once a dialect is chosen,
+/// only its own type names are accepted.
+fn dialect_data_type(dialect: &DetectedDialect, type_str: &str) -> Result<DataType, DatabaseError> {
+    let type_upper = type_str.to_ascii_uppercase();
+    let (name, arguments) = match type_upper.split_once('(') {
+        Some((name, rest)) => (name, Some(rest.trim_end_matches(')').trim())),
+        None => (type_upper.as_str(), None),
+    };
+
+    let data_type = match (dialect, name) {
+        (_, "INT" | "INTEGER" | "SMALLINT") => Some(DataType::Integer),
+        (_, "FLOAT" | "REAL" | "DECIMAL" | "NUMERIC") => Some(DataType::Float),
+        (_, "CHAR" | "VARCHAR") => Some(DataType::Text),
+        (DetectedDialect::Standard, "BIGINT") => Some(DataType::Integer),
+        (DetectedDialect::Standard, "DOUBLE") => Some(DataType::Float),
+        (DetectedDialect::Standard, "TEXT" | "CLOB") => Some(DataType::Text),
+        (DetectedDialect::Standard, "BOOLEAN") => Some(DataType::Boolean),
+        // TINYINT(1) is how MySQL stores booleans
+        (DetectedDialect::MySQL, "TINYINT") if arguments == Some("1") => Some(DataType::Boolean),
+        (DetectedDialect::MySQL, "TINYINT" | "MEDIUMINT" | "BIGINT") => Some(DataType::Integer),
+        (DetectedDialect::MySQL, "DOUBLE") => Some(DataType::Float),
+        (DetectedDialect::MySQL, "TEXT" | "TINYTEXT" | "MEDIUMTEXT" | "LONGTEXT") => {
+            Some(DataType::Text)
+        }
+        (DetectedDialect::MySQL, "BOOL" | "BOOLEAN") => Some(DataType::Boolean),
+        (DetectedDialect::MsSQL, "TINYINT" | "BIGINT") => Some(DataType::Integer),
+        (DetectedDialect::MsSQL, "MONEY" | "SMALLMONEY") => Some(DataType::Float),
+        (DetectedDialect::MsSQL, "NCHAR" | "NVARCHAR" | "TEXT" | "NTEXT") => Some(DataType::Text),
+        (DetectedDialect::MsSQL, "BIT") => Some(DataType::Boolean),
+        // NUMBER(1) is how Oracle stores booleans, and a scale of 0 makes an integer
+        (DetectedDialect::Oracle, "NUMBER") => Some(match arguments {
+            Some("1") => DataType::Boolean,
+            Some(arguments) if arguments.split(',').nth(1).is_none_or(|s| s.trim() == "0") => {
+                DataType::Integer
+            }
+            _ => DataType::Float,
+        }),
+        (DetectedDialect::Oracle, "BINARY_FLOAT" | "BINARY_DOUBLE") => Some(DataType::Float),
+        (DetectedDialect::Oracle, "VARCHAR2" | "NVARCHAR2" | "NCHAR" | "CLOB" | "NCLOB") => {
+            Some(DataType::Text)
+        }
+        _ => None,
+    };
+
+    data_type.ok_or_else(|| {
+        DatabaseError::InvalidDataType(format!(
+            "{} is not a {} column type",
+            type_str,
+            dialect.name()
+        ))
+    })
+}
+
+/// Splits `text` at each `separator` outside parentheses and quoted literals.
+/// A `)` separator splits where the unmatched closing parenthesis is.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth: usize = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, ch) in text.char_indices() {
+        match (quote, ch) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+}
+
+/// Removes a MySQL-style `COMMENT 'text'` (or `COMMENT = 'text'`) from a column
+/// definition or the table options, returning the remaining text and the comment.
+fn take_inline_comment(text: &str) -> Result<(String, Option<String>), DatabaseError> {
+    let keyword_ends = |c: char| c.is_whitespace() || matches!(c, '=' | '\'' | '"');
+    let Some(i) = find_keyword(text, "COMMENT", keyword_ends) else {
+        return Ok((text.to_string(), None));
+    };
+
+    let literal = text[i + 7..].trim_start().trim_start_matches('=').trim_start();
+    let (comment, consumed) = read_quoted(literal).ok_or_else(|| {
+        DatabaseError::ParseError("COMMENT must be followed by a quoted string".to_string())
+    })?;
+    let end = text.len() - literal.len() + consumed;
+    Ok((format!("{} {}", &text[..i], &text[end..]), Some(comment)))
+}
+
+/// A comparison with the current time, or with `timestamp` when the SQL gave
+/// a `TIMESTAMP '...'` literal instead.
+fn time_comparison(
+    column: String,
+    operator: ComparisonOperator,
+    timestamp: Option<SqlValue>,
+) -> WhereExpr {
+    match timestamp {
+        Some(value) => WhereExpr::Comparison(WhereClause {
+            column,
+            operator,
+            value,
+        }),
+        None => WhereExpr::CurrentTimeComparison { column, operator },
+    }
+}
+
+/// Rejects an MS-SQL `IDENTITY(seed, increment)` other than `IDENTITY(1, 1)`:
+/// generated values always start at 1 and go up by 1.
+fn check_identity_arguments(column_def: &str) -> Result<(), DatabaseError> {
+    let upper = column_def.to_ascii_uppercase();
+    let Some(pos) = upper.find("IDENTITY") else {
+        return Ok(());
+    };
+    let Some(arguments) = upper[pos + "IDENTITY".len()..].trim_start().strip_prefix('(') else {
+        return Ok(());
+    };
+    let arguments = arguments.split(')').next().unwrap_or_default();
+    let compact: String = arguments.split_whitespace().collect();
+    if compact == "1,1" {
+        Ok(())
+    } else {
+        Err(DatabaseError::ParseError(format!(
+            "IDENTITY({}) is not supported; AUTO_INCREMENT values start at 1 and step by 1",
+            arguments.trim()
+        )))
+    }
+}
+
+/// Removes `DEFAULT <value>` from a column definition, returning the remaining
+/// text and the value as written: a quoted literal, quotes included, or a word.
+fn take_default(text: &str) -> Result<(String, Option<String>), DatabaseError> {
+    let keyword_ends = |c: char| c.is_whitespace() || matches!(c, '\'' | '"');
+    // The DEFAULT of `GENERATED BY DEFAULT AS IDENTITY` is not followed by a value
+    let mut start = 0;
+    let i = loop {
+        let Some(offset) = find_keyword(&text[start..], "DEFAULT", keyword_ends) else {
+            return Ok((text.to_string(), None));
+        };
+        let i = start + offset;
+        let previous_word = text[..i].split_whitespace().next_back();
+        if !previous_word.is_some_and(|word| word.eq_ignore_ascii_case("BY")) {
+            break i;
+        }
+        start = i + 7;
+    };
+
+    let literal = text[i + 7..].trim_start();
+    let consumed = match read_quoted(literal) {
+        Some((_, consumed)) => consumed,
+        None => literal.find(char::is_whitespace).unwrap_or(literal.len()),
+    };
+    if consumed == 0 {
+        return Err(DatabaseError::ParseError(
+            "DEFAULT must be followed by a value".to_string(),
+        ));
+    }
+    let end = text.len() - literal.len() + consumed;
+    let value = literal[..consumed].to_string();
+    Ok((format!("{} {}", &text[..i], &text[end..]), Some(value)))
+}
+
+/// Where `keyword` starts in `text` as a word of its own outside quotes: after
+/// whitespace or `)`, and followed by the end of `text` or a `keyword_ends` char.
+fn find_keyword(text: &str, keyword: &str, keyword_ends: impl Fn(char) -> bool) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    let mut previous = ' ';
+
+    for (i, ch) in text.char_indices() {
+        match (quote, ch) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            (None, _)
+                if (previous.is_whitespace() || previous == ')')
+                    && text
+                        .get(i..i + keyword.len())
+                        .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+                    && text[i + keyword.len()..].chars().next().is_none_or(&keyword_ends) =>
+            {
+                return Some(i);
+            }
+            _ => {}
+        }
+        previous = ch;
+    }
+    None
+}
+
+/// The column named by `VALUES(col)` or `EXCLUDED.col` in an upsert assignment.
+fn inserted_reference(value: &str) -> Option<&str> {
+    if let Some(prefix) = value.get(..9).filter(|p| p.eq_ignore_ascii_case("EXCLUDED.")) {
+        return Some(&value[prefix.len()..]);
+    }
+    let keyword = value.get(..6).filter(|p| p.eq_ignore_ascii_case("VALUES"))?;
+    value[keyword.len()..]
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+        .map(str::trim)
+}
+
+/// Reads the `'...'` or `"..."` literal `text` starts with, where a doubled quote
+/// stands for one. Returns the content and the bytes it spans, quotes included.
+fn read_quoted(text: &str) -> Option<(String, usize)> {
+    let quote = text.chars().next().filter(|c| matches!(c, '\'' | '"'))?;
+    let mut content = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        if ch != quote {
+            content.push(ch);
+        } else if chars.peek().is_some_and(|(_, next)| *next == quote) {
+            content.push(quote);
+            chars.next();
+        } else {
+            return Some((content, i + 1));
+        }
+    }
+    None
+}
+
+/// Splits a WHERE clause into words, quoted literals, parentheses, commas and
+/// comparison operators, so `(age>18)` tokenizes the same as `( age > 18 )`.
+fn tokenize_where(clause: &str) -> Vec<String> {
+    let clause = clause.trim().trim_end_matches(';');
+    let chars: Vec<char> = clause.chars().collect();
+    let is_operator = |ch: char| matches!(ch, '=' | '<' | '>' | '!');
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        let start = i;
+
+        if ch.is_whitespace() {
+            i += 1;
+            continue;
+        } else if matches!(ch, '\'' | '"' | '`') {
+            i += 1;
+            while i < chars.len() && chars[i] != ch {
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+        } else if matches!(ch, '(' | ')' | ',') {
+            i += 1;
+        } else if is_operator(ch) {
+            while i < chars.len() && is_operator(chars[i]) {
+                i += 1;
+            }
+        } else {
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !is_operator(chars[i])
+                && !matches!(chars[i], '(' | ')' | ',' | '\'' | '"' | '`')
+            {
+                i += 1;
+            }
+        }
+
+        tokens.push(chars[start..i].iter().collect());
+    }
+
+    tokens
+}
+
+/// Recursive descent over WHERE tokens: OR of ANDs of comparisons or groups.
+/// Recursion only happens per parenthesis group or subquery, and `depth`
+/// counts both against `max_nesting_depth`.
+struct WhereParser<'a> {
+    parser: &'a AnySQL,
+    tokens: Vec<String>,
+    pos: usize,
+    depth: usize,
+}
+
+impl WhereParser<'_> {
+    fn parse_or(&mut self) -> Result<WhereExpr, DatabaseError> {
+        let mut terms = vec![self.parse_and()?];
+        while self.next_is("OR") {
+            terms.push(self.parse_and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { WhereExpr::Or(terms) })
+    }
+
+    fn parse_and(&mut self) -> Result<WhereExpr, DatabaseError> {
+        let mut terms = vec![self.parse_primary()?];
+        while self.next_is("AND") {
+            terms.push(self.parse_primary()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { WhereExpr::And(terms) })
+    }
+
+    fn parse_primary(&mut self) -> Result<WhereExpr, DatabaseError> {
+        if self.next_is("(") {
+            self.depth += 1;
+            self.parser.check_nesting(self.depth)?;
+            let expr = self.parse_or()?;
+            if !self.next_is(")") {
+                return Err(DatabaseError::ParseError(
+                    "Missing closing parenthesis in WHERE clause".to_string(),
+                ));
+            }
+            self.depth -= 1;
+            return Ok(expr);
+        }
+
+        let keyword_at = |offset: usize, keyword: &str| {
+            self.tokens
+                .get(self.pos + offset)
+                .map(|token| token.eq_ignore_ascii_case(keyword))
+                .unwrap_or(false)
+        };
+        // A bare boolean column, optionally negated: `WHERE active`, `WHERE NOT active`
+        let negated = keyword_at(0, "NOT");
+        let column_pos = self.pos + usize::from(negated);
+        let ends_term = match self.tokens.get(column_pos + 1) {
+            None => true,
+            Some(token) => {
+                token == ")" || token.eq_ignore_ascii_case("AND") || token.eq_ignore_ascii_case("OR")
+            }
+        };
+        if let Some(column) = self.tokens.get(column_pos) {
+            if ends_term && !matches!(column.as_str(), "(" | ")" | ",") {
+                let column = normalize_identifier(column);
+                self.pos = column_pos + 1;
+                return Ok(WhereExpr::BooleanColumn { column, negated });
+            }
+        }
+
+        if keyword_at(1, "IN") {
+            return self.parse_in(false);
+        }
+        if keyword_at(1, "NOT") && keyword_at(2, "IN") {
+            return self.parse_in(true);
+        }
+        if keyword_at(1, "BETWEEN") {
+            return self.parse_between(false);
+        }
+        if keyword_at(1, "NOT") && keyword_at(2, "BETWEEN") {
+            return self.parse_between(true);
+        }
+
+        // `NOW() > column` reads as `column < NOW()`
+        let (current_time_len, timestamp) = self.time_operand(self.pos);
+        if current_time_len > 0 {
+            let (Some(operator), Some(column)) = (
+                self.tokens.get(self.pos + current_time_len),
+                self.tokens.get(self.pos + current_time_len + 1),
+            ) else {
+                return Err(DatabaseError::ParseError("Invalid WHERE clause".to_string()));
+            };
+            let operator = match self.parser.parse_comparison_operator(operator)? {
+                ComparisonOperator::GreaterThan => ComparisonOperator::LessThan,
+                ComparisonOperator::LessThan => ComparisonOperator::GreaterThan,
+                ComparisonOperator::GreaterThanOrEqual => ComparisonOperator::LessThanOrEqual,
+                ComparisonOperator::LessThanOrEqual => ComparisonOperator::GreaterThanOrEqual,
+                ComparisonOperator::Like | ComparisonOperator::NotLike => {
+                    return Err(DatabaseError::ParseError(
+                        "NOW() cannot be the pattern side of LIKE".to_string(),
+                    ));
                 }
+                operator => operator,
+            };
+            let column = normalize_identifier(column);
+            self.pos += current_time_len + 2;
+            return Ok(time_comparison(column, operator, timestamp));
+        }
+
+        // `NOT LIKE` is the only two-word operator
+        let negated_like = self
+            .tokens
+            .get(self.pos + 1..self.pos + 3)
+            .map(|pair| pair[0].eq_ignore_ascii_case("NOT") && pair[1].eq_ignore_ascii_case("LIKE"))
+            .unwrap_or(false);
+        let operator_len = if negated_like { 2 } else { 1 };
+
+        let (Some(column), Some(value)) = (
+            self.tokens.get(self.pos),
+            self.tokens.get(self.pos + 1 + operator_len),
+        ) else {
+            return Err(DatabaseError::ParseError("Invalid WHERE clause".to_string()));
+        };
+        let operator = if negated_like {
+            "NOT LIKE".to_string()
+        } else {
+            self.tokens[self.pos + 1].clone()
+        };
+
+        let is_subquery = value == "("
+            && self
+                .tokens
+                .get(self.pos + 2 + operator_len)
+                .map(|token| token.eq_ignore_ascii_case("SELECT"))
+                .unwrap_or(false);
+        if is_subquery {
+            let column = normalize_identifier(column);
+            let operator = self.parser.parse_comparison_operator(&operator)?;
+            self.pos += 1 + operator_len;
+            let subquery = self.parenthesized_group(&column)?;
+            return Ok(WhereExpr::ScalarComparison {
+                column,
+                operator,
+                subquery: Box::new(
+                    self.parser.parse_select_anysql(&subquery.join(" "), self.depth + 1)?,
+                ),
+            });
+        }
+
+        let (current_time_len, timestamp) = self.time_operand(self.pos + 1 + operator_len);
+        if current_time_len > 0 {
+            let column = normalize_identifier(column);
+            let operator = self.parser.parse_comparison_operator(&operator)?;
+            self.pos += 1 + operator_len + current_time_len;
+            return Ok(time_comparison(column, operator, timestamp));
+        }
+
+        let clause = self.parser.parse_comparison_value(column, &operator, value)?;
+        self.pos += 2 + operator_len;
+        Ok(WhereExpr::Comparison(clause))
+    }
+
+    /// How many tokens at `at` spell a time: `NOW ( )`, `CURRENT_TIMESTAMP` or
+    /// `CURRENT_TIMESTAMP ( )`, or a `TIMESTAMP 'YYYY-MM-DD HH:MM:SS'` literal
+    /// whose value is returned too; 0 for anything else.
+    fn time_operand(&self, at: usize) -> (usize, Option<SqlValue>) {
+        let token_is = |offset: usize, text: &str| {
+            self.tokens
+                .get(at + offset)
+                .is_some_and(|token| token.eq_ignore_ascii_case(text))
+        };
+        let empty_parentheses = token_is(1, "(") && token_is(2, ")");
+        if token_is(0, "NOW") && empty_parentheses {
+            (3, None)
+        } else if token_is(0, "CURRENT_TIMESTAMP") {
+            (if empty_parentheses { 3 } else { 1 }, None)
+        } else if token_is(0, "TIMESTAMP") {
+            let literal = self.tokens.get(at + 1).and_then(|token| {
+                token.strip_prefix('\'')?.strip_suffix('\'').map(|text| text.to_string())
+            });
+            match literal {
+                Some(text) => (2, Some(SqlValue::Text(text.into()))),
+                None => (0, None),
+            }
+        } else {
+            (0, None)
+        }
+    }
+
+    /// `column [NOT] IN (v1, v2, ...)` or `column [NOT] IN (SELECT ...)`. A
+    /// subquery's tokens are rejoined and parsed as a SELECT of its own.
+    fn parse_in(&mut self, negated: bool) -> Result<WhereExpr, DatabaseError> {
+        let column = normalize_identifier(&self.tokens[self.pos]);
+        self.pos += if negated { 3 } else { 2 };
+
+        if self.tokens.get(self.pos).map(String::as_str) != Some("(") {
+            return Err(DatabaseError::ParseError(format!(
+                "Expected '(' after {} IN",
+                column
+            )));
+        }
+        let operand = self.parenthesized_group(&column)?;
+
+        let is_subquery = operand
+            .first()
+            .map(|token| token.eq_ignore_ascii_case("SELECT"))
+            .unwrap_or(false);
+        let source = if is_subquery {
+            let subquery = self.parser.parse_select_anysql(&operand.join(" "), self.depth + 1)?;
+            InSource::Subquery(Box::new(subquery))
+        } else {
+            InSource::Values(self.parse_in_values(&column, &operand)?)
+        };
+
+        Ok(WhereExpr::In {
+            column,
+            source,
+            negated,
+        })
+    }
+
+    /// Consumes `( ... )` starting at the current token and returns the tokens
+    /// between the matching parentheses.
+    fn parenthesized_group(&mut self, column: &str) -> Result<Vec<String>, DatabaseError> {
+        self.pos += 1;
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(token) = self.tokens.get(self.pos) {
+            match token.as_str() {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                let group = self.tokens[start..self.pos].to_vec();
+                self.pos += 1;
+                return Ok(group);
             }
+            self.pos += 1;
+        }
+
+        Err(DatabaseError::ParseError(format!(
+            "Missing closing parenthesis in condition on {}",
+            column
+        )))
+    }
+
+    /// `column [NOT] BETWEEN low AND high`. The AND here belongs to BETWEEN, so it
+    /// is consumed before `parse_and` can see it.
+    fn parse_between(&mut self, negated: bool) -> Result<WhereExpr, DatabaseError> {
+        let column = normalize_identifier(&self.tokens[self.pos]);
+        self.pos += if negated { 3 } else { 2 };
+
+        let invalid = || {
+            DatabaseError::ParseError(format!("Expected {} BETWEEN low AND high", column))
+        };
+        let (Some(low), Some(and), Some(high)) = (
+            self.tokens.get(self.pos),
+            self.tokens.get(self.pos + 1),
+            self.tokens.get(self.pos + 2),
+        ) else {
+            return Err(invalid());
+        };
+        if !and.eq_ignore_ascii_case("AND") {
+            return Err(invalid());
+        }
+
+        let low = self.parser.parse_value_anysql(low)?;
+        let high = self.parser.parse_value_anysql(high)?;
+        self.pos += 3;
+
+        Ok(WhereExpr::Between {
+            column,
+            low,
+            high,
+            negated,
+        })
+    }
+
+    /// Comma-separated literals; `()` is an empty list.
+    fn parse_in_values(
+        &self,
+        column: &str,
+        operand: &[String],
+    ) -> Result<Vec<SqlValue>, DatabaseError> {
+        if operand.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut values = Vec::new();
+        for item in operand.split(|token| token == ",") {
+            let [value] = item else {
+                return Err(DatabaseError::ParseError(format!(
+                    "Invalid value list after {} IN",
+                    column
+                )));
+            };
+            values.push(self.parser.parse_value_anysql(value)?);
         }
 
-        // 가장 높은 점수의 dialect 반환
-        let (best_dialect, best_score) = dialect_scores
-            .into_iter()
-            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
-            .unwrap_or((DetectedDialect::Standard, 1.0));
+        Ok(values)
+    }
 
-        (best_dialect, best_score)
+    /// Consumes the next token if it is `keyword` (case-insensitive).
+    fn next_is(&mut self, keyword: &str) -> bool {
+        let matches = self
+            .tokens
+            .get(self.pos)
+            .map(|token| token.eq_ignore_ascii_case(keyword))
+            .unwrap_or(false);
+        if matches {
+            self.pos += 1;
+        }
+        matches
     }
 }
 
@@ -217,6 +1182,7 @@ pub struct AnySQL {
     dialect_cache: Arc<Mutex<DialectCache>>,
     keyword_matcher: KeywordHashMatcher,
     performance_metrics: Arc<Mutex<PerformanceMetrics>>,
+    max_nesting_depth: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -310,43 +1276,132 @@ impl AnySQL {
                 avg_parse_time_ns: 0,
                 dialect_accuracy: 0.0,
             })),
+            max_nesting_depth: DEFAULT_MAX_QUERY_DEPTH,
         }
     }
 
+    pub fn with_max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+
+    /// Dialect scores for `sql` without parsing it or touching the dialect cache.
+    pub fn explain_dialect(&self, sql: &str) -> DialectExplanation {
+        self.keyword_matcher.explain_dialect(&sql.trim().to_uppercase())
+    }
+
     pub fn parse(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        self.parse_with_dialect_hint(sql, None).map(|(statement, _)| statement)
+    }
+
+    /// Parses with a caller-chosen dialect, skipping detection and the dialect cache.
+    /// `None` detects the dialect as `parse` does and accepts the syntax of every
+    /// dialect; an explicit one applies its own quoting and column types.
+    pub fn parse_with_dialect_hint(
+        &self,
+        sql: &str,
+        dialect: Option<DetectedDialect>,
+    ) -> Result<(SqlStatement, DetectedDialect), DatabaseError> {
         let sql = sql.trim();
+        self.check_parse_input(sql)?;
 
-        if sql.is_empty() {
-            return Err(DatabaseError::ParseError("Empty SQL statement".to_string()));
-        }
+        let Some(dialect) = dialect else {
+            let analysis = self.hyperthink_sql_analysis(sql)?;
+
+            println!(
+                "[HYPERTHINKING] Detected dialect: {:?}, Statement type: {:?}",
+                analysis.detected_dialect, analysis.statement_type
+            );
+
+            let statement = self.parse_statement(sql, analysis.statement_type, None)?;
+            return Ok((statement, analysis.detected_dialect));
+        };
 
-        let analysis = self.hyperthink_sql_analysis(sql)?;
+        let tokens: Vec<String> = sql
+            .to_uppercase()
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        let statement_type = self.determine_statement_type(&tokens)?;
 
         println!(
-            "[HYPERTHINKING] Detected dialect: {:?}, Statement type: {:?}",
-            analysis.detected_dialect, analysis.statement_type
+            "[HYPERTHINKING] Dialect override: {:?}, Statement type: {:?}",
+            dialect, statement_type
         );
 
-        match analysis.statement_type {
-            StatementType::CreateDatabase => self.parse_create_database_anysql(sql),
-            StatementType::CreateTable => self.parse_create_table_anysql(sql),
-            StatementType::Insert => self.parse_insert_anysql(sql),
-            StatementType::Select => self.parse_select_anysql(sql),
-            StatementType::Update => self.parse_update_anysql(sql),
-            StatementType::Delete => self.parse_delete_anysql(sql),
-            StatementType::DropTable => self.parse_drop_table_anysql(sql),
-            StatementType::DropDatabase => self.parse_drop_database_anysql(sql),
-            StatementType::AlterTable => self.parse_alter_table_anysql(sql),
+        let statement = self.parse_statement(sql, statement_type, Some(&dialect))?;
+        Ok((statement, dialect))
+    }
+
+    /// Parses `sql` with its `?` placeholders bound to `params` in order. The
+    /// values go straight into the statement, so they are never read as SQL.
+    pub fn parse_with_params(
+        &self,
+        sql: &str,
+        dialect: Option<DetectedDialect>,
+        params: &[SqlValue],
+    ) -> Result<(SqlStatement, DetectedDialect), DatabaseError> {
+        let (numbered, count) = number_placeholders(sql);
+        if count != params.len() {
+            return Err(DatabaseError::ParseError(format!(
+                "Statement has {} ? placeholders but {} params were given",
+                count,
+                params.len()
+            )));
         }
+        if count == 0 {
+            return self.parse_with_dialect_hint(sql, dialect);
+        }
+
+        BOUND_PARAMS.with(|bound| *bound.borrow_mut() = Some(params.to_vec()));
+        let result = self.parse_with_dialect_hint(&numbered, dialect);
+        BOUND_PARAMS.with(|bound| bound.borrow_mut().take());
+        result
     }
 
-    fn hyperthink_sql_analysis(&self, sql: &str) -> Result<SQLAnalysis, DatabaseError> {
-        let start_time = Instant::now();
-        let sql_hash = calculate_sql_hash(sql);
+    fn check_parse_input(&self, sql: &str) -> Result<(), DatabaseError> {
+        if sql.is_empty() {
+            return Err(DatabaseError::ParseError("Empty SQL statement".to_string()));
+        }
 
-        // 🚀 OPTIMIZATION: Check cache first
-        if let Ok(mut cache) = self.dialect_cache.lock() {
-            if let Some(cached_result) = cache.get(sql_hash) {
+        Ok(())
+    }
+
+    /// Rejects a parenthesis group or subquery `depth` levels deep once that is
+    /// deeper than `max_nesting_depth`, before recursing into it.
+    fn check_nesting(&self, depth: usize) -> Result<(), DatabaseError> {
+        if depth > self.max_nesting_depth {
+            return Err(DatabaseError::QueryTooComplex);
+        }
+        Ok(())
+    }
+
+    /// Parses `sql` as `statement_type`, with the identifier quoting and column
+    /// types of `dialect` when one was chosen.
+    fn parse_statement(
+        &self,
+        sql: &str,
+        statement_type: &StatementType,
+        dialect: Option<&DetectedDialect>,
+    ) -> Result<SqlStatement, DatabaseError> {
+        let Some(dialect) = dialect else {
+            return (statement_type.parse)(self, sql);
+        };
+        check_identifier_quotes(sql, dialect)?;
+
+        PARSE_DIALECT.with(|active| *active.borrow_mut() = Some(dialect.clone()));
+        let result = (statement_type.parse)(self, sql);
+        PARSE_DIALECT.with(|active| active.borrow_mut().take());
+        result
+    }
+
+    fn hyperthink_sql_analysis(&self, sql: &str) -> Result<SQLAnalysis, DatabaseError> {
+        let start_time = Instant::now();
+        let sql_hash = calculate_sql_hash(sql);
+
+        // 🚀 OPTIMIZATION: Check cache first
+        if let Ok(mut cache) = self.dialect_cache.lock() {
+            if let Some(cached_result) = cache.get(sql_hash) {
                 if let Ok(mut metrics) = self.performance_metrics.lock() {
                     metrics.total_queries += 1;
                     metrics.cache_hits += 1;
@@ -410,38 +1465,34 @@ impl AnySQL {
         })
     }
 
-    fn determine_statement_type(&self, tokens: &[String]) -> Result<StatementType, DatabaseError> {
+    fn determine_statement_type(
+        &self,
+        tokens: &[String],
+    ) -> Result<&'static StatementType, DatabaseError> {
         if tokens.is_empty() {
             return Err(DatabaseError::ParseError("No tokens found".to_string()));
         }
 
-        match tokens[0].as_str() {
-            "CREATE" => {
-                if tokens.len() > 1 && tokens[1] == "DATABASE" {
-                    Ok(StatementType::CreateDatabase)
-                } else {
-                    Ok(StatementType::CreateTable)
-                }
-            }
-            "DROP" => {
-                if tokens.len() > 1 && tokens[1] == "DATABASE" {
-                    Ok(StatementType::DropDatabase)
-                } else {
-                    Ok(StatementType::DropTable)
-                }
-            }
-            "ALTER" => Ok(StatementType::AlterTable),
-            "INSERT" | "INSERT_INTO" => Ok(StatementType::Insert),
-            "SELECT" => Ok(StatementType::Select),
-            "UPDATE" => Ok(StatementType::Update),
-            "DELETE" => Ok(StatementType::Delete),
-            _ => {
-                Err(DatabaseError::ParseError(format!(
-                    "Unknown statement type: {}",
-                    tokens[0]
-                )))
-            }
-        }
+        STATEMENT_TYPES
+            .iter()
+            .find(|statement_type| {
+                tokens.len() >= statement_type.keywords.len()
+                    && statement_type
+                        .keywords
+                        .iter()
+                        .zip(tokens)
+                        .all(|(keyword, token)| keyword == token)
+            })
+            .ok_or_else(|| {
+                let mut supported: Vec<&str> =
+                    STATEMENT_TYPES.iter().map(|statement_type| statement_type.name).collect();
+                supported.dedup();
+                DatabaseError::ParseError(format!(
+                    "unsupported statement: {} (supported: {})",
+                    tokens[0],
+                    supported.join(", ")
+                ))
+            })
     }
 
     fn parse_create_database_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
@@ -466,12 +1517,22 @@ impl AnySQL {
             ));
         }
 
-        let table_name = normalize_table_name(tokens[2]);
+        let if_not_exists = has_keywords(&tokens[2..], &["IF", "NOT", "EXISTS"]);
+        let name_pos = if if_not_exists { 5 } else { 2 };
+        let Some(name) = tokens.get(name_pos) else {
+            return Err(DatabaseError::ParseError(
+                "Invalid CREATE TABLE syntax".to_string(),
+            ));
+        };
+        let table_name = normalize_table_name(name);
 
-        if tokens.len() == 3 || !sql.contains('(') {
+        if tokens.len() == name_pos + 1 || !sql.contains('(') {
             return Ok(SqlStatement::CreateTable {
                 table_name,
                 columns: Vec::new(),
+                durability: self.parse_table_durability(&tokens[name_pos + 1..])?,
+                comment: None,
+                if_not_exists,
             });
         }
         let start_pos = sql.find('(').unwrap();
@@ -479,13 +1540,42 @@ impl AnySQL {
         let columns_str = &sql[start_pos + 1..end_pos];
 
         let columns = self.parse_columns_anysql(columns_str)?;
+        let (options, comment) = take_inline_comment(&sql[end_pos + 1..])?;
+        let options: Vec<&str> = options.split_whitespace().collect();
 
         Ok(SqlStatement::CreateTable {
             table_name,
             columns,
+            durability: self.parse_table_durability(&options)?,
+            comment,
+            if_not_exists,
         })
     }
 
+    /// Table options after the column list: `DURABILITY [=] SYNC | ASYNC`.
+    fn parse_table_durability(&self, options: &[&str]) -> Result<Durability, DatabaseError> {
+        let options: Vec<&str> = options
+            .iter()
+            .flat_map(|token| token.trim_end_matches(';').split('='))
+            .filter(|token| !token.is_empty())
+            .collect();
+
+        let Some(position) = options
+            .iter()
+            .position(|token| token.eq_ignore_ascii_case("DURABILITY"))
+        else {
+            return Ok(Durability::default());
+        };
+
+        match options.get(position + 1).map(|value| value.to_uppercase()) {
+            Some(value) if value == "SYNC" => Ok(Durability::Sync),
+            Some(value) if value == "ASYNC" => Ok(Durability::Async),
+            _ => Err(DatabaseError::ParseError(
+                "DURABILITY must be SYNC or ASYNC".to_string(),
+            )),
+        }
+    }
+
     fn parse_columns_anysql(
         &self,
         columns_str: &str,
@@ -495,6 +1585,10 @@ impl AnySQL {
         let column_defs = self.smart_split_columns(columns_str);
 
         for column_def in column_defs {
+            // Taken out first so words inside the comment are not read as constraints
+            let (column_def, comment) = take_inline_comment(&column_def)?;
+            let (column_def, default) = take_default(&column_def)?;
+            check_identity_arguments(&column_def)?;
             let column_tokens: Vec<&str> = column_def.trim().split_whitespace().collect();
 
             if column_tokens.len() < 2 {
@@ -503,9 +1597,19 @@ impl AnySQL {
 
             let column_name = normalize_identifier(column_tokens[0]);
             let data_type = self.parse_data_type_anysql(column_tokens[1])?;
+            let default = match default.as_deref().map(|literal| (literal, read_quoted(literal))) {
+                Some((_, Some((text, _)))) => Some(SqlValue::Text(text.into())),
+                Some((literal, None)) => match self.parse_value_anysql(literal)? {
+                    SqlValue::Null => None,
+                    value => Some(value),
+                },
+                None => None,
+            };
 
             let mut nullable = true;
             let mut primary_key = false;
+            let mut auto_increment = false;
+            let mut explicit_null = false;
 
             for i in 2..column_tokens.len() {
                 match column_tokens[i].to_uppercase().as_str() {
@@ -515,24 +1619,57 @@ impl AnySQL {
                     {
                         nullable = false;
                     }
+                    "NULL" if !column_tokens[i - 1].eq_ignore_ascii_case("NOT") => {
+                        explicit_null = true;
+                    }
                     "PRIMARY"
                         if i + 1 < column_tokens.len()
                             && column_tokens[i + 1].to_uppercase() == "KEY" =>
                     {
                         primary_key = true;
                     }
-                    "IDENTITY" | "AUTO_INCREMENT" => {
+                    "IDENTITY" | "AUTO_INCREMENT" | "AUTOINCREMENT" => {
                         primary_key = true;
+                        auto_increment = true;
+                    }
+                    // MS-SQL's IDENTITY(seed, increment), only ever (1, 1)
+                    token if token.starts_with("IDENTITY(") => {
+                        primary_key = true;
+                        auto_increment = true;
                     }
                     _ => {}
                 }
             }
 
+            if columns
+                .iter()
+                .any(|existing: &ColumnDefinition| existing.name == column_name)
+            {
+                return Err(DatabaseError::ParseError(format!(
+                    "Duplicate column name '{}'",
+                    column_name
+                )));
+            }
+
+            // Primary keys are implicitly NOT NULL
+            if primary_key {
+                if explicit_null {
+                    return Err(DatabaseError::ParseError(format!(
+                        "Primary key column '{}' cannot be declared NULL",
+                        column_name
+                    )));
+                }
+                nullable = false;
+            }
+
             columns.push(ColumnDefinition {
                 name: column_name,
                 data_type,
                 nullable,
                 primary_key,
+                comment,
+                default,
+                auto_increment,
             });
         }
 
@@ -573,6 +1710,9 @@ impl AnySQL {
     }
 
     fn parse_data_type_anysql(&self, type_str: &str) -> Result<DataType, DatabaseError> {
+        if let Some(dialect) = PARSE_DIALECT.with(|active| active.borrow().clone()) {
+            return dialect_data_type(&dialect, type_str);
+        }
         let type_upper = type_str.to_uppercase(); // Single conversion per call
 
         // HYPERTHINKING: Support all dialect data types
@@ -660,8 +1800,15 @@ impl AnySQL {
             vec!["*".to_string()]
         };
 
+        // ON CONFLICT / ON DUPLICATE KEY UPDATE follows the values
+        let (values_end, on_conflict_sql) =
+            match find_keyword(&sql[values_pos..], "ON", char::is_whitespace) {
+                Some(on_pos) => (values_pos + on_pos, Some(&sql[values_pos + on_pos..])),
+                None => (sql.len(), None),
+            };
+
         // Extract values
-        let values_part = &sql[values_pos + 6..];
+        let values_part = &sql[values_pos + 6..values_end];
         let start_pos = values_part.find('(').ok_or_else(|| {
             DatabaseError::ParseError("Missing opening parenthesis in VALUES".to_string())
         })?;
@@ -678,19 +1825,183 @@ impl AnySQL {
             values.push(value);
         }
 
+        let (conflict_target, on_conflict) = match on_conflict_sql {
+            Some(text) => {
+                let (target, on_conflict) = self.parse_on_conflict(text, &columns, &values)?;
+                (target, Some(on_conflict))
+            }
+            None => (Vec::new(), None),
+        };
+
         Ok(SqlStatement::Insert {
             table_name,
             columns,
             values,
+            on_conflict,
+            conflict_target,
         })
     }
 
-    fn parse_select_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
-        let tokens: Vec<&str> = sql.split_whitespace().collect();
+    /// `ON DUPLICATE KEY UPDATE ...` (MySQL) or `ON CONFLICT [(cols)] DO NOTHING |
+    /// DO UPDATE SET ...` (PostgreSQL, SQLite), with the conflict target columns.
+    /// Without a target any primary key or unique index the row collides with counts.
+    fn parse_on_conflict(
+        &self,
+        text: &str,
+        columns: &[String],
+        values: &[SqlValue],
+    ) -> Result<(Vec<String>, OnConflict), DatabaseError> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let upper: Vec<String> = tokens.iter().map(|token| token.to_uppercase()).collect();
+        let inserted: Vec<(String, SqlValue)> =
+            columns.iter().cloned().zip(values.iter().cloned()).collect();
+
+        if upper.len() > 4 && upper[1..4] == ["DUPLICATE", "KEY", "UPDATE"] {
+            let assignments = self.parse_set_clauses(&tokens[4..].join(" "), Some(&inserted))?;
+            return Ok((Vec::new(), OnConflict::Update(assignments)));
+        }
 
-        let from_pos = tokens
-            .iter()
-            .position(|&token| token.to_uppercase() == "FROM")
+        if upper.get(1).map(String::as_str) != Some("CONFLICT") {
+            return Err(DatabaseError::ParseError(
+                "Expected ON CONFLICT or ON DUPLICATE KEY UPDATE after VALUES".to_string(),
+            ));
+        }
+        let do_pos = upper.iter().position(|token| token == "DO").ok_or_else(|| {
+            DatabaseError::ParseError("ON CONFLICT needs DO NOTHING or DO UPDATE".to_string())
+        })?;
+
+        let target_sql = tokens[2..do_pos].join(" ");
+        let target = match target_sql.trim() {
+            "" => Vec::new(),
+            sql => sql
+                .strip_prefix('(')
+                .and_then(|sql| sql.strip_suffix(')'))
+                .ok_or_else(|| {
+                    DatabaseError::ParseError(format!(
+                        "ON CONFLICT target must be a parenthesised column list, got '{}'",
+                        sql
+                    ))
+                })?
+                .split(',')
+                .map(normalize_identifier)
+                .collect(),
+        };
+
+        let action = match upper[do_pos + 1..].iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["NOTHING"] => OnConflict::DoNothing,
+            ["UPDATE", "SET", ..] => {
+                if upper.iter().any(|token| token == "WHERE") {
+                    return Err(DatabaseError::ParseError(
+                        "WHERE in ON CONFLICT DO UPDATE is not supported".to_string(),
+                    ));
+                }
+                let set_sql = tokens[do_pos + 3..].join(" ");
+                OnConflict::Update(self.parse_set_clauses(&set_sql, Some(&inserted))?)
+            }
+            _ => {
+                return Err(DatabaseError::ParseError(
+                    "ON CONFLICT needs DO NOTHING or DO UPDATE SET".to_string(),
+                ))
+            }
+        };
+        Ok((target, action))
+    }
+
+    /// `col = value, ...`. With `inserted` (an upsert), `VALUES(col)` and
+    /// `EXCLUDED.col` stand for the value the INSERT gave `col`.
+    fn parse_set_clauses(
+        &self,
+        set_str: &str,
+        inserted: Option<&[(String, SqlValue)]>,
+    ) -> Result<Vec<(String, SqlValue)>, DatabaseError> {
+        let mut set_clauses = Vec::new();
+
+        for assignment in set_str.split(',') {
+            let parts: Vec<&str> = assignment.split('=').collect();
+            if parts.len() != 2 {
+                return Err(DatabaseError::ParseError("Invalid SET clause".to_string()));
+            }
+
+            let column_name = normalize_identifier(parts[0]);
+            let raw_value = parts[1].trim();
+            let value = match (inserted, inserted_reference(raw_value)) {
+                (Some(inserted), Some(reference)) => {
+                    let reference = normalize_identifier(reference);
+                    inserted
+                        .iter()
+                        .find(|(column, _)| *column == reference)
+                        .map(|(_, value)| value.clone())
+                        .ok_or_else(|| {
+                            DatabaseError::ParseError(format!(
+                                "'{}' refers to column '{}', which the INSERT does not list",
+                                raw_value, reference
+                            ))
+                        })?
+                }
+                _ => self.parse_value_anysql(raw_value)?,
+            };
+            set_clauses.push((column_name, value));
+        }
+
+        Ok(set_clauses)
+    }
+
+    /// SELECTs joined by `UNION [ALL]` outside parentheses, folded left to right.
+    /// A query without UNION is a plain SELECT.
+    fn parse_union_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let tokens = sql_words(sql);
+        let depths = token_depths(&tokens);
+        let mut statement: Option<SqlStatement> = None;
+        let mut all = false;
+        let mut start = 0;
+
+        for end in (0..=tokens.len()).filter(|&i| {
+            i == tokens.len() || (depths[i] == 0 && tokens[i].eq_ignore_ascii_case("UNION"))
+        }) {
+            let branch = &tokens[start..end];
+            if !branch.first().is_some_and(|token| token.eq_ignore_ascii_case("SELECT")) {
+                return Err(DatabaseError::ParseError(
+                    "Each side of UNION must be a SELECT".to_string(),
+                ));
+            }
+            let branch = self.parse_select_anysql(words_text(sql, branch), 0)?;
+
+            statement = Some(match statement {
+                None => branch,
+                Some(left) => SqlStatement::Union {
+                    left: Box::new(left),
+                    right: Box::new(branch),
+                    all,
+                },
+            });
+
+            all = tokens
+                .get(end + 1)
+                .is_some_and(|token| token.eq_ignore_ascii_case("ALL"));
+            start = end + if all { 2 } else { 1 };
+        }
+
+        statement.ok_or_else(|| DatabaseError::ParseError("Empty SELECT".to_string()))
+    }
+
+    /// A SELECT nested `depth` subqueries deep.
+    fn parse_select_anysql(&self, sql: &str, depth: usize) -> Result<SqlStatement, DatabaseError> {
+        self.check_nesting(depth)?;
+        let tokens = sql_words(sql);
+        let depths = token_depths(&tokens);
+        // Clause keywords only count outside parentheses, i.e. not inside a subquery
+        let keyword_pos = |keyword: &str| {
+            (0..tokens.len()).find(|&i| depths[i] == 0 && tokens[i].eq_ignore_ascii_case(keyword))
+        };
+        let keyword_pair_pos = |first: &str, second: &[&str]| {
+            (0..tokens.len().saturating_sub(1)).find(|&i| {
+                depths[i] == 0
+                    && tokens[i].eq_ignore_ascii_case(first)
+                    && second.iter().any(|s| tokens[i + 1].eq_ignore_ascii_case(s))
+            })
+        };
+
+        let from_pos = keyword_pos("FROM")
             .ok_or_else(|| DatabaseError::ParseError("Missing FROM clause".to_string()))?;
 
         if from_pos + 1 >= tokens.len() {
@@ -699,65 +2010,262 @@ impl AnySQL {
             ));
         }
 
-        let columns: Vec<String> = tokens[1..from_pos]
-            .iter()
-            .flat_map(|s| s.split(','))
-            .map(|s| normalize_identifier(s))
-            .collect();
+        let distinct = from_pos > 1 && tokens[1].eq_ignore_ascii_case("DISTINCT");
+        let list_start = if distinct { 2 } else { 1 };
+        let SelectList {
+            columns,
+            column_aliases,
+            aggregates,
+            scalar_subqueries,
+        } = self.parse_select_list(words_text(sql, &tokens[list_start..from_pos]), depth)?;
 
         let table_name = normalize_table_name(tokens[from_pos + 1]);
 
-        let where_pos = tokens
-            .iter()
-            .position(|&token| token.to_uppercase() == "WHERE");
-
-        let limit_pos = tokens
-            .iter()
-            .position(|&token| token.to_uppercase() == "LIMIT");
+        let where_pos = keyword_pos("WHERE");
+        let limit_pos = keyword_pos("LIMIT");
+        let offset_pos = keyword_pos("OFFSET");
 
-        let offset_pos = tokens
-            .iter()
-            .position(|&token| token.to_uppercase() == "OFFSET");
+        // SQL standard / MS-SQL paging: FETCH FIRST|NEXT n ROWS ONLY
+        let fetch_pos = keyword_pair_pos("FETCH", &["FIRST", "NEXT"]);
+        let order_pos = keyword_pair_pos("ORDER", &["BY"]);
+        let group_pos = keyword_pair_pos("GROUP", &["BY"]);
 
         let where_clause = if let Some(where_pos) = where_pos {
-            let where_end = limit_pos.or(offset_pos).unwrap_or(tokens.len());
-            Some(self.parse_where_clause_anysql(&tokens[where_pos + 1..where_end])?)
+            let where_end = [group_pos, order_pos, limit_pos, offset_pos, fetch_pos]
+                .into_iter()
+                .flatten()
+                .filter(|&pos| pos > where_pos)
+                .min()
+                .unwrap_or(tokens.len());
+            let clause = words_text(sql, &tokens[where_pos + 1..where_end]);
+            Some(self.parse_where_clause_anysql(clause, depth)?)
         } else {
             None
         };
 
-        let limit = if let Some(limit_pos) = limit_pos {
-            if limit_pos + 1 < tokens.len() {
-                tokens[limit_pos + 1].parse::<usize>().ok()
-            } else {
-                None
-            }
+        let group_by = if let Some(group_pos) = group_pos {
+            let group_end = [order_pos, limit_pos, offset_pos, fetch_pos]
+                .into_iter()
+                .flatten()
+                .filter(|&pos| pos > group_pos)
+                .min()
+                .unwrap_or(tokens.len());
+            parse_group_by(&tokens[group_pos + 2..group_end])?
         } else {
-            None
+            Vec::new()
         };
 
-        let offset = if let Some(offset_pos) = offset_pos {
-            if offset_pos + 1 < tokens.len() {
-                tokens[offset_pos + 1].parse::<usize>().ok()
-            } else {
-                None
+        let order_by = if let Some(order_pos) = order_pos {
+            let order_end = [limit_pos, offset_pos, fetch_pos]
+                .into_iter()
+                .flatten()
+                .filter(|&pos| pos > order_pos)
+                .min()
+                .unwrap_or(tokens.len());
+            let mut order_by = self.parse_order_by_anysql(&tokens[order_pos + 2..order_end])?;
+            // Rows are sorted before the aliases are applied
+            for key in &mut order_by {
+                if let Some((column, _)) = column_aliases.iter().find(|(_, a)| *a == key.column) {
+                    key.column = column.clone();
+                }
             }
+            Some(order_by)
         } else {
             None
         };
 
+        let (limit, limit_offset) = match (limit_pos, fetch_pos) {
+            (Some(_), Some(_)) => {
+                return Err(DatabaseError::ParseError(
+                    "LIMIT and FETCH FIRST cannot be combined".to_string(),
+                ))
+            }
+            (Some(limit_pos), None) => {
+                let limit_end = offset_pos
+                    .filter(|&pos| pos > limit_pos)
+                    .unwrap_or(tokens.len());
+                let (count, offset) = parse_limit_clause(&tokens[limit_pos + 1..limit_end])?;
+                (Some(count), offset)
+            }
+            (None, Some(fetch_pos)) => (Some(parse_fetch_count(&tokens[fetch_pos + 2..])?), None),
+            (None, None) => (None, None),
+        };
+
+        // `OFFSET n` and `OFFSET n ROWS` both land here
+        let offset = offset_pos
+            .map(|offset_pos| paging_value(&tokens, offset_pos + 1, "OFFSET"))
+            .transpose()?;
+        let offset = match (limit_offset, offset) {
+            (Some(_), Some(_)) => {
+                return Err(DatabaseError::ParseError(
+                    "LIMIT offset, count and OFFSET cannot be combined".to_string(),
+                ))
+            }
+            (limit_offset, offset) => limit_offset.or(offset),
+        };
+
         Ok(SqlStatement::Select {
             table_name,
             columns,
+            scalar_subqueries,
+            column_aliases,
+            distinct,
+            aggregates,
+            group_by,
             where_clause,
             optimization_hint: None,
+            order_by,
             limit,
             offset,
         })
     }
 
+    /// Splits the select list into plain columns, aggregate calls such as
+    /// `COUNT(*)` and `(SELECT ...) [AS name]` scalar subqueries. Unnamed
+    /// subqueries are called `subquery_1`, `subquery_2`, ... Two items may not
+    /// give the result the same name, except a column listed twice.
+    fn parse_select_list(&self, list: &str, depth: usize) -> Result<SelectList, DatabaseError> {
+        let mut select_list = SelectList {
+            columns: Vec::new(),
+            column_aliases: Vec::new(),
+            aggregates: Vec::new(),
+            scalar_subqueries: Vec::new(),
+        };
+        // (result name, whether it is a plain column)
+        let mut output_names: Vec<(String, bool)> = Vec::new();
+
+        for item in split_top_level(list, ',') {
+            let item = item.trim();
+            if let Some(aggregate) = parse_aggregate_item(item)? {
+                output_names.push((aggregate.name.clone(), false));
+                select_list.aggregates.push(aggregate);
+                continue;
+            }
+            let Some(inner) = item.strip_prefix('(') else {
+                let (column, alias) = match item.split_once(char::is_whitespace) {
+                    Some((column, alias)) => (normalize_identifier(column), Some(alias)),
+                    None => (normalize_identifier(item), None),
+                };
+                if let Some(alias) = alias {
+                    if column.ends_with('*') {
+                        return Err(DatabaseError::ParseError(format!(
+                            "'{}' cannot have an alias",
+                            column
+                        )));
+                    }
+                    let alias = select_alias(alias, column.clone(), &column)?;
+                    output_names.push((alias.clone(), false));
+                    select_list.column_aliases.push((column.clone(), alias));
+                } else if !column.ends_with('*') {
+                    output_names.push((column.clone(), true));
+                }
+                select_list.columns.push(column);
+                continue;
+            };
+
+            let close = split_top_level(inner, ')')[0].len();
+            if close == inner.len() {
+                return Err(DatabaseError::ParseError(
+                    "Missing closing parenthesis in select list".to_string(),
+                ));
+            }
+            let subquery = inner[..close].trim();
+            if !subquery.to_uppercase().starts_with("SELECT") {
+                return Err(DatabaseError::ParseError(format!(
+                    "Expected a subquery in select list, found '({})'",
+                    subquery
+                )));
+            }
+
+            let default_name = format!("subquery_{}", select_list.scalar_subqueries.len() + 1);
+            let alias = select_alias(&inner[close + 1..], default_name, "subquery")?;
+            output_names.push((alias.clone(), false));
+            select_list
+                .scalar_subqueries
+                .push((alias, self.parse_select_anysql(subquery, depth + 1)?));
+        }
+
+        for (i, (name, plain)) in output_names.iter().enumerate() {
+            if output_names[..i]
+                .iter()
+                .any(|(earlier, earlier_plain)| earlier == name && !(*plain && *earlier_plain))
+            {
+                return Err(DatabaseError::ParseError(format!(
+                    "Result column '{}' is named twice in the select list",
+                    name
+                )));
+            }
+        }
+
+        Ok(select_list)
+    }
+
+    /// Sort keys after ORDER BY: `column [ASC|DESC] [NULLS FIRST|LAST]`, comma separated.
+    fn parse_order_by_anysql(&self, tokens: &[&str]) -> Result<Vec<OrderBy>, DatabaseError> {
+        let clause = tokens.join(" ");
+        let clause = clause.trim().trim_end_matches(';');
+        let mut keys = Vec::new();
+
+        for item in clause.split(',') {
+            let parts: Vec<&str> = item.split_whitespace().collect();
+            let Some(column) = parts.first() else {
+                return Err(DatabaseError::ParseError(
+                    "Empty ORDER BY item".to_string(),
+                ));
+            };
+
+            let mut direction = SortDirection::Asc;
+            let mut nulls = NullsOrder::default();
+            let mut index = 1;
+
+            if let Some(token) = parts.get(index) {
+                if token.eq_ignore_ascii_case("ASC") {
+                    index += 1;
+                } else if token.eq_ignore_ascii_case("DESC") {
+                    direction = SortDirection::Desc;
+                    index += 1;
+                }
+            }
+
+            if let Some(token) = parts.get(index) {
+                if token.eq_ignore_ascii_case("NULLS") {
+                    nulls = match parts.get(index + 1).map(|t| t.to_uppercase()) {
+                        Some(ref t) if t == "FIRST" => NullsOrder::First,
+                        Some(ref t) if t == "LAST" => NullsOrder::Last,
+                        _ => {
+                            return Err(DatabaseError::ParseError(
+                                "NULLS must be followed by FIRST or LAST".to_string(),
+                            ))
+                        }
+                    };
+                    index += 2;
+                }
+            }
+
+            if index < parts.len() {
+                return Err(DatabaseError::ParseError(format!(
+                    "Unexpected token '{}' in ORDER BY",
+                    parts[index]
+                )));
+            }
+
+            // `ORDER BY count(*)` refers to the `COUNT(*)` result column
+            let column = match parse_aggregate_item(column)? {
+                Some(aggregate) => aggregate.name,
+                None => normalize_identifier(column),
+            };
+            keys.push(OrderBy {
+                column,
+                direction,
+                nulls,
+            });
+        }
+
+        Ok(keys)
+    }
+
     fn parse_update_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
-        let tokens: Vec<&str> = sql.split_whitespace().collect();
+        let tokens = sql_words(sql);
 
         if tokens.len() < 4 {
             return Err(DatabaseError::ParseError(
@@ -778,23 +2286,10 @@ impl AnySQL {
         let set_end = where_pos.unwrap_or(tokens.len());
         let set_tokens = &tokens[set_pos + 1..set_end];
 
-        let mut set_clauses = Vec::new();
-        let set_str = set_tokens.join(" ");
-        let assignments: Vec<&str> = set_str.split(',').collect();
-
-        for assignment in assignments {
-            let parts: Vec<&str> = assignment.split('=').collect();
-            if parts.len() != 2 {
-                return Err(DatabaseError::ParseError("Invalid SET clause".to_string()));
-            }
-
-            let column_name = normalize_identifier(parts[0]);
-            let value = self.parse_value_anysql(parts[1].trim())?;
-            set_clauses.push((column_name, value));
-        }
+        let set_clauses = self.parse_set_clauses(words_text(sql, set_tokens), None)?;
 
         let where_clause = if let Some(where_pos) = where_pos {
-            Some(self.parse_where_clause_anysql(&tokens[where_pos + 1..])?)
+            Some(self.parse_where_clause_anysql(words_text(sql, &tokens[where_pos + 1..]), 0)?)
         } else {
             None
         };
@@ -807,7 +2302,7 @@ impl AnySQL {
     }
 
     fn parse_delete_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
-        let tokens: Vec<&str> = sql.split_whitespace().collect();
+        let tokens = sql_words(sql);
 
         if tokens.len() < 3 || tokens[1].to_uppercase() != "FROM" {
             return Err(DatabaseError::ParseError(
@@ -821,7 +2316,7 @@ impl AnySQL {
             .iter()
             .position(|&token| token.to_uppercase() == "WHERE")
         {
-            Some(self.parse_where_clause_anysql(&tokens[where_pos + 1..])?)
+            Some(self.parse_where_clause_anysql(words_text(sql, &tokens[where_pos + 1..]), 0)?)
         } else {
             None
         };
@@ -832,19 +2327,49 @@ impl AnySQL {
         })
     }
 
-    fn parse_where_clause_anysql(&self, tokens: &[&str]) -> Result<WhereClause, DatabaseError> {
-        if tokens.len() < 3 {
+    /// `column op value` comparisons combined with AND/OR and parentheses, in a
+    /// query nested `depth` subqueries deep.
+    fn parse_where_clause_anysql(
+        &self,
+        clause: &str,
+        depth: usize,
+    ) -> Result<WhereExpr, DatabaseError> {
+        let mut where_parser = WhereParser {
+            parser: self,
+            tokens: tokenize_where(clause),
+            pos: 0,
+            depth,
+        };
+
+        let expr = where_parser.parse_or()?;
+        if let Some(token) = where_parser.tokens.get(where_parser.pos) {
+            return Err(DatabaseError::ParseError(format!(
+                "Unexpected '{}' in WHERE clause",
+                token
+            )));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison_value(
+        &self,
+        column: &str,
+        operator: &str,
+        value: &str,
+    ) -> Result<WhereClause, DatabaseError> {
+        let operator = self.parse_comparison_operator(operator)?;
+        let value = self.parse_value_anysql(value)?;
+
+        if matches!(operator, ComparisonOperator::Like | ComparisonOperator::NotLike)
+            && !matches!(value, SqlValue::Text(_))
+        {
             return Err(DatabaseError::ParseError(
-                "Invalid WHERE clause".to_string(),
+                "LIKE pattern must be a quoted string".to_string(),
             ));
         }
 
-        let column = normalize_identifier(tokens[0]);
-        let operator = self.parse_comparison_operator(tokens[1])?;
-        let value = self.parse_value_anysql(tokens[2])?;
-
         Ok(WhereClause {
-            column,
+            column: normalize_identifier(column),
             operator,
             value,
         })
@@ -858,6 +2383,8 @@ impl AnySQL {
             "<" => Ok(ComparisonOperator::LessThan),
             ">=" => Ok(ComparisonOperator::GreaterThanOrEqual),
             "<=" => Ok(ComparisonOperator::LessThanOrEqual),
+            "LIKE" => Ok(ComparisonOperator::Like),
+            "NOT LIKE" => Ok(ComparisonOperator::NotLike),
             _ => Err(DatabaseError::ParseError(format!(
                 "Unknown comparison operator: {}",
                 op
@@ -865,9 +2392,15 @@ impl AnySQL {
         }
     }
 
+    /// Unquoted tokens are trimmed before they are read as NULL, a boolean or a
+    /// number; a quoted string keeps everything between its quotes, spaces included.
     fn parse_value_anysql(&self, value_str: &str) -> Result<SqlValue, DatabaseError> {
         let value_str = value_str.trim();
 
+        if let Some(value) = bound_param(value_str) {
+            return Ok(value);
+        }
+
         if value_str.eq_ignore_ascii_case("NULL") {
             return Ok(SqlValue::Null);
         }
@@ -881,12 +2414,13 @@ impl AnySQL {
         }
 
         // Handle quoted strings (all quote types)
-        if (value_str.starts_with('\'') && value_str.ends_with('\''))
-            || (value_str.starts_with('"') && value_str.ends_with('"'))
-            || (value_str.starts_with('`') && value_str.ends_with('`'))
+        if value_str.len() >= 2
+            && ((value_str.starts_with('\'') && value_str.ends_with('\''))
+                || (value_str.starts_with('"') && value_str.ends_with('"'))
+                || (value_str.starts_with('`') && value_str.ends_with('`')))
         {
             let text = value_str[1..value_str.len() - 1].to_string();
-            return Ok(SqlValue::Text(text));
+            return Ok(SqlValue::Text(text.into()));
         }
 
         // Try parsing as number
@@ -901,7 +2435,7 @@ impl AnySQL {
         }
 
         // Default to text
-        Ok(SqlValue::Text(value_str.to_string()))
+        Ok(SqlValue::Text(value_str.into()))
     }
 
     fn parse_drop_table_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
@@ -913,8 +2447,313 @@ impl AnySQL {
             ));
         }
 
-        let table_name = normalize_table_name(tokens[2]);
-        Ok(SqlStatement::DropTable { table_name })
+        let if_exists = has_keywords(&tokens[2..], &["IF", "EXISTS"]);
+        let name_pos = if if_exists { 4 } else { 2 };
+        let Some(name) = tokens.get(name_pos) else {
+            return Err(DatabaseError::ParseError(
+                "Invalid DROP TABLE syntax".to_string(),
+            ));
+        };
+        Ok(SqlStatement::DropTable {
+            table_name: normalize_table_name(name.trim_end_matches(';')),
+            if_exists,
+        })
+    }
+
+    /// `CREATE [UNIQUE] INDEX name ON table (column[, ...])`. ASC/DESC after a
+    /// column is accepted and ignored.
+    fn parse_create_index_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let invalid = || {
+            DatabaseError::ParseError(
+                "Invalid CREATE INDEX syntax; expected CREATE [UNIQUE] INDEX name ON table (columns)"
+                    .to_string(),
+            )
+        };
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        let (open, close) = match (sql.find('('), sql.rfind(')')) {
+            (Some(open), Some(close)) if open < close && close == sql.len() - 1 => (open, close),
+            _ => return Err(invalid()),
+        };
+
+        let head: Vec<&str> = sql[..open].split_whitespace().collect();
+        let (is_unique, rest) = match head.as_slice() {
+            [_, unique, index, rest @ ..]
+                if unique.eq_ignore_ascii_case("UNIQUE") && index.eq_ignore_ascii_case("INDEX") =>
+            {
+                (true, rest)
+            }
+            [_, index, rest @ ..] if index.eq_ignore_ascii_case("INDEX") => (false, rest),
+            _ => return Err(invalid()),
+        };
+        let [index_name, on, table] = rest else {
+            return Err(invalid());
+        };
+        if !on.eq_ignore_ascii_case("ON") {
+            return Err(invalid());
+        }
+
+        let mut column_names = Vec::new();
+        let mut case_insensitive = false;
+        for column in sql[open + 1..close].split(',') {
+            let words: Vec<&str> = column.split_whitespace().collect();
+            let name = words.first().ok_or_else(invalid)?;
+            // `col COLLATE NOCASE [ASC|DESC]`; BINARY is the default collation
+            let collate = words.iter().position(|word| word.eq_ignore_ascii_case("COLLATE"));
+            if let Some(position) = collate {
+                match words.get(position + 1) {
+                    Some(collation) if collation.eq_ignore_ascii_case("NOCASE") => {
+                        case_insensitive = true;
+                    }
+                    Some(collation) if collation.eq_ignore_ascii_case("BINARY") => {}
+                    _ => {
+                        return Err(DatabaseError::ParseError(format!(
+                            "Unsupported collation in index column '{}'; use NOCASE or BINARY",
+                            column.trim()
+                        )))
+                    }
+                }
+            }
+            column_names.push(normalize_identifier(name));
+        }
+        if case_insensitive && column_names.len() > 1 {
+            return Err(DatabaseError::ParseError(
+                "COLLATE NOCASE is only supported on single-column indexes".to_string(),
+            ));
+        }
+
+        Ok(SqlStatement::CreateCompositeIndex {
+            index_name: normalize_identifier(index_name),
+            table_name: normalize_table_name(table),
+            column_names,
+            is_unique,
+            case_insensitive,
+        })
+    }
+
+    /// `DROP INDEX name [ON table]`, or MS-SQL's `DROP INDEX table.name`.
+    fn parse_drop_index_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let tokens: Vec<&str> = sql.trim().trim_end_matches(';').split_whitespace().collect();
+
+        match tokens.as_slice() {
+            [_, _, name] => Ok(match name.split_once('.') {
+                Some((table, index)) => SqlStatement::DropIndex {
+                    index_name: normalize_identifier(index),
+                    table_name: Some(normalize_table_name(table)),
+                },
+                None => SqlStatement::DropIndex {
+                    index_name: normalize_identifier(name),
+                    table_name: None,
+                },
+            }),
+            [_, _, name, on, table] if on.eq_ignore_ascii_case("ON") => {
+                Ok(SqlStatement::DropIndex {
+                    index_name: normalize_identifier(name),
+                    table_name: Some(normalize_table_name(table)),
+                })
+            }
+            _ => Err(DatabaseError::ParseError(
+                "Invalid DROP INDEX syntax; expected DROP INDEX name [ON table]".to_string(),
+            )),
+        }
+    }
+
+    /// `ANALYZE`, `ANALYZE t` or MySQL's `ANALYZE TABLE t`.
+    fn parse_analyze_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let mut tokens: Vec<&str> = sql.split_whitespace().skip(1).collect();
+
+        if tokens
+            .first()
+            .map(|token| token.eq_ignore_ascii_case("TABLE"))
+            .unwrap_or(false)
+        {
+            tokens.remove(0);
+            if tokens.is_empty() {
+                return Err(DatabaseError::ParseError(
+                    "Invalid ANALYZE TABLE syntax".to_string(),
+                ));
+            }
+        }
+
+        match tokens.as_slice() {
+            [] => Ok(SqlStatement::Analyze { table_name: None }),
+            [table] => Ok(SqlStatement::Analyze {
+                table_name: Some(normalize_table_name(table)),
+            }),
+            _ => Err(DatabaseError::ParseError(
+                "ANALYZE takes at most one table name".to_string(),
+            )),
+        }
+    }
+
+    /// `BEGIN [TRANSACTION | WORK]`, `START TRANSACTION`, and `COMMIT` / `ROLLBACK`
+    /// with the same optional noise word.
+    fn parse_transaction_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let tokens: Vec<String> = sql
+            .trim()
+            .trim_end_matches(';')
+            .split_whitespace()
+            .map(|token| token.to_uppercase())
+            .collect();
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+        match tokens.as_slice() {
+            ["BEGIN"] | ["BEGIN", "TRANSACTION" | "WORK"] | ["START", "TRANSACTION"] => {
+                Ok(SqlStatement::Begin)
+            }
+            ["COMMIT"] | ["COMMIT", "TRANSACTION" | "WORK"] => Ok(SqlStatement::Commit),
+            ["ROLLBACK"] | ["ROLLBACK", "TRANSACTION" | "WORK"] => Ok(SqlStatement::Rollback),
+            _ => Err(DatabaseError::ParseError(format!(
+                "Invalid transaction statement: {}",
+                sql.trim()
+            ))),
+        }
+    }
+
+    /// `COMMENT ON TABLE t IS 'text'` and `COMMENT ON COLUMN t.c IS 'text'`. `IS
+    /// NULL` removes the comment.
+    fn parse_comment_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let sql = sql.trim().trim_end_matches(';').trim();
+        let tokens: Vec<&str> = sql.split_whitespace().collect();
+        let invalid = || {
+            DatabaseError::ParseError(
+                "Invalid COMMENT syntax; expected COMMENT ON TABLE|COLUMN name IS 'text'"
+                    .to_string(),
+            )
+        };
+        if tokens.len() < 6
+            || !tokens[1].eq_ignore_ascii_case("ON")
+            || !tokens[4].eq_ignore_ascii_case("IS")
+        {
+            return Err(invalid());
+        }
+
+        // The comment is everything after IS, spaces included
+        let mut rest = sql;
+        for _ in 0..5 {
+            rest = rest.trim_start();
+            rest = &rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..];
+        }
+        let rest = rest.trim();
+        let comment = if rest.eq_ignore_ascii_case("NULL") {
+            None
+        } else {
+            match read_quoted(rest) {
+                Some((comment, consumed)) if consumed == rest.len() => Some(comment),
+                _ => return Err(invalid()),
+            }
+        };
+
+        let (table_name, column_name) = match tokens[2].to_uppercase().as_str() {
+            "TABLE" => (normalize_table_name(tokens[3]), None),
+            "COLUMN" => {
+                let (table, column) = tokens[3].rsplit_once('.').ok_or_else(|| {
+                    DatabaseError::ParseError(
+                        "COMMENT ON COLUMN needs table.column".to_string(),
+                    )
+                })?;
+                (normalize_table_name(table), Some(normalize_identifier(column)))
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(SqlStatement::Comment {
+            table_name,
+            column_name,
+            comment,
+        })
+    }
+
+    fn parse_checkpoint_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        if sql.trim().trim_end_matches(';').trim().eq_ignore_ascii_case("CHECKPOINT") {
+            Ok(SqlStatement::Checkpoint)
+        } else {
+            Err(DatabaseError::ParseError(
+                "CHECKPOINT takes no arguments".to_string(),
+            ))
+        }
+    }
+
+    /// `SHOW TABLES`, and `DESCRIBE | DESC <table>` or its spelling `SHOW COLUMNS
+    /// FROM <table>`.
+    fn parse_show_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let tokens: Vec<&str> = sql.trim().trim_end_matches(';').split_whitespace().collect();
+        let keywords: Vec<String> = tokens.iter().map(|token| token.to_uppercase()).collect();
+        let keywords: Vec<&str> = keywords.iter().map(String::as_str).collect();
+        match (keywords.as_slice(), tokens.last()) {
+            (["SHOW", "TABLES"], _) => Ok(SqlStatement::ShowTables),
+            (["SHOW", "COLUMNS", "FROM" | "IN", _] | ["DESCRIBE" | "DESC", _], Some(table)) => {
+                Ok(SqlStatement::Describe {
+                    table_name: normalize_table_name(table),
+                })
+            }
+            _ => Err(DatabaseError::ParseError(
+                "Expected SHOW TABLES, SHOW COLUMNS FROM <table> or DESCRIBE <table>".to_string(),
+            )),
+        }
+    }
+
+    /// `GRANT priv[, ...] ON table|* TO 'email'` and `REVOKE ... FROM 'email'`.
+    /// `ALL [PRIVILEGES]` is accepted as a privilege; `*` and `*.*` mean every table.
+    fn parse_grant_anysql(&self, sql: &str, is_grant: bool) -> Result<SqlStatement, DatabaseError> {
+        let keyword = if is_grant { "GRANT" } else { "REVOKE" };
+        let target_keyword = if is_grant { "TO" } else { "FROM" };
+        let invalid = || DatabaseError::ParseError(format!("Invalid {} syntax", keyword));
+
+        let tokens: Vec<&str> = sql.trim_end_matches(';').split_whitespace().collect();
+        let on_pos = tokens
+            .iter()
+            .position(|token| token.eq_ignore_ascii_case("ON"))
+            .ok_or_else(invalid)?;
+        let target_pos = tokens
+            .iter()
+            .position(|token| token.eq_ignore_ascii_case(target_keyword))
+            .filter(|pos| *pos == on_pos + 2)
+            .ok_or_else(invalid)?;
+        if tokens.len() != target_pos + 2 {
+            return Err(invalid());
+        }
+
+        let mut privileges = Vec::new();
+        for privilege in tokens[1..on_pos].join(" ").split(',') {
+            let privilege = privilege.trim().to_uppercase();
+            let privilege = match privilege.as_str() {
+                "ALL" | "ALL PRIVILEGES" => "ALL".to_string(),
+                "SELECT" | "INSERT" | "UPDATE" | "DELETE" | "CREATE" | "DROP" | "ALTER" => {
+                    privilege
+                }
+                _ => {
+                    return Err(DatabaseError::ParseError(format!(
+                        "Unknown privilege '{}' in {}",
+                        privilege, keyword
+                    )));
+                }
+            };
+            privileges.push(privilege);
+        }
+
+        let table_name = match tokens[on_pos + 1] {
+            "*" | "*.*" => None,
+            table => Some(normalize_table_name(table)),
+        };
+
+        let grantee = normalize_identifier(tokens[target_pos + 1]);
+        if grantee.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(if is_grant {
+            SqlStatement::Grant {
+                privileges,
+                table_name,
+                grantee,
+            }
+        } else {
+            SqlStatement::Revoke {
+                privileges,
+                table_name,
+                grantee,
+            }
+        })
     }
 
     fn parse_drop_database_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
@@ -930,6 +2769,31 @@ impl AnySQL {
         Ok(SqlStatement::DropDatabase { database_name })
     }
 
+    fn parse_use_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let tokens: Vec<&str> = sql.trim().trim_end_matches(';').split_whitespace().collect();
+        match tokens.as_slice() {
+            [_, name] => Ok(SqlStatement::Use {
+                database_name: normalize_table_name(name),
+            }),
+            _ => Err(DatabaseError::ParseError(
+                "USE takes one database name: USE <name>".to_string(),
+            )),
+        }
+    }
+
+    fn parse_reset_database_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
+        let tokens: Vec<&str> = sql.trim().trim_end_matches(';').split_whitespace().collect();
+        match tokens.as_slice() {
+            [_, _, name] => Ok(SqlStatement::ResetDatabase {
+                database_name: normalize_table_name(name),
+            }),
+            _ => Err(DatabaseError::ParseError(
+                "RESET DATABASE needs the database name as confirmation: RESET DATABASE <name>"
+                    .to_string(),
+            )),
+        }
+    }
+
     fn parse_alter_table_anysql(&self, sql: &str) -> Result<SqlStatement, DatabaseError> {
         use super::core_types::AlterAction;
 
@@ -963,6 +2827,9 @@ impl AnySQL {
                             data_type,
                             nullable: true, // Default to nullable
                             primary_key: false,
+                            comment: None,
+                            default: None,
+                            auto_increment: false,
                         },
                     }
                 } else {
@@ -1000,6 +2867,9 @@ impl AnySQL {
                             data_type,
                             nullable: true, // Default to nullable
                             primary_key: false,
+                            comment: None,
+                            default: None,
+                            auto_increment: false,
                         },
                     }
                 } else {
@@ -1027,6 +2897,37 @@ impl AnySQL {
         }
     }
 
+    pub fn performance_snapshot(&self) -> Option<ParserMetricsSnapshot> {
+        self.snapshot_metrics(false)
+    }
+
+    /// Returns the current counters and zeroes them in the same critical section,
+    /// so every query is counted in exactly one interval.
+    pub fn take_performance_snapshot(&self) -> Option<ParserMetricsSnapshot> {
+        self.snapshot_metrics(true)
+    }
+
+    fn snapshot_metrics(&self, reset: bool) -> Option<ParserMetricsSnapshot> {
+        // Same lock order as the cache-hit path in hyperthink_sql_analysis
+        let mut cache = self.dialect_cache.lock().ok()?;
+        let mut metrics = self.performance_metrics.lock().ok()?;
+
+        let snapshot = ParserMetricsSnapshot {
+            metrics: metrics.clone(),
+            dialect_cache_hits: cache.hits,
+            dialect_cache_misses: cache.misses,
+            dialect_cache_entries: cache.cache.len(),
+        };
+
+        if reset {
+            *metrics = PerformanceMetrics::default();
+            cache.hits = 0;
+            cache.misses = 0;
+        }
+
+        Some(snapshot)
+    }
+
     pub fn get_cache_hit_rate(&self) -> f32 {
         if let Ok(cache) = self.dialect_cache.lock() {
             cache.hit_rate()
@@ -1047,29 +2948,793 @@ impl AnySQL {
 
 #[derive(Debug, Clone)]
 struct SQLAnalysis {
-    statement_type: StatementType,
+    statement_type: &'static StatementType,
     detected_dialect: DetectedDialect,
     original_sql: String,
     tokens: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
-enum StatementType {
-    CreateDatabase,
-    CreateTable,
-    Insert,
-    Select,
-    Update,
-    Delete,
-    DropTable,
-    DropDatabase,
-    AlterTable,
+/// A registered statement: the leading keywords that select it and its parse
+/// function. Entries are tried in order, so a keyword sequence must come before
+/// any shorter prefix of it (`CREATE DATABASE` before `CREATE`).
+struct StatementType {
+    name: &'static str,
+    keywords: &'static [&'static str],
+    parse: fn(&AnySQL, &str) -> Result<SqlStatement, DatabaseError>,
 }
 
+impl std::fmt::Debug for StatementType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+const STATEMENT_TYPES: &[StatementType] = &[
+    StatementType {
+        name: "CREATE DATABASE",
+        keywords: &["CREATE", "DATABASE"],
+        parse: AnySQL::parse_create_database_anysql,
+    },
+    StatementType {
+        name: "CREATE INDEX",
+        keywords: &["CREATE", "INDEX"],
+        parse: AnySQL::parse_create_index_anysql,
+    },
+    StatementType {
+        name: "CREATE INDEX",
+        keywords: &["CREATE", "UNIQUE", "INDEX"],
+        parse: AnySQL::parse_create_index_anysql,
+    },
+    StatementType {
+        name: "CREATE TABLE",
+        keywords: &["CREATE"],
+        parse: AnySQL::parse_create_table_anysql,
+    },
+    StatementType {
+        name: "DROP DATABASE",
+        keywords: &["DROP", "DATABASE"],
+        parse: AnySQL::parse_drop_database_anysql,
+    },
+    StatementType {
+        name: "RESET DATABASE",
+        keywords: &["RESET", "DATABASE"],
+        parse: AnySQL::parse_reset_database_anysql,
+    },
+    StatementType {
+        name: "USE",
+        keywords: &["USE"],
+        parse: AnySQL::parse_use_anysql,
+    },
+    StatementType {
+        name: "DROP INDEX",
+        keywords: &["DROP", "INDEX"],
+        parse: AnySQL::parse_drop_index_anysql,
+    },
+    StatementType {
+        name: "DROP TABLE",
+        keywords: &["DROP"],
+        parse: AnySQL::parse_drop_table_anysql,
+    },
+    StatementType {
+        name: "ALTER TABLE",
+        keywords: &["ALTER"],
+        parse: AnySQL::parse_alter_table_anysql,
+    },
+    StatementType {
+        name: "INSERT",
+        keywords: &["INSERT"],
+        parse: AnySQL::parse_insert_anysql,
+    },
+    StatementType {
+        name: "INSERT",
+        keywords: &["INSERT_INTO"],
+        parse: AnySQL::parse_insert_anysql,
+    },
+    StatementType {
+        name: "SELECT",
+        keywords: &["SELECT"],
+        parse: AnySQL::parse_union_anysql,
+    },
+    StatementType {
+        name: "UPDATE",
+        keywords: &["UPDATE"],
+        parse: AnySQL::parse_update_anysql,
+    },
+    StatementType {
+        name: "DELETE",
+        keywords: &["DELETE"],
+        parse: AnySQL::parse_delete_anysql,
+    },
+    StatementType {
+        name: "ANALYZE",
+        keywords: &["ANALYZE"],
+        parse: AnySQL::parse_analyze_anysql,
+    },
+    StatementType {
+        name: "BEGIN",
+        keywords: &["BEGIN"],
+        parse: AnySQL::parse_transaction_anysql,
+    },
+    StatementType {
+        name: "BEGIN",
+        keywords: &["START", "TRANSACTION"],
+        parse: AnySQL::parse_transaction_anysql,
+    },
+    StatementType {
+        name: "COMMIT",
+        keywords: &["COMMIT"],
+        parse: AnySQL::parse_transaction_anysql,
+    },
+    StatementType {
+        name: "ROLLBACK",
+        keywords: &["ROLLBACK"],
+        parse: AnySQL::parse_transaction_anysql,
+    },
+    StatementType {
+        name: "COMMENT",
+        keywords: &["COMMENT", "ON"],
+        parse: AnySQL::parse_comment_anysql,
+    },
+    StatementType {
+        name: "CHECKPOINT",
+        keywords: &["CHECKPOINT"],
+        parse: AnySQL::parse_checkpoint_anysql,
+    },
+    StatementType {
+        name: "SHOW TABLES",
+        keywords: &["SHOW", "TABLES"],
+        parse: AnySQL::parse_show_anysql,
+    },
+    StatementType {
+        name: "DESCRIBE",
+        keywords: &["SHOW", "COLUMNS"],
+        parse: AnySQL::parse_show_anysql,
+    },
+    StatementType {
+        name: "DESCRIBE",
+        keywords: &["DESCRIBE"],
+        parse: AnySQL::parse_show_anysql,
+    },
+    StatementType {
+        name: "DESCRIBE",
+        keywords: &["DESC"],
+        parse: AnySQL::parse_show_anysql,
+    },
+    StatementType {
+        name: "GRANT",
+        keywords: &["GRANT"],
+        parse: |parser, sql| parser.parse_grant_anysql(sql, true),
+    },
+    StatementType {
+        name: "REVOKE",
+        keywords: &["REVOKE"],
+        parse: |parser, sql| parser.parse_grant_anysql(sql, false),
+    },
+];
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum DetectedDialect {
+pub enum DetectedDialect {
     Standard,
     MsSQL,
     MySQL,
     Oracle,
 }
+
+impl DetectedDialect {
+    pub const ALL: [DetectedDialect; 4] = [
+        DetectedDialect::Standard,
+        DetectedDialect::MsSQL,
+        DetectedDialect::MySQL,
+        DetectedDialect::Oracle,
+    ];
+
+    /// Accepts the names from `name` plus common aliases, case-insensitively.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "standard" | "ansi" | "sql" => Some(DetectedDialect::Standard),
+            "mssql" | "sqlserver" | "tsql" => Some(DetectedDialect::MsSQL),
+            "mysql" | "mariadb" => Some(DetectedDialect::MySQL),
+            "oracle" => Some(DetectedDialect::Oracle),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DetectedDialect::Standard => "standard",
+            DetectedDialect::MsSQL => "mssql",
+            DetectedDialect::MySQL => "mysql",
+            DetectedDialect::Oracle => "oracle",
+        }
+    }
+
+    /// `name` as a quoted identifier: backticks for MySQL, brackets for MS-SQL
+    /// and double quotes otherwise, with embedded closing quotes doubled.
+    pub fn quote_identifier(&self, name: &str) -> String {
+        match self {
+            DetectedDialect::MySQL => format!("`{}`", name.replace('`', "``")),
+            DetectedDialect::MsSQL => format!("[{}]", name.replace(']', "]]")),
+            DetectedDialect::Standard | DetectedDialect::Oracle => {
+                format!("\"{}\"", name.replace('"', "\"\""))
+            }
+        }
+    }
+
+    /// `text` as a string literal; MySQL also treats backslashes as escapes and
+    /// MS-SQL gets a Unicode `N'...'` literal.
+    pub fn quote_string(&self, text: &str) -> String {
+        let escaped = text.replace('\'', "''");
+        match self {
+            DetectedDialect::MySQL => format!("'{}'", escaped.replace('\\', "\\\\")),
+            DetectedDialect::MsSQL => format!("N'{}'", escaped),
+            DetectedDialect::Standard | DetectedDialect::Oracle => format!("'{}'", escaped),
+        }
+    }
+
+    /// `value` as a literal, with booleans as 1 and 0 where the column type for
+    /// them is numeric.
+    pub fn literal(&self, value: &SqlValue) -> String {
+        match (self, value) {
+            (_, SqlValue::Null) => "NULL".to_string(),
+            (_, SqlValue::Integer(i)) => i.to_string(),
+            (_, SqlValue::Float(f)) => format!("{:?}", f),
+            (_, SqlValue::Text(text)) => self.quote_string(text),
+            (DetectedDialect::MsSQL | DetectedDialect::Oracle, SqlValue::Boolean(b)) => {
+                (if *b { "1" } else { "0" }).to_string()
+            }
+            (_, SqlValue::Boolean(b)) => (if *b { "TRUE" } else { "FALSE" }).to_string(),
+        }
+    }
+
+    /// How a column definition marks generated ids, with a leading space.
+    pub fn auto_increment(&self) -> &'static str {
+        match self {
+            DetectedDialect::MySQL => " AUTO_INCREMENT",
+            DetectedDialect::MsSQL => " IDENTITY(1,1)",
+            DetectedDialect::Standard | DetectedDialect::Oracle => {
+                " GENERATED BY DEFAULT AS IDENTITY"
+            }
+        }
+    }
+
+    /// The dialect's name for a MirseoDB column type.
+    pub fn column_type(&self, data_type: &DataType) -> &'static str {
+        match (self, data_type) {
+            (DetectedDialect::MsSQL, DataType::Integer) => "BIGINT",
+            (DetectedDialect::MsSQL, DataType::Text) => "NVARCHAR(MAX)",
+            (DetectedDialect::MsSQL, DataType::Boolean) => "BIT",
+            (DetectedDialect::MySQL, DataType::Integer) => "BIGINT",
+            (DetectedDialect::MySQL, DataType::Float) => "DOUBLE",
+            (DetectedDialect::Oracle, DataType::Integer) => "NUMBER(19)",
+            (DetectedDialect::Oracle, DataType::Float) => "BINARY_DOUBLE",
+            (DetectedDialect::Oracle, DataType::Text) => "CLOB",
+            (DetectedDialect::Oracle, DataType::Boolean) => "NUMBER(1)",
+            (_, data_type) => data_type.as_str(),
+        }
+    }
+}
+
+/// A dialect keyword found in a query and the weight it added to that dialect.
+#[derive(Debug, Clone)]
+pub struct KeywordMatch {
+    pub keyword: String,
+    pub dialect: DetectedDialect,
+    pub weight: f32,
+}
+
+/// Why a query was classified as it was: the chosen dialect, every dialect's
+/// score (highest first) and the matched keywords (heaviest first).
+#[derive(Debug, Clone)]
+pub struct DialectExplanation {
+    pub dialect: DetectedDialect,
+    pub confidence: f32,
+    pub scores: Vec<(DetectedDialect, f32)>,
+    pub matches: Vec<KeywordMatch>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_table_rejects_duplicate_columns() {
+        let parser = AnySQL::new();
+
+        match parser.parse("CREATE TABLE t (id INT, id TEXT)") {
+            Err(DatabaseError::ParseError(msg)) => assert!(msg.contains("Duplicate column")),
+            other => panic!("expected duplicate column error, got {:?}", other),
+        }
+
+        assert!(parser.parse("CREATE TABLE t (id INT, name TEXT)").is_ok());
+    }
+
+    #[test]
+    fn test_primary_keys_are_not_null() {
+        let parser = AnySQL::new();
+
+        let Ok(SqlStatement::CreateTable { columns, .. }) =
+            parser.parse("CREATE TABLE t (id INT PRIMARY KEY, code INT NOT NULL, note TEXT NULL)")
+        else {
+            panic!("CREATE TABLE did not parse");
+        };
+        let nullable: Vec<bool> = columns.iter().map(|c| c.nullable).collect();
+        assert_eq!(nullable, vec![false, false, true]);
+
+        match parser.parse("CREATE TABLE t (id INT PRIMARY KEY NULL)") {
+            Err(DatabaseError::ParseError(msg)) => assert!(msg.contains("cannot be declared NULL")),
+            other => panic!("expected a nullable primary key error, got {:?}", other),
+        }
+        assert!(parser.parse("CREATE TABLE t (id INT NOT NULL PRIMARY KEY)").is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_statement_lists_registered_keywords() {
+        let parser = AnySQL::new();
+
+        match parser.parse("EXPLAIN SELECT * FROM t") {
+            Err(DatabaseError::ParseError(msg)) => {
+                assert!(msg.starts_with("unsupported statement: EXPLAIN"));
+                assert!(msg.contains("CREATE DATABASE, CREATE INDEX, CREATE TABLE"));
+                assert_eq!(msg.matches("INSERT").count(), 1);
+            }
+            other => panic!("expected unsupported statement error, got {:?}", other),
+        }
+        assert!(matches!(
+            parser.parse_with_dialect_hint("vacuum", Some(DetectedDialect::Standard)),
+            Err(DatabaseError::ParseError(msg)) if msg.starts_with("unsupported statement: VACUUM")
+        ));
+
+        assert!(matches!(
+            parser.parse("CREATE DATABASE shop"),
+            Ok(SqlStatement::CreateDatabase { .. })
+        ));
+        assert!(matches!(
+            parser.parse("REVOKE SELECT ON t FROM 'a@b.c'"),
+            Ok(SqlStatement::Revoke { .. })
+        ));
+        assert!(matches!(parser.parse("start transaction"), Ok(SqlStatement::Begin)));
+        assert!(matches!(parser.parse("ROLLBACK WORK;"), Ok(SqlStatement::Rollback)));
+    }
+
+    #[test]
+    fn test_index_statements_parse() {
+        let parser = AnySQL::new();
+
+        let sql = "CREATE UNIQUE INDEX idx_pair ON orders (customer_id, placed_at DESC)";
+        match parser.parse(sql) {
+            Ok(SqlStatement::CreateCompositeIndex {
+                index_name,
+                table_name,
+                column_names,
+                is_unique,
+                case_insensitive,
+            }) => {
+                assert_eq!(index_name, "idx_pair");
+                assert_eq!(table_name, "ORDERS");
+                assert_eq!(column_names, ["customer_id", "placed_at"]);
+                assert!(is_unique && !case_insensitive);
+            }
+            other => panic!("expected CREATE INDEX, got {:?}", other),
+        }
+        assert!(matches!(
+            parser.parse("CREATE INDEX idx_email ON users (email COLLATE NOCASE)"),
+            Ok(SqlStatement::CreateCompositeIndex { column_names, case_insensitive: true, .. })
+                if column_names == ["email"]
+        ));
+        assert!(parser.parse("CREATE INDEX idx_email ON users (email COLLATE RTRIM)").is_err());
+        assert!(parser
+            .parse("CREATE INDEX idx_pair ON users (email COLLATE NOCASE, name)")
+            .is_err());
+        assert!(matches!(
+            parser.parse("DROP INDEX orders.idx_pair"),
+            Ok(SqlStatement::DropIndex { index_name, table_name: Some(table) })
+                if index_name == "idx_pair" && table == "ORDERS"
+        ));
+        assert!(matches!(
+            parser.parse("DROP INDEX idx_pair"),
+            Ok(SqlStatement::DropIndex { table_name: None, .. })
+        ));
+        assert!(parser.parse("CREATE INDEX idx ON orders ()").is_err());
+        assert!(parser.parse("CREATE INDEX idx orders (id)").is_err());
+    }
+
+    #[test]
+    fn test_comments_parse() {
+        let parser = AnySQL::new();
+
+        let sql = "CREATE TABLE notes (id INT PRIMARY KEY, \
+                   body TEXT COMMENT 'may be NULL, or ''empty''' NOT NULL) \
+                   COMMENT='Free-form notes'";
+        match parser.parse(sql) {
+            Ok(SqlStatement::CreateTable {
+                columns, comment, ..
+            }) => {
+                assert_eq!(comment.as_deref(), Some("Free-form notes"));
+                assert_eq!(columns[1].comment.as_deref(), Some("may be NULL, or 'empty'"));
+                assert!(!columns[1].nullable);
+                assert_eq!(columns[0].comment, None);
+            }
+            other => panic!("expected CREATE TABLE, got {:?}", other),
+        }
+        assert!(matches!(
+            parser.parse("COMMENT ON COLUMN notes.body IS 'Markdown text'"),
+            Ok(SqlStatement::Comment { table_name, column_name: Some(column), comment: Some(text) })
+                if table_name == "NOTES" && column == "body" && text == "Markdown text"
+        ));
+        assert!(matches!(
+            parser.parse("COMMENT ON TABLE notes IS NULL;"),
+            Ok(SqlStatement::Comment { column_name: None, comment: None, .. })
+        ));
+        assert!(parser.parse("COMMENT ON TABLE notes IS 'unterminated").is_err());
+        assert!(parser.parse("COMMENT ON COLUMN body IS 'no table'").is_err());
+    }
+
+    #[test]
+    fn test_aggregates_and_group_by_parse() {
+        let parser = AnySQL::new();
+        let statement = parser
+            .parse(
+                "SELECT country, count( * ), SUM(price) AS total FROM t GROUP BY country \
+                 ORDER BY count(*) DESC",
+            )
+            .unwrap();
+        let SqlStatement::Select {
+            columns,
+            aggregates,
+            group_by,
+            order_by,
+            ..
+        } = statement
+        else {
+            panic!("expected SELECT");
+        };
+
+        assert_eq!(columns, vec!["country".to_string()]);
+        let names: Vec<&str> = aggregates.iter().map(|aggregate| aggregate.name.as_str()).collect();
+        assert_eq!(names, vec!["COUNT(*)", "total"]);
+        assert_eq!(aggregates[1].column.as_deref(), Some("price"));
+        assert_eq!(group_by, vec!["country".to_string()]);
+        assert_eq!(order_by.unwrap()[0].column, "COUNT(*)");
+
+        assert!(parser.parse("SELECT SUM(*) FROM t").is_err());
+        assert!(parser.parse("SELECT COUNT(*) FROM t GROUP BY").is_err());
+    }
+
+    #[test]
+    fn test_nesting_depth_guard() {
+        let parser = AnySQL::new().with_max_nesting_depth(3);
+        let grouped = |levels: usize| {
+            format!("SELECT * FROM t WHERE {}id = 1{}", "(".repeat(levels), ")".repeat(levels))
+        };
+        assert!(parser.parse(&grouped(3)).is_ok());
+        assert!(matches!(parser.parse(&grouped(4)), Err(DatabaseError::QueryTooComplex)));
+
+        // Subqueries count as a level, and so do groups inside them
+        let subqueries = |levels: usize| {
+            let mut sql = "SELECT id FROM t".to_string();
+            for _ in 0..levels {
+                sql = format!("SELECT id FROM t WHERE id IN ({})", sql);
+            }
+            sql
+        };
+        assert!(parser.parse(&subqueries(3)).is_ok());
+        assert!(matches!(parser.parse(&subqueries(4)), Err(DatabaseError::QueryTooComplex)));
+        let mixed = "SELECT * FROM t WHERE id IN (SELECT id FROM u WHERE (((a = 1))) OR b = 2)";
+        assert!(matches!(parser.parse(mixed), Err(DatabaseError::QueryTooComplex)));
+        let scalar = format!("SELECT {}1{} FROM t", "(SELECT ".repeat(4), " FROM t)".repeat(4));
+        assert!(matches!(parser.parse(&scalar), Err(DatabaseError::QueryTooComplex)));
+
+        // Parentheses that are not nesting do not count
+        let parser = AnySQL::new().with_max_nesting_depth(0);
+        assert!(parser.parse("SELECT * FROM t WHERE note = '((((((' AND id IN (1, 2)").is_ok());
+        assert!(parser.parse("CREATE TABLE t (id INT, n VARCHAR(10))").is_ok());
+    }
+
+    #[test]
+    fn test_paging_dialects_share_limit_offset() {
+        let parser = AnySQL::new();
+        let paging = |sql: &str| match parser.parse(sql).unwrap() {
+            SqlStatement::Select { limit, offset, .. } => (limit, offset),
+            other => panic!("expected SELECT, got {:?}", other),
+        };
+
+        assert_eq!(paging("SELECT * FROM t LIMIT 5 OFFSET 10"), (Some(5), Some(10)));
+        assert_eq!(
+            paging("SELECT * FROM t ORDER BY id OFFSET 10 ROWS FETCH FIRST 5 ROWS ONLY"),
+            (Some(5), Some(10))
+        );
+        assert_eq!(paging("SELECT * FROM t OFFSET 3 ROWS;"), (None, Some(3)));
+        assert_eq!(paging("SELECT * FROM t FETCH NEXT ROW ONLY"), (Some(1), None));
+
+        assert!(parser.parse("SELECT * FROM t FETCH FIRST 5 ROWS").is_err());
+        assert!(parser.parse("SELECT * FROM t LIMIT 1 FETCH FIRST 5 ROWS ONLY").is_err());
+
+        // MySQL puts the offset first
+        assert_eq!(paging("SELECT * FROM t LIMIT 10, 5"), (Some(5), Some(10)));
+        assert_eq!(paging("SELECT * FROM t ORDER BY id LIMIT 10 ,5;"), (Some(5), Some(10)));
+        assert_eq!(paging("SELECT * FROM t LIMIT 0"), (Some(0), None));
+        assert!(parser.parse("SELECT * FROM t LIMIT 10, 5 OFFSET 2").is_err());
+        assert!(parser.parse("SELECT * FROM t LIMIT 10,").is_err());
+        assert!(parser.parse("SELECT * FROM t LIMIT -1, 5").is_err());
+        assert!(parser.parse("SELECT * FROM t LIMIT ten").is_err());
+    }
+
+    #[test]
+    fn test_paging_values_are_bounded() {
+        let parser = AnySQL::new();
+
+        match parser.parse("SELECT * FROM t LIMIT 99999999999999999999999999") {
+            Err(DatabaseError::ParseError(msg)) => assert!(msg.contains("too large")),
+            other => panic!("expected overflow error, got {:?}", other),
+        }
+        assert!(parser.parse("SELECT * FROM t OFFSET -1").is_err());
+        assert!(parser.parse("SELECT * FROM t LIMIT").is_err());
+
+        let max = format!("SELECT * FROM t OFFSET {}", usize::MAX);
+        assert!(matches!(
+            parser.parse(&max),
+            Ok(SqlStatement::Select { offset: Some(usize::MAX), .. })
+        ));
+    }
+
+    #[test]
+    fn test_explain_dialect_reports_matched_keywords() {
+        let parser = AnySQL::new();
+        let sql = "CREATE TABLE users (id INT, name VARCHAR2(20))";
+
+        let explanation = parser.explain_dialect(sql);
+        let (dialect, confidence) = parser
+            .keyword_matcher
+            .detect_dialect_optimized(&sql.to_uppercase());
+
+        assert_eq!(explanation.dialect, dialect);
+        assert_eq!(explanation.confidence, confidence);
+        assert_eq!(explanation.scores.len(), DetectedDialect::ALL.len());
+        assert_eq!(explanation.scores[0].1, confidence);
+        assert!(explanation
+            .matches
+            .iter()
+            .any(|m| m.keyword == "VARCHAR2" && m.dialect == DetectedDialect::Oracle));
+    }
+
+    #[test]
+    fn test_dialect_override_skips_detection() {
+        let parser = AnySQL::new();
+        let sql = "SELECT * FROM users WHERE id = 1";
+
+        assert_eq!(DetectedDialect::from_name("MariaDB"), Some(DetectedDialect::MySQL));
+        assert_eq!(DetectedDialect::from_name("postgres"), None);
+
+        let (statement, dialect) = parser
+            .parse_with_dialect_hint(sql, Some(DetectedDialect::Oracle))
+            .unwrap();
+        assert!(matches!(statement, SqlStatement::Select { .. }));
+        assert_eq!(dialect, DetectedDialect::Oracle);
+
+        // The override path leaves the dialect cache alone
+        let snapshot = parser.performance_snapshot().unwrap();
+        assert_eq!(snapshot.dialect_cache_entries, 0);
+        assert_eq!(snapshot.metrics.total_queries, 0);
+    }
+
+    #[test]
+    fn test_dialect_override_changes_column_types() {
+        let parser = AnySQL::new();
+        let sql = "CREATE TABLE t (id NUMBER(10), flag TINYINT(1))";
+        let types = |dialect| match parser.parse_with_dialect_hint(sql, dialect) {
+            Ok((SqlStatement::CreateTable { columns, .. }, _)) => {
+                Ok(columns.into_iter().map(|c| c.data_type).collect::<Vec<_>>())
+            }
+            Ok((other, _)) => panic!("expected CREATE TABLE, got {:?}", other),
+            Err(err) => Err(err),
+        };
+
+        // Without a dialect every type name is accepted
+        assert!(matches!(types(None).unwrap()[..], [DataType::Text, DataType::Integer]));
+
+        match types(Some(DetectedDialect::Oracle)) {
+            Err(DatabaseError::InvalidDataType(message)) => assert!(message.contains("TINYINT")),
+            other => panic!("TINYINT is not an Oracle type, got {:?}", other),
+        }
+        match types(Some(DetectedDialect::MySQL)) {
+            Err(DatabaseError::InvalidDataType(message)) => assert!(message.contains("NUMBER")),
+            other => panic!("NUMBER is not a MySQL type, got {:?}", other),
+        }
+
+        let oracle = "CREATE TABLE t (id NUMBER(10), ok NUMBER(1), price NUMBER(8,2))";
+        match parser.parse_with_dialect_hint(oracle, Some(DetectedDialect::Oracle)) {
+            Ok((SqlStatement::CreateTable { columns, .. }, _)) => {
+                let types: Vec<_> = columns.into_iter().map(|c| c.data_type).collect();
+                assert!(matches!(
+                    types[..],
+                    [DataType::Integer, DataType::Boolean, DataType::Float]
+                ));
+            }
+            other => panic!("expected CREATE TABLE, got {:?}", other),
+        }
+        match parser.parse_with_dialect_hint(
+            "CREATE TABLE t (id INT, flag TINYINT(1))",
+            Some(DetectedDialect::MySQL),
+        ) {
+            Ok((SqlStatement::CreateTable { columns, .. }, _)) => {
+                assert!(matches!(columns[1].data_type, DataType::Boolean));
+            }
+            other => panic!("expected CREATE TABLE, got {:?}", other),
+        }
+        match parser.parse_with_dialect_hint(
+            "CREATE TABLE t (id INT, active BIT, name NVARCHAR(40))",
+            Some(DetectedDialect::MsSQL),
+        ) {
+            Ok((SqlStatement::CreateTable { columns, .. }, _)) => {
+                let types: Vec<_> = columns.into_iter().map(|c| c.data_type).collect();
+                assert!(matches!(
+                    types[..],
+                    [DataType::Integer, DataType::Boolean, DataType::Text]
+                ));
+            }
+            other => panic!("expected CREATE TABLE, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dialect_override_checks_identifier_quotes() {
+        let parser = AnySQL::new();
+        let backticks = "SELECT `name` FROM users";
+        let brackets = "SELECT [name] FROM users";
+
+        assert!(parser.parse_with_dialect_hint(backticks, None).is_ok());
+        assert!(parser.parse_with_dialect_hint(backticks, Some(DetectedDialect::MySQL)).is_ok());
+        assert!(parser.parse_with_dialect_hint(brackets, Some(DetectedDialect::MsSQL)).is_ok());
+        for dialect in [
+            DetectedDialect::Standard,
+            DetectedDialect::MsSQL,
+            DetectedDialect::Oracle,
+        ] {
+            assert!(matches!(
+                parser.parse_with_dialect_hint(backticks, Some(dialect)),
+                Err(DatabaseError::ParseError(_))
+            ));
+        }
+        for dialect in [
+            DetectedDialect::Standard,
+            DetectedDialect::MySQL,
+            DetectedDialect::Oracle,
+        ] {
+            assert!(matches!(
+                parser.parse_with_dialect_hint(brackets, Some(dialect)),
+                Err(DatabaseError::ParseError(_))
+            ));
+        }
+
+        // Quote characters inside string literals are not identifiers
+        let literal = "SELECT name FROM users WHERE note = 'a `b` [c]'";
+        assert!(parser.parse_with_dialect_hint(literal, Some(DetectedDialect::Oracle)).is_ok());
+    }
+
+    #[test]
+    fn test_grant_and_revoke_parse() {
+        let parser = AnySQL::new();
+
+        match parser
+            .parse("GRANT SELECT, insert ON orders TO 'user@example.com'")
+            .unwrap()
+        {
+            SqlStatement::Grant {
+                privileges,
+                table_name,
+                grantee,
+            } => {
+                assert_eq!(privileges, vec!["SELECT", "INSERT"]);
+                assert_eq!(table_name.as_deref(), Some("ORDERS"));
+                assert_eq!(grantee, "user@example.com");
+            }
+            other => panic!("expected GRANT, got {:?}", other),
+        }
+
+        assert!(matches!(
+            parser.parse("REVOKE ALL PRIVILEGES ON *.* FROM 'user@example.com'"),
+            Ok(SqlStatement::Revoke { table_name: None, .. })
+        ));
+        assert!(parser.parse("GRANT TRUNCATE ON t TO 'u'").is_err());
+        assert!(parser.parse("REVOKE SELECT ON t TO 'u'").is_err());
+    }
+
+    #[test]
+    fn test_upsert_clauses_parse() {
+        let parser = AnySQL::new();
+        let on_conflict = |sql: &str| match parser.parse(sql) {
+            Ok(SqlStatement::Insert { values, on_conflict, .. }) => {
+                assert_eq!(values.len(), 2, "ON clause leaked into the values of {}", sql);
+                format!("{:?}", on_conflict)
+            }
+            other => panic!("expected INSERT, got {:?}", other),
+        };
+
+        assert_eq!(on_conflict("INSERT INTO t (id, n) VALUES (1, 'on')"), "None");
+        assert_eq!(
+            on_conflict(
+                "INSERT INTO t (id, n) VALUES (1, 2) ON DUPLICATE KEY UPDATE n = VALUES(n)"
+            ),
+            "Some(Update([(\"n\", Integer(2))]))"
+        );
+        assert_eq!(
+            on_conflict(
+                "INSERT INTO t (id, n) VALUES (1, 2) ON CONFLICT (id) DO UPDATE SET n = excluded.n"
+            ),
+            "Some(Update([(\"n\", Integer(2))]))"
+        );
+        assert_eq!(
+            on_conflict("INSERT INTO t (id, n) VALUES (1, 2) ON CONFLICT DO NOTHING"),
+            "Some(DoNothing)"
+        );
+        assert!(parser
+            .parse("INSERT INTO t (id) VALUES (1) ON CONFLICT DO UPDATE SET n = EXCLUDED.n")
+            .is_err());
+        assert!(parser.parse("INSERT INTO t (id) VALUES (1) ON CONFLICT (id)").is_err());
+        assert!(parser.parse("INSERT INTO t (id) VALUES (1) ON CONFLICT id DO NOTHING").is_err());
+        let target = |sql: &str| match parser.parse(sql) {
+            Ok(SqlStatement::Insert { conflict_target, .. }) => conflict_target,
+            other => panic!("expected INSERT, got {:?}", other),
+        };
+        assert_eq!(
+            target("INSERT INTO t (a, b) VALUES (1, 2) ON CONFLICT (a, b) DO NOTHING"),
+            ["a", "b"]
+        );
+        assert!(target("INSERT INTO t (a) VALUES (1) ON CONFLICT DO NOTHING").is_empty());
+    }
+
+    #[test]
+    fn test_where_clause_tree_shape() {
+        let parser = AnySQL::new();
+        let where_of = |sql: &str| match parser.parse(sql).unwrap() {
+            SqlStatement::Select { where_clause, .. } => where_clause.unwrap(),
+            other => panic!("expected SELECT, got {:?}", other),
+        };
+
+        match where_of("SELECT * FROM t WHERE a = 1 OR b = 2 AND c = 3") {
+            WhereExpr::Or(terms) => {
+                assert!(matches!(terms[0], WhereExpr::Comparison(_)));
+                assert!(matches!(&terms[1], WhereExpr::And(inner) if inner.len() == 2));
+            }
+            other => panic!("expected OR at the root, got {:?}", other),
+        }
+
+        match where_of("SELECT * FROM t WHERE (a = 1 OR b = 2) AND c='x y' LIMIT 5") {
+            WhereExpr::And(terms) => {
+                assert!(matches!(&terms[0], WhereExpr::Or(inner) if inner.len() == 2));
+                assert!(matches!(
+                    &terms[1],
+                    WhereExpr::Comparison(WhereClause { value: SqlValue::Text(text), .. }) if &**text == "x y"
+                ));
+            }
+            other => panic!("expected AND at the root, got {:?}", other),
+        }
+
+        assert!(parser.parse("SELECT * FROM t WHERE (a = 1 OR b = 2").is_err());
+        assert!(parser.parse("SELECT * FROM t WHERE a = 1 b = 2").is_err());
+    }
+
+    #[test]
+    fn test_clause_keywords_inside_literals() {
+        let parser = AnySQL::new();
+
+        let sql = "SELECT * FROM t WHERE name = 'x  LIMIT 1' ORDER BY id LIMIT 2";
+        match parser.parse(sql).unwrap() {
+            SqlStatement::Select {
+                where_clause: Some(WhereExpr::Comparison(WhereClause { value, .. })),
+                limit,
+                ..
+            } => {
+                assert!(matches!(value, SqlValue::Text(text) if &*text == "x  LIMIT 1"));
+                assert_eq!(limit, Some(2));
+            }
+            other => panic!("expected SELECT with one comparison, got {:?}", other),
+        }
+
+        assert!(matches!(
+            parser.parse("SELECT * FROM t WHERE a = 'UNION SELECT' UNION SELECT * FROM u"),
+            Ok(SqlStatement::Union { .. })
+        ));
+        assert_eq!(sql_words("a = 'b  c' d"), vec!["a", "=", "'b  c'", "d"]);
+    }
+}
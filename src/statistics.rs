@@ -0,0 +1,291 @@
+//! Per-column statistics (row/NULL counts, min/max and an approximate distinct
+//! count) computed from a table snapshot. Distinct counts use a HyperLogLog sketch
+//! so large tables do not need a set of every value.
+
+use super::core_types::{ComparisonOperator, SqlValue, Table, WhereClause};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const HLL_PRECISION: u32 = 10;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// HyperLogLog cardinality sketch with 1024 registers (about 3% standard error).
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS],
+        }
+    }
+
+    pub fn insert(&mut self, value: &SqlValue) {
+        let hash = hash_value(value);
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        if rank as u8 > self.registers[index] {
+            self.registers[index] = rank as u8;
+        }
+    }
+
+    pub fn estimate(&self) -> usize {
+        let m = HLL_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-(rank as i32)))
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate while many registers are empty
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            raw.round() as usize
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hash_value(value: &SqlValue) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match value {
+        SqlValue::Integer(i) => (0u8, i).hash(&mut hasher),
+        SqlValue::Float(f) => (1u8, f.to_bits()).hash(&mut hasher),
+        SqlValue::Text(s) => (2u8, s).hash(&mut hasher),
+        SqlValue::Boolean(b) => (3u8, b).hash(&mut hasher),
+        SqlValue::Null => 4u8.hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnStatistics {
+    pub name: String,
+    pub null_count: usize,
+    /// Bounds are only kept when every non-NULL value has the same comparable type.
+    pub min: Option<SqlValue>,
+    pub max: Option<SqlValue>,
+    pub distinct_estimate: usize,
+    /// Dictionary size when the column is dictionary-encoded (see `dictionary`).
+    pub dictionary_size: Option<usize>,
+}
+
+impl ColumnStatistics {
+    /// True when the bounds prove no row can satisfy `where_clause`. Columns with
+    /// NULLs or missing values never qualify: the WHERE evaluator treats a NULL as
+    /// equal to anything and errors on a missing column, and skipping must not
+    /// change either outcome.
+    pub fn rules_out(&self, where_clause: &WhereClause) -> bool {
+        if self.null_count > 0 {
+            return false;
+        }
+        let (Some(min), Some(max)) = (&self.min, &self.max) else {
+            return false;
+        };
+        let value = &where_clause.value;
+        let (Some(against_min), Some(against_max)) =
+            (compare_same_type(value, min), compare_same_type(value, max))
+        else {
+            return false;
+        };
+
+        match where_clause.operator {
+            ComparisonOperator::Equal => {
+                against_min == Ordering::Less || against_max == Ordering::Greater
+            }
+            ComparisonOperator::GreaterThan => against_max != Ordering::Less,
+            ComparisonOperator::GreaterThanOrEqual => against_max == Ordering::Greater,
+            ComparisonOperator::LessThan => against_min != Ordering::Greater,
+            ComparisonOperator::LessThanOrEqual => against_min == Ordering::Less,
+            ComparisonOperator::NotEqual
+            | ComparisonOperator::Like
+            | ComparisonOperator::NotLike => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableStatistics {
+    pub row_count: usize,
+    pub columns: Vec<ColumnStatistics>,
+}
+
+impl TableStatistics {
+    /// Scans `table` once; columns appear in definition order.
+    pub fn compute(table: &Table) -> Self {
+        let columns = table
+            .columns
+            .iter()
+            .map(|definition| column_statistics(table, &definition.name))
+            .collect();
+
+        Self {
+            row_count: table.rows.len(),
+            columns,
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnStatistics> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+fn column_statistics(table: &Table, name: &str) -> ColumnStatistics {
+    let mut sketch = HyperLogLog::new();
+    let mut null_count = 0;
+    let mut non_null_count = 0;
+    let mut bounds: Option<(SqlValue, SqlValue)> = None;
+    let mut comparable = true;
+
+    for row in &table.rows {
+        let value = match row.columns.get(name) {
+            None | Some(SqlValue::Null) => {
+                null_count += 1;
+                continue;
+            }
+            Some(value) => value,
+        };
+
+        non_null_count += 1;
+        sketch.insert(value);
+
+        if !comparable {
+            continue;
+        }
+        bounds = match bounds {
+            None if compare_same_type(value, value).is_some() => {
+                Some((value.clone(), value.clone()))
+            }
+            None => None,
+            Some((min, max)) => widen_bounds(min, max, value),
+        };
+        comparable = bounds.is_some();
+    }
+
+    let (min, max) = match bounds {
+        Some((min, max)) => (Some(min), Some(max)),
+        None => (None, None),
+    };
+
+    ColumnStatistics {
+        name: name.to_string(),
+        null_count,
+        min,
+        max,
+        // The sketch can overshoot slightly on small inputs
+        distinct_estimate: sketch.estimate().min(non_null_count),
+        dictionary_size: table.dictionaries.dictionary_size(name),
+    }
+}
+
+fn widen_bounds(min: SqlValue, max: SqlValue, value: &SqlValue) -> Option<(SqlValue, SqlValue)> {
+    let below = compare_same_type(value, &min)? == Ordering::Less;
+    let above = compare_same_type(value, &max)? == Ordering::Greater;
+
+    Some((
+        if below { value.clone() } else { min },
+        if above { value.clone() } else { max },
+    ))
+}
+
+/// Orders two values of the same type; mixed types and NaN are not comparable.
+pub fn compare_same_type(a: &SqlValue, b: &SqlValue) -> Option<Ordering> {
+    match (a, b) {
+        (SqlValue::Integer(a), SqlValue::Integer(b)) => Some(a.cmp(b)),
+        (SqlValue::Float(a), SqlValue::Float(b)) => a.partial_cmp(b),
+        (SqlValue::Text(a), SqlValue::Text(b)) => Some(a.cmp(b)),
+        (SqlValue::Boolean(a), SqlValue::Boolean(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_types::{ColumnDefinition, DataType, Durability, Row};
+    use crate::indexing::IndexManager;
+
+    fn table_with(values: Vec<SqlValue>) -> Table {
+        Table {
+            name: "T".to_string(),
+            columns: vec![ColumnDefinition {
+                name: "v".to_string(),
+                data_type: DataType::Integer,
+                nullable: true,
+                primary_key: false,
+                comment: None,
+                default: None,
+                auto_increment: false,
+            }],
+            rows: values
+                .into_iter()
+                .map(|value| Row {
+                    columns: [("v".to_string(), value)].into_iter().collect(),
+                })
+                .collect(),
+            index_manager: IndexManager::new(),
+            next_row_id: 1,
+            next_auto_increment: 1,
+            durability: Durability::Sync,
+            dictionaries: Default::default(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_column_statistics_bounds_and_counts() {
+        let mut values: Vec<SqlValue> = (0..5000).map(|i| SqlValue::Integer(i % 1000)).collect();
+        values.push(SqlValue::Null);
+        let stats = TableStatistics::compute(&table_with(values));
+        let column = stats.column("v").unwrap();
+
+        assert_eq!(stats.row_count, 5001);
+        assert_eq!(column.null_count, 1);
+        assert!(matches!(column.min, Some(SqlValue::Integer(0))));
+        assert!(matches!(column.max, Some(SqlValue::Integer(999))));
+        assert!((900..=1100).contains(&column.distinct_estimate));
+
+        let mixed = TableStatistics::compute(&table_with(vec![
+            SqlValue::Integer(1),
+            SqlValue::Text("a".into()),
+        ]));
+        assert!(mixed.column("v").unwrap().min.is_none());
+    }
+
+    #[test]
+    fn test_bounds_rule_out_impossible_ranges() {
+        let bound = |operator, value| WhereClause {
+            column: "v".to_string(),
+            operator,
+            value: SqlValue::Integer(value),
+        };
+        let complete =
+            TableStatistics::compute(&table_with((10..100).map(SqlValue::Integer).collect()));
+        let column = complete.column("v").unwrap();
+        assert!(column.rules_out(&bound(ComparisonOperator::GreaterThan, 99)));
+        assert!(!column.rules_out(&bound(ComparisonOperator::GreaterThanOrEqual, 99)));
+        assert!(column.rules_out(&bound(ComparisonOperator::LessThan, 10)));
+        assert!(column.rules_out(&bound(ComparisonOperator::Equal, 200)));
+        assert!(!column.rules_out(&bound(ComparisonOperator::Equal, 50)));
+
+        // A NULL row keeps the column from ever being skipped
+        let with_null =
+            TableStatistics::compute(&table_with(vec![SqlValue::Integer(1), SqlValue::Null]));
+        assert!(!with_null
+            .column("v")
+            .unwrap()
+            .rules_out(&bound(ComparisonOperator::GreaterThan, 5)));
+    }
+}
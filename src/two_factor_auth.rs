@@ -3,13 +3,55 @@ use std::fs;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-const TOTP_DIGITS: usize = 6;
+pub const DEFAULT_TOTP_DIGITS: u32 = 6;
+/// Code lengths authenticator apps accept.
+pub const TOTP_DIGIT_RANGE: std::ops::RangeInclusive<u32> = 6..=8;
 const TOTP_PERIOD: u64 = 30;
 const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
 
+/// HMAC hash function of a user's TOTP codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl TotpAlgorithm {
+    /// The name used by `otpauth://` URLs and the secrets file.
+    pub fn name(self) -> &'static str {
+        match self {
+            TotpAlgorithm::Sha1 => "SHA1",
+            TotpAlgorithm::Sha256 => "SHA256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_uppercase().replace('-', "").as_str() {
+            "SHA1" => Some(TotpAlgorithm::Sha1),
+            "SHA256" => Some(TotpAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    fn hash(self, input: &[u8]) -> Vec<u8> {
+        match self {
+            TotpAlgorithm::Sha1 => sha1(input),
+            TotpAlgorithm::Sha256 => sha256(input),
+        }
+    }
+}
+
+/// A user's shared secret with the code settings their authenticator was set up with.
+#[derive(Debug, Clone)]
+struct TotpSecret {
+    secret: String,
+    algorithm: TotpAlgorithm,
+    digits: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct TwoFactorAuth {
-    secrets: HashMap<String, String>, // user_id -> secret
+    secrets: HashMap<String, TotpSecret>, // user_id -> secret
 }
 
 impl TwoFactorAuth {
@@ -29,19 +71,9 @@ impl TwoFactorAuth {
         let content = fs::read_to_string(config_path)
             .map_err(|e| format!("Failed to read 2FA config: {}", e))?;
 
-        let mut secrets = HashMap::new();
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-
-            if let Some((user_id, secret)) = line.split_once(':') {
-                secrets.insert(user_id.trim().to_string(), secret.trim().to_string());
-            }
-        }
-
-        Ok(Self { secrets })
+        Ok(Self {
+            secrets: parse_secrets(&content),
+        })
     }
 
     pub fn save(&self) -> Result<(), String> {
@@ -54,18 +86,51 @@ impl TwoFactorAuth {
         let config_path = format!("{}/2fa_secrets.dat", config_dir);
         let mut content = String::new();
         content.push_str("# MirseoDB 2FA Secrets\n");
-        content.push_str("# Format: user_id:secret\n\n");
+        content.push_str("# Format: user_id:secret:algorithm:digits\n\n");
 
         for (user_id, secret) in &self.secrets {
-            content.push_str(&format!("{}:{}\n", user_id, secret));
+            content.push_str(&format!(
+                "{}:{}:{}:{}\n",
+                user_id,
+                secret.secret,
+                secret.algorithm.name(),
+                secret.digits
+            ));
         }
 
         fs::write(&config_path, content).map_err(|e| format!("Failed to write 2FA config: {}", e))
     }
 
     pub fn generate_secret_for_user(&mut self, user_id: &str) -> Result<String, String> {
+        self.generate_secret_with_options(user_id, TotpAlgorithm::Sha1, DEFAULT_TOTP_DIGITS)
+    }
+
+    /// Like `generate_secret_for_user`, for codes of `digits` (6 to 8) digits
+    /// computed with `algorithm`.
+    pub fn generate_secret_with_options(
+        &mut self,
+        user_id: &str,
+        algorithm: TotpAlgorithm,
+        digits: u32,
+    ) -> Result<String, String> {
+        if !TOTP_DIGIT_RANGE.contains(&digits) {
+            return Err(format!(
+                "2FA codes must have {} to {} digits, not {}",
+                TOTP_DIGIT_RANGE.start(),
+                TOTP_DIGIT_RANGE.end(),
+                digits
+            ));
+        }
+
         let secret = generate_random_secret();
-        self.secrets.insert(user_id.to_string(), secret.clone());
+        self.secrets.insert(
+            user_id.to_string(),
+            TotpSecret {
+                secret: secret.clone(),
+                algorithm,
+                digits,
+            },
+        );
         self.save()?;
         Ok(secret)
     }
@@ -91,21 +156,31 @@ impl TwoFactorAuth {
     }
 
     pub fn generate_qr_code(&self, user_id: &str, issuer: &str) -> Result<String, String> {
+        generate_qr_ascii(&self.otpauth_url(user_id, issuer)?)
+    }
+
+    /// The `otpauth://` URL authenticator apps are set up from, including the
+    /// algorithm and digit count of `user_id`'s codes.
+    pub fn otpauth_url(&self, user_id: &str, issuer: &str) -> Result<String, String> {
         let secret = self
             .secrets
             .get(user_id)
             .ok_or_else(|| "User not found".to_string())?;
 
-        let otpauth_url = format!(
-            "otpauth://totp/{}:{}?secret={}&issuer={}",
-            issuer, user_id, secret, issuer
-        );
-
-        generate_qr_ascii(&otpauth_url)
+        Ok(format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+            issuer,
+            user_id,
+            secret.secret,
+            issuer,
+            secret.algorithm.name(),
+            secret.digits,
+            TOTP_PERIOD
+        ))
     }
 
     pub fn get_setup_info(&self, user_id: &str) -> Option<String> {
-        self.secrets.get(user_id).cloned()
+        self.secrets.get(user_id).map(|secret| secret.secret.clone())
     }
 
     pub fn has_user(&self, user_id: &str) -> bool {
@@ -178,11 +253,11 @@ fn base32_decode(input: &str) -> Result<Vec<u8>, String> {
     Ok(result)
 }
 
-fn generate_totp(secret: &str, time_counter: u64) -> String {
-    let key = base32_decode(secret).unwrap_or_default();
+fn generate_totp(secret: &TotpSecret, time_counter: u64) -> String {
+    let key = base32_decode(&secret.secret).unwrap_or_default();
     let counter_bytes = time_counter.to_be_bytes();
 
-    let hash = hmac_sha1(&key, &counter_bytes);
+    let hash = hmac(secret.algorithm, &key, &counter_bytes);
     let offset = (hash[hash.len() - 1] & 0x0f) as usize;
 
     let binary = ((hash[offset] & 0x7f) as u32) << 24
@@ -190,18 +265,18 @@ fn generate_totp(secret: &str, time_counter: u64) -> String {
         | ((hash[offset + 2] & 0xff) as u32) << 8
         | (hash[offset + 3] & 0xff) as u32;
 
-    let otp = binary % 10_u32.pow(TOTP_DIGITS as u32);
-    format!("{:0width$}", otp, width = TOTP_DIGITS)
+    let otp = binary % 10_u32.pow(secret.digits);
+    format!("{:0width$}", otp, width = secret.digits as usize)
 }
 
-// Simple HMAC-SHA1 implementation
-fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+// Simple HMAC implementation; SHA-1 and SHA-256 share the 64-byte block size
+fn hmac(algorithm: TotpAlgorithm, key: &[u8], message: &[u8]) -> Vec<u8> {
     const BLOCK_SIZE: usize = 64;
     const IPAD: u8 = 0x36;
     const OPAD: u8 = 0x5c;
 
     let mut k = if key.len() > BLOCK_SIZE {
-        sha1(key)
+        algorithm.hash(key)
     } else {
         key.to_vec()
     };
@@ -218,14 +293,14 @@ fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
         outer_key[i] = k[i] ^ OPAD;
     }
 
-    // HMAC = SHA1(outer_key || SHA1(inner_key || message))
+    // HMAC = H(outer_key || H(inner_key || message))
     let mut inner_hash_input = inner_key;
     inner_hash_input.extend_from_slice(message);
-    let inner_hash = sha1(&inner_hash_input);
+    let inner_hash = algorithm.hash(&inner_hash_input);
 
     let mut outer_hash_input = outer_key;
     outer_hash_input.extend_from_slice(&inner_hash);
-    sha1(&outer_hash_input)
+    algorithm.hash(&outer_hash_input)
 }
 
 // Simple SHA-1 implementation
@@ -309,6 +384,77 @@ fn sha1(input: &[u8]) -> Vec<u8> {
     result
 }
 
+const SHA256_ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// Simple SHA-256 implementation, padded like SHA-1
+fn sha256(input: &[u8]) -> Vec<u8> {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let mut message = input.to_vec();
+    let bit_len = (input.len() as u64).wrapping_mul(8);
+    message.push(0x80);
+    while !(message.len() + 8).is_multiple_of(64) {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_len.to_be_bytes());
+
+    for chunk in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for (round_constant, word) in SHA256_ROUND_CONSTANTS.iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ ((!e) & g);
+            let temp1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*round_constant)
+                .wrapping_add(word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    h.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
 fn generate_qr_ascii(data: &str) -> Result<String, String> {
     // Simple ASCII QR code representation
     // This is a simplified representation - not a real QR code
@@ -354,11 +500,67 @@ fn generate_qr_ascii(data: &str) -> Result<String, String> {
     result.push_str("Setup Instructions:\n");
     result.push_str("1. Install Google Authenticator or similar TOTP app\n");
     result.push_str("2. Add account manually using the secret key above\n");
-    result.push_str("3. Use the 6-digit code for authentication\n");
+    result.push_str("3. Use the code the app shows for authentication\n");
 
     Ok(result)
 }
 
+/// The lines of `2fa_secrets.dat`: `user_id:secret[:algorithm:digits]`. A line
+/// that cannot be read is logged and skipped so the other users keep their 2FA.
+fn parse_secrets(content: &str) -> HashMap<String, TotpSecret> {
+    let mut secrets = HashMap::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_secret_line(line) {
+            Ok((user_id, secret)) => {
+                secrets.insert(user_id, secret);
+            }
+            Err(e) => eprintln!(
+                "[MirseoDB][2fa] Skipping line {} of 2fa_secrets.dat: {}",
+                number + 1,
+                e
+            ),
+        }
+    }
+
+    secrets
+}
+
+fn parse_secret_line(line: &str) -> Result<(String, TotpSecret), String> {
+    let (user_id, rest) = line
+        .split_once(':')
+        .ok_or_else(|| "expected user_id:secret".to_string())?;
+
+    // Lines written before algorithm and digits were stored hold the secret only
+    let mut fields = rest.split(':').map(str::trim);
+    let secret = fields.next().unwrap_or_default().to_string();
+    let algorithm = match fields.next() {
+        Some(name) => TotpAlgorithm::from_name(name)
+            .ok_or_else(|| format!("Unknown 2FA algorithm '{}'", name))?,
+        None => TotpAlgorithm::Sha1,
+    };
+    let digits = match fields.next() {
+        Some(digits) => digits
+            .parse()
+            .ok()
+            .filter(|digits| TOTP_DIGIT_RANGE.contains(digits))
+            .ok_or_else(|| format!("Invalid 2FA digit count '{}'", digits))?,
+        None => DEFAULT_TOTP_DIGITS,
+    };
+    Ok((
+        user_id.trim().to_string(),
+        TotpSecret {
+            secret,
+            algorithm,
+            digits,
+        },
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,10 +575,100 @@ mod tests {
 
     #[test]
     fn test_totp_generation() {
-        let secret = "JBSWY3DPEHPK3PXP";
+        let secret = TotpSecret {
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            algorithm: TotpAlgorithm::Sha1,
+            digits: DEFAULT_TOTP_DIGITS,
+        };
         let time_counter = 1;
-        let token = generate_totp(secret, time_counter);
+        let token = generate_totp(&secret, time_counter);
         assert_eq!(token.len(), 6);
         assert!(token.chars().all(|c| c.is_ascii_digit()));
     }
+
+    #[test]
+    fn test_sha256_digests() {
+        let hex = |bytes: Vec<u8>| -> String {
+            bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+        };
+        assert_eq!(
+            hex(sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    /// Appendix B of RFC 6238, with 8-digit codes.
+    #[test]
+    fn test_rfc6238_vectors() {
+        let cases = [
+            (TotpAlgorithm::Sha1, &b"12345678901234567890"[..], [
+                "94287082", "07081804", "14050471", "89005924", "69279037", "65353130",
+            ]),
+            (TotpAlgorithm::Sha256, &b"12345678901234567890123456789012"[..], [
+                "46119246", "68084774", "67062674", "91819424", "90698825", "77737706",
+            ]),
+        ];
+        let times = [59, 1111111109, 1111111111, 1234567890, 2000000000, 20000000000];
+
+        for (algorithm, key, expected) in cases {
+            let secret = TotpSecret {
+                secret: base32_encode(key),
+                algorithm,
+                digits: 8,
+            };
+            for (time, code) in times.iter().zip(expected) {
+                let token = generate_totp(&secret, time / TOTP_PERIOD);
+                assert_eq!(token, code, "{:?} at {}", algorithm, time);
+            }
+        }
+    }
+
+    #[test]
+    fn test_code_settings_reach_the_otpauth_url() {
+        let mut two_factor_auth = TwoFactorAuth::new();
+        two_factor_auth.secrets.insert(
+            "alice".to_string(),
+            TotpSecret {
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                algorithm: TotpAlgorithm::Sha256,
+                digits: 8,
+            },
+        );
+        assert_eq!(
+            two_factor_auth.otpauth_url("alice", "MirseoDB").unwrap(),
+            "otpauth://totp/MirseoDB:alice?secret=JBSWY3DPEHPK3PXP&issuer=MirseoDB\
+             &algorithm=SHA256&digits=8&period=30"
+        );
+        assert!(two_factor_auth.generate_qr_code("alice", "MirseoDB").is_ok());
+        assert_eq!(TotpAlgorithm::from_name("sha-256"), Some(TotpAlgorithm::Sha256));
+        assert_eq!(TotpAlgorithm::from_name("SHA512"), None);
+        assert!(two_factor_auth
+            .generate_secret_with_options("bob", TotpAlgorithm::Sha1, 9)
+            .is_err());
+
+        // Stored settings are checked like requested ones
+        let secrets = parse_secrets("alice:JBSWY3DPEHPK3PXP\nbob:JBSWY3DPEHPK3PXP:SHA256:8\n");
+        assert_eq!(secrets["alice"].digits, DEFAULT_TOTP_DIGITS);
+        assert_eq!(secrets["bob"].digits, 8);
+        assert!(parse_secrets("bob:JBSWY3DPEHPK3PXP:SHA1:0").is_empty());
+        assert!(parse_secrets("bob:JBSWY3DPEHPK3PXP:SHA1:12").is_empty());
+    }
+
+    #[test]
+    fn test_malformed_secret_lines_are_skipped() {
+        let content = "# MirseoDB 2FA Secrets\n\
+                       alice:JBSWY3DPEHPK3PXP:SHA1:6\n\
+                       bob:JBSWY3DPEHPK3PXP:MD5:6\n\
+                       no separator\n\
+                       carol:JBSWY3DPEHPK3PXP:SHA256:8\n";
+        let secrets = parse_secrets(content);
+        assert_eq!(secrets.len(), 2);
+        assert_eq!(secrets["alice"].algorithm, TotpAlgorithm::Sha1);
+        assert_eq!(secrets["carol"].digits, 8);
+        assert!(!secrets.contains_key("bob"));
+    }
 }
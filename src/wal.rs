@@ -0,0 +1,265 @@
+//! Write-ahead log. Mutating statements are appended to `.mirseoDB/<db>.wal`
+//! before they run, each under the next LSN, and `Database::load` replays the
+//! records newer than the LSN stored in the database file. A successful save
+//! writes that LSN into the file and removes the log.
+
+use super::core_types::{DatabaseError, SqlValue};
+use super::smart_parser::DetectedDialect;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// A statement as it was executed: its SQL, the values bound to its `?`
+/// placeholders and the dialect the client chose for it, so replay produces the
+/// same statement. `None` means the dialect was detected from the SQL.
+#[derive(Debug, Clone)]
+pub struct LoggedStatement {
+    pub dialect: Option<DetectedDialect>,
+    pub sql: String,
+    pub params: Vec<SqlValue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalRecord {
+    pub lsn: u64,
+    pub statement: LoggedStatement,
+}
+
+pub struct WriteAheadLog {
+    path: Option<PathBuf>, // None numbers records without writing them
+    fsync: bool,
+    last_lsn: u64,
+}
+
+impl WriteAheadLog {
+    pub fn new(db_name: &str, fsync: bool) -> Self {
+        Self {
+            path: Some(log_path(db_name)),
+            fsync,
+            last_lsn: 0,
+        }
+    }
+
+    /// A log that keeps no file, for test databases that are never reloaded.
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        Self {
+            path: None,
+            fsync: false,
+            last_lsn: 0,
+        }
+    }
+
+    /// LSN of the newest record written, or of the database file when no
+    /// record has been written since it was saved.
+    pub fn last_lsn(&self) -> u64 {
+        self.last_lsn
+    }
+
+    /// Continues numbering after `lsn`; used on load with the file's LSN.
+    pub fn advance_to(&mut self, lsn: u64) {
+        self.last_lsn = self.last_lsn.max(lsn);
+    }
+
+    /// Appends `statements` in one write and returns the LSN of the last one.
+    pub fn append(&mut self, statements: &[LoggedStatement]) -> Result<u64, DatabaseError> {
+        let mut buffer = Vec::new();
+        let mut lsn = self.last_lsn;
+        for statement in statements {
+            lsn += 1;
+            buffer.extend_from_slice(&lsn.to_le_bytes());
+            buffer.push(statement.dialect.as_ref().map_or(0, |d| DIALECT_CHOSEN | dialect_id(d)));
+            push_bytes(&mut buffer, statement.sql.as_bytes())?;
+            push_len(&mut buffer, statement.params.len())?;
+            for param in &statement.params {
+                push_param(&mut buffer, param)?;
+            }
+        }
+
+        let Some(path) = &self.path else {
+            self.last_lsn = lsn;
+            return Ok(lsn);
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| DatabaseError::IoError(e.to_string()))?;
+        }
+        let io_error =
+            |e: std::io::Error| DatabaseError::IoError(format!("Write-ahead log: {}", e));
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(io_error)?;
+        file.write_all(&buffer).map_err(io_error)?;
+        if self.fsync {
+            file.sync_all().map_err(io_error)?;
+        }
+
+        self.last_lsn = lsn;
+        Ok(lsn)
+    }
+
+    /// Every complete record in the log. A record cut short by a crash during
+    /// the append ends the log.
+    pub fn read_records(&mut self) -> Result<Vec<WalRecord>, DatabaseError> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+        let buffer = match fs::read(path) {
+            Ok(buffer) => buffer,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(DatabaseError::IoError(format!(
+                    "Failed to read write-ahead log: {}",
+                    e
+                )))
+            }
+        };
+
+        let mut records = Vec::new();
+        let mut cursor = 0;
+        while let Some((record, next)) = read_record(&buffer, cursor) {
+            self.advance_to(record.lsn);
+            records.push(record);
+            cursor = next;
+        }
+        if cursor < buffer.len() {
+            eprintln!(
+                "[MirseoDB] Write-ahead log ends in an incomplete record ({} bytes ignored)",
+                buffer.len() - cursor
+            );
+        }
+        Ok(records)
+    }
+
+    /// Drops the logged records once the database file holds their changes.
+    pub fn truncate(&self) -> Result<(), DatabaseError> {
+        match &self.path {
+            Some(path) => remove_log(path),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn log_path(db_name: &str) -> PathBuf {
+    Path::new(".mirseoDB").join(format!("{}.wal", db_name))
+}
+
+pub fn remove_log(path: &Path) -> Result<(), DatabaseError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(DatabaseError::IoError(format!(
+            "Failed to remove write-ahead log: {}",
+            e
+        ))),
+    }
+}
+
+fn push_len(buffer: &mut Vec<u8>, len: usize) -> Result<(), DatabaseError> {
+    let len = u32::try_from(len).map_err(|_| {
+        DatabaseError::IoError("Statement too long for the write-ahead log".to_string())
+    })?;
+    buffer.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn push_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) -> Result<(), DatabaseError> {
+    push_len(buffer, bytes.len())?;
+    buffer.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn push_param(buffer: &mut Vec<u8>, value: &SqlValue) -> Result<(), DatabaseError> {
+    match value {
+        SqlValue::Null => buffer.push(0),
+        SqlValue::Integer(v) => {
+            buffer.push(1);
+            buffer.extend_from_slice(&v.to_le_bytes());
+        }
+        SqlValue::Float(v) => {
+            buffer.push(2);
+            buffer.extend_from_slice(&v.to_le_bytes());
+        }
+        SqlValue::Text(v) => {
+            buffer.push(3);
+            push_bytes(buffer, v.as_bytes())?;
+        }
+        SqlValue::Boolean(v) => {
+            buffer.push(4);
+            buffer.push(u8::from(*v));
+        }
+    }
+    Ok(())
+}
+
+fn read_record(buffer: &[u8], mut cursor: usize) -> Option<(WalRecord, usize)> {
+    let lsn = u64::from_le_bytes(take(buffer, &mut cursor, 8)?.try_into().ok()?);
+    let dialect = match take(buffer, &mut cursor, 1)?[0] {
+        // Logs from before explicit dialects recorded only the detected one
+        id if id & DIALECT_CHOSEN == 0 => None,
+        id => Some(dialect_from_id(id & !DIALECT_CHOSEN)?),
+    };
+    let sql = read_text(buffer, &mut cursor)?;
+    let param_count = read_len(buffer, &mut cursor)?;
+    let mut params = Vec::new();
+    for _ in 0..param_count {
+        params.push(read_param(buffer, &mut cursor)?);
+    }
+
+    let statement = LoggedStatement {
+        dialect,
+        sql,
+        params,
+    };
+    Some((WalRecord { lsn, statement }, cursor))
+}
+
+fn take<'a>(buffer: &'a [u8], cursor: &mut usize, len: usize) -> Option<&'a [u8]> {
+    let bytes = buffer.get(*cursor..cursor.checked_add(len)?)?;
+    *cursor += len;
+    Some(bytes)
+}
+
+fn read_len(buffer: &[u8], cursor: &mut usize) -> Option<usize> {
+    Some(u32::from_le_bytes(take(buffer, cursor, 4)?.try_into().ok()?) as usize)
+}
+
+fn read_text(buffer: &[u8], cursor: &mut usize) -> Option<String> {
+    let len = read_len(buffer, cursor)?;
+    String::from_utf8(take(buffer, cursor, len)?.to_vec()).ok()
+}
+
+fn read_param(buffer: &[u8], cursor: &mut usize) -> Option<SqlValue> {
+    let value = match take(buffer, cursor, 1)?[0] {
+        0 => SqlValue::Null,
+        1 => SqlValue::Integer(i64::from_le_bytes(take(buffer, cursor, 8)?.try_into().ok()?)),
+        2 => SqlValue::Float(f64::from_le_bytes(take(buffer, cursor, 8)?.try_into().ok()?)),
+        3 => SqlValue::Text(read_text(buffer, cursor)?.into()),
+        4 => SqlValue::Boolean(take(buffer, cursor, 1)?[0] != 0),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Set in a record's dialect byte when the client chose the dialect.
+const DIALECT_CHOSEN: u8 = 0x80;
+
+fn dialect_id(dialect: &DetectedDialect) -> u8 {
+    match dialect {
+        DetectedDialect::Standard => 0,
+        DetectedDialect::MsSQL => 1,
+        DetectedDialect::MySQL => 2,
+        DetectedDialect::Oracle => 3,
+    }
+}
+
+fn dialect_from_id(id: u8) -> Option<DetectedDialect> {
+    match id {
+        0 => Some(DetectedDialect::Standard),
+        1 => Some(DetectedDialect::MsSQL),
+        2 => Some(DetectedDialect::MySQL),
+        3 => Some(DetectedDialect::Oracle),
+        _ => None,
+    }
+}